@@ -0,0 +1,823 @@
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use jwalk::{Parallelism, WalkDir};
+use log::debug;
+use rayon::prelude::*;
+
+use crate::disk::find_mount;
+use crate::filter::{Filter, Verdict};
+use crate::scanner::Artifact;
+
+/// Error type for unparseable `--older-than` / `--newer-than` values.
+#[derive(thiserror::Error, Debug)]
+#[error("invalid duration '{0}': expected a number followed by s, m, h, d, or w (e.g., 30d)")]
+pub struct DurationError(String);
+
+/// Parse a duration such as `90s`, `15m`, `12h`, `30d`, or `2w`.
+pub fn parse_duration(s: &str) -> Result<Duration, DurationError> {
+    let err = || DurationError(s.to_string());
+    let trimmed = s.trim();
+    let split = trimmed
+        .find(|c: char| !c.is_ascii_digit())
+        .ok_or_else(err)?;
+    let (digits, unit) = trimmed.split_at(split);
+    let n: u64 = digits.parse().map_err(|_| err())?;
+    let secs_per_unit = match unit {
+        "s" => 1,
+        "m" => 60,
+        "h" => 60 * 60,
+        "d" => 24 * 60 * 60,
+        "w" => 7 * 24 * 60 * 60,
+        _ => return Err(err()),
+    };
+    n.checked_mul(secs_per_unit)
+        .map(Duration::from_secs)
+        .ok_or_else(err)
+}
+
+/// Filters artifacts by modification and access times.
+///
+/// `older_than` keeps only artifacts last modified more than that long ago;
+/// `newer_than` skips artifacts modified within that window, protecting
+/// projects under active development. Both may be set. An artifact whose
+/// modification time cannot be read is skipped whenever either bound is set.
+///
+/// `not_accessed_in` skips artifacts containing any file read within that
+/// window. File access times are used rather than the directory's own, since
+/// walking the tree (as the scan and size passes do) updates directory atimes.
+#[derive(Debug, Default)]
+pub struct AgeFilter {
+    pub older_than: Option<Duration>,
+    pub newer_than: Option<Duration>,
+    pub not_accessed_in: Option<Duration>,
+}
+
+impl AgeFilter {
+    /// Whether the filter has no bounds and keeps everything.
+    pub fn is_empty(&self) -> bool {
+        self.older_than.is_none() && self.newer_than.is_none() && self.not_accessed_in.is_none()
+    }
+
+    /// Test whether an artifact modified at `mtime` passes the filter at time `now`.
+    pub fn keeps(&self, mtime: SystemTime, now: SystemTime) -> bool {
+        // Modification times in the future count as brand new.
+        let age = now.duration_since(mtime).unwrap_or_default();
+        if self.older_than.is_some_and(|min| age <= min) {
+            return false;
+        }
+        if self.newer_than.is_some_and(|window| age < window) {
+            return false;
+        }
+        true
+    }
+
+    /// Test whether an artifact last accessed at `atime` passes the
+    /// `not_accessed_in` bound at time `now`.
+    pub fn keeps_access(&self, atime: SystemTime, now: SystemTime) -> bool {
+        let idle = now.duration_since(atime).unwrap_or_default();
+        self.not_accessed_in.is_none_or(|window| idle >= window)
+    }
+
+    /// Filter a list of artifacts by their current modification and access times.
+    ///
+    /// Access times require walking each artifact, so those walks run in
+    /// parallel and only for artifacts that pass the modification-time bounds.
+    pub fn apply(&self, artifacts: Vec<Artifact>) -> Vec<Artifact> {
+        if self.is_empty() {
+            return artifacts;
+        }
+        let now = SystemTime::now();
+        artifacts
+            .into_par_iter()
+            .filter(|a| match self.verdict(&a.path, now) {
+                Verdict::Keep(_) => true,
+                Verdict::Drop(reason) => {
+                    debug!("Filtered out: {} ({reason})", a.path.display());
+                    false
+                }
+            })
+            .collect()
+    }
+
+    /// Decide whether to keep the artifact at `path` at time `now`, and why.
+    pub fn verdict(&self, path: &Path, now: SystemTime) -> Verdict {
+        let mut notes = Vec::new();
+        if self.older_than.is_some() || self.newer_than.is_some() {
+            let mtime = match std::fs::symlink_metadata(path).and_then(|m| m.modified()) {
+                Ok(mtime) => mtime,
+                Err(e) => return Verdict::Drop(format!("cannot read modification time: {e}")),
+            };
+            let age = now.duration_since(mtime).unwrap_or_default();
+            if let Some(min) = self.older_than.filter(|&min| age <= min) {
+                return Verdict::Drop(format!(
+                    "modified {} ago, not older than --older-than {}",
+                    format_age(age),
+                    format_age(min)
+                ));
+            }
+            if let Some(window) = self.newer_than.filter(|&window| age < window) {
+                return Verdict::Drop(format!(
+                    "modified {} ago, within --newer-than {}",
+                    format_age(age),
+                    format_age(window)
+                ));
+            }
+            notes.push(format!("modified {} ago", format_age(age)));
+        }
+        if let Some(window) = self.not_accessed_in {
+            let Some(atime) = last_access(path) else {
+                return Verdict::Drop("cannot read access times".to_string());
+            };
+            let idle = now.duration_since(atime).unwrap_or_default();
+            if idle < window {
+                return Verdict::Drop(format!(
+                    "last read {} ago, within --not-accessed-in {}",
+                    format_age(idle),
+                    format_age(window)
+                ));
+            }
+            notes.push(format!("last read {} ago", format_age(idle)));
+        }
+        Verdict::Keep((!notes.is_empty()).then(|| notes.join(", ")))
+    }
+}
+
+impl Filter for AgeFilter {
+    fn keep(&self, artifact: &Artifact) -> bool {
+        Filter::verdict(self, artifact).is_keep()
+    }
+
+    /// Decide against the current time.
+    fn verdict(&self, artifact: &Artifact) -> Verdict {
+        AgeFilter::verdict(self, &artifact.path, SystemTime::now())
+    }
+}
+
+/// Newest modification time of any file under `project`, the project's last
+/// source change. Paths in `skip` (the detected artifacts) and `.git` are not
+/// descended into. `None` if no other files exist.
+pub fn last_source_change(project: &Path, skip: Arc<HashSet<PathBuf>>) -> Option<SystemTime> {
+    WalkDir::new(project)
+        .parallelism(Parallelism::Serial)
+        .follow_links(false)
+        .skip_hidden(false)
+        .process_read_dir(move |_depth, dir, _state, children| {
+            children.retain(|entry| match entry {
+                Ok(e) => e.file_name != ".git" && !skip.contains(&dir.join(&e.file_name)),
+                Err(_) => true,
+            });
+        })
+        .into_iter()
+        .filter_map(|e| e.ok())
+        .filter(|e| e.file_type().is_file())
+        .filter_map(|e| e.metadata().ok()?.modified().ok())
+        .max()
+}
+
+/// Projects checked once each for recent source changes (`--idle-for`).
+#[derive(Debug)]
+pub struct IdleProjects {
+    window: Duration,
+    now: SystemTime,
+    last_change: HashMap<PathBuf, Option<SystemTime>>,
+}
+
+impl IdleProjects {
+    /// Find the last source change of every project of `artifacts`, in
+    /// parallel. `detected` holds the path of every artifact the scan found,
+    /// so that no artifact counts as a source change.
+    pub fn check(artifacts: &[Artifact], detected: HashSet<PathBuf>, window: Duration) -> Self {
+        let mut projects: Vec<&Path> = artifacts.iter().map(|a| a.project.as_path()).collect();
+        projects.sort();
+        projects.dedup();
+
+        let detected = Arc::new(detected);
+        let last_change = projects
+            .into_par_iter()
+            .map(|project| {
+                let changed = last_source_change(project, Arc::clone(&detected));
+                (project.to_path_buf(), changed)
+            })
+            .collect();
+        Self {
+            window,
+            now: SystemTime::now(),
+            last_change,
+        }
+    }
+}
+
+impl Filter for IdleProjects {
+    fn keep(&self, artifact: &Artifact) -> bool {
+        Filter::verdict(self, artifact).is_keep()
+    }
+
+    /// Drop artifacts of projects whose sources changed within the window.
+    /// Projects with no source files count as idle.
+    fn verdict(&self, artifact: &Artifact) -> Verdict {
+        let Some(changed) = self.last_change.get(&artifact.project).copied().flatten() else {
+            return Verdict::Keep(Some("project has no source files".to_string()));
+        };
+        let idle = self.now.duration_since(changed).unwrap_or_default();
+        if idle < self.window {
+            Verdict::Drop(format!(
+                "project source changed {} ago, within --idle-for {}",
+                format_age(idle),
+                format_age(self.window)
+            ))
+        } else {
+            Verdict::Keep(Some(format!(
+                "project source last changed {} ago",
+                format_age(idle)
+            )))
+        }
+    }
+}
+
+/// Format a duration in its largest whole unit, matching `parse_duration`
+/// (e.g., `3d`, `5h`, `12m`, `40s`).
+pub fn format_age(d: Duration) -> String {
+    let secs = d.as_secs();
+    match secs {
+        s if s >= 24 * 60 * 60 => format!("{}d", s / (24 * 60 * 60)),
+        s if s >= 60 * 60 => format!("{}h", s / (60 * 60)),
+        s if s >= 60 => format!("{}m", s / 60),
+        s => format!("{s}s"),
+    }
+}
+
+/// A point in time broken into UTC calendar fields.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct UtcTime {
+    pub year: i64,
+    pub month: u32,
+    pub day: u32,
+    pub hour: u32,
+    pub minute: u32,
+    pub second: u32,
+}
+
+impl UtcTime {
+    /// Convert a system time; times before the epoch clamp to it.
+    pub fn of(time: SystemTime) -> Self {
+        let secs = time
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs();
+        let (days, rem) = (secs / 86_400, secs % 86_400);
+        // Civil-from-days (Howard Hinnant's algorithm), for days since 1970-01-01.
+        let z = days as i64 + 719_468;
+        let era = z.div_euclid(146_097);
+        let doe = z.rem_euclid(146_097);
+        let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+        let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+        let mp = (5 * doy + 2) / 153;
+        let day = doy - (153 * mp + 2) / 5 + 1;
+        let month = if mp < 10 { mp + 3 } else { mp - 9 };
+        Self {
+            year: yoe + era * 400 + i64::from(month <= 2),
+            month: month as u32,
+            day: day as u32,
+            hour: (rem / 3600) as u32,
+            minute: (rem % 3600 / 60) as u32,
+            second: (rem % 60) as u32,
+        }
+    }
+}
+
+/// RFC 3339, e.g. `2026-10-16T10:15:00Z`.
+impl std::fmt::Display for UtcTime {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}Z",
+            self.year, self.month, self.day, self.hour, self.minute, self.second
+        )
+    }
+}
+
+impl UtcTime {
+    /// Read the RFC 3339 form `Display` writes, e.g. `2026-10-16T10:15:00Z`.
+    pub fn parse(s: &str) -> Option<Self> {
+        let b = s.as_bytes();
+        let fixed = [
+            (4, b'-'),
+            (7, b'-'),
+            (10, b'T'),
+            (13, b':'),
+            (16, b':'),
+            (19, b'Z'),
+        ];
+        if b.len() != 20 || fixed.iter().any(|&(i, c)| b[i] != c) {
+            return None;
+        }
+        let field = |range: std::ops::Range<usize>| -> Option<u32> {
+            let digits = s.get(range)?;
+            digits
+                .bytes()
+                .all(|c| c.is_ascii_digit())
+                .then(|| digits.parse().ok())?
+        };
+        let time = Self {
+            year: i64::from(field(0..4)?),
+            month: field(5..7)?,
+            day: field(8..10)?,
+            hour: field(11..13)?,
+            minute: field(14..16)?,
+            second: field(17..19)?,
+        };
+        let valid = (1..=12).contains(&time.month)
+            && (1..=31).contains(&time.day)
+            && time.hour < 24
+            && time.minute < 60
+            && time.second < 60;
+        valid.then_some(time)
+    }
+
+    /// The moment this is, clamped to the epoch like [`of`](Self::of).
+    pub fn to_system_time(self) -> SystemTime {
+        // Days-from-civil, the inverse of the algorithm in `of`.
+        let year = self.year - i64::from(self.month <= 2);
+        let era = year.div_euclid(400);
+        let yoe = year.rem_euclid(400);
+        let mp = i64::from((self.month + 9) % 12);
+        let doy = (153 * mp + 2) / 5 + i64::from(self.day) - 1;
+        let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+        let days = era * 146_097 + doe - 719_468;
+        let secs = days * 86_400
+            + i64::from(self.hour) * 3600
+            + i64::from(self.minute) * 60
+            + i64::from(self.second);
+        UNIX_EPOCH + Duration::from_secs(secs.max(0) as u64)
+    }
+}
+
+/// Serde for an `Option<SystemTime>` as an RFC 3339 string to the second,
+/// for `#[serde(with = "...")]`: a stable form for JSON, where serde's own is
+/// a `{secs_since_epoch, nanos_since_epoch}` object.
+#[cfg(feature = "serde")]
+pub mod rfc3339 {
+    use std::time::SystemTime;
+
+    use serde::{Deserialize, Deserializer, Serializer, de::Error};
+
+    use super::UtcTime;
+
+    pub fn serialize<S: Serializer>(
+        time: &Option<SystemTime>,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        match time {
+            Some(time) => serializer.collect_str(&UtcTime::of(*time)),
+            None => serializer.serialize_none(),
+        }
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<Option<SystemTime>, D::Error> {
+        Option::<String>::deserialize(deserializer)?
+            .map(|s| {
+                UtcTime::parse(&s)
+                    .map(UtcTime::to_system_time)
+                    .ok_or_else(|| D::Error::custom(format!("invalid RFC 3339 time: {s}")))
+            })
+            .transpose()
+    }
+}
+
+/// Drop every artifact of the `n` most recently built projects of each build
+/// system, so that whatever is being worked on right now is left alone.
+///
+/// A project's build time is the newest modification time among its
+/// artifacts of that build system; unreadable times sort as oldest.
+pub fn keep_latest(artifacts: Vec<Artifact>, n: usize) -> Vec<Artifact> {
+    let latest = LatestProjects::find(&artifacts, n);
+    artifacts.into_iter().filter(|a| latest.keep(a)).collect()
+}
+
+/// The `n` most recently built projects of each build system (`--keep-latest`).
+#[derive(Debug)]
+pub struct LatestProjects {
+    n: usize,
+    projects: HashSet<(&'static str, PathBuf)>,
+}
+
+impl LatestProjects {
+    /// Find the latest projects among `artifacts`. See `keep_latest`.
+    pub fn find(artifacts: &[Artifact], n: usize) -> Self {
+        Self {
+            n,
+            projects: latest_projects(artifacts, n),
+        }
+    }
+}
+
+impl Filter for LatestProjects {
+    fn keep(&self, artifact: &Artifact) -> bool {
+        Filter::verdict(self, artifact).is_keep()
+    }
+
+    /// Drop artifacts belonging to one of the latest projects.
+    fn verdict(&self, artifact: &Artifact) -> Verdict {
+        if self
+            .projects
+            .contains(&(artifact.build_system, artifact.project.clone()))
+        {
+            Verdict::Drop(format!(
+                "one of the {} most recently built {} projects (--keep-latest)",
+                self.n, artifact.build_system
+            ))
+        } else {
+            Verdict::Keep(None)
+        }
+    }
+}
+
+fn latest_projects(artifacts: &[Artifact], n: usize) -> HashSet<(&'static str, PathBuf)> {
+    let mut latest = HashSet::new();
+    if n == 0 {
+        return latest;
+    }
+
+    let mut built: HashMap<(&'static str, &Path), SystemTime> = HashMap::new();
+    for a in artifacts {
+        let mtime = std::fs::symlink_metadata(&a.path)
+            .and_then(|m| m.modified())
+            .unwrap_or(SystemTime::UNIX_EPOCH);
+        built
+            .entry((a.build_system, &a.project))
+            .and_modify(|t| *t = (*t).max(mtime))
+            .or_insert(mtime);
+    }
+
+    let mut by_system: HashMap<&'static str, Vec<(SystemTime, &Path)>> = HashMap::new();
+    for ((system, project), mtime) in built {
+        by_system.entry(system).or_default().push((mtime, project));
+    }
+    for (system, mut projects) in by_system {
+        // Newest first; ties broken by path so the result is deterministic.
+        projects.sort_by(|a, b| b.0.cmp(&a.0).then_with(|| a.1.cmp(b.1)));
+        for (_, project) in projects.into_iter().take(n) {
+            debug!("Keeping latest {system} project: {}", project.display());
+            latest.insert((system, project.to_path_buf()));
+        }
+    }
+    latest
+}
+
+/// The most recent access time of any regular file under `path`, or of
+/// `path` itself when it is a file or contains no files.
+///
+/// Walks serially: callers already run on rayon's global pool, and walking
+/// on it too starves once it is saturated (see `size::walk_pool`).
+fn last_access(path: &Path) -> Option<SystemTime> {
+    let newest_file = WalkDir::new(path)
+        .parallelism(Parallelism::Serial)
+        .follow_links(false)
+        .skip_hidden(false)
+        .into_iter()
+        .filter_map(|e| e.ok())
+        .filter(|e| e.file_type().is_file())
+        .filter_map(|e| e.metadata().ok()?.accessed().ok())
+        .max();
+    newest_file.or_else(|| std::fs::symlink_metadata(path).ok()?.accessed().ok())
+}
+
+/// How a filesystem maintains access times, as far as it can be determined.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AtimeMode {
+    /// Updated on every read (`strictatime`).
+    Strict,
+    /// Updated at most once a day, or when older than the mtime (`relatime`).
+    Relative,
+    /// Never updated (`noatime`).
+    Disabled,
+}
+
+/// Describe why access times under `root` may be an unreliable signal for a
+/// `--not-accessed-in` window, or `None` if they look trustworthy (or the
+/// mount options cannot be determined on this platform).
+pub fn atime_warning(root: &Path, window: Duration) -> Option<String> {
+    let (mount_point, mode) = atime_mode(root)?;
+    match mode {
+        AtimeMode::Disabled => Some(format!(
+            "{} is mounted noatime, so access times are not updated; \
+             --not-accessed-in only reflects when files were created",
+            mount_point.display()
+        )),
+        AtimeMode::Relative if window < Duration::from_secs(24 * 60 * 60) => Some(format!(
+            "{} is mounted relatime, which updates access times at most once a day; \
+             windows shorter than 1d are unreliable",
+            mount_point.display()
+        )),
+        _ => None,
+    }
+}
+
+/// Look up the mount containing `path` and its atime mode.
+#[cfg(target_os = "linux")]
+fn atime_mode(path: &Path) -> Option<(PathBuf, AtimeMode)> {
+    let mounts = std::fs::read_to_string("/proc/self/mounts").ok()?;
+    parse_atime_mode(&mounts, path)
+}
+
+#[cfg(not(target_os = "linux"))]
+fn atime_mode(_path: &Path) -> Option<(PathBuf, AtimeMode)> {
+    None
+}
+
+/// Find the longest mount point in `/proc/self/mounts`-formatted text that
+/// contains `path`, and read its atime mode from the mount options.
+#[cfg_attr(not(target_os = "linux"), allow(dead_code))]
+fn parse_atime_mode(mounts: &str, path: &Path) -> Option<(PathBuf, AtimeMode)> {
+    let mount = find_mount(mounts, path)?;
+    let mode = mount
+        .options
+        .split(',')
+        .find_map(|opt| match opt {
+            "noatime" => Some(AtimeMode::Disabled),
+            "relatime" => Some(AtimeMode::Relative),
+            "strictatime" => Some(AtimeMode::Strict),
+            _ => None,
+        })
+        // The kernel lists `relatime`/`noatime` explicitly; neither means strict.
+        .unwrap_or(AtimeMode::Strict);
+    Some((mount.point, mode))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const DAY: Duration = Duration::from_secs(24 * 60 * 60);
+
+    fn days_ago(now: SystemTime, days: u32) -> SystemTime {
+        now - DAY * days
+    }
+
+    #[test]
+    fn parse_duration_units() {
+        assert_eq!(parse_duration("90s").unwrap(), Duration::from_secs(90));
+        assert_eq!(parse_duration("15m").unwrap(), Duration::from_secs(900));
+        assert_eq!(parse_duration("12h").unwrap(), Duration::from_secs(43_200));
+        assert_eq!(parse_duration("30d").unwrap(), DAY * 30);
+        assert_eq!(parse_duration("2w").unwrap(), DAY * 14);
+    }
+
+    #[test]
+    fn parse_duration_rejects_malformed() {
+        for bad in [
+            "",
+            "d",
+            "30",
+            "30x",
+            "-1d",
+            "1.5d",
+            "30 d",
+            "99999999999999999999d",
+        ] {
+            let err = parse_duration(bad).unwrap_err();
+            assert!(err.to_string().contains("invalid duration"), "{bad}");
+        }
+    }
+
+    #[test]
+    fn empty_filter_keeps_everything() {
+        let now = SystemTime::now();
+        let f = AgeFilter::default();
+        assert!(f.is_empty());
+        assert!(f.keeps(now, now));
+        assert!(f.keeps(days_ago(now, 1000), now));
+    }
+
+    #[test]
+    fn older_than_keeps_only_old() {
+        let now = SystemTime::now();
+        let f = AgeFilter {
+            older_than: Some(DAY * 30),
+            ..Default::default()
+        };
+        assert!(f.keeps(days_ago(now, 31), now));
+        assert!(!f.keeps(days_ago(now, 29), now));
+    }
+
+    #[test]
+    fn newer_than_protects_recent() {
+        let now = SystemTime::now();
+        let f = AgeFilter {
+            newer_than: Some(DAY * 7),
+            ..Default::default()
+        };
+        assert!(!f.keeps(days_ago(now, 1), now));
+        assert!(f.keeps(days_ago(now, 8), now));
+        // Clock skew: a future mtime is treated as just modified.
+        assert!(!f.keeps(now + DAY, now));
+    }
+
+    #[test]
+    fn bounds_compose() {
+        let now = SystemTime::now();
+        let f = AgeFilter {
+            older_than: Some(DAY * 7),
+            newer_than: Some(DAY * 30),
+            ..Default::default()
+        };
+        assert!(!f.keeps(days_ago(now, 10), now));
+        assert!(f.keeps(days_ago(now, 40), now));
+    }
+
+    #[test]
+    fn verdict_reports_age() {
+        let tmp = tempfile::TempDir::new().unwrap();
+        let now = SystemTime::now();
+        let f = AgeFilter {
+            newer_than: Some(DAY * 7),
+            ..Default::default()
+        };
+        assert_eq!(
+            f.verdict(tmp.path(), now + DAY * 2),
+            Verdict::Drop("modified 2d ago, within --newer-than 7d".to_string())
+        );
+        assert_eq!(
+            f.verdict(tmp.path(), now + DAY * 10),
+            Verdict::Keep(Some("modified 10d ago".to_string()))
+        );
+    }
+
+    #[test]
+    fn format_age_uses_largest_unit() {
+        assert_eq!(format_age(DAY * 3 + Duration::from_secs(60)), "3d");
+        assert_eq!(format_age(Duration::from_secs(5 * 3600)), "5h");
+        assert_eq!(format_age(Duration::from_secs(720)), "12m");
+        assert_eq!(format_age(Duration::from_secs(40)), "40s");
+    }
+
+    #[test]
+    fn utc_time_formats_rfc3339() {
+        assert_eq!(UtcTime::of(UNIX_EPOCH).to_string(), "1970-01-01T00:00:00Z");
+        let leap_day = UNIX_EPOCH + Duration::from_secs(1_709_210_096);
+        assert_eq!(UtcTime::of(leap_day).to_string(), "2024-02-29T12:34:56Z");
+    }
+
+    #[test]
+    fn utc_time_parses_what_it_formats() {
+        for secs in [0, 1_709_210_096, 951_782_400, 4_102_444_799] {
+            let time = UNIX_EPOCH + Duration::from_secs(secs);
+            let parsed = UtcTime::parse(&UtcTime::of(time).to_string()).unwrap();
+            assert_eq!(parsed, UtcTime::of(time));
+            assert_eq!(parsed.to_system_time(), time);
+        }
+        for bad in [
+            "2024-02-29 12:34:56Z",
+            "2024-13-01T00:00:00Z",
+            "2024-02-29T12:34:56",
+        ] {
+            assert_eq!(UtcTime::parse(bad), None, "{bad}");
+        }
+    }
+
+    #[test]
+    fn not_accessed_in_keeps_idle() {
+        let now = SystemTime::now();
+        let f = AgeFilter {
+            not_accessed_in: Some(DAY * 90),
+            ..Default::default()
+        };
+        assert!(!f.is_empty());
+        assert!(f.keeps_access(days_ago(now, 91), now));
+        assert!(!f.keeps_access(days_ago(now, 10), now));
+    }
+
+    #[test]
+    fn last_access_uses_newest_file() {
+        let tmp = tempfile::TempDir::new().unwrap();
+        let old = tmp.path().join("old.o");
+        let new = tmp.path().join("sub/new.o");
+        std::fs::create_dir_all(new.parent().unwrap()).unwrap();
+        std::fs::write(&old, "").unwrap();
+        std::fs::write(&new, "").unwrap();
+        let now = SystemTime::now();
+        let set_atime = |path: &Path, days: u32| {
+            let times = std::fs::FileTimes::new().set_accessed(days_ago(now, days));
+            std::fs::File::options()
+                .write(true)
+                .open(path)
+                .unwrap()
+                .set_times(times)
+                .unwrap();
+        };
+        set_atime(&old, 200);
+        set_atime(&new, 100);
+
+        let atime = last_access(tmp.path()).unwrap();
+        let idle = now.duration_since(atime).unwrap();
+        assert!(idle >= DAY * 99 && idle <= DAY * 101, "{idle:?}");
+    }
+
+    const MOUNTS: &str = "\
+sysfs /sys sysfs rw,nosuid,nodev,noexec,relatime 0 0
+/dev/sda1 / ext4 rw,relatime 0 0
+/dev/sdb1 /mnt/data xfs rw,noatime 0 0
+/dev/sdc1 /mnt/my\\040disk ext4 rw 0 0
+";
+
+    #[test]
+    fn parse_atime_mode_picks_longest_mount() {
+        let mode = |p: &str| parse_atime_mode(MOUNTS, Path::new(p)).map(|(_, m)| m);
+        assert_eq!(mode("/home/me"), Some(AtimeMode::Relative));
+        assert_eq!(mode("/mnt/data/projects"), Some(AtimeMode::Disabled));
+        assert_eq!(mode("/mnt/database"), Some(AtimeMode::Relative));
+        assert_eq!(mode("/mnt/my disk/src"), Some(AtimeMode::Strict));
+        assert_eq!(parse_atime_mode("", Path::new("/")), None);
+    }
+
+    #[test]
+    fn idle_for_ignores_artifact_and_git_mtimes() {
+        let tmp = tempfile::TempDir::new().unwrap();
+        let now = SystemTime::now();
+        let backdated_file = |path: &Path, days: u32| {
+            std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+            std::fs::write(path, "").unwrap();
+            std::fs::File::options()
+                .write(true)
+                .open(path)
+                .unwrap()
+                .set_modified(days_ago(now, days))
+                .unwrap();
+        };
+        let artifact = |project: &Path| Artifact {
+            risk: crate::rules::Risk::Deps,
+            ..Artifact::for_test(project.join("node_modules"), "Node.js")
+        };
+
+        // Idle sources, but a freshly touched artifact and Git metadata.
+        let idle = tmp.path().join("idle");
+        backdated_file(&idle.join("src/index.js"), 90);
+        backdated_file(&idle.join("node_modules/.cache/x"), 0);
+        backdated_file(&idle.join(".git/FETCH_HEAD"), 0);
+        // Recently edited sources.
+        let active = tmp.path().join("active");
+        backdated_file(&active.join("src/index.js"), 3);
+        backdated_file(&active.join("node_modules/x"), 90);
+
+        let artifacts = vec![artifact(&idle), artifact(&active)];
+        let detected = artifacts.iter().map(|a| a.path.clone()).collect();
+        let projects = IdleProjects::check(&artifacts, detected, DAY * 60);
+
+        assert_eq!(
+            Filter::verdict(&projects, &artifacts[0]),
+            Verdict::Keep(Some("project source last changed 90d ago".to_string()))
+        );
+        assert_eq!(
+            Filter::verdict(&projects, &artifacts[1]),
+            Verdict::Drop("project source changed 3d ago, within --idle-for 60d".to_string())
+        );
+    }
+
+    #[test]
+    fn keep_latest_protects_newest_projects_per_system() {
+        let tmp = tempfile::TempDir::new().unwrap();
+        let now = SystemTime::now();
+        let artifact = |project: &str, dir: &str, system: &'static str, days: u32| {
+            let project = tmp.path().join(project);
+            let path = project.join(dir);
+            std::fs::create_dir_all(&path).unwrap();
+            std::fs::File::open(&path)
+                .unwrap()
+                .set_modified(days_ago(now, days))
+                .unwrap();
+            Artifact::for_test(path, system)
+        };
+        let artifacts = vec![
+            artifact("old", "target", "Rust/Cargo", 30),
+            artifact("new", "target", "Rust/Cargo", 1),
+            artifact("mid", "target", "Rust/Cargo", 10),
+            // Two artifacts of one project count as a single project.
+            artifact("web", "node_modules", "Node.js", 50),
+            artifact("web", ".next", "Node.js", 2),
+            artifact("api", "node_modules", "Node.js", 5),
+        ];
+
+        let kept = keep_latest(artifacts.clone(), 1);
+        let mut names: Vec<_> = kept
+            .iter()
+            .map(|a| a.path.strip_prefix(tmp.path()).unwrap().to_path_buf())
+            .collect();
+        names.sort();
+        assert_eq!(
+            names,
+            [
+                PathBuf::from("api/node_modules"),
+                PathBuf::from("mid/target"),
+                PathBuf::from("old/target"),
+            ]
+        );
+
+        assert_eq!(keep_latest(artifacts.clone(), 0).len(), 6);
+        assert!(keep_latest(artifacts, 3).is_empty());
+    }
+}
@@ -0,0 +1,301 @@
+use std::fmt;
+use std::fs::OpenOptions;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+#[cfg(feature = "serde")]
+use serde::Deserialize;
+use serde::Serialize;
+
+use crate::age::UtcTime;
+use crate::quarantine::data_dir;
+use crate::scanner::Artifact;
+
+/// Error writing the deletion log.
+#[derive(thiserror::Error, Debug)]
+pub enum AuditError {
+    #[error("cannot locate the deletion log: neither XDG_DATA_HOME nor HOME is set")]
+    NoHome,
+    #[error("cannot write deletion log {}: {source}", path.display())]
+    Io {
+        path: PathBuf,
+        source: std::io::Error,
+    },
+    #[error("cannot write deletion log: {0}")]
+    Serialize(#[from] toml::ser::Error),
+}
+
+/// What happened to one artifact during a `--delete` run. Serialized
+/// variants are named by their [`label`](Self::label).
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(Serialize, Deserialize),
+    serde(rename_all = "kebab-case")
+)]
+pub enum Outcome {
+    Deleted,
+    /// Removed by the build tool's clean command (`--use-tool`), named here.
+    Cleaned(String),
+    Trashed,
+    /// Moved into the quarantine run with this ID.
+    Quarantined(String),
+    /// Moved aside for a background process to delete (`--background`),
+    /// which may yet fail.
+    MovedAside,
+    /// Left in place because removing it failed.
+    Failed(Failure),
+    /// Left in place because the run was interrupted before reaching it.
+    Skipped,
+    /// Left in place because a process or build was using it; the message
+    /// says which.
+    InUse(String),
+    /// Left in place because it is on a read-only filesystem.
+    ReadOnly,
+}
+
+impl Outcome {
+    /// Short name for the outcome, as written to the deletion log.
+    pub fn label(&self) -> &'static str {
+        match self {
+            Outcome::Deleted => "deleted",
+            Outcome::Cleaned(_) => "cleaned",
+            Outcome::Trashed => "trashed",
+            Outcome::Quarantined(_) => "quarantined",
+            Outcome::MovedAside => "moved-aside",
+            Outcome::Failed(_) => "failed",
+            Outcome::Skipped => "skipped",
+            Outcome::InUse(_) => "in-use",
+            Outcome::ReadOnly => "read-only",
+        }
+    }
+
+    /// Whether the artifact is gone from where it was: deleted, trashed,
+    /// quarantined or moved aside.
+    pub fn is_removed(&self) -> bool {
+        matches!(
+            self,
+            Outcome::Deleted
+                | Outcome::Cleaned(_)
+                | Outcome::Trashed
+                | Outcome::Quarantined(_)
+                | Outcome::MovedAside
+        )
+    }
+
+    /// Why the artifact was left in place, if it was for a stated reason.
+    pub fn error(&self) -> Option<&str> {
+        match self {
+            Outcome::Failed(failure) => Some(&failure.message),
+            Outcome::InUse(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+/// Why an artifact could not be removed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct Failure {
+    pub kind: FailureKind,
+    /// The full error message.
+    pub message: String,
+}
+
+impl Failure {
+    pub fn new(kind: FailureKind, message: impl Into<String>) -> Self {
+        Self {
+            kind,
+            message: message.into(),
+        }
+    }
+}
+
+impl fmt::Display for Failure {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.message)
+    }
+}
+
+/// The broad cause of a [`Failure`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(Serialize, Deserialize),
+    serde(rename_all = "kebab-case")
+)]
+pub enum FailureKind {
+    /// Permission was denied, or the filesystem is read-only.
+    PermissionDenied,
+    /// Something kept files busy even after retrying.
+    Busy,
+    /// Deletion made no progress within `--timeout`.
+    TimedOut,
+    /// Moving to the trash failed.
+    Trash,
+    /// Moving into quarantine failed.
+    Quarantine,
+    /// The before-delete hook failed.
+    Hook,
+    /// Any other error.
+    Other,
+}
+
+/// One line of the deletion log.
+#[derive(Debug, Serialize)]
+struct Record<'a> {
+    timestamp: &'a str,
+    path: &'a Path,
+    build_system: &'a str,
+    size_bytes: u64,
+    outcome: &'static str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    run: Option<&'a str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    tool: Option<&'a str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<&'a str>,
+}
+
+#[derive(Serialize)]
+struct Records<'a> {
+    deletion: Vec<Record<'a>>,
+}
+
+/// Default deletion log, `deletions.toml` under the data directory.
+pub fn default_path() -> Result<PathBuf, AuditError> {
+    data_dir()
+        .map(|d| d.join("deletions.toml"))
+        .ok_or(AuditError::NoHome)
+}
+
+/// Append one `[[deletion]]` table per artifact to the log at `path`, creating
+/// it and its parent directories if needed. Appending keeps the whole file a
+/// valid TOML document.
+pub fn append(
+    path: &Path,
+    time: SystemTime,
+    artifacts: &[Artifact],
+    outcomes: &[Outcome],
+) -> Result<(), AuditError> {
+    let timestamp = UtcTime::of(time).to_string();
+    let deletion = artifacts
+        .iter()
+        .zip(outcomes)
+        .map(|(artifact, outcome)| Record {
+            timestamp: &timestamp,
+            path: &artifact.path,
+            build_system: artifact.build_system,
+            size_bytes: artifact.size_bytes,
+            outcome: outcome.label(),
+            run: match outcome {
+                Outcome::Quarantined(r) => Some(r.as_str()),
+                _ => None,
+            },
+            tool: match outcome {
+                Outcome::Cleaned(t) => Some(t.as_str()),
+                _ => None,
+            },
+            error: outcome.error(),
+        })
+        .collect();
+    let text = toml::to_string(&Records { deletion })?;
+
+    let io = |source| AuditError::Io {
+        path: path.to_path_buf(),
+        source,
+    };
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent).map_err(io)?;
+    }
+    let mut file = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)
+        .map_err(io)?;
+    writeln!(file, "{text}").map_err(io)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::{Duration, UNIX_EPOCH};
+    use tempfile::TempDir;
+
+    fn artifact(path: &str) -> Artifact {
+        Artifact {
+            size_bytes: 2048,
+            ..Artifact::for_test(path, "Rust/Cargo")
+        }
+    }
+
+    #[derive(serde::Deserialize)]
+    struct Log {
+        deletion: Vec<toml::Table>,
+    }
+
+    #[test]
+    #[cfg(feature = "serde")]
+    fn outcomes_serialize_by_label() {
+        let outcomes = [
+            Outcome::Deleted,
+            Outcome::Cleaned("cargo clean".to_string()),
+            Outcome::Failed(Failure::new(FailureKind::PermissionDenied, "denied")),
+            Outcome::InUse("cargo build".to_string()),
+            Outcome::ReadOnly,
+            Outcome::MovedAside,
+        ];
+        let json = serde_json::to_value(&outcomes).unwrap();
+        assert_eq!(
+            json,
+            serde_json::json!([
+                "deleted",
+                { "cleaned": "cargo clean" },
+                { "failed": { "kind": "permission-denied", "message": "denied" } },
+                { "in-use": "cargo build" },
+                "read-only",
+                "moved-aside",
+            ])
+        );
+        let read: Vec<Outcome> = serde_json::from_value(json).unwrap();
+        assert_eq!(read, outcomes);
+    }
+
+    #[test]
+    fn appends_parseable_records() {
+        let tmp = TempDir::new().unwrap();
+        let log = tmp.path().join("nested/deletions.toml");
+        let time = UNIX_EPOCH + Duration::from_secs(1_709_210_096);
+        let artifacts = [artifact("/dev/app/target"), artifact("/dev/api/target")];
+
+        append(
+            &log,
+            time,
+            &artifacts,
+            &[
+                Outcome::Deleted,
+                Outcome::Failed(Failure::new(FailureKind::Busy, "busy")),
+            ],
+        )
+        .unwrap();
+        append(
+            &log,
+            time,
+            &artifacts[..1],
+            &[Outcome::Quarantined("r1".into())],
+        )
+        .unwrap();
+
+        let parsed: Log = toml::from_str(&std::fs::read_to_string(&log).unwrap()).unwrap();
+        assert_eq!(parsed.deletion.len(), 3);
+        let first = &parsed.deletion[0];
+        assert_eq!(first["timestamp"].as_str(), Some("2024-02-29T12:34:56Z"));
+        assert_eq!(first["path"].as_str(), Some("/dev/app/target"));
+        assert_eq!(first["build_system"].as_str(), Some("Rust/Cargo"));
+        assert_eq!(first["size_bytes"].as_integer(), Some(2048));
+        assert_eq!(first["outcome"].as_str(), Some("deleted"));
+        assert_eq!(parsed.deletion[1]["error"].as_str(), Some("busy"));
+        assert_eq!(parsed.deletion[2]["run"].as_str(), Some("r1"));
+    }
+}
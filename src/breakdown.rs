@@ -0,0 +1,126 @@
+use std::collections::HashMap;
+use std::path::Path;
+
+use jwalk::WalkDir;
+
+/// Directories whose contents are grouped under the directory's name rather
+/// than by extension, since they hold many kinds of files that only matter
+/// together: rustc's incremental compilation cache and Cargo's fingerprints.
+const GROUPED_DIRS: &[&str] = &["incremental", ".fingerprint"];
+
+/// Bytes of one kind of file inside an artifact.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Category {
+    /// A file extension such as `.rlib`, a grouped directory such as
+    /// `incremental/`, or `(no extension)`.
+    pub name: String,
+    pub bytes: u64,
+}
+
+/// Where the bytes inside the artifact at `path` live, by category, largest
+/// first. Counts the apparent length of each file.
+pub fn breakdown(path: &Path) -> Vec<Category> {
+    let mut bytes: HashMap<String, u64> = HashMap::new();
+    for entry in WalkDir::new(path)
+        .follow_links(false)
+        .skip_hidden(false)
+        .into_iter()
+        .filter_map(|e| e.ok())
+        .filter(|e| e.file_type().is_file())
+    {
+        let Ok(meta) = entry.metadata() else {
+            continue;
+        };
+        let file = entry.path();
+        // A file artifact is categorized by its own name.
+        let rel = match file.strip_prefix(path) {
+            Ok(rel) if !rel.as_os_str().is_empty() => rel,
+            _ => Path::new(file.file_name().unwrap_or_default()),
+        };
+        *bytes.entry(category(rel)).or_default() += meta.len();
+    }
+    let mut categories: Vec<Category> = bytes
+        .into_iter()
+        .map(|(name, bytes)| Category { name, bytes })
+        .collect();
+    categories.sort_by(|a, b| b.bytes.cmp(&a.bytes).then_with(|| a.name.cmp(&b.name)));
+    categories
+}
+
+/// The category of the file at `rel`, relative to its artifact.
+fn category(rel: &Path) -> String {
+    let grouped = rel
+        .parent()
+        .into_iter()
+        .flat_map(Path::components)
+        .find(|c| {
+            GROUPED_DIRS
+                .iter()
+                .any(|d| c.as_os_str() == std::ffi::OsStr::new(d))
+        });
+    if let Some(dir) = grouped {
+        return format!("{}/", dir.as_os_str().to_string_lossy());
+    }
+    match rel.extension() {
+        Some(ext) => format!(".{}", ext.to_string_lossy().to_lowercase()),
+        None => "(no extension)".to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use tempfile::TempDir;
+
+    #[test]
+    fn categories_by_extension_and_grouped_directory() {
+        assert_eq!(category(Path::new("debug/deps/libfoo.rlib")), ".rlib");
+        assert_eq!(category(Path::new("dist/app.JS.MAP")), ".map");
+        assert_eq!(category(Path::new("debug/app")), "(no extension)");
+        assert_eq!(
+            category(Path::new("debug/incremental/app-1x/s-2y/dep-graph.bin")),
+            "incremental/"
+        );
+        assert_eq!(
+            category(Path::new("debug/.fingerprint/app-1x/bin-app")),
+            ".fingerprint/"
+        );
+        // Only directories group; a file named like one does not.
+        assert_eq!(category(Path::new("incremental")), "(no extension)");
+    }
+
+    #[test]
+    fn breakdown_sums_categories_largest_first() {
+        let tmp = TempDir::new().unwrap();
+        let target = tmp.path().join("target");
+        fs::create_dir_all(target.join("debug/deps")).unwrap();
+        fs::create_dir_all(target.join("debug/incremental/app")).unwrap();
+        fs::write(target.join("debug/deps/liba.rlib"), vec![0u8; 600]).unwrap();
+        fs::write(target.join("debug/deps/libb.rlib"), vec![0u8; 100]).unwrap();
+        fs::write(
+            target.join("debug/incremental/app/query-cache.bin"),
+            vec![0u8; 250],
+        )
+        .unwrap();
+        fs::write(target.join("debug/app"), vec![0u8; 50]).unwrap();
+
+        assert_eq!(
+            breakdown(&target),
+            [
+                Category {
+                    name: ".rlib".to_string(),
+                    bytes: 700
+                },
+                Category {
+                    name: "incremental/".to_string(),
+                    bytes: 250
+                },
+                Category {
+                    name: "(no extension)".to_string(),
+                    bytes: 50
+                },
+            ]
+        );
+    }
+}
@@ -0,0 +1,336 @@
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use log::debug;
+use serde::{Deserialize, Serialize};
+
+use crate::interrupt::CancelToken;
+use crate::progress::Progress;
+use crate::scanner::Artifact;
+use crate::size::{SizeOptions, SizeStats, SizingProgress, compute_sizes_with_progress};
+
+/// Error saving the size cache.
+#[derive(thiserror::Error, Debug)]
+pub enum CacheError {
+    #[error("cannot write size cache {}: {source}", path.display())]
+    Io {
+        path: PathBuf,
+        source: std::io::Error,
+    },
+    #[error("cannot write size cache: {0}")]
+    Serialize(#[from] toml::ser::Error),
+}
+
+/// Cache directory, `clean-builds` under `$XDG_CACHE_HOME` or `~/.cache`.
+pub fn cache_dir() -> Option<PathBuf> {
+    let cache_home = std::env::var_os("XDG_CACHE_HOME")
+        .filter(|d| !d.is_empty())
+        .map(PathBuf::from)
+        .or_else(|| {
+            std::env::var_os("HOME")
+                .filter(|h| !h.is_empty())
+                .map(|h| PathBuf::from(h).join(".cache"))
+        })?;
+    Some(cache_home.join("clean-builds"))
+}
+
+/// Default size cache, `sizes.toml` under [`cache_dir`].
+pub fn default_path() -> Option<PathBuf> {
+    cache_dir().map(|d| d.join("sizes.toml"))
+}
+
+/// What an artifact looked like when it was sized: the modification time of
+/// the artifact itself and how many entries it held directly. Changes deeper
+/// inside that leave both alone go unnoticed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+struct Stamp {
+    modified_ns: u64,
+    entries: u64,
+}
+
+impl Stamp {
+    fn of(path: &Path) -> Option<Self> {
+        let meta = std::fs::symlink_metadata(path).ok()?;
+        let modified_ns = epoch_ns(meta.modified().ok()?)?;
+        let entries = if meta.is_dir() {
+            std::fs::read_dir(path).ok()?.count() as u64
+        } else {
+            meta.len()
+        };
+        Some(Self {
+            modified_ns,
+            entries,
+        })
+    }
+}
+
+/// One cached size, as stored.
+#[derive(Debug, Serialize, Deserialize)]
+struct Entry {
+    path: PathBuf,
+    /// Whether `bytes` is allocated space (`--du`) rather than apparent size.
+    allocated: bool,
+    #[serde(flatten)]
+    stamp: Stamp,
+    bytes: u64,
+    files: u64,
+    dirs: u64,
+    /// [`SizeStats::newest`] in nanoseconds since the epoch.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    newest_ns: Option<u64>,
+}
+
+impl Entry {
+    fn stats(&self) -> SizeStats {
+        SizeStats {
+            bytes: self.bytes,
+            files: self.files,
+            dirs: self.dirs,
+            newest: self
+                .newest_ns
+                .map(|ns| UNIX_EPOCH + Duration::from_nanos(ns)),
+            margin: None,
+            outside_bytes: 0,
+            reclaimable: None,
+        }
+    }
+}
+
+/// Nanoseconds since the epoch, if `time` is after it and fits.
+fn epoch_ns(time: SystemTime) -> Option<u64> {
+    let since = time.duration_since(UNIX_EPOCH).ok()?;
+    u64::try_from(since.as_nanos()).ok()
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct Entries {
+    #[serde(default)]
+    artifact: Vec<Entry>,
+}
+
+/// Artifact sizes from earlier runs, so an unchanged artifact need not be
+/// walked again (disabled by `--no-cache`).
+#[derive(Debug, Default)]
+pub struct SizeCache {
+    sizes: HashMap<(PathBuf, bool), (Stamp, SizeStats)>,
+}
+
+impl SizeCache {
+    /// Read the cache at `path`. A missing or unreadable cache is empty.
+    pub fn load(path: &Path) -> Self {
+        let entries: Entries = match std::fs::read_to_string(path) {
+            Ok(text) => toml::from_str(&text).unwrap_or_else(|e| {
+                debug!("Ignoring invalid size cache {}: {e}", path.display());
+                Entries::default()
+            }),
+            Err(e) => {
+                debug!("No size cache at {}: {e}", path.display());
+                Entries::default()
+            }
+        };
+        Self {
+            sizes: entries
+                .artifact
+                .into_iter()
+                .map(|e| {
+                    let stats = e.stats();
+                    ((e.path, e.allocated), (e.stamp, stats))
+                })
+                .collect(),
+        }
+    }
+
+    /// Write the cache to `path`, dropping artifacts that no longer exist.
+    /// The file is replaced in one step, so concurrent runs never leave it
+    /// half written.
+    pub fn save(&self, path: &Path) -> Result<(), CacheError> {
+        let mut artifact: Vec<Entry> = self
+            .sizes
+            .iter()
+            .filter(|((path, _), _)| path.symlink_metadata().is_ok())
+            .map(|((path, allocated), (stamp, stats))| Entry {
+                path: path.clone(),
+                allocated: *allocated,
+                stamp: *stamp,
+                bytes: stats.bytes,
+                files: stats.files,
+                dirs: stats.dirs,
+                newest_ns: stats.newest.and_then(epoch_ns),
+            })
+            .collect();
+        artifact.sort_by(|a, b| (&a.path, a.allocated).cmp(&(&b.path, b.allocated)));
+        let text = toml::to_string(&Entries { artifact })?;
+        let io = |source| CacheError::Io {
+            path: path.to_path_buf(),
+            source,
+        };
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir).map_err(io)?;
+        }
+        let partial = path.with_extension(format!("toml.{}", std::process::id()));
+        std::fs::write(&partial, text).map_err(io)?;
+        std::fs::rename(&partial, path).map_err(io)
+    }
+
+    /// Size `artifacts` like [`compute_sizes_with`](crate::size::compute_sizes_with), reusing cached sizes of
+    /// those unchanged since and caching the rest. Hard links shared between
+    /// artifacts (`options.dedup_links`) depend on every artifact sized
+    /// together, estimates (`options.estimate`) are not worth keeping, and
+    /// link targets (`options.link_targets`) and what deleting the artifact
+    /// would free (`options.reclaimable`) change outside the artifact, so
+    /// all of them bypass the cache. Sizes cut short by `options.stop_above`
+    /// are not cached.
+    pub fn compute_sizes(&mut self, artifacts: &mut [Artifact], options: &SizeOptions) {
+        self.compute_sizes_with_progress(artifacts, options, &(), &CancelToken::new());
+    }
+
+    /// [`compute_sizes`](Self::compute_sizes), reporting progress like
+    /// [`compute_sizes_with_progress`]. Cached artifacts are reported
+    /// before any is walked, and are sized even when `cancel` is cancelled.
+    pub fn compute_sizes_with_progress(
+        &mut self,
+        artifacts: &mut [Artifact],
+        options: &SizeOptions,
+        progress: &dyn Progress,
+        cancel: &CancelToken,
+    ) {
+        if options.dedup_links || options.estimate || options.link_targets || options.reclaimable {
+            compute_sizes_with_progress(artifacts, options, progress, cancel);
+            return;
+        }
+        // Stamp before walking, so changes made meanwhile are caught next time.
+        let stamps: Vec<Option<Stamp>> = artifacts.iter().map(|a| Stamp::of(&a.path)).collect();
+        let mut missing = Vec::new();
+        let mut hits = SizingProgress {
+            total: artifacts.len(),
+            ..Default::default()
+        };
+        for (i, (artifact, stamp)) in artifacts.iter_mut().zip(&stamps).enumerate() {
+            let key = (artifact.path.clone(), options.allocated);
+            match (stamp, self.sizes.get(&key)) {
+                (Some(stamp), Some((cached, stats))) if stamp == cached => {
+                    debug!("{}: cached size", artifact.path.display());
+                    artifact.size_bytes = stats.bytes;
+                    artifact.stats = Some(*stats);
+                    hits.done += 1;
+                    hits.bytes += stats.bytes;
+                    progress.on_sized(artifact, stats.bytes, hits);
+                }
+                _ => missing.push(i),
+            }
+        }
+        debug!(
+            "Size cache: {} hits, {} misses",
+            artifacts.len() - missing.len(),
+            missing.len()
+        );
+
+        let mut walked: Vec<Artifact> = missing.iter().map(|&i| artifacts[i].clone()).collect();
+        compute_sizes_with_progress(
+            &mut walked,
+            options,
+            &AfterCached { progress, hits },
+            cancel,
+        );
+        for (i, artifact) in missing.into_iter().zip(walked) {
+            let partial = |stats: &SizeStats| options.stop_above.is_some_and(|l| stats.bytes > l);
+            if let (Some(stamp), Some(stats)) = (stamps[i], artifact.stats.filter(|s| !partial(s)))
+            {
+                self.sizes
+                    .insert((artifact.path.clone(), options.allocated), (stamp, stats));
+            }
+            artifacts[i].size_bytes = artifact.size_bytes;
+            artifacts[i].stats = artifact.stats;
+        }
+    }
+}
+
+/// Reports sizing of the artifacts missing from the cache as following on
+/// from those found in it.
+struct AfterCached<'a> {
+    progress: &'a dyn Progress,
+    hits: SizingProgress,
+}
+
+impl Progress for AfterCached<'_> {
+    fn on_sized(&self, artifact: &Artifact, bytes: u64, progress: SizingProgress) {
+        self.progress.on_sized(
+            artifact,
+            bytes,
+            SizingProgress {
+                done: self.hits.done + progress.done,
+                total: self.hits.total,
+                bytes: self.hits.bytes + progress.bytes,
+            },
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use tempfile::TempDir;
+
+    #[test]
+    fn unchanged_artifacts_reuse_their_cached_size() {
+        let tmp = TempDir::new().unwrap();
+        let dir = tmp.path().join("target");
+        fs::create_dir_all(dir.join("debug")).unwrap();
+        fs::write(dir.join("debug/app"), "12345").unwrap();
+        let mut artifacts = vec![Artifact::for_test(dir.clone(), "Rust/Cargo")];
+        let mut cache = SizeCache::default();
+
+        cache.compute_sizes(&mut artifacts, &SizeOptions::default());
+        assert_eq!(artifacts[0].size_bytes, 5);
+
+        // A change below the top level leaves the stamp alone.
+        fs::write(dir.join("debug/app"), "1234567890").unwrap();
+        cache.compute_sizes(&mut artifacts, &SizeOptions::default());
+        assert_eq!(artifacts[0].size_bytes, 5);
+
+        // A new entry at the top level invalidates it.
+        fs::write(dir.join(".rustc_info.json"), "{}").unwrap();
+        cache.compute_sizes(&mut artifacts, &SizeOptions::default());
+        assert_eq!(artifacts[0].size_bytes, 12);
+    }
+
+    #[test]
+    fn saved_cache_loads_back() {
+        let tmp = TempDir::new().unwrap();
+        let dir = tmp.path().join("target");
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(dir.join("app"), "12345").unwrap();
+        let gone = tmp.path().join("gone");
+        fs::create_dir_all(&gone).unwrap();
+        let mut cache = SizeCache::default();
+        cache.compute_sizes(
+            &mut [
+                Artifact::for_test(dir.clone(), "Rust/Cargo"),
+                Artifact::for_test(gone.clone(), "Rust/Cargo"),
+            ],
+            &SizeOptions::default(),
+        );
+        fs::write(dir.join("app"), "1234567890").unwrap();
+        fs::remove_dir(&gone).unwrap();
+
+        let path = tmp.path().join("cache/sizes.toml");
+        cache.save(&path).unwrap();
+        let mut loaded = SizeCache::load(&path);
+        let mut artifacts = [Artifact::for_test(dir, "Rust/Cargo")];
+        loaded.compute_sizes(&mut artifacts, &SizeOptions::default());
+
+        assert_eq!(artifacts[0].size_bytes, 5);
+        assert_eq!(artifacts[0].stats.unwrap().files, 1);
+        assert_eq!(loaded.sizes.len(), 1);
+    }
+
+    #[test]
+    fn invalid_cache_is_empty() {
+        let tmp = TempDir::new().unwrap();
+        let path = tmp.path().join("sizes.toml");
+        fs::write(&path, "not toml [").unwrap();
+        assert!(SizeCache::load(&path).sizes.is_empty());
+    }
+}
@@ -1,53 +1,605 @@
-use clap::Parser;
+use clap::{ArgGroup, Parser, Subcommand};
+use std::ffi::OsString;
+use std::num::NonZeroUsize;
 use std::path::PathBuf;
+use std::time::Duration;
+
+use crate::age::parse_duration;
+use crate::expr::Expr;
+use crate::rules::Risk;
+use crate::schedule::Profile;
+use crate::size::parse_size;
+
+const EXIT_STATUS: &str = "\
+Exit status:
+  0    Success: every selected artifact was deleted (except those on read-only
+       filesystems, unless --strict), or a dry run finished
+  1    Error (bad option value, unreadable path, I/O failure)
+  2    Invalid command line
+  3    Some deletions failed
+  4    Nothing was deleted: every deletion failed or the prompt was declined
+  5    Artifacts were found in a dry run with --exit-code
+  6    A confirmation was needed, but --no-input forbade asking
+  130  Interrupted by Ctrl+C";
 
 /// Recursively scan for and remove build artifacts.
 ///
 /// By default, runs in dry-run mode showing a summary of artifacts found.
 /// Use --delete to actually remove them.
 #[derive(Parser, Debug)]
-#[command(name = "clean-builds", version)]
+#[command(
+    name = "clean-builds",
+    version,
+    args_conflicts_with_subcommands = true,
+    after_help = EXIT_STATUS,
+    // Options that only make sense when deleting.
+    group = ArgGroup::new("deletion").multiple(true).requires("delete")
+)]
 pub struct Cli {
+    #[command(subcommand)]
+    pub command: Option<Command>,
+
     /// Root directory to scan
     #[arg(default_value = ".")]
     pub path: PathBuf,
 
+    /// Scan the whole Git repository containing PATH, from its top level
+    #[arg(long, env = "CLEAN_BUILDS_GIT_ROOT")]
+    pub git_root: bool,
+
     /// Actually delete artifacts (default is dry-run)
     #[arg(long)]
     pub delete: bool,
 
+    /// Only report what would be deleted; an error with --delete or --yes
+    #[arg(
+        short = 'n',
+        long,
+        conflicts_with_all = ["delete", "yes", "deletion"],
+        env = "CLEAN_BUILDS_DRY_RUN"
+    )]
+    pub dry_run: bool,
+
     /// Skip confirmation prompt (use with --delete)
     #[arg(short = 'y', long = "yes")]
     pub yes: bool,
 
+    /// Exit with status 6 instead of asking for a confirmation that --yes does not give
+    #[arg(long, env = "CLEAN_BUILDS_NO_INPUT")]
+    pub no_input: bool,
+
+    /// Delete artifacts of these risk categories without asking, and ask only about the rest (use with --delete)
+    #[arg(
+        long,
+        value_name = "RISK",
+        value_enum,
+        value_delimiter = ',',
+        group = "deletion",
+        conflicts_with_all = ["yes", "confirm_per_system"]
+    )]
+    pub yes_for: Vec<Risk>,
+
+    /// Ask separately for each build system instead of once for everything (use with --delete)
+    #[arg(long, group = "deletion", conflicts_with = "yes")]
+    pub confirm_per_system: bool,
+
+    /// Move artifacts to the system trash instead of deleting them (use with --delete)
+    #[arg(long, group = "deletion")]
+    pub trash: bool,
+
+    /// Move artifacts into a quarantine that `restore` can undo (use with --delete)
+    #[arg(long, group = "deletion", conflicts_with = "trash")]
+    pub quarantine: bool,
+
+    /// Run the build tool's clean command (e.g., `cargo clean`) where there is one (use with --delete)
+    #[arg(long, group = "deletion", conflicts_with_all = ["trash", "quarantine"])]
+    pub use_tool: bool,
+
+    /// Move artifacts aside instantly and delete them in a detached background process (use with --delete)
+    #[arg(
+        long,
+        group = "deletion",
+        conflicts_with_all = ["trash", "quarantine", "use_tool", "contents_only", "shred"]
+    )]
+    pub background: bool,
+
+    /// Overwrite file contents before deleting them, for artifacts that may hold secrets (use with --delete)
+    #[arg(
+        long,
+        group = "deletion",
+        conflicts_with_all = ["trash", "quarantine", "use_tool"]
+    )]
+    pub shred: bool,
+
+    /// Empty artifact directories but keep the directories themselves (use with --delete)
+    #[arg(
+        long,
+        group = "deletion",
+        conflicts_with_all = ["trash", "quarantine", "use_tool"]
+    )]
+    pub contents_only: bool,
+
+    /// Delete slowly at low IO priority so a background cleanup doesn't bog down the machine (use with --delete)
+    #[arg(long, group = "deletion")]
+    pub nice: bool,
+
+    /// Give up on an artifact whose deletion makes no progress for DURATION, e.g. on a dead mount (use with --delete)
+    #[arg(long, value_name = "DURATION", value_parser = parse_duration, group = "deletion")]
+    pub timeout: Option<Duration>,
+
+    /// Deleting more than SIZE at once requires typing the artifact count to confirm (default: 100GB; use with --delete)
+    #[arg(long, value_name = "SIZE", value_parser = parse_size, group = "deletion")]
+    pub strict_confirm_size: Option<u64>,
+
+    /// Deleting more than N artifacts at once requires typing their count to confirm (default: 1000; use with --delete)
+    #[arg(long, value_name = "N", group = "deletion")]
+    pub strict_confirm_count: Option<usize>,
+
+    /// Count artifacts skipped on read-only filesystems as failures in the exit status (use with --delete)
+    #[arg(long, group = "deletion")]
+    pub strict: bool,
+
+    /// Make read-only directories inside artifacts writable when they block deletion, like rm -rf (use with --delete)
+    #[arg(long, group = "deletion")]
+    pub force_permissions: bool,
+
+    /// Append a record of each deletion to FILE (default: deletions.toml in the data directory)
+    #[arg(
+        long,
+        value_name = "FILE",
+        num_args = 0..=1,
+        require_equals = true,
+        group = "deletion"
+    )]
+    pub log_file: Option<Option<PathBuf>>,
+
+    /// Run CMD before deleting anything; if it fails, nothing is deleted (use with --delete)
+    #[arg(long, value_name = "CMD", group = "deletion")]
+    pub before_run: Option<String>,
+
+    /// Run CMD after the deletion run (use with --delete)
+    #[arg(long, value_name = "CMD", group = "deletion")]
+    pub after_run: Option<String>,
+
+    /// Run CMD before deleting each artifact; if it fails, the artifact is kept (use with --delete)
+    #[arg(long, value_name = "CMD", group = "deletion")]
+    pub before_delete: Option<String>,
+
+    /// Run CMD after deleting each artifact (use with --delete)
+    #[arg(long, value_name = "CMD", group = "deletion")]
+    pub after_delete: Option<String>,
+
     /// Show individual artifact paths
-    #[arg(short, long)]
+    #[arg(short, long, env = "CLEAN_BUILDS_VERBOSE")]
     pub verbose: bool,
 
-    /// Include only artifacts matching glob pattern (repeatable)
-    #[arg(long, value_name = "PATTERN")]
+    /// Include only artifacts matching glob pattern (repeatable, `!PATTERN` negates)
+    #[arg(
+        long,
+        value_name = "PATTERN",
+        env = "CLEAN_BUILDS_INCLUDE",
+        value_delimiter = ','
+    )]
     pub include: Vec<String>,
 
-    /// Exclude artifacts matching glob pattern (repeatable)
-    #[arg(long, value_name = "PATTERN")]
+    /// Exclude artifacts matching glob pattern (repeatable, `!PATTERN` negates)
+    #[arg(
+        long,
+        value_name = "PATTERN",
+        env = "CLEAN_BUILDS_EXCLUDE",
+        value_delimiter = ','
+    )]
     pub exclude: Vec<String>,
 
+    /// Include only artifacts whose relative path matches REGEX (repeatable)
+    #[arg(long, value_name = "REGEX", env = "CLEAN_BUILDS_INCLUDE_REGEX")]
+    pub include_regex: Vec<String>,
+
+    /// Exclude artifacts whose relative path matches REGEX (repeatable)
+    #[arg(long, value_name = "REGEX", env = "CLEAN_BUILDS_EXCLUDE_REGEX")]
+    pub exclude_regex: Vec<String>,
+
+    /// Include only artifacts whose project directory name matches GLOB (repeatable)
+    #[arg(
+        long,
+        value_name = "GLOB",
+        env = "CLEAN_BUILDS_PROJECT",
+        value_delimiter = ','
+    )]
+    pub project: Vec<String>,
+
+    /// Exclude artifacts whose project directory name matches GLOB (repeatable)
+    #[arg(
+        long,
+        value_name = "GLOB",
+        env = "CLEAN_BUILDS_EXCLUDE_PROJECT",
+        value_delimiter = ','
+    )]
+    pub exclude_project: Vec<String>,
+
+    /// Read include patterns from FILE, one per line, `#` comments (repeatable)
+    #[arg(
+        long,
+        value_name = "FILE",
+        env = "CLEAN_BUILDS_INCLUDE_FROM",
+        value_delimiter = ','
+    )]
+    pub include_from: Vec<PathBuf>,
+
+    /// Read exclude patterns from FILE, one per line, `#` comments (repeatable)
+    #[arg(
+        long,
+        value_name = "FILE",
+        env = "CLEAN_BUILDS_EXCLUDE_FROM",
+        value_delimiter = ','
+    )]
+    pub exclude_from: Vec<PathBuf>,
+
     /// Include only these build systems (repeatable, see --list-systems)
-    #[arg(long, value_name = "ID", conflicts_with = "exclude_system")]
+    #[arg(
+        long,
+        value_name = "ID",
+        conflicts_with = "exclude_system",
+        env = "CLEAN_BUILDS_SYSTEM",
+        value_delimiter = ','
+    )]
     pub system: Vec<String>,
 
     /// Exclude these build systems (repeatable, see --list-systems)
-    #[arg(long, value_name = "ID", conflicts_with = "system")]
+    #[arg(
+        long,
+        value_name = "ID",
+        conflicts_with = "system",
+        env = "CLEAN_BUILDS_EXCLUDE_SYSTEM",
+        value_delimiter = ','
+    )]
     pub exclude_system: Vec<String>,
 
+    /// Include only these risk categories (comma-separated or repeatable)
+    #[arg(
+        long,
+        value_name = "RISK",
+        value_enum,
+        value_delimiter = ',',
+        env = "CLEAN_BUILDS_RISK"
+    )]
+    pub risk: Vec<Risk>,
+
+    /// Include only artifacts last modified more than DURATION ago (e.g., 30d)
+    #[arg(long, value_name = "DURATION", value_parser = parse_duration, env = "CLEAN_BUILDS_OLDER_THAN")]
+    pub older_than: Option<Duration>,
+
+    /// Skip artifacts modified within DURATION, protecting active projects (e.g., 7d)
+    #[arg(
+        env = "CLEAN_BUILDS_NEWER_THAN",
+        long,
+        value_name = "DURATION",
+        value_parser = parse_duration,
+        visible_alias = "keep-recent"
+    )]
+    pub newer_than: Option<Duration>,
+
+    /// Include only artifacts whose files nobody has read within DURATION (e.g., 90d)
+    #[arg(long, value_name = "DURATION", value_parser = parse_duration, env = "CLEAN_BUILDS_NOT_ACCESSED_IN")]
+    pub not_accessed_in: Option<Duration>,
+
+    /// Include only artifacts of projects whose source files are unchanged for DURATION (e.g., 60d)
+    #[arg(long, value_name = "DURATION", value_parser = parse_duration, env = "CLEAN_BUILDS_IDLE_FOR")]
+    pub idle_for: Option<Duration>,
+
+    /// Skip artifacts fewer than N directories below PATH (PATH/target is depth 1)
+    #[arg(long, value_name = "N", env = "CLEAN_BUILDS_MIN_DEPTH")]
+    pub min_depth: Option<usize>,
+
+    /// Skip artifacts more than N directories below PATH
+    #[arg(long, value_name = "N", env = "CLEAN_BUILDS_MAX_ARTIFACT_DEPTH")]
+    pub max_artifact_depth: Option<usize>,
+
+    /// Leave alone the N most recently built projects of each build system
+    #[arg(long, value_name = "N", env = "CLEAN_BUILDS_KEEP_LATEST")]
+    pub keep_latest: Option<usize>,
+
+    /// Skip artifacts smaller than SIZE (e.g., 500MB)
+    #[arg(long, value_name = "SIZE", value_parser = parse_size, env = "CLEAN_BUILDS_MIN_SIZE")]
+    pub min_size: Option<u64>,
+
+    /// Skip artifacts larger than SIZE (e.g., 10GB)
+    #[arg(long, value_name = "SIZE", value_parser = parse_size, env = "CLEAN_BUILDS_MAX_SIZE")]
+    pub max_size: Option<u64>,
+
+    /// Measure the disk space artifacts take up, like du, instead of their files' apparent size
+    #[arg(long, env = "CLEAN_BUILDS_DU")]
+    pub du: bool,
+
+    /// Measure every artifact afresh instead of reusing sizes of unchanged ones from earlier runs
+    #[arg(long, env = "CLEAN_BUILDS_NO_CACHE")]
+    pub no_cache: bool,
+
+    /// Estimate sizes by sampling wide directories instead of walking every file
+    #[arg(long, conflicts_with = "dedup_links", env = "CLEAN_BUILDS_ESTIMATE")]
+    pub estimate: bool,
+
+    /// Also report how much deleting artifacts would free, without files hard-linked or cloned elsewhere
+    #[arg(long, conflicts_with = "estimate", env = "CLEAN_BUILDS_RECLAIMABLE")]
+    pub reclaimable: bool,
+
+    /// Size artifacts while scanning for them, in one walk (the default with --no-cache and no filters)
+    #[arg(
+        long,
+        conflicts_with_all = ["estimate", "link_targets"],
+        env = "CLEAN_BUILDS_SINGLE_PASS"
+    )]
+    pub single_pass: bool,
+
+    /// Use N threads for scanning and sizing (default: one per CPU)
+    #[arg(short, long, value_name = "N", env = "CLEAN_BUILDS_JOBS")]
+    pub jobs: Option<NonZeroUsize>,
+
+    /// Also report how much symlinks inside artifacts point to outside them
+    #[arg(long, conflicts_with = "estimate", env = "CLEAN_BUILDS_LINK_TARGETS")]
+    pub link_targets: bool,
+
+    /// Count files hard-linked into several artifacts only once
+    #[arg(long, env = "CLEAN_BUILDS_DEDUP_LINKS")]
+    pub dedup_links: bool,
+
+    /// Delete only the oldest artifacts needed to free SIZE (e.g., 50GB)
+    #[arg(long, value_name = "SIZE", value_parser = parse_size, env = "CLEAN_BUILDS_FREE")]
+    pub free: Option<u64>,
+
+    /// Include only artifacts owned by USER (a name or numeric UID)
+    #[arg(
+        long,
+        value_name = "USER",
+        conflicts_with = "only_mine",
+        env = "CLEAN_BUILDS_OWNER"
+    )]
+    pub owner: Option<String>,
+
+    /// Include only artifacts owned by the current user
+    #[arg(long, env = "CLEAN_BUILDS_ONLY_MINE")]
+    pub only_mine: bool,
+
+    /// Include only artifacts on the filesystem holding PATH, e.g. a full disk to --free space on
+    #[arg(long, value_name = "PATH", env = "CLEAN_BUILDS_ON_VOLUME")]
+    pub on_volume: Option<PathBuf>,
+
+    /// Include only artifacts for which EXPR is true, e.g. 'size > 1GB && age > 30d' (repeatable)
+    #[arg(
+        long,
+        value_name = "EXPR",
+        value_parser = Expr::parse,
+        env = "CLEAN_BUILDS_FILTER",
+        value_delimiter = ','
+    )]
+    pub filter: Vec<Expr>,
+
+    /// Skip artifacts in Git repositories with unpushed commits or stashes
+    #[arg(long, env = "CLEAN_BUILDS_SKIP_UNPUSHED")]
+    pub skip_unpushed: bool,
+
+    /// Print, for every detected artifact, which filter kept or dropped it
+    #[arg(long)]
+    pub explain: bool,
+
+    /// In a dry run, check that each artifact could be deleted and list predicted failures
+    #[arg(long, conflicts_with = "delete")]
+    pub check: bool,
+
+    /// In a dry run, print the artifacts found as JSON instead of a summary
+    #[arg(long, conflicts_with_all = ["delete", "explain", "check", "verbose"], env = "CLEAN_BUILDS_JSON")]
+    pub json: bool,
+
+    /// Show what kinds of files take up each artifact's space; with =N, only for the N largest
+    #[arg(
+        long,
+        value_name = "N",
+        num_args = 0..=1,
+        require_equals = true,
+        conflicts_with = "json"
+    )]
+    pub breakdown: Option<Option<usize>>,
+
+    /// In a dry run, exit with status 5 if any artifacts are found
+    #[arg(long, conflicts_with = "delete")]
+    pub exit_code: bool,
+
+    /// Skip artifacts of projects that running processes (editors, dev servers) are using
+    #[arg(long, env = "CLEAN_BUILDS_SKIP_IN_USE")]
+    pub skip_in_use: bool,
+
+    /// Delete artifacts even if a running process or build is using them (use with --delete)
+    #[arg(long, group = "deletion")]
+    pub force_in_use: bool,
+
+    /// Delete without locking PATH against other runs deleting under it at the same time (use with --delete)
+    #[arg(long, group = "deletion")]
+    pub no_lock: bool,
+
     /// List available build system IDs and exit
     #[arg(long)]
     pub list_systems: bool,
 }
 
+impl Cli {
+    /// Whether any filter drops artifacts before they are sized, so sizing
+    /// them while scanning (`--single-pass`) would size some for nothing.
+    pub fn filters_before_sizing(&self) -> bool {
+        !(self.include.is_empty()
+            && self.exclude.is_empty()
+            && self.include_regex.is_empty()
+            && self.exclude_regex.is_empty()
+            && self.include_from.is_empty()
+            && self.exclude_from.is_empty()
+            && self.project.is_empty()
+            && self.exclude_project.is_empty())
+            || self.older_than.is_some()
+            || self.newer_than.is_some()
+            || self.not_accessed_in.is_some()
+            || self.idle_for.is_some()
+            || self.min_depth.is_some()
+            || self.max_artifact_depth.is_some()
+            || self.keep_latest.is_some()
+            || self.owner.is_some()
+            || self.only_mine
+            || self.on_volume.is_some()
+            || self.skip_unpushed
+            || self.skip_in_use
+            || self.filter.iter().any(|e| !e.needs_sizes())
+    }
+}
+
+/// Commands for deleting an exported artifact list, managing quarantined
+/// artifacts (`--quarantine`), and the hidden worker behind `--background`.
+#[derive(Subcommand, Debug, PartialEq, Eq)]
+pub enum Command {
+    /// Delete the artifacts in a scan exported with --json, if they still match a rule
+    Delete {
+        /// JSON file to read, or - for standard input
+        #[arg(long, value_name = "FILE")]
+        from_json: PathBuf,
+        /// Skip the confirmation prompt (required when reading standard input)
+        #[arg(short = 'y', long = "yes")]
+        yes: bool,
+        /// Exit with status 6 instead of asking for confirmation
+        #[arg(long, env = "CLEAN_BUILDS_NO_INPUT")]
+        no_input: bool,
+    },
+    /// Move quarantined artifacts back to where they were
+    Restore {
+        /// Run ID to restore (default: the most recent run)
+        run: Option<String>,
+    },
+    /// Permanently delete quarantined artifacts
+    Purge {
+        /// Run ID to purge (default: every run)
+        run: Option<String>,
+    },
+    /// Browse the artifacts found, select some and delete them, in an interactive terminal UI
+    Tui {
+        /// Root directory to scan
+        #[arg(default_value = ".")]
+        path: PathBuf,
+    },
+    /// Stay running and rescan PATH periodically, reporting how much the artifacts grew or cleaning them up
+    Watch {
+        /// Root directory to scan
+        #[arg(default_value = ".")]
+        path: PathBuf,
+        /// Time between scans (e.g., 30m, 6h, 1d)
+        #[arg(long, value_name = "DURATION", value_parser = parse_duration, default_value = "6h")]
+        interval: Duration,
+        /// Delete what each scan finds without asking, instead of reporting growth
+        #[arg(long)]
+        delete: bool,
+        /// Stop after N scans (default: run until stopped)
+        #[arg(long, value_name = "N")]
+        scans: Option<usize>,
+    },
+    /// Check rule files
+    Rules {
+        #[command(subcommand)]
+        action: RulesAction,
+    },
+    /// Install, remove or show a systemd timer or launchd agent that cleans up on a schedule
+    Schedule {
+        #[command(subcommand)]
+        action: ScheduleAction,
+    },
+    /// Check rules, pattern files, data directories and filesystems, and print the settings in effect
+    Doctor {
+        /// Directory to check as a scan root
+        #[arg(default_value = ".")]
+        path: PathBuf,
+    },
+    /// Explain what a run makes of PATH: which rule matches it, which marker is there or missing, and which filters keep or drop it
+    Explain {
+        /// Directory (or file) to explain
+        path: PathBuf,
+        /// The run to explain: its root and options, as given to clean-builds (default: the current directory, no options)
+        #[arg(
+            trailing_var_arg = true,
+            allow_hyphen_values = true,
+            value_name = "RUN"
+        )]
+        run: Vec<OsString>,
+    },
+    /// Write a commented starter config file, scanning PATH by default
+    Init {
+        /// Directory to scan when none is given
+        #[arg(default_value = ".")]
+        path: PathBuf,
+        /// Glob pattern of artifacts to leave alone (repeatable)
+        #[arg(long, value_name = "PATTERN")]
+        exclude: Vec<String>,
+        /// Replace an existing config file
+        #[arg(long)]
+        force: bool,
+        /// Print the config file instead of writing it
+        #[arg(long)]
+        print: bool,
+    },
+    /// Print the version, compiled-in features, output formats and rule-set revision
+    Version {
+        /// Print them as JSON
+        #[arg(long)]
+        json: bool,
+    },
+    /// Delete directories that --background moved aside
+    #[command(hide = true)]
+    BackgroundWorker {
+        /// Holding directories to delete
+        paths: Vec<PathBuf>,
+    },
+}
+
+/// What `rules` does.
+#[derive(Subcommand, Debug, PartialEq, Eq)]
+pub enum RulesAction {
+    /// Report errors in a rule file, and rules that built-in or earlier rules shadow
+    Lint {
+        /// Rule file in the format of the built-in rules, or - for standard input (default: the built-in rules)
+        file: Option<PathBuf>,
+        /// Also list what the file's rules match under DIR
+        #[arg(long, value_name = "DIR")]
+        sample: Option<PathBuf>,
+    },
+}
+
+/// What `schedule` does.
+#[derive(Subcommand, Debug, PartialEq, Eq)]
+pub enum ScheduleAction {
+    /// Write and start a timer that deletes artifacts under PATH without asking
+    Install {
+        /// Directory to clean up
+        #[arg(default_value = ".")]
+        path: PathBuf,
+        /// Run every day at 3 a.m.
+        #[arg(long, group = "frequency")]
+        daily: bool,
+        /// Run every Sunday at 3 a.m. (the default)
+        #[arg(long, group = "frequency")]
+        weekly: bool,
+        /// Run on the 1st of every month at 3 a.m.
+        #[arg(long, group = "frequency")]
+        monthly: bool,
+        /// Which artifacts each run deletes
+        #[arg(long, value_enum, default_value = "safe")]
+        profile: Profile,
+        /// Print the files instead of installing them
+        #[arg(long)]
+        print: bool,
+    },
+    /// Stop the scheduled cleanup and delete its files
+    Remove,
+    /// Show whether a cleanup is scheduled and when it runs next
+    Status,
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use clap::CommandFactory;
 
     #[test]
     fn defaults() {
@@ -55,12 +607,54 @@ mod tests {
         assert_eq!(cli.path, PathBuf::from("."));
         assert!(!cli.delete);
         assert!(!cli.yes);
+        assert!(!cli.confirm_per_system);
+        assert!(!cli.trash);
+        assert!(!cli.quarantine);
+        assert!(!cli.use_tool);
+        assert!(!cli.contents_only);
+        assert!(!cli.shred);
+        assert!(!cli.background);
+        assert!(!cli.nice);
+        assert!(!cli.force_permissions);
+        assert!(cli.timeout.is_none());
+        assert_eq!(cli.log_file, None);
+        assert!(cli.before_run.is_none());
+        assert!(cli.after_run.is_none());
+        assert!(cli.before_delete.is_none());
+        assert!(cli.after_delete.is_none());
+        assert_eq!(cli.command, None);
         assert!(!cli.verbose);
         assert!(cli.include.is_empty());
         assert!(cli.exclude.is_empty());
+        assert!(cli.include_regex.is_empty());
+        assert!(cli.exclude_regex.is_empty());
+        assert!(cli.project.is_empty());
+        assert!(cli.exclude_project.is_empty());
+        assert!(cli.include_from.is_empty());
+        assert!(cli.exclude_from.is_empty());
         assert!(cli.system.is_empty());
         assert!(cli.exclude_system.is_empty());
         assert!(!cli.list_systems);
+        assert!(cli.risk.is_empty());
+        assert!(cli.older_than.is_none());
+        assert!(cli.newer_than.is_none());
+        assert!(cli.not_accessed_in.is_none());
+        assert!(cli.idle_for.is_none());
+        assert!(cli.min_depth.is_none());
+        assert!(cli.max_artifact_depth.is_none());
+        assert!(cli.keep_latest.is_none());
+        assert!(cli.min_size.is_none());
+        assert!(cli.max_size.is_none());
+        assert!(cli.free.is_none());
+        assert!(cli.owner.is_none());
+        assert!(!cli.only_mine);
+        assert!(cli.filter.is_empty());
+        assert!(!cli.skip_unpushed);
+        assert!(!cli.skip_in_use);
+        assert!(!cli.force_in_use);
+        assert!(!cli.explain);
+        assert!(!cli.exit_code);
+        assert!(!cli.check);
     }
 
     #[test]
@@ -127,6 +721,338 @@ mod tests {
         assert!(cli.list_systems);
     }
 
+    #[test]
+    fn risk_flag_comma_separated() {
+        let cli = Cli::parse_from(["clean-builds", "--risk", "cache,output"]);
+        assert_eq!(cli.risk, vec![Risk::Cache, Risk::Output]);
+    }
+
+    #[test]
+    fn risk_flag_rejects_unknown() {
+        let result = Cli::try_parse_from(["clean-builds", "--risk", "everything"]);
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn age_flags() {
+        let cli = Cli::parse_from(["clean-builds", "--older-than", "30d", "--keep-recent", "2w"]);
+        assert_eq!(cli.older_than, Some(Duration::from_secs(30 * 86_400)));
+        assert_eq!(cli.newer_than, Some(Duration::from_secs(14 * 86_400)));
+    }
+
+    #[test]
+    fn age_flag_rejects_bad_duration() {
+        let result = Cli::try_parse_from(["clean-builds", "--newer-than", "soon"]);
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn owner_and_only_mine_conflict() {
+        let result = Cli::try_parse_from(["clean-builds", "--owner", "alice", "--only-mine"]);
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn filter_expression_parsed_at_startup() {
+        let cli = Cli::parse_from(["clean-builds", "--filter", "size > 1GB && age > 30d"]);
+        assert_eq!(cli.filter[0].to_string(), "(size > 1GB && age > 30d)");
+        let result = Cli::try_parse_from(["clean-builds", "--filter", "size >"]);
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn trash_requires_delete() {
+        assert!(Cli::try_parse_from(["clean-builds", "--trash"]).is_err());
+        let cli = Cli::parse_from(["clean-builds", "--delete", "--trash"]);
+        assert!(cli.trash);
+    }
+
+    #[test]
+    fn quarantine_requires_delete_and_conflicts_with_trash() {
+        assert!(Cli::try_parse_from(["clean-builds", "--quarantine"]).is_err());
+        let args = ["clean-builds", "--delete", "--quarantine", "--trash"];
+        assert!(Cli::try_parse_from(args).is_err());
+    }
+
+    #[test]
+    fn confirm_per_system_conflicts_with_yes() {
+        assert!(Cli::try_parse_from(["clean-builds", "--confirm-per-system"]).is_err());
+        let args = ["clean-builds", "--delete", "--confirm-per-system", "--yes"];
+        assert!(Cli::try_parse_from(args).is_err());
+        let cli = Cli::parse_from(["clean-builds", "--delete", "--confirm-per-system"]);
+        assert!(cli.confirm_per_system);
+    }
+
+    #[test]
+    fn background_requires_plain_delete() {
+        assert!(Cli::try_parse_from(["clean-builds", "--background"]).is_err());
+        let args = ["clean-builds", "--delete", "--background", "--trash"];
+        assert!(Cli::try_parse_from(args).is_err());
+        let cli = Cli::parse_from(["clean-builds", "--delete", "--background"]);
+        assert!(cli.background);
+    }
+
+    #[test]
+    fn background_worker_subcommand() {
+        let cli = Cli::parse_from(["clean-builds", "background-worker", "/a/.x", "/b/.x"]);
+        assert_eq!(
+            cli.command,
+            Some(Command::BackgroundWorker {
+                paths: vec![PathBuf::from("/a/.x"), PathBuf::from("/b/.x")]
+            })
+        );
+    }
+
+    #[test]
+    fn delete_subcommand_reads_json() {
+        let cli = Cli::parse_from(["clean-builds", "delete", "--from-json", "-", "-y"]);
+        assert_eq!(
+            cli.command,
+            Some(Command::Delete {
+                from_json: PathBuf::from("-"),
+                yes: true,
+                no_input: false,
+            })
+        );
+        assert!(Cli::try_parse_from(["clean-builds", "delete"]).is_err());
+    }
+
+    #[test]
+    fn dry_run_rules_out_deleting() {
+        let cli = Cli::parse_from(["clean-builds", "-n"]);
+        assert!(cli.dry_run && !cli.delete);
+        for args in [
+            &["--delete", "-n"][..],
+            &["-n", "--delete", "--yes"],
+            &["--dry-run", "--yes"],
+            &["-n", "--yes-for", "cache"],
+            &["-n", "--trash"],
+        ] {
+            let parsed = Cli::try_parse_from(["clean-builds"].iter().chain(args));
+            assert!(parsed.is_err(), "{args:?}");
+        }
+        // A dry run asks nothing, so --no-input is harmless in one.
+        assert!(Cli::try_parse_from(["clean-builds", "-n", "--no-input"]).is_ok());
+        // --yes answers every question, so --no-input never fires with it.
+        let cli = Cli::parse_from(["clean-builds", "--delete", "--yes", "--no-input"]);
+        assert!(cli.delete && cli.yes && cli.no_input);
+    }
+
+    #[test]
+    fn json_is_for_dry_runs() {
+        assert!(Cli::parse_from(["clean-builds", "--json"]).json);
+        assert!(Cli::try_parse_from(["clean-builds", "--json", "--delete"]).is_err());
+    }
+
+    #[test]
+    fn shred_requires_plain_delete() {
+        assert!(Cli::try_parse_from(["clean-builds", "--shred"]).is_err());
+        let args = ["clean-builds", "--delete", "--shred", "--trash"];
+        assert!(Cli::try_parse_from(args).is_err());
+        let cli = Cli::parse_from(["clean-builds", "--delete", "--shred"]);
+        assert!(cli.shred);
+    }
+
+    #[test]
+    fn contents_only_requires_plain_delete() {
+        assert!(Cli::try_parse_from(["clean-builds", "--contents-only"]).is_err());
+        let args = [
+            "clean-builds",
+            "--delete",
+            "--contents-only",
+            "--quarantine",
+        ];
+        assert!(Cli::try_parse_from(args).is_err());
+        let cli = Cli::parse_from(["clean-builds", "--delete", "--contents-only"]);
+        assert!(cli.contents_only);
+    }
+
+    #[test]
+    fn use_tool_requires_plain_delete() {
+        assert!(Cli::try_parse_from(["clean-builds", "--use-tool"]).is_err());
+        let args = ["clean-builds", "--delete", "--use-tool", "--trash"];
+        assert!(Cli::try_parse_from(args).is_err());
+        let cli = Cli::parse_from(["clean-builds", "--delete", "--use-tool"]);
+        assert!(cli.use_tool);
+    }
+
+    #[test]
+    fn log_file_value_is_optional() {
+        let cli = Cli::parse_from(["clean-builds", "--delete", "--log-file", "/tmp"]);
+        assert_eq!(cli.log_file, Some(None));
+        assert_eq!(cli.path, PathBuf::from("/tmp"));
+        let cli = Cli::parse_from(["clean-builds", "--delete", "--log-file=out.toml"]);
+        assert_eq!(cli.log_file, Some(Some(PathBuf::from("out.toml"))));
+        assert!(Cli::try_parse_from(["clean-builds", "--log-file"]).is_err());
+    }
+
+    #[test]
+    fn breakdown_count_is_optional() {
+        assert_eq!(Cli::parse_from(["clean-builds"]).breakdown, None);
+        let cli = Cli::parse_from(["clean-builds", "--breakdown", "/tmp"]);
+        assert_eq!(cli.breakdown, Some(None));
+        assert_eq!(cli.path, PathBuf::from("/tmp"));
+        let cli = Cli::parse_from(["clean-builds", "--breakdown=3"]);
+        assert_eq!(cli.breakdown, Some(Some(3)));
+        assert!(Cli::try_parse_from(["clean-builds", "--breakdown", "--json"]).is_err());
+    }
+
+    #[test]
+    fn restore_and_purge_subcommands() {
+        let cli = Cli::parse_from(["clean-builds", "restore"]);
+        assert_eq!(cli.command, Some(Command::Restore { run: None }));
+        let cli = Cli::parse_from(["clean-builds", "purge", "20261016-101500"]);
+        assert_eq!(
+            cli.command,
+            Some(Command::Purge {
+                run: Some("20261016-101500".to_string())
+            })
+        );
+        let cli = Cli::parse_from(["clean-builds", "/tmp", "--delete"]);
+        assert_eq!(cli.command, None);
+        assert_eq!(cli.path, PathBuf::from("/tmp"));
+    }
+
+    #[test]
+    fn size_flags() {
+        let cli = Cli::parse_from(["clean-builds", "--min-size", "1KB", "--max-size", "2M"]);
+        assert_eq!(cli.min_size, Some(1024));
+        assert_eq!(cli.max_size, Some(2 * 1024 * 1024));
+    }
+
+    #[test]
+    fn check_conflicts_with_delete() {
+        assert!(Cli::parse_from(["clean-builds", "--check"]).check);
+        assert!(Cli::try_parse_from(["clean-builds", "--check", "--delete"]).is_err());
+    }
+
+    #[test]
+    fn exit_code_conflicts_with_delete() {
+        assert!(Cli::try_parse_from(["clean-builds", "--exit-code"]).is_ok());
+        assert!(Cli::try_parse_from(["clean-builds", "--exit-code", "--delete"]).is_err());
+    }
+
+    #[test]
+    fn force_in_use_requires_delete() {
+        assert!(Cli::try_parse_from(["clean-builds", "--force-in-use"]).is_err());
+        let cli = Cli::parse_from(["clean-builds", "--delete", "--force-in-use"]);
+        assert!(cli.force_in_use);
+    }
+
+    #[test]
+    fn hooks_require_delete() {
+        assert!(Cli::try_parse_from(["clean-builds", "--after-delete", "true"]).is_err());
+        let cli = Cli::parse_from([
+            "clean-builds",
+            "--delete",
+            "--before-run",
+            "echo start",
+            "--after-delete",
+            "notify",
+        ]);
+        assert_eq!(cli.before_run.as_deref(), Some("echo start"));
+        assert_eq!(cli.after_delete.as_deref(), Some("notify"));
+    }
+
+    #[test]
+    fn timeout_flag() {
+        assert!(Cli::try_parse_from(["clean-builds", "--timeout", "30s"]).is_err());
+        let cli = Cli::parse_from(["clean-builds", "--delete", "--timeout", "2m"]);
+        assert_eq!(cli.timeout, Some(Duration::from_secs(120)));
+    }
+
+    #[test]
+    fn strict_confirm_flags() {
+        let cli = Cli::parse_from([
+            "clean-builds",
+            "--delete",
+            "--strict-confirm-size",
+            "10GB",
+            "--strict-confirm-count",
+            "50",
+        ]);
+        assert_eq!(cli.strict_confirm_size, Some(10 * 1024 * 1024 * 1024));
+        assert_eq!(cli.strict_confirm_count, Some(50));
+    }
+
+    #[test]
+    fn strict_confirm_requires_delete() {
+        assert!(Cli::try_parse_from(["clean-builds", "--strict-confirm-count", "5"]).is_err());
+    }
+
+    #[test]
+    fn strict_requires_delete() {
+        assert!(Cli::try_parse_from(["clean-builds", "--strict"]).is_err());
+        assert!(Cli::parse_from(["clean-builds", "--delete", "--strict"]).strict);
+    }
+
+    #[test]
+    fn du_flag() {
+        assert!(!Cli::parse_from(["clean-builds"]).du);
+        assert!(Cli::parse_from(["clean-builds", "--du"]).du);
+    }
+
+    #[test]
+    fn no_cache_flag() {
+        assert!(!Cli::parse_from(["clean-builds"]).no_cache);
+        assert!(Cli::parse_from(["clean-builds", "--no-cache"]).no_cache);
+    }
+
+    #[test]
+    fn estimate_conflicts_with_dedup_links() {
+        assert!(Cli::parse_from(["clean-builds", "--estimate"]).estimate);
+        let args = ["clean-builds", "--estimate", "--dedup-links"];
+        assert!(Cli::try_parse_from(args).is_err());
+    }
+
+    #[test]
+    fn single_pass_conflicts_with_estimate_and_link_targets() {
+        assert!(Cli::parse_from(["clean-builds", "--single-pass"]).single_pass);
+        for flag in ["--estimate", "--link-targets"] {
+            let args = ["clean-builds", "--single-pass", flag];
+            assert!(Cli::try_parse_from(args).is_err());
+        }
+    }
+
+    #[test]
+    fn filters_before_sizing_ignores_size_filters() {
+        let filters = |args: &[&str]| {
+            let args = ["clean-builds"].iter().chain(args);
+            Cli::parse_from(args).filters_before_sizing()
+        };
+        assert!(!filters(&[]));
+        assert!(!filters(&["--min-size", "1GB", "--filter", "size > 1GB"]));
+        assert!(filters(&["--older-than", "30d"]));
+        assert!(filters(&["--exclude", "**/vendor/**"]));
+        assert!(filters(&["--on-volume", "/mnt/data"]));
+        assert!(filters(&["--filter", "age > 30d"]));
+    }
+
+    #[test]
+    fn reclaimable_conflicts_with_estimate() {
+        assert!(Cli::parse_from(["clean-builds", "--reclaimable"]).reclaimable);
+        let args = ["clean-builds", "--reclaimable", "--estimate"];
+        assert!(Cli::try_parse_from(args).is_err());
+    }
+
+    #[test]
+    fn link_targets_conflicts_with_estimate() {
+        assert!(Cli::parse_from(["clean-builds", "--link-targets"]).link_targets);
+        let args = ["clean-builds", "--link-targets", "--estimate"];
+        assert!(Cli::try_parse_from(args).is_err());
+    }
+
+    #[test]
+    fn dedup_links_flag() {
+        assert!(Cli::parse_from(["clean-builds", "--dedup-links"]).dedup_links);
+    }
+
+    #[test]
+    fn free_flag() {
+        let cli = Cli::parse_from(["clean-builds", "--free", "50GB"]);
+        assert_eq!(cli.free, Some(50 * 1024 * 1024 * 1024));
+    }
+
     #[test]
     fn system_and_exclude_system_conflict() {
         let result = Cli::try_parse_from([
@@ -138,4 +1064,64 @@ mod tests {
         ]);
         assert!(result.is_err());
     }
+
+    #[test]
+    fn deletion_options_say_they_need_delete() {
+        let command = Cli::command();
+        let group = command
+            .get_groups()
+            .find(|g| g.get_id() == "deletion")
+            .unwrap();
+        for id in group.get_args() {
+            let arg = command.get_arguments().find(|a| a.get_id() == id).unwrap();
+            let help = arg.get_help().unwrap().to_string();
+            assert!(help.contains("use with --delete"), "--{id}: {help}");
+        }
+    }
+
+    #[test]
+    fn every_filter_has_a_variable_and_lists_split_on_commas() {
+        let command = Cli::command();
+        let filters = [
+            "include",
+            "exclude",
+            "include_regex",
+            "exclude_regex",
+            "project",
+            "exclude_project",
+            "include_from",
+            "exclude_from",
+            "system",
+            "exclude_system",
+            "risk",
+            "older_than",
+            "newer_than",
+            "not_accessed_in",
+            "idle_for",
+            "min_depth",
+            "max_artifact_depth",
+            "keep_latest",
+            "min_size",
+            "max_size",
+            "free",
+            "owner",
+            "only_mine",
+            "on_volume",
+            "filter",
+            "skip_unpushed",
+            "skip_in_use",
+        ];
+        for arg in command.get_arguments() {
+            let id = arg.get_id().as_str();
+            if filters.contains(&id) {
+                assert!(arg.get_env().is_some(), "--{id} has no variable");
+            }
+            // Regexes use commas themselves, e.g. `a{1,3}`.
+            let splits = arg.get_value_delimiter() == Some(',')
+                || matches!(id, "include_regex" | "exclude_regex");
+            if arg.get_env().is_some() && matches!(arg.get_action(), clap::ArgAction::Append) {
+                assert!(splits, "CLEAN_BUILDS_{} takes one value", id.to_uppercase());
+            }
+        }
+    }
 }
@@ -0,0 +1,311 @@
+use std::path::{Path, PathBuf};
+
+use clap::{ArgAction, CommandFactory};
+
+use crate::cli::Cli;
+
+/// Options the config file cannot set, although the environment can: a
+/// value from the file becomes a default, which clap does not check for
+/// conflicts, so a file setting `dry-run` would not stop `--delete` and one
+/// setting `json` would quietly turn `--delete` into a listing. Every option
+/// that conflicts with `--delete`, `--explain` or `--check` belongs here.
+const NOT_IN_CONFIG: &[&str] = &["dry-run", "json", "check", "exit-code"];
+
+/// Error reading or writing the config file.
+#[derive(thiserror::Error, Debug)]
+pub enum ConfigError {
+    #[error("cannot find the home directory (set HOME or XDG_CONFIG_HOME)")]
+    NoHome,
+    #[error("cannot read {}: {source}", path.display())]
+    Read {
+        path: PathBuf,
+        source: std::io::Error,
+    },
+    #[error("{}: {message}", path.display())]
+    Invalid { path: PathBuf, message: String },
+    #[error("{} already exists; pass --force to replace it", path.display())]
+    Exists { path: PathBuf },
+    #[error("cannot write {}: {source}", path.display())]
+    Write {
+        path: PathBuf,
+        source: std::io::Error,
+    },
+}
+
+/// Per-user config directory: `$XDG_CONFIG_HOME/clean-builds`, falling back
+/// to `~/.config/clean-builds`.
+pub fn config_dir() -> Option<PathBuf> {
+    let config_home = std::env::var_os("XDG_CONFIG_HOME")
+        .filter(|d| !d.is_empty())
+        .map(PathBuf::from)
+        .or_else(|| {
+            std::env::var_os("HOME")
+                .filter(|h| !h.is_empty())
+                .map(|h| PathBuf::from(h).join(".config"))
+        })?;
+    Some(config_home.join("clean-builds"))
+}
+
+/// Default config file, `config.toml` under [`config_dir`].
+pub fn default_path() -> Option<PathBuf> {
+    config_dir().map(|d| d.join("config.toml"))
+}
+
+/// Defaults for a run, read from a config file. Keys are the long names of
+/// options that can also be set in the environment, plus `path` for the
+/// scan root; values are strings, numbers, `true` or `false`, or lists of
+/// them. The command line and the environment override the file.
+#[derive(Debug, Default)]
+pub struct Config {
+    /// Default values, keyed by argument ID.
+    values: Vec<(String, Vec<String>)>,
+}
+
+impl Config {
+    /// Read the config file at `path`. `None` if there is none.
+    pub fn load(path: &Path) -> Result<Option<Self>, ConfigError> {
+        match std::fs::read_to_string(path) {
+            Ok(text) => Self::parse(&text)
+                .map(Some)
+                .map_err(|message| ConfigError::Invalid {
+                    path: path.to_path_buf(),
+                    message,
+                }),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+            Err(source) => Err(ConfigError::Read {
+                path: path.to_path_buf(),
+                source,
+            }),
+        }
+    }
+
+    /// Parse config file `text`.
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let table: toml::Table = toml::from_str(text).map_err(|e| e.message().to_string())?;
+        let command = Cli::command();
+        let mut config = Config::default();
+        for (key, value) in table {
+            let arg = command
+                .get_arguments()
+                .find(|arg| match arg.get_long() {
+                    Some(long) => long == key && arg.get_env().is_some(),
+                    None => arg.get_id() == key.as_str(),
+                })
+                .filter(|_| !NOT_IN_CONFIG.contains(&key.as_str()))
+                .ok_or_else(|| {
+                    format!("`{key}` is not an option the config file can set; see --help for the CLEAN_BUILDS_ variables it mirrors")
+                })?;
+            let values = match value {
+                toml::Value::Array(items) => items.into_iter().map(scalar).collect(),
+                value => scalar(value).map(|v| vec![v]),
+            }
+            .ok_or_else(|| {
+                format!("`{key}` takes a string, number, true or false, or a list of them")
+            })?;
+            let takes_many = matches!(arg.get_action(), ArgAction::Append)
+                || arg.get_value_delimiter().is_some();
+            if values.len() > 1 && !takes_many {
+                return Err(format!("`{key}` takes one value"));
+            }
+            config.values.push((arg.get_id().to_string(), values));
+        }
+        Ok(config)
+    }
+
+    /// `command` with the values in the file as its defaults.
+    pub fn apply(&self, mut command: clap::Command) -> clap::Command {
+        for (id, values) in &self.values {
+            command = command.mut_arg(id, |arg| arg.default_values(values));
+        }
+        command
+    }
+}
+
+/// A TOML string, integer or boolean as an option value.
+fn scalar(value: toml::Value) -> Option<String> {
+    match value {
+        toml::Value::String(s) => Some(s),
+        toml::Value::Integer(n) => Some(n.to_string()),
+        toml::Value::Boolean(b) => Some(b.to_string()),
+        _ => None,
+    }
+}
+
+/// A commented starter config file scanning `root` by default and leaving
+/// out artifacts matching `exclude`.
+pub fn starter(root: &Path, exclude: &[String]) -> String {
+    let quote = |s: &str| toml::Value::String(s.to_string()).to_string();
+    let exclude = if exclude.is_empty() {
+        "# exclude = [\"**/vendor/**\"]".to_string()
+    } else {
+        let patterns: Vec<_> = exclude.iter().map(|p| quote(p)).collect();
+        format!("exclude = [{}]", patterns.join(", "))
+    };
+    format!(
+        r#"# clean-builds configuration, written by `clean-builds init`.
+#
+# Each key is the long name of an option, used when the option is given
+# neither on the command line nor in its CLEAN_BUILDS_ variable. Options that
+# delete cannot be set here, so a run deletes only when asked to.
+
+# Directory to scan when none is given.
+path = {root}
+
+# Artifacts to leave alone (--exclude).
+{exclude}
+
+# Only some build systems (--system), or all but some (--exclude-system);
+# see `clean-builds --list-systems`.
+# system = ["cargo", "node"]
+# exclude-system = ["python"]
+
+# Only these kinds of artifact (--risk).
+# risk = ["cache", "output"]
+
+# Only artifacts not modified for this long (--older-than).
+# older-than = "30d"
+
+# Only artifacts at least this large (--min-size).
+# min-size = "100MB"
+
+# Worker threads (--jobs).
+# jobs = 4
+"#,
+        root = quote(&root.to_string_lossy()),
+    )
+}
+
+/// Write `text` to the config file at `path`, unless one exists and not
+/// `force`.
+pub fn write(path: &Path, text: &str, force: bool) -> Result<(), ConfigError> {
+    if path.exists() && !force {
+        return Err(ConfigError::Exists {
+            path: path.to_path_buf(),
+        });
+    }
+    let write = |source| ConfigError::Write {
+        path: path.to_path_buf(),
+        source,
+    };
+    if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir).map_err(write)?;
+    }
+    std::fs::write(path, text).map_err(write)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use clap::FromArgMatches;
+    use tempfile::TempDir;
+
+    fn run_with(config: &str, args: &[&str]) -> Cli {
+        let command = Config::parse(config).unwrap().apply(Cli::command());
+        let matches = command
+            .try_get_matches_from(std::iter::once("clean-builds").chain(args.iter().copied()))
+            .unwrap();
+        Cli::from_arg_matches(&matches).unwrap()
+    }
+
+    #[test]
+    fn values_are_defaults_the_command_line_overrides() {
+        let config = r#"
+path = "/home/me/Developer"
+exclude = ["**/vendor/**"]
+system = ["cargo", "node"]
+older-than = "30d"
+jobs = 4
+du = true
+"#;
+        let cli = run_with(config, &[]);
+        assert_eq!(cli.path, Path::new("/home/me/Developer"));
+        assert_eq!(cli.exclude, ["**/vendor/**"]);
+        assert_eq!(cli.system, ["cargo", "node"]);
+        assert!(cli.older_than.is_some());
+        assert_eq!(cli.jobs.map(|j| j.get()), Some(4));
+        assert!(cli.du);
+
+        let cli = run_with(config, &["/tmp", "--exclude", "x", "--jobs", "2"]);
+        assert_eq!(cli.path, Path::new("/tmp"));
+        assert_eq!(cli.exclude, ["x"]);
+        assert_eq!(cli.jobs.map(|j| j.get()), Some(2));
+    }
+
+    #[test]
+    fn rejects_options_that_delete_and_bad_values() {
+        for (text, expected) in [
+            ("delete = true", "`delete` is not an option"),
+            ("dry-run = true", "`dry-run` is not an option"),
+            ("colour = \"red\"", "`colour` is not an option"),
+            ("jobs = 1.5", "`jobs` takes a string"),
+            (
+                "older-than = [\"1d\", \"2d\"]",
+                "`older-than` takes one value",
+            ),
+            ("jobs =", "string"),
+        ] {
+            let message = Config::parse(text).unwrap_err();
+            assert!(message.contains(expected), "{text}: {message}");
+        }
+    }
+
+    #[test]
+    fn options_conflicting_with_delete_cannot_be_set() {
+        let command = Cli::command();
+        let conflicting: Vec<&str> = command
+            .get_arguments()
+            .filter(|arg| {
+                // Conflicts are declared on one side only.
+                let conflicts = |a: &clap::Arg, b: &str| {
+                    command
+                        .get_arg_conflicts_with(a)
+                        .iter()
+                        .any(|c| c.get_id() == b)
+                };
+                ["delete", "explain", "check"].iter().any(|&id| {
+                    let other = command.get_arguments().find(|a| a.get_id() == id).unwrap();
+                    conflicts(arg, id) || conflicts(other, arg.get_id().as_str())
+                })
+            })
+            .filter_map(|arg| arg.get_long())
+            .collect();
+        assert!(conflicting.contains(&"json"), "{conflicting:?}");
+        for long in conflicting {
+            let message = Config::parse(&format!("{long} = true")).unwrap_err();
+            assert!(message.contains("is not an option"), "{long}: {message}");
+        }
+
+        // The environment may set `json`: there clap still sees the conflict.
+        assert!(Config::parse("json = true").is_err());
+    }
+
+    #[test]
+    fn starter_config_parses_and_sets_root_and_exclusions() {
+        let text = starter(Path::new("/home/me/Developer"), &["old-*".to_string()]);
+        let cli = run_with(&text, &[]);
+        assert_eq!(cli.path, Path::new("/home/me/Developer"));
+        assert_eq!(cli.exclude, ["old-*"]);
+        assert!(cli.system.is_empty());
+
+        let cli = run_with(&starter(Path::new("/p"), &[]), &[]);
+        assert!(cli.exclude.is_empty());
+    }
+
+    #[test]
+    fn write_refuses_to_replace_without_force() {
+        let tmp = TempDir::new().unwrap();
+        let path = tmp.path().join("clean-builds/config.toml");
+        write(&path, "a = 1\n", false).unwrap();
+        assert!(matches!(
+            write(&path, "b = 2\n", false),
+            Err(ConfigError::Exists { .. })
+        ));
+        write(&path, "b = 2\n", true).unwrap();
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "b = 2\n");
+        assert!(
+            Config::load(&tmp.path().join("missing.toml"))
+                .unwrap()
+                .is_none()
+        );
+    }
+}
@@ -1,200 +1,2627 @@
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
+use std::ffi::OsString;
 use std::io::{BufRead, Write};
-use std::path::Path;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Mutex, mpsc};
+use std::time::{Duration, Instant, SystemTime};
 
 use log::{debug, info, warn};
 use rayon::prelude::*;
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
 
+use crate::age::format_age;
+use crate::audit::{self, AuditError, Failure, FailureKind, Outcome};
+use crate::disk::Volumes;
+use crate::hooks::{HookError, Hooks};
+use crate::in_use::InUseProjects;
+use crate::interrupt::{CancelToken, InterruptGuard};
+use crate::preflight::read_only_mount;
+use crate::progress::{DeletionBars, Observer};
+use crate::quarantine::{Quarantine, QuarantineError};
+use crate::rules::Risk;
 use crate::scanner::Artifact;
 use crate::size::format_size;
+use crate::throttle::{self, Throttle};
 
 /// Error type for deletion operations.
 #[derive(thiserror::Error, Debug)]
 pub enum DeleteError {
-    #[error("failed to delete {path}: {source}")]
+    #[error("failed to delete {path}: {source}{}", retried(*retries))]
     RemoveDir {
         path: String,
         source: std::io::Error,
+        /// Retries made before giving up on a transient error.
+        retries: u32,
     },
+    #[error("failed to move {path} to the trash: {source}")]
+    Trash { path: String, source: trash::Error },
+    #[error("failed to quarantine artifacts: {0}")]
+    Quarantine(#[from] QuarantineError),
+    #[error(transparent)]
+    Audit(#[from] AuditError),
+    #[error(transparent)]
+    Hook(#[from] HookError),
+    #[error("gave up deleting {path}: no progress for {}", format_age(*timeout))]
+    TimedOut { path: String, timeout: Duration },
+    #[error("gave up on {command} in {project}: still running after {}", format_age(*timeout))]
+    ToolTimedOut {
+        command: String,
+        project: String,
+        timeout: Duration,
+    },
+    #[error(
+        "a confirmation is needed ({question}) but --no-input forbids asking; pass --yes to delete without asking"
+    )]
+    InputRequired { question: String },
     #[error("I/O error: {0}")]
     Io(#[from] std::io::Error),
 }
 
+impl From<&DeleteError> for Failure {
+    fn from(e: &DeleteError) -> Self {
+        let kind = match e {
+            DeleteError::RemoveDir { source, .. } => match source.kind() {
+                std::io::ErrorKind::PermissionDenied | std::io::ErrorKind::ReadOnlyFilesystem => {
+                    FailureKind::PermissionDenied
+                }
+                _ if is_transient(source) => FailureKind::Busy,
+                _ => FailureKind::Other,
+            },
+            DeleteError::Trash { .. } => FailureKind::Trash,
+            DeleteError::Quarantine(_) => FailureKind::Quarantine,
+            DeleteError::Hook(_) => FailureKind::Hook,
+            DeleteError::TimedOut { .. } | DeleteError::ToolTimedOut { .. } => {
+                FailureKind::TimedOut
+            }
+            DeleteError::Audit(_) | DeleteError::InputRequired { .. } | DeleteError::Io(_) => {
+                FailureKind::Other
+            }
+        };
+        Failure::new(kind, e.to_string())
+    }
+}
+
+fn retried(retries: u32) -> String {
+    match retries {
+        0 => String::new(),
+        1 => " (after 1 retry)".to_string(),
+        n => format!(" (after {n} retries)"),
+    }
+}
+
+/// How deletion retries failures that are likely to clear up on their own,
+/// such as a file briefly held open by an indexer or virus scanner.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Retry {
+    /// Retries after the first attempt; 0 disables retrying.
+    pub retries: u32,
+    /// Wait before the first retry, doubled before each one after.
+    pub backoff: Duration,
+}
+
+impl Default for Retry {
+    /// Four retries over about 1.5 seconds.
+    fn default() -> Self {
+        Self {
+            retries: 4,
+            backoff: Duration::from_millis(100),
+        }
+    }
+}
+
+/// When a removal is large enough that confirming it takes typing the number
+/// of artifacts instead of "y", so a reflexive answer cannot approve it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LargeDeletion {
+    /// More than this many bytes in total.
+    pub bytes: u64,
+    /// More than this many artifacts.
+    pub count: usize,
+}
+
+impl Default for LargeDeletion {
+    /// More than 100 GB or 1000 artifacts.
+    fn default() -> Self {
+        Self {
+            bytes: 100 * 1024 * 1024 * 1024,
+            count: 1000,
+        }
+    }
+}
+
+impl LargeDeletion {
+    /// Whether removing `count` artifacts totalling `bytes` is large.
+    pub fn applies(&self, count: usize, bytes: u64) -> bool {
+        count > self.count || bytes > self.bytes
+    }
+}
+
+/// How `confirm_and_delete` removes artifacts.
+#[derive(Debug, Clone, Default)]
+pub struct DeleteOptions {
+    /// Skip the confirmation prompt (`--yes`).
+    pub skip_confirm: bool,
+    /// Fail with [`DeleteError::InputRequired`] instead of asking a question
+    /// that `skip_confirm` does not answer (`--no-input`).
+    pub no_input: bool,
+    /// Move artifacts to the system trash instead of deleting them (`--trash`).
+    pub trash: bool,
+    /// Move artifacts into this quarantine directory instead of deleting
+    /// them (`--quarantine`).
+    pub quarantine: Option<PathBuf>,
+    /// Run the build tool's clean command in each project before deleting
+    /// what is left directly (`--use-tool`).
+    pub use_tool: bool,
+    /// Delete one artifact at a time at low IO priority, pausing between
+    /// batches of files (`--nice`).
+    pub throttle: Option<Throttle>,
+    /// How to retry transient deletion failures.
+    pub retry: Retry,
+    /// On Ctrl+C, stop starting new removals, let those in flight finish,
+    /// and report what was done, marking the [`DeleteReport`] interrupted.
+    /// A second Ctrl+C exits at once.
+    pub handle_interrupt: bool,
+    /// On Unix, make directories inside an artifact readable and writable
+    /// when they refuse deletion, like `rm -rf` (`--force-permissions`).
+    pub force_permissions: bool,
+    /// Append a record of every artifact removed or left behind to this log
+    /// (`--log-file`).
+    pub log_file: Option<PathBuf>,
+    /// Rename artifacts aside into a hidden holding directory next to each,
+    /// then run this command with the holding directories appended as a
+    /// detached process to delete them (`--background`). `clean-builds`
+    /// itself passes its hidden `background-worker` subcommand.
+    pub background: Option<Vec<OsString>>,
+    /// Give up on an artifact, reporting it as failed, once deleting it has
+    /// removed nothing for this long (`--timeout`), e.g. on a dead network
+    /// mount. The abandoned deletion keeps running in the background. A
+    /// build tool's clean command still running after this long is killed,
+    /// and its artifacts reported as failed.
+    pub timeout: Option<Duration>,
+    /// Overwrite file contents with zeros before unlinking them (`--shred`).
+    pub shred: bool,
+    /// Remove what is inside artifact directories but keep the directories
+    /// themselves, along with any [`KEPT_MARKERS`] in them (`--contents-only`).
+    pub contents_only: bool,
+    /// Remove artifacts even when a running process or build is using them
+    /// (`--force-in-use`). Otherwise they are skipped.
+    pub force_in_use: bool,
+    /// Commands to run before and after the run and each artifact.
+    pub hooks: Hooks,
+    /// Removals that must be confirmed by typing the artifact count
+    /// (`--strict-confirm-size`, `--strict-confirm-count`).
+    pub large: LargeDeletion,
+    /// Told what happened to each artifact once the run has settled it.
+    pub progress: Observer,
+    /// Stops the run like Ctrl+C does with `handle_interrupt`, for a host
+    /// application to cancel it.
+    pub cancel: CancelToken,
+}
+
+/// What [`confirm_and_delete`] did.
+#[derive(Debug, Clone, Default)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct DeleteReport {
+    /// Whether the user confirmed. If not, nothing was touched and
+    /// `outcomes` is empty.
+    pub confirmed: bool,
+    /// What happened to each artifact, in the order given.
+    pub outcomes: Vec<(Artifact, Outcome)>,
+    /// Disk space the affected volumes gained, when deleting permanently and
+    /// free space could be measured.
+    pub bytes_reclaimed: Option<u64>,
+    /// How long the run took after confirmation.
+    pub duration: Duration,
+    /// Whether Ctrl+C or [`DeleteOptions::cancel`] stopped the run before
+    /// every artifact was reached.
+    pub interrupted: bool,
+    /// How long each artifact deleted file by file took, whether or not it
+    /// succeeded, in no particular order. Empty when artifacts were moved
+    /// rather than deleted.
+    pub timings: Vec<Timing>,
+}
+
+/// How long deleting one artifact took.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct Timing {
+    pub path: PathBuf,
+    pub duration: Duration,
+    /// Files and links removed; directories are not counted.
+    pub files: u64,
+}
+
+impl DeleteReport {
+    /// Number of artifacts deleted, trashed or quarantined.
+    pub fn removed(&self) -> usize {
+        self.outcomes.iter().filter(|(_, o)| o.is_removed()).count()
+    }
+
+    /// Total size of the artifacts deleted, trashed or quarantined, as
+    /// measured before the run.
+    pub fn bytes_removed(&self) -> u64 {
+        self.outcomes
+            .iter()
+            .filter(|(_, o)| o.is_removed())
+            .map(|(a, _)| a.size_bytes)
+            .sum()
+    }
+
+    /// Files and links removed from artifacts deleted file by file.
+    pub fn files_removed(&self) -> u64 {
+        self.timings.iter().map(|t| t.files).sum()
+    }
+
+    /// The `n` artifacts that took longest to delete, slowest first.
+    pub fn slowest(&self, n: usize) -> Vec<&Timing> {
+        let mut timings: Vec<&Timing> = self.timings.iter().collect();
+        timings.sort_by_key(|t| std::cmp::Reverse(t.duration));
+        timings.truncate(n);
+        timings
+    }
+
+    /// Number of artifacts skipped because they are on a read-only filesystem.
+    pub fn read_only(&self) -> usize {
+        self.outcomes
+            .iter()
+            .filter(|(_, o)| *o == Outcome::ReadOnly)
+            .count()
+    }
+
+    /// Each artifact that could not be removed, with why.
+    pub fn failures(&self) -> impl Iterator<Item = (&Artifact, &Failure)> {
+        self.outcomes.iter().filter_map(|(a, o)| match o {
+            Outcome::Failed(failure) => Some((a, failure)),
+            _ => None,
+        })
+    }
+}
+
 /// Prompt the user for confirmation and delete artifacts in parallel if confirmed.
-/// Returns the number of artifacts deleted, or 0 if the user declined.
+/// Returns what happened to each artifact; if the user declined, the report
+/// is not `confirmed` and lists nothing.
+///
+/// With `options.trash`, artifacts are moved to the trash one at a time
+/// instead. Any that cannot be trashed are listed, and the user is asked
+/// whether to delete them permanently; with `skip_confirm` they are left alone.
+///
+/// With `options.quarantine`, artifacts are moved into a new quarantine run
+/// that `clean-builds restore` can undo. Any that cannot be moved are listed
+/// and left in place.
+///
+/// Unless `options.force_in_use` is set, artifacts that a running process or
+/// build is using are skipped with a warning.
+///
+/// `options.hooks` run once the user confirms: the before-run hook must
+/// succeed for anything to be removed, and an artifact whose before-delete
+/// hook fails is left alone. Failures of the after hooks are only logged.
 pub fn confirm_and_delete(
     out: &mut dyn Write,
     input: &mut dyn BufRead,
     artifacts: &[Artifact],
-    skip_confirm: bool,
-) -> Result<usize, DeleteError> {
+    options: &DeleteOptions,
+) -> Result<DeleteReport, DeleteError> {
     let total_bytes: u64 = artifacts.iter().map(|a| a.size_bytes).sum();
 
-    if !skip_confirm {
-        write!(
-            out,
-            "\nDelete {} targets ({})? [y/N] ",
-            artifacts.len(),
-            format_size(total_bytes)
-        )?;
-        out.flush()?;
+    if !options.skip_confirm {
+        let question = question(options, artifacts.len(), "targets", total_bytes);
+        writeln!(out)?;
+        if !confirm_removal(out, input, options, &question, artifacts.len(), total_bytes)? {
+            writeln!(out, "Aborted.")?;
+            return Ok(DeleteReport::default());
+        }
+    }
+
+    if options.throttle.is_some() {
+        throttle::lower_io_priority();
+    }
+    let guard = if options.handle_interrupt {
+        Some(InterruptGuard::install()?)
+    } else {
+        None
+    };
+    let never = AtomicBool::new(false);
+    let cancel = Cancel::new(
+        guard.as_ref().map_or(&never, InterruptGuard::flag),
+        &options.cancel,
+    );
+    let timings = Mutex::new(Vec::new());
+    // Moving to the trash, quarantine or aside frees nothing yet, so only
+    // deletion measures what it reclaimed.
+    let moves = options.trash || options.quarantine.is_some() || options.background.is_some();
+    let volumes = (!moves).then(|| Volumes::of(artifacts));
+    let free_before = volumes.as_ref().and_then(Volumes::free_bytes);
+    let started = SystemTime::now();
+    let clock = Instant::now();
+    options.hooks.before_run(artifacts)?;
+    let mut held: Vec<Option<Outcome>> = artifacts
+        .iter()
+        .zip(in_use(artifacts, options))
+        .map(|(artifact, reason)| {
+            if artifact.path.parent().is_some_and(read_only_mount) {
+                warn!(
+                    "Skipping {}: on a read-only filesystem",
+                    artifact.path.display()
+                );
+                Some(Outcome::ReadOnly)
+            } else {
+                reason.map(Outcome::InUse)
+            }
+        })
+        .collect();
+    for (artifact, held) in artifacts.iter().zip(&mut held) {
+        if held.is_some() {
+            continue;
+        }
+        if let Err(e) = options.hooks.before_delete(artifact) {
+            warn!("Skipping {}: {e}", artifact.path.display());
+            *held = Some(Outcome::Failed(Failure::new(
+                FailureKind::Hook,
+                e.to_string(),
+            )));
+        }
+    }
+    let idle: Vec<Artifact> = artifacts
+        .iter()
+        .zip(&held)
+        .filter(|(_, held)| held.is_none())
+        .map(|(a, _)| a.clone())
+        .collect();
+    let mut removed = if let Some(dir) = &options.quarantine {
+        quarantine_all(out, &idle, dir)?
+    } else if let Some(worker) = &options.background {
+        move_aside_all(out, &idle, worker)?
+    } else if options.trash {
+        trash_all(out, input, &idle, options, &cancel, &timings)?
+    } else {
+        clean_and_delete(out, &idle, options, &cancel, &timings)?
+    }
+    .into_iter();
+    let outcomes: Vec<Outcome> = held
+        .into_iter()
+        .map(|held| held.unwrap_or_else(|| removed.next().expect("one outcome per idle artifact")))
+        .collect();
+    for (artifact, outcome) in artifacts.iter().zip(&outcomes) {
+        options.progress.on_deleted(artifact, outcome);
+    }
+
+    let count = |wanted: fn(&Outcome) -> bool| outcomes.iter().filter(|o| wanted(o)).count();
+    let deleted = count(|o| *o == Outcome::Deleted);
+    let cleaned = count(|o| matches!(o, Outcome::Cleaned(_)));
+    let trashed = count(|o| *o == Outcome::Trashed);
+    let quarantined = count(|o| matches!(o, Outcome::Quarantined(_)));
+    let moved = count(|o| *o == Outcome::MovedAside);
+    let skipped = count(|o| *o == Outcome::Skipped);
+    let busy = count(|o| matches!(o, Outcome::InUse(_)));
+    let read_only = count(|o| *o == Outcome::ReadOnly);
+    if read_only > 0 {
+        writeln!(
+            out,
+            "\nSkipped {read_only} artifact directories on read-only filesystems."
+        )?;
+    }
+    if busy > 0 {
+        writeln!(
+            out,
+            "\nSkipped {busy} artifact directories in use (pass --force-in-use to remove them anyway)."
+        )?;
+    }
+    let of = if skipped > 0 {
+        let freed: u64 = artifacts
+            .iter()
+            .zip(&outcomes)
+            .filter(|(_, o)| o.is_removed())
+            .map(|(a, _)| a.size_bytes)
+            .sum();
+        writeln!(
+            out,
+            "\nInterrupted: {skipped} artifact directories were left untouched."
+        )?;
+        format!(
+            "of {} artifact directories ({} of {})",
+            artifacts.len(),
+            format_size(freed),
+            format_size(total_bytes)
+        )
+    } else {
+        format!(
+            "of {} artifact directories ({})",
+            artifacts.len(),
+            format_size(total_bytes)
+        )
+    };
+    if let Some(run) = outcomes.iter().find_map(|o| match o {
+        Outcome::Quarantined(run) => Some(run),
+        _ => None,
+    }) {
+        writeln!(out, "\nQuarantined {quarantined} {of} as run {run}.")?;
+        writeln!(
+            out,
+            "Undo with 'clean-builds restore {run}', or free the space with 'clean-builds purge'."
+        )?;
+    } else if options.quarantine.is_some() {
+        writeln!(out, "\nQuarantined 0 {of}.")?;
+    } else if options.trash && deleted > 0 {
+        writeln!(out, "\nTrashed {trashed} and deleted {deleted} {of}.")?;
+    } else if options.trash {
+        writeln!(out, "\nTrashed {trashed} {of}.")?;
+    } else if options.background.is_some() {
+        writeln!(out, "\nMoved aside {moved} {of}.")?;
+        if moved > 0 {
+            writeln!(out, "They are being deleted in the background.")?;
+        }
+    } else {
+        let verb = if options.contents_only {
+            "Emptied"
+        } else {
+            "Deleted"
+        };
+        writeln!(out, "\n{verb} {} {of}.", deleted + cleaned)?;
+        if cleaned > 0 {
+            writeln!(out, "{cleaned} removed by the build tool's clean command.")?;
+        }
+    }
+    let free_after = volumes.as_ref().and_then(Volumes::free_bytes);
+    let reclaimed = free_before
+        .zip(free_after)
+        .map(|(before, after)| after.saturating_sub(before));
+    if let Some(reclaimed) = reclaimed {
+        let estimated: u64 = artifacts
+            .iter()
+            .zip(&outcomes)
+            .filter(|(_, o)| matches!(o, Outcome::Deleted | Outcome::Cleaned(_)))
+            .map(|(a, _)| a.size_bytes)
+            .sum();
+        writeln!(
+            out,
+            "Reclaimed {} of disk space (estimated {}).",
+            format_size(reclaimed),
+            format_size(estimated)
+        )?;
+    }
+    let report = DeleteReport {
+        confirmed: true,
+        outcomes: artifacts.iter().cloned().zip(outcomes.clone()).collect(),
+        bytes_reclaimed: reclaimed,
+        duration: clock.elapsed(),
+        interrupted: skipped > 0,
+        timings: timings.into_inner().unwrap_or_else(|e| e.into_inner()),
+    };
+    if !report.timings.is_empty() {
+        print_statistics(out, &report)?;
+    }
+
+    if let Some(log) = &options.log_file {
+        audit::append(log, started, artifacts, &outcomes)?;
+    }
+
+    for (artifact, outcome) in artifacts.iter().zip(&outcomes) {
+        if let Err(e) = options.hooks.after_delete(artifact, outcome) {
+            warn!("{e}");
+        }
+    }
+    let removed = deleted + cleaned + trashed + quarantined;
+    if let Err(e) = options.hooks.after_run(artifacts, removed) {
+        warn!("{e}");
+    }
+
+    Ok(report)
+}
+
+/// Print how long deletion took, how fast it went, and which artifacts were
+/// slowest, to tell whether it is bound by disk IO.
+fn print_statistics(out: &mut dyn Write, report: &DeleteReport) -> std::io::Result<()> {
+    let timed: HashSet<&Path> = report.timings.iter().map(|t| t.path.as_path()).collect();
+    let bytes: u64 = report
+        .outcomes
+        .iter()
+        .filter(|(a, o)| *o == Outcome::Deleted && timed.contains(a.path.as_path()))
+        .map(|(a, _)| a.size_bytes)
+        .sum();
+    let secs = report.duration.as_secs_f64();
+    let rate = if secs > 0.0 {
+        format!(" ({}/s)", format_size((bytes as f64 / secs) as u64))
+    } else {
+        String::new()
+    };
+    let files = report.files_removed();
+    writeln!(
+        out,
+        "Removed {files} file{} in {}{rate}.",
+        if files == 1 { "" } else { "s" },
+        format_elapsed(report.duration)
+    )?;
+    if report.timings.len() > 1 {
+        writeln!(out, "Slowest:")?;
+        for timing in report.slowest(3) {
+            writeln!(
+                out,
+                "  {} ({}, {} file{})",
+                timing.path.display(),
+                format_elapsed(timing.duration),
+                timing.files,
+                if timing.files == 1 { "" } else { "s" }
+            )?;
+        }
+    }
+    Ok(())
+}
+
+/// Format a duration to a tenth of a second, or in minutes and seconds once
+/// it reaches a minute.
+fn format_elapsed(d: Duration) -> String {
+    let secs = d.as_secs();
+    if secs >= 60 {
+        format!("{}m{:02}s", secs / 60, secs % 60)
+    } else {
+        format!("{:.1}s", d.as_secs_f64())
+    }
+}
+
+/// For each artifact, why it is in use, or `None` if it is free to remove.
+fn in_use(artifacts: &[Artifact], options: &DeleteOptions) -> Vec<Option<String>> {
+    if options.force_in_use {
+        return vec![None; artifacts.len()];
+    }
+    let projects = InUseProjects::detect();
+    artifacts
+        .par_iter()
+        .map(|artifact| {
+            let reason = projects.reason(artifact);
+            if let Some(reason) = &reason {
+                warn!("Skipping {}: {reason}", artifact.path.display());
+            }
+            reason
+        })
+        .collect()
+}
+
+/// Ask separately for each build system whether to remove its artifacts
+/// (`--confirm-per-system`), returning those of the systems confirmed.
+/// Prints "Aborted." if none were.
+pub fn confirm_per_system(
+    out: &mut dyn Write,
+    input: &mut dyn BufRead,
+    artifacts: &[Artifact],
+    options: &DeleteOptions,
+) -> Result<Vec<Artifact>, DeleteError> {
+    let mut systems: BTreeMap<&str, (usize, u64)> = BTreeMap::new();
+    for a in artifacts {
+        let (count, bytes) = systems.entry(a.build_system).or_default();
+        *count += 1;
+        *bytes += a.size_bytes;
+    }
+    writeln!(out)?;
+    let mut confirmed = HashSet::new();
+    for (system, (count, bytes)) in systems {
+        let what = format!("{system} artifacts");
+        let question = question(options, count, &what, bytes);
+        if confirm_removal(out, input, options, &question, count, bytes)? {
+            confirmed.insert(system);
+        }
+    }
+    if confirmed.is_empty() {
+        writeln!(out, "Aborted.")?;
+    }
+    Ok(artifacts
+        .iter()
+        .filter(|a| confirmed.contains(a.build_system))
+        .cloned()
+        .collect())
+}
+
+/// Remove artifacts of the `auto` risk categories without asking
+/// (`--yes-for`), and ask once about the rest, returning the artifacts
+/// to remove. Prints "Aborted." if that leaves none.
+pub fn confirm_by_risk(
+    out: &mut dyn Write,
+    input: &mut dyn BufRead,
+    artifacts: &[Artifact],
+    options: &DeleteOptions,
+    auto: &[Risk],
+) -> Result<Vec<Artifact>, DeleteError> {
+    let (confirmed, asked): (Vec<&Artifact>, Vec<&Artifact>) =
+        artifacts.iter().partition(|a| auto.contains(&a.risk));
+    writeln!(out)?;
+    if !confirmed.is_empty() {
+        let bytes = confirmed.iter().map(|a| a.size_bytes).sum();
+        writeln!(
+            out,
+            "{} {} artifacts ({}) need no confirmation (--yes-for).",
+            confirmed.len(),
+            risk_names(&confirmed),
+            format_size(bytes)
+        )?;
+    }
+    let mut ask_granted = false;
+    if !asked.is_empty() {
+        let bytes = asked.iter().map(|a| a.size_bytes).sum();
+        let what = format!("{} artifacts", risk_names(&asked));
+        let question = question(options, asked.len(), &what, bytes);
+        ask_granted = confirm_removal(out, input, options, &question, asked.len(), bytes)?;
+    }
+    if confirmed.is_empty() && !ask_granted {
+        writeln!(out, "Aborted.")?;
+    }
+    Ok(artifacts
+        .iter()
+        .filter(|a| ask_granted || auto.contains(&a.risk))
+        .cloned()
+        .collect())
+}
+
+/// The risk categories of `artifacts`, e.g. `cache/output`.
+fn risk_names(artifacts: &[&Artifact]) -> String {
+    let risks: BTreeSet<Risk> = artifacts.iter().map(|a| a.risk).collect();
+    risks
+        .iter()
+        .map(|r| r.as_str())
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+/// The confirmation question for removing `count` of `what` totalling
+/// `bytes`, worded for how `options` removes them.
+fn question(options: &DeleteOptions, count: usize, what: &str, bytes: u64) -> String {
+    let size = format_size(bytes);
+    if options.quarantine.is_some() {
+        format!("Move {count} {what} ({size}) to quarantine?")
+    } else if options.trash {
+        format!("Move {count} {what} ({size}) to the trash?")
+    } else if options.contents_only {
+        format!("Empty {count} {what} ({size})?")
+    } else {
+        format!("Delete {count} {what} ({size})?")
+    }
+}
+
+/// Ask whether to remove `count` artifacts totalling `bytes`. A large removal
+/// (see [`LargeDeletion`]) is only confirmed by typing the count back. With
+/// `options.no_input`, fails instead of asking.
+fn confirm_removal(
+    out: &mut dyn Write,
+    input: &mut dyn BufRead,
+    options: &DeleteOptions,
+    question: &str,
+    count: usize,
+    bytes: u64,
+) -> Result<bool, DeleteError> {
+    if options.no_input {
+        return Err(DeleteError::InputRequired {
+            question: question.to_string(),
+        });
+    }
+    if !options.large.applies(count, bytes) {
+        return Ok(confirm(out, input, question)?);
+    }
+    write!(
+        out,
+        "{question}\nThis is a large deletion. Type {count} to confirm: "
+    )?;
+    out.flush()?;
+
+    let mut response = String::new();
+    input.read_line(&mut response)?;
+    Ok(response.trim() == count.to_string())
+}
+
+/// Ask a yes/no question, defaulting to no.
+fn confirm(out: &mut dyn Write, input: &mut dyn BufRead, question: &str) -> std::io::Result<bool> {
+    write!(out, "{question} [y/N] ")?;
+    out.flush()?;
+
+    let mut response = String::new();
+    input.read_line(&mut response)?;
+    let response = response.trim().to_lowercase();
+    Ok(response == "y" || response == "yes")
+}
+
+/// Tracks whether removals should stop being started (Ctrl+C, or the
+/// caller's [`CancelToken`]).
+struct Cancel<'a> {
+    flag: &'a AtomicBool,
+    token: &'a CancelToken,
+    warned: AtomicBool,
+}
+
+impl<'a> Cancel<'a> {
+    fn new(flag: &'a AtomicBool, token: &'a CancelToken) -> Self {
+        Self {
+            flag,
+            token,
+            warned: AtomicBool::new(false),
+        }
+    }
+
+    /// Whether to stop, explaining what happens next the first time.
+    fn requested(&self) -> bool {
+        let interrupted = self.flag.load(Ordering::SeqCst);
+        let requested = interrupted || self.token.is_cancelled();
+        if requested && !self.warned.swap(true, Ordering::SeqCst) {
+            if interrupted {
+                warn!(
+                    "Interrupted; finishing removals in progress (press Ctrl+C again to stop now)"
+                );
+            } else {
+                warn!("Cancelled; finishing removals in progress");
+            }
+        }
+        requested
+    }
+}
+
+/// Print each failure in `outcomes`, preceded by a blank line.
+fn print_failures(out: &mut dyn Write, outcomes: &[Outcome]) -> std::io::Result<()> {
+    let mut failures = outcomes.iter().filter_map(|o| match o {
+        Outcome::Failed(failure) => Some(failure),
+        _ => None,
+    });
+    if let Some(first) = failures.next() {
+        writeln!(out)?;
+        for err in std::iter::once(first).chain(failures) {
+            writeln!(out, "Error: {err}")?;
+        }
+    }
+    Ok(())
+}
+
+/// Permanently delete artifacts, printing any errors. Deletes in parallel
+/// unless `options.throttle` is set, in which case artifacts go one at a time
+/// on the calling thread.
+fn delete_all(
+    out: &mut dyn Write,
+    artifacts: &[Artifact],
+    options: &DeleteOptions,
+    cancel: &Cancel,
+    timings: &Mutex<Vec<Timing>>,
+) -> Result<Vec<Outcome>, DeleteError> {
+    let throttle = options.throttle;
+    info!("Deleting {} artifact directories", artifacts.len());
+    let progress = DeletionBars::new(artifacts, "Deleting");
+    let delete_one = |artifact: &Artifact| {
+        if cancel.requested() {
+            return Outcome::Skipped;
+        }
+        debug!("Deleting {}", artifact.path.display());
+        let mut bar = progress.start(artifact);
+        let started = Instant::now();
+        let mut files = 0;
+        let result = match options.timeout {
+            Some(timeout) => delete_watched(&artifact.path, options, timeout, &mut |bytes| {
+                files += 1;
+                bar.inc(bytes)
+            }),
+            None => {
+                let mut pacer = throttle.map(|t| t.pacer());
+                delete_artifact(&artifact.path, options, &mut |bytes| {
+                    files += 1;
+                    bar.inc(bytes);
+                    if let Some(pacer) = &mut pacer {
+                        pacer.tick();
+                    }
+                })
+            }
+        };
+        let timing = Timing {
+            path: artifact.path.clone(),
+            duration: started.elapsed(),
+            files,
+        };
+        timings
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .push(timing);
+        match result {
+            Ok(0) => {
+                bar.finish("deleted");
+                Outcome::Deleted
+            }
+            Ok(retries) => {
+                info!("Deleted {}{}", artifact.path.display(), retried(retries));
+                bar.finish("deleted");
+                Outcome::Deleted
+            }
+            Err(e) => {
+                bar.finish("failed");
+                warn!("{e}");
+                Outcome::Failed(Failure::from(&e))
+            }
+        }
+    };
+    let outcomes: Vec<Outcome> = if throttle.is_some() {
+        artifacts.iter().map(delete_one).collect()
+    } else {
+        artifacts.par_iter().map(delete_one).collect()
+    };
+    progress.finish();
+    print_failures(out, &outcomes)?;
+    Ok(outcomes)
+}
+
+/// Delete artifacts, first running their build tools' clean commands when
+/// `options.use_tool` is set. Whatever a tool leaves behind is deleted
+/// directly, unless the tool timed out.
+fn clean_and_delete(
+    out: &mut dyn Write,
+    artifacts: &[Artifact],
+    options: &DeleteOptions,
+    cancel: &Cancel,
+    timings: &Mutex<Vec<Timing>>,
+) -> Result<Vec<Outcome>, DeleteError> {
+    let cleaned = if options.use_tool {
+        run_clean_tools(
+            artifacts,
+            options.throttle.is_some(),
+            options.timeout,
+            cancel,
+        )
+    } else {
+        vec![None; artifacts.len()]
+    };
+    let rest: Vec<Artifact> = artifacts
+        .iter()
+        .zip(&cleaned)
+        .filter(|(_, outcome)| outcome.is_none())
+        .map(|(a, _)| a.clone())
+        .collect();
+    // Lists the tools that timed out.
+    let by_tool: Vec<Outcome> = cleaned.iter().flatten().cloned().collect();
+    print_failures(out, &by_tool)?;
+    let mut deleted = delete_all(out, &rest, options, cancel, timings)?.into_iter();
+    Ok(cleaned
+        .into_iter()
+        .map(|outcome| {
+            outcome.unwrap_or_else(|| deleted.next().expect("one outcome per remaining artifact"))
+        })
+        .collect())
+}
+
+/// How a build tool's clean command ended.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ToolRun {
+    Succeeded,
+    /// Missing, failed, or not started because the run was cancelled.
+    Failed,
+    /// Killed for running longer than `--timeout`.
+    TimedOut(Duration),
+}
+
+/// Run each project's clean command once, in parallel unless `serial`
+/// (so that tools inherit a lowered IO priority), killing any still running
+/// after `timeout`. Returns, for each artifact, `Cleaned` with the tool that
+/// removed it, `Failed` if its tool timed out, or `None` if it is to be
+/// deleted directly (no command, tool missing or failed, or the tool left
+/// it behind).
+fn run_clean_tools(
+    artifacts: &[Artifact],
+    serial: bool,
+    timeout: Option<Duration>,
+    cancel: &Cancel,
+) -> Vec<Option<Outcome>> {
+    let mut commands: Vec<(&Path, &'static [&'static str])> = artifacts
+        .iter()
+        .filter_map(|a| Some((a.project.as_path(), a.clean?)))
+        .collect();
+    commands.sort_unstable();
+    commands.dedup();
+    let run = |&(project, command): &(&Path, &[&str])| {
+        if cancel.requested() {
+            ToolRun::Failed
+        } else {
+            run_clean_tool(project, command, timeout)
+        }
+    };
+    let results: Vec<ToolRun> = if serial {
+        commands.iter().map(run).collect()
+    } else {
+        commands.par_iter().map(run).collect()
+    };
+    let results: HashMap<(&Path, &[&str]), ToolRun> = commands.into_iter().zip(results).collect();
+    artifacts
+        .iter()
+        .map(|a| {
+            let command = a.clean?;
+            match results[&(a.project.as_path(), command)] {
+                ToolRun::Succeeded if std::fs::symlink_metadata(&a.path).is_err() => {
+                    Some(Outcome::Cleaned(command[0].to_string()))
+                }
+                ToolRun::TimedOut(timeout) => {
+                    let e = DeleteError::ToolTimedOut {
+                        command: command.join(" "),
+                        project: a.project.display().to_string(),
+                        timeout,
+                    };
+                    Some(Outcome::Failed(Failure::from(&e)))
+                }
+                _ => None,
+            }
+        })
+        .collect()
+}
+
+/// Environment variables that point a build tool's clean command somewhere
+/// other than the artifact it was run for.
+const TOOL_OVERRIDES: &[&str] = &[
+    "CARGO_TARGET_DIR",
+    "CARGO_BUILD_TARGET_DIR",
+    "MAVEN_ARGS",
+    "MILL_OUTPUT_DIR",
+];
+
+/// Run `command` in `project`, killing it if it runs longer than `timeout`.
+fn run_clean_tool(project: &Path, command: &[&str], timeout: Option<Duration>) -> ToolRun {
+    let display = command.join(" ");
+    info!("Running {display} in {}", project.display());
+    let mut tool = Command::new(command[0]);
+    for name in TOOL_OVERRIDES {
+        tool.env_remove(name);
+    }
+    let spawned = tool
+        .args(&command[1..])
+        .current_dir(project)
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::piped())
+        .spawn();
+    let mut child = match spawned {
+        Ok(child) => child,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+            debug!("{} is not installed; deleting directly", command[0]);
+            return ToolRun::Failed;
+        }
+        Err(e) => {
+            warn!(
+                "cannot run {display} in {}: {e}; deleting directly",
+                project.display()
+            );
+            return ToolRun::Failed;
+        }
+    };
+    // Read on another thread, so a tool filling the pipe does not stall.
+    let mut stderr = child.stderr.take();
+    let reader = std::thread::spawn(move || {
+        let mut text = String::new();
+        if let Some(stderr) = &mut stderr {
+            let _ = std::io::Read::read_to_string(stderr, &mut text);
+        }
+        text
+    });
+    let status = match timeout {
+        Some(timeout) => wait_until(&mut child, Instant::now() + timeout),
+        None => child.wait().map(Some),
+    };
+    match status {
+        Ok(Some(status)) if status.success() => ToolRun::Succeeded,
+        Ok(Some(status)) => {
+            let stderr = reader.join().unwrap_or_default();
+            let detail = stderr.lines().last().unwrap_or_default().trim();
+            warn!(
+                "{display} failed in {} ({status}{}{detail}); deleting directly",
+                project.display(),
+                if detail.is_empty() { "" } else { ": " }
+            );
+            ToolRun::Failed
+        }
+        Ok(None) => {
+            // Not joining the reader: the tool's own children may still
+            // hold the pipe open.
+            let timeout = timeout.unwrap_or_default();
+            warn!(
+                "{display} still running in {} after {}; killed it",
+                project.display(),
+                format_age(timeout)
+            );
+            ToolRun::TimedOut(timeout)
+        }
+        Err(e) => {
+            warn!(
+                "cannot wait for {display} in {}: {e}; deleting directly",
+                project.display()
+            );
+            ToolRun::Failed
+        }
+    }
+}
+
+/// Wait for `child` to exit until `deadline`, then kill it. Returns its exit
+/// status, or `None` if it was killed.
+fn wait_until(
+    child: &mut std::process::Child,
+    deadline: Instant,
+) -> std::io::Result<Option<std::process::ExitStatus>> {
+    loop {
+        if let Some(status) = child.try_wait()? {
+            return Ok(Some(status));
+        }
+        let now = Instant::now();
+        if now >= deadline {
+            child.kill()?;
+            child.wait()?;
+            return Ok(None);
+        }
+        std::thread::sleep((deadline - now).min(Duration::from_millis(50)));
+    }
+}
+
+/// Move artifacts to the trash one at a time. Any that cannot be trashed are
+/// listed, and the user is asked whether to delete them permanently; with
+/// `options.skip_confirm` they are left alone.
+fn trash_all(
+    out: &mut dyn Write,
+    input: &mut dyn BufRead,
+    artifacts: &[Artifact],
+    options: &DeleteOptions,
+    cancel: &Cancel,
+    timings: &Mutex<Vec<Timing>>,
+) -> Result<Vec<Outcome>, DeleteError> {
+    info!(
+        "Moving {} artifact directories to the trash",
+        artifacts.len()
+    );
+    let progress = DeletionBars::new(artifacts, "Trashing");
+    let mut outcomes: Vec<Outcome> = artifacts
+        .iter()
+        .map(|artifact| {
+            if cancel.requested() {
+                return Outcome::Skipped;
+            }
+            debug!("Trashing {}", artifact.path.display());
+            let bar = progress.start(artifact);
+            match trash::delete(&artifact.path) {
+                Ok(()) => {
+                    bar.finish("trashed");
+                    Outcome::Trashed
+                }
+                Err(source) => {
+                    bar.finish("failed");
+                    let e = DeleteError::Trash {
+                        path: artifact.path.display().to_string(),
+                        source,
+                    };
+                    warn!("{e}");
+                    Outcome::Failed(Failure::from(&e))
+                }
+            }
+        })
+        .collect();
+    progress.finish();
+
+    let untrashed: Vec<usize> = (0..artifacts.len())
+        .filter(|&i| matches!(outcomes[i], Outcome::Failed(_)))
+        .collect();
+    if untrashed.is_empty() || cancel.requested() {
+        return Ok(outcomes);
+    }
+    print_failures(out, &outcomes)?;
+    let question = format!(
+        "Permanently delete the {} that could not be trashed?",
+        if untrashed.len() == 1 {
+            "artifact".to_string()
+        } else {
+            format!("{} artifacts", untrashed.len())
+        }
+    );
+    if !options.skip_confirm && confirm(out, input, &question)? {
+        let rest: Vec<Artifact> = untrashed.iter().map(|&i| artifacts[i].clone()).collect();
+        for (i, outcome) in untrashed
+            .into_iter()
+            .zip(delete_all(out, &rest, options, cancel, timings)?)
+        {
+            outcomes[i] = outcome;
+        }
+    }
+    Ok(outcomes)
+}
+
+/// Rename each artifact into a hidden holding directory in its parent, which
+/// is on the same filesystem and so instant, then start `worker` detached
+/// with the holding directories appended to delete them. Prints any errors.
+fn move_aside_all(
+    out: &mut dyn Write,
+    artifacts: &[Artifact],
+    worker: &[OsString],
+) -> Result<Vec<Outcome>, DeleteError> {
+    info!("Moving {} artifact directories aside", artifacts.len());
+    let holding_name = format!(".clean-builds-{}", std::process::id());
+    let mut holding = Vec::new();
+    let outcomes: Vec<Outcome> = artifacts
+        .iter()
+        .map(|artifact| match move_aside(&artifact.path, &holding_name) {
+            Ok(dir) => {
+                if !holding.contains(&dir) {
+                    holding.push(dir);
+                }
+                Outcome::MovedAside
+            }
+            Err(source) => {
+                let e = DeleteError::RemoveDir {
+                    path: artifact.path.display().to_string(),
+                    source,
+                    retries: 0,
+                };
+                warn!("{e}");
+                Outcome::Failed(Failure::from(&e))
+            }
+        })
+        .collect();
+    if !holding.is_empty() {
+        spawn_detached(worker, &holding)?;
+    }
+    print_failures(out, &outcomes)?;
+    Ok(outcomes)
+}
+
+/// Rename `path` into the directory `holding_name` beside it, creating that
+/// directory if needed. Returns the holding directory.
+fn move_aside(path: &Path, holding_name: &str) -> std::io::Result<PathBuf> {
+    let parent = path.parent().unwrap_or(Path::new("."));
+    let holding = parent.join(holding_name);
+    match std::fs::create_dir(&holding) {
+        Err(e) if e.kind() != std::io::ErrorKind::AlreadyExists => return Err(e),
+        _ => {}
+    }
+    let name = path.file_name().unwrap_or(path.as_os_str());
+    std::fs::rename(path, holding.join(name))?;
+    debug!("Moved {} aside to {}", path.display(), holding.display());
+    Ok(holding)
+}
+
+/// Start `command` with `paths` appended, detached from this process's
+/// terminal and signals so it outlives it.
+fn spawn_detached(command: &[OsString], paths: &[PathBuf]) -> std::io::Result<()> {
+    let (program, args) = command.split_first().ok_or_else(|| {
+        std::io::Error::new(std::io::ErrorKind::InvalidInput, "no background command")
+    })?;
+    let mut worker = Command::new(program);
+    worker
+        .args(args)
+        .args(paths)
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null());
+    detach(&mut worker);
+    let child = worker.spawn()?;
+    info!("Deleting in the background (pid {})", child.id());
+    Ok(())
+}
+
+/// Put the worker in its own process group, so Ctrl+C in the terminal does
+/// not reach it.
+#[cfg(unix)]
+fn detach(command: &mut Command) {
+    use std::os::unix::process::CommandExt;
+    command.process_group(0);
+}
+
+/// Start the worker without a console, in its own process group.
+#[cfg(windows)]
+fn detach(command: &mut Command) {
+    use std::os::windows::process::CommandExt;
+    const DETACHED_PROCESS: u32 = 0x0000_0008;
+    const CREATE_NEW_PROCESS_GROUP: u32 = 0x0000_0200;
+    command.creation_flags(DETACHED_PROCESS | CREATE_NEW_PROCESS_GROUP);
+}
+
+#[cfg(not(any(unix, windows)))]
+fn detach(_command: &mut Command) {}
+
+/// Delete the holding directories that `--background` moved artifacts into;
+/// the body of the `background-worker` subcommand. Runs at low IO priority,
+/// since nobody is waiting for it. Failures are logged and skipped.
+pub fn finish_background(holding: &[PathBuf]) {
+    throttle::lower_io_priority();
+    let options = DeleteOptions::default();
+    for dir in holding {
+        if let Err(e) = delete_artifact(dir, &options, &mut |_| {}) {
+            warn!("{e}");
+        }
+    }
+}
+
+/// Move artifacts into a new quarantine run under `dir`, printing any errors.
+fn quarantine_all(
+    out: &mut dyn Write,
+    artifacts: &[Artifact],
+    dir: &Path,
+) -> Result<Vec<Outcome>, DeleteError> {
+    info!(
+        "Moving {} artifact directories to quarantine in {}",
+        artifacts.len(),
+        dir.display()
+    );
+    let run = Quarantine::new(dir).quarantine(artifacts)?;
+    let outcomes: Vec<Outcome> = artifacts
+        .iter()
+        .map(|artifact| {
+            let failure = run
+                .errors
+                .iter()
+                .find(|e| matches!(e, QuarantineError::Io { path, .. } if *path == artifact.path));
+            match failure {
+                Some(e) => Outcome::Failed(Failure::new(FailureKind::Quarantine, e.to_string())),
+                None => Outcome::Quarantined(run.id.clone()),
+            }
+        })
+        .collect();
+    print_failures(out, &outcomes)?;
+    Ok(outcomes)
+}
+
+/// Delete a single artifact directory (or file artifact), reporting the size
+/// of each file as it is removed. Transient failures are retried according
+/// to `options.retry`; returns the number of retries needed.
+///
+/// Unless only emptying it, the artifact is first renamed aside (see
+/// [`rename_aside`]) and, if removal fails, renamed back.
+fn delete_artifact(
+    path: &Path,
+    options: &DeleteOptions,
+    removed: &mut dyn FnMut(u64),
+) -> Result<u32, DeleteError> {
+    let long = extended_length_path(path);
+    let target = if options.contents_only {
+        long.clone()
+    } else {
+        rename_aside(&long)
+    };
+    let result = with_retry(options.retry, |attempt| {
+        let result = if options.contents_only {
+            empty_tree(&target, options, removed)
+        } else {
+            remove_tree(&target, options, removed)
+        };
+        match result {
+            // A retry found the artifact already gone, e.g. removed by whatever
+            // was holding it.
+            Err(e) if attempt > 0 && e.kind() == std::io::ErrorKind::NotFound => Ok(()),
+            result => {
+                if let Err(e) = &result {
+                    debug!("Deleting {} failed: {e}", path.display());
+                }
+                result
+            }
+        }
+    });
+    if result.is_err() && target != long {
+        // Put what is left back, so the artifact is found again next time
+        // rather than hiding under a temporary name.
+        if let Err(e) = std::fs::rename(&target, &long) {
+            warn!(
+                "cannot move {} back to {}: {e}",
+                target.display(),
+                long.display()
+            );
+        }
+    }
+    result.map_err(|(source, retries)| DeleteError::RemoveDir {
+        path: path.display().to_string(),
+        source,
+        retries,
+    })
+}
+
+/// Rename `path` to a hidden name beside it, on the same filesystem and so
+/// atomic, letting builds see it gone at once instead of writing into a
+/// half-deleted directory. Returns the new path, or `path` itself if it
+/// cannot be renamed, e.g. while a file in it is open on Windows.
+fn rename_aside(path: &Path) -> PathBuf {
+    let Some(name) = path.file_name() else {
+        return path.to_path_buf();
+    };
+    let mut hidden = OsString::from(".");
+    hidden.push(name);
+    hidden.push(format!(".clean-builds-{}", std::process::id()));
+    let aside = path.with_file_name(hidden);
+    match std::fs::rename(path, &aside) {
+        Ok(()) => {
+            debug!("Renamed {} to {}", path.display(), aside.display());
+            aside
+        }
+        Err(e) => {
+            debug!("Deleting {} in place: {e}", path.display());
+            path.to_path_buf()
+        }
+    }
+}
+
+/// Delete an artifact like `delete_artifact`, but on a separate thread that
+/// is abandoned if it removes nothing for `timeout`. A deletion stuck in the
+/// kernel cannot be cancelled, only left behind.
+fn delete_watched(
+    path: &Path,
+    options: &DeleteOptions,
+    timeout: Duration,
+    removed: &mut dyn FnMut(u64),
+) -> Result<u32, DeleteError> {
+    let worker_path = path.to_path_buf();
+    let worker_options = options.clone();
+    let result = watch(
+        timeout,
+        move |removed| {
+            let mut pacer = worker_options.throttle.map(|t| t.pacer());
+            delete_artifact(&worker_path, &worker_options, &mut |bytes| {
+                removed(bytes);
+                if let Some(pacer) = &mut pacer {
+                    pacer.tick();
+                }
+            })
+        },
+        removed,
+    );
+    result.unwrap_or_else(|| {
+        Err(DeleteError::TimedOut {
+            path: path.display().to_string(),
+            timeout,
+        })
+    })
+}
+
+/// What a watched worker tells the thread waiting for it.
+enum Message<R> {
+    Removed(u64),
+    Done(R),
+}
+
+/// Run `work` on a new thread, passing on the byte counts it reports to
+/// `removed`. Returns its result, or `None` if it reported nothing for
+/// `timeout` and was abandoned.
+fn watch<R: Send + 'static>(
+    timeout: Duration,
+    work: impl FnOnce(&mut dyn FnMut(u64)) -> R + Send + 'static,
+    removed: &mut dyn FnMut(u64),
+) -> Option<R> {
+    let (tx, rx) = mpsc::channel();
+    std::thread::spawn(move || {
+        let progress = tx.clone();
+        let result = work(&mut |bytes| {
+            let _ = progress.send(Message::Removed(bytes));
+        });
+        let _ = tx.send(Message::Done(result));
+    });
+    loop {
+        match rx.recv_timeout(timeout) {
+            Ok(Message::Removed(bytes)) => removed(bytes),
+            Ok(Message::Done(result)) => return Some(result),
+            // A worker that panicked is as good as stuck.
+            Err(_) => return None,
+        }
+    }
+}
+
+/// Run `op` (given the attempt number, from 0) until it succeeds, fails with
+/// a permanent error, or runs out of retries. Returns the number of retries
+/// made alongside the result.
+fn with_retry(
+    retry: Retry,
+    mut op: impl FnMut(u32) -> std::io::Result<()>,
+) -> Result<u32, (std::io::Error, u32)> {
+    let mut backoff = retry.backoff;
+    let mut attempt = 0;
+    loop {
+        match op(attempt) {
+            Ok(()) => return Ok(attempt),
+            Err(e) if attempt < retry.retries && is_transient(&e) => {
+                std::thread::sleep(backoff);
+                backoff *= 2;
+                attempt += 1;
+            }
+            Err(e) => return Err((e, attempt)),
+        }
+    }
+}
+
+/// Whether an error is likely to go away if the operation is tried again
+/// shortly: a busy file, or a directory something is still writing into. On
+/// Windows, indexers and virus scanners holding a file cause sharing
+/// violations and access denied errors as well.
+fn is_transient(e: &std::io::Error) -> bool {
+    use std::io::ErrorKind;
+    match e.kind() {
+        ErrorKind::ResourceBusy | ErrorKind::ExecutableFileBusy | ErrorKind::DirectoryNotEmpty => {
+            true
+        }
+        ErrorKind::PermissionDenied => cfg!(windows),
+        // ERROR_SHARING_VIOLATION, ERROR_LOCK_VIOLATION
+        _ => cfg!(windows) && matches!(e.raw_os_error(), Some(32 | 33)),
+    }
+}
+
+/// Remove `path` and everything below it without following symlinks, like
+/// `remove_dir_all`, but one file at a time so progress can be reported.
+///
+/// With `options.force_permissions`, a directory that cannot be listed, or
+/// whose entries cannot be removed, is made accessible (`u+rwx`) and tried
+/// once more. Only directories inside `path` are changed, never its parent.
+/// With `options.shred`, file contents are overwritten before unlinking.
+fn remove_tree(
+    path: &Path,
+    options: &DeleteOptions,
+    removed: &mut dyn FnMut(u64),
+) -> std::io::Result<()> {
+    let meta = std::fs::symlink_metadata(path)?;
+    if is_dir_link(&meta) {
+        return remove_entry(path, &meta, |p| std::fs::remove_dir(p));
+    }
+    if !meta.is_dir() {
+        if options.shred && meta.is_file() {
+            shred(path, &meta)?;
+        }
+        remove_entry(path, &meta, |p| std::fs::remove_file(p))?;
+        removed(if meta.is_file() { meta.len() } else { 0 });
+        return Ok(());
+    }
+
+    remove_children(path, options, &[], removed)?;
+    remove_entry(path, &meta, |p| std::fs::remove_dir(p))
+}
+
+/// Overwrite the contents of file `path` with zeros and flush them to disk,
+/// so the data does not linger in freed blocks once the file is unlinked
+/// (`--shred`). Files with other hard links are left intact, since
+/// overwriting them would destroy the linked copies too.
+///
+/// On SSDs and copy-on-write or journaling filesystems the old blocks may
+/// survive anyway; this only helps where writes land in place.
+fn shred(path: &Path, meta: &std::fs::Metadata) -> std::io::Result<()> {
+    if hard_links(meta) > 1 {
+        debug!("Not shredding {}: it has other hard links", path.display());
+        return Ok(());
+    }
+    let open = || std::fs::OpenOptions::new().write(true).open(path);
+    let mut file = match open() {
+        Err(e) if e.kind() == std::io::ErrorKind::PermissionDenied => {
+            let mut permissions = meta.permissions();
+            #[allow(clippy::permissions_set_readonly_false)] // It is deleted next.
+            permissions.set_readonly(false);
+            std::fs::set_permissions(path, permissions)?;
+            open()?
+        }
+        file => file?,
+    };
+    let zeros = [0u8; 64 * 1024];
+    let mut left = meta.len();
+    while left > 0 {
+        let n = left.min(zeros.len() as u64) as usize;
+        file.write_all(&zeros[..n])?;
+        left -= n as u64;
+    }
+    file.sync_all()
+}
+
+#[cfg(unix)]
+fn hard_links(meta: &std::fs::Metadata) -> u64 {
+    use std::os::unix::fs::MetadataExt;
+    meta.nlink()
+}
+
+#[cfg(not(unix))]
+fn hard_links(_meta: &std::fs::Metadata) -> u64 {
+    1
+}
+
+/// Files that `--contents-only` leaves in an emptied artifact directory:
+/// placeholders that keep it in version control, and the cache marker that
+/// tells backup tools to skip it.
+pub const KEPT_MARKERS: &[&str] = &[".gitkeep", "CACHEDIR.TAG"];
+
+/// Remove everything inside directory `path` except [`KEPT_MARKERS`],
+/// keeping `path` itself, e.g. when it is a mount point or bind target.
+/// Anything other than a real directory is removed like `remove_tree`.
+fn empty_tree(
+    path: &Path,
+    options: &DeleteOptions,
+    removed: &mut dyn FnMut(u64),
+) -> std::io::Result<()> {
+    let meta = std::fs::symlink_metadata(path)?;
+    if is_dir_link(&meta) || !meta.is_dir() {
+        return remove_tree(path, options, removed);
+    }
+    remove_children(path, options, KEPT_MARKERS, removed)
+}
+
+/// Remove every entry of directory `dir` except those named in `keep`. See
+/// `remove_tree` for how `options` apply.
+fn remove_children(
+    dir: &Path,
+    options: &DeleteOptions,
+    keep: &[&str],
+    removed: &mut dyn FnMut(u64),
+) -> std::io::Result<()> {
+    let denied = |e: &std::io::Error| {
+        options.force_permissions && e.kind() == std::io::ErrorKind::PermissionDenied
+    };
+    let mut forced = false;
+    let entries = match std::fs::read_dir(dir) {
+        Err(e) if denied(&e) => {
+            make_accessible(dir)?;
+            forced = true;
+            std::fs::read_dir(dir)?
+        }
+        entries => entries?,
+    };
+    for entry in entries {
+        let entry = entry?;
+        if keep.iter().any(|name| entry.file_name() == *name) {
+            continue;
+        }
+        let child = entry.path();
+        match remove_tree(&child, options, removed) {
+            Err(e) if !forced && denied(&e) => {
+                make_accessible(dir)?;
+                forced = true;
+                remove_tree(&child, options, removed)?;
+            }
+            result => result?,
+        }
+    }
+    Ok(())
+}
+
+/// Give the owner full access to directory `dir` so it can be listed and
+/// its entries removed.
+#[cfg(unix)]
+fn make_accessible(dir: &Path) -> std::io::Result<()> {
+    use std::os::unix::fs::PermissionsExt;
+    debug!("Making {} accessible", dir.display());
+    let mut permissions = std::fs::metadata(dir)?.permissions();
+    permissions.set_mode(permissions.mode() | 0o700);
+    std::fs::set_permissions(dir, permissions)
+}
+
+#[cfg(not(unix))]
+fn make_accessible(_dir: &Path) -> std::io::Result<()> {
+    Ok(())
+}
+
+/// Whether `meta` is a Windows directory symlink or junction, which must be
+/// removed with `remove_dir` without descending into it.
+#[cfg(windows)]
+fn is_dir_link(meta: &std::fs::Metadata) -> bool {
+    use std::os::windows::fs::FileTypeExt;
+    meta.file_type().is_symlink_dir()
+}
+
+#[cfg(not(windows))]
+fn is_dir_link(_meta: &std::fs::Metadata) -> bool {
+    false
+}
+
+/// Run `remove` on `path`. On Windows, entries with the read-only attribute
+/// (common in vendored trees and NuGet outputs) refuse deletion, so if that
+/// is why it failed, clear the attribute and try again.
+#[cfg(windows)]
+fn remove_entry(
+    path: &Path,
+    meta: &std::fs::Metadata,
+    remove: fn(&Path) -> std::io::Result<()>,
+) -> std::io::Result<()> {
+    match remove(path) {
+        Err(e)
+            if e.kind() == std::io::ErrorKind::PermissionDenied
+                && meta.permissions().readonly() =>
+        {
+            debug!("Clearing read-only attribute of {}", path.display());
+            let mut permissions = meta.permissions();
+            permissions.set_readonly(false);
+            std::fs::set_permissions(path, permissions)?;
+            remove(path)
+        }
+        result => result,
+    }
+}
+
+#[cfg(not(windows))]
+fn remove_entry(
+    path: &Path,
+    _meta: &std::fs::Metadata,
+    remove: fn(&Path) -> std::io::Result<()>,
+) -> std::io::Result<()> {
+    remove(path)
+}
+
+/// On Windows, the `\\?\` form of an absolute path, which lifts the 260
+/// character `MAX_PATH` limit for deep trees such as `node_modules`.
+#[cfg(windows)]
+fn extended_length_path(path: &Path) -> PathBuf {
+    path.to_str()
+        .and_then(extended_length)
+        .map_or_else(|| path.to_path_buf(), PathBuf::from)
+}
+
+#[cfg(not(windows))]
+fn extended_length_path(path: &Path) -> PathBuf {
+    path.to_path_buf()
+}
+
+/// Rewrite a Windows path in extended-length form: `C:\x` becomes
+/// `\\?\C:\x` and `\\server\share\x` becomes `\\?\UNC\server\share\x`.
+/// Returns `None` for paths that are already in that form or that are not
+/// absolute, which the prefix cannot be applied to.
+#[cfg_attr(not(windows), allow(dead_code))]
+fn extended_length(path: &str) -> Option<String> {
+    if path.starts_with(r"\\?\") || path.starts_with(r"\\.\") {
+        return None;
+    }
+    if let Some(unc) = path.strip_prefix(r"\\") {
+        return Some(format!(r"\\?\UNC\{}", unc.replace('/', r"\")));
+    }
+    let bytes = path.as_bytes();
+    let is_drive = bytes.len() >= 3
+        && bytes[0].is_ascii_alphabetic()
+        && bytes[1] == b':'
+        && matches!(bytes[2], b'\\' | b'/');
+    // Forward slashes are not translated in extended-length paths.
+    is_drive.then(|| format!(r"\\?\{}", path.replace('/', r"\")))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rules::Risk;
+    use std::fs;
+    use std::io::Cursor;
+    use tempfile::TempDir;
+
+    fn prompt() -> DeleteOptions {
+        DeleteOptions::default()
+    }
+
+    fn yes() -> DeleteOptions {
+        DeleteOptions {
+            skip_confirm: true,
+            ..Default::default()
+        }
+    }
+
+    fn make_test_artifact(tmp: &TempDir, name: &'static str) -> Artifact {
+        let path = tmp.path().join(name);
+        fs::create_dir_all(&path).unwrap();
+        fs::write(path.join("file.txt"), "test data").unwrap();
+        Artifact {
+            size_bytes: 9,
+            ..Artifact::for_test(path, "Test")
+        }
+    }
+
+    #[test]
+    fn confirm_yes_deletes() {
+        let tmp = TempDir::new().unwrap();
+        let artifacts = vec![make_test_artifact(&tmp, "target")];
+
+        let mut out = Vec::new();
+        let mut input = Cursor::new(b"y\n".to_vec());
+        let deleted = confirm_and_delete(&mut out, &mut input, &artifacts, &prompt())
+            .unwrap()
+            .removed();
+
+        assert_eq!(deleted, 1);
+        assert!(!tmp.path().join("target").exists());
+    }
+
+    #[test]
+    fn confirm_no_aborts() {
+        let tmp = TempDir::new().unwrap();
+        let artifacts = vec![make_test_artifact(&tmp, "target")];
+
+        let mut out = Vec::new();
+        let mut input = Cursor::new(b"n\n".to_vec());
+        let deleted = confirm_and_delete(&mut out, &mut input, &artifacts, &prompt())
+            .unwrap()
+            .removed();
+
+        assert_eq!(deleted, 0);
+        assert!(tmp.path().join("target").exists());
+        let output = String::from_utf8(out).unwrap();
+        assert!(output.contains("Aborted"));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn report_describes_each_artifact() {
+        let tmp = TempDir::new().unwrap();
+        let artifacts = vec![
+            make_test_artifact(&tmp, "target"),
+            make_test_artifact(&tmp, "build"),
+        ];
+        let options = DeleteOptions {
+            hooks: Hooks {
+                before_delete: Some(
+                    "case $CLEAN_BUILDS_HOOK_PATH in */build) exit 1;; esac".into(),
+                ),
+                ..Default::default()
+            },
+            ..yes()
+        };
+
+        let mut out = Vec::new();
+        let mut input = Cursor::new(Vec::new());
+        let report = confirm_and_delete(&mut out, &mut input, &artifacts, &options).unwrap();
+
+        assert!(report.confirmed);
+        assert!(!report.interrupted);
+        assert_eq!(report.outcomes.len(), 2);
+        assert_eq!(report.outcomes[0].1, Outcome::Deleted);
+        assert_eq!(report.removed(), 1);
+        assert_eq!(report.bytes_removed(), 9);
+        let failures: Vec<_> = report.failures().collect();
+        assert_eq!(failures.len(), 1);
+        assert_eq!(failures[0].0.path, tmp.path().join("build"));
+        assert_eq!(failures[0].1.kind, FailureKind::Hook);
+    }
+
+    #[test]
+    fn report_times_each_deletion() {
+        let tmp = TempDir::new().unwrap();
+        let artifacts = vec![
+            make_test_artifact(&tmp, "target"),
+            make_test_artifact(&tmp, "build"),
+        ];
+
+        let mut out = Vec::new();
+        let mut input = Cursor::new(Vec::new());
+        let report = confirm_and_delete(&mut out, &mut input, &artifacts, &yes()).unwrap();
+
+        assert_eq!(report.timings.len(), 2);
+        assert_eq!(report.files_removed(), 2);
+        assert_eq!(report.slowest(1).len(), 1);
+        let output = String::from_utf8(out).unwrap();
+        assert!(output.contains("Removed 2 files in "), "{output}");
+        assert!(output.contains("Slowest:"), "{output}");
+        assert!(output.contains(", 1 file)"), "{output}");
+    }
+
+    #[test]
+    fn rename_aside_hides_the_artifact_beside_itself() {
+        let tmp = TempDir::new().unwrap();
+        let artifact = make_test_artifact(&tmp, "target");
+
+        let aside = rename_aside(&artifact.path);
+
+        assert!(!artifact.path.exists());
+        assert_eq!(aside.parent(), artifact.path.parent());
+        let name = aside.file_name().unwrap().to_str().unwrap();
+        assert!(name.starts_with(".target.clean-builds-"), "{name}");
+        assert!(aside.join("file.txt").exists());
+    }
+
+    #[test]
+    fn deletion_leaves_no_renamed_directory_behind() {
+        let tmp = TempDir::new().unwrap();
+        let artifacts = vec![make_test_artifact(&tmp, "target")];
+
+        confirm_and_delete(
+            &mut Vec::new(),
+            &mut Cursor::new(Vec::new()),
+            &artifacts,
+            &yes(),
+        )
+        .unwrap();
+
+        assert_eq!(fs::read_dir(tmp.path()).unwrap().count(), 0);
+    }
+
+    #[test]
+    fn elapsed_time_formatting() {
+        assert_eq!(format_elapsed(Duration::from_millis(1234)), "1.2s");
+        assert_eq!(format_elapsed(Duration::from_secs(61)), "1m01s");
+    }
+
+    #[test]
+    fn failures_are_classified_by_cause() {
+        let remove = |kind| DeleteError::RemoveDir {
+            path: "/dev/app/target".to_string(),
+            source: std::io::Error::from(kind),
+            retries: 0,
+        };
+        let kind = |e: DeleteError| Failure::from(&e).kind;
+        assert_eq!(
+            kind(remove(std::io::ErrorKind::PermissionDenied)),
+            FailureKind::PermissionDenied
+        );
+        assert_eq!(
+            kind(remove(std::io::ErrorKind::ResourceBusy)),
+            FailureKind::Busy
+        );
+        assert_eq!(
+            kind(remove(std::io::ErrorKind::InvalidInput)),
+            FailureKind::Other
+        );
+        let timed_out = DeleteError::TimedOut {
+            path: "/dev/app/target".to_string(),
+            timeout: Duration::from_secs(5),
+        };
+        assert_eq!(kind(timed_out), FailureKind::TimedOut);
+    }
+
+    #[test]
+    fn large_deletion_needs_the_count_typed() {
+        let tmp = TempDir::new().unwrap();
+        let artifacts = vec![
+            make_test_artifact(&tmp, "target"),
+            make_test_artifact(&tmp, "build"),
+        ];
+        let options = DeleteOptions {
+            large: LargeDeletion {
+                bytes: u64::MAX,
+                count: 1,
+            },
+            ..prompt()
+        };
+
+        let mut out = Vec::new();
+        let mut input = Cursor::new(b"y\n".to_vec());
+        let report = confirm_and_delete(&mut out, &mut input, &artifacts, &options).unwrap();
+        assert!(!report.confirmed);
+        assert!(tmp.path().join("target").exists());
+        let output = String::from_utf8(out).unwrap();
+        assert!(output.contains("Type 2 to confirm"), "{output}");
+
+        let mut input = Cursor::new(b"2\n".to_vec());
+        let deleted = confirm_and_delete(&mut Vec::new(), &mut input, &artifacts, &options)
+            .unwrap()
+            .removed();
+        assert_eq!(deleted, 2);
+    }
+
+    #[test]
+    fn large_deletion_thresholds() {
+        let large = LargeDeletion {
+            bytes: 100,
+            count: 10,
+        };
+        assert!(!large.applies(10, 100));
+        assert!(large.applies(11, 0));
+        assert!(large.applies(1, 101));
+    }
+
+    #[test]
+    fn skip_confirm_deletes_immediately() {
+        let tmp = TempDir::new().unwrap();
+        let artifacts = vec![make_test_artifact(&tmp, "build")];
+
+        let mut out = Vec::new();
+        let mut input = Cursor::new(Vec::new());
+        let deleted = confirm_and_delete(&mut out, &mut input, &artifacts, &yes())
+            .unwrap()
+            .removed();
+
+        assert_eq!(deleted, 1);
+        assert!(!tmp.path().join("build").exists());
+    }
+
+    #[test]
+    fn no_input_fails_instead_of_asking() {
+        let tmp = TempDir::new().unwrap();
+        let mut node = make_test_artifact(&tmp, "node_modules");
+        node.risk = Risk::Deps;
+        let artifacts = vec![make_test_artifact(&tmp, "target"), node];
+        let options = DeleteOptions {
+            no_input: true,
+            ..Default::default()
+        };
+
+        let mut out = Vec::new();
+        let mut input = Cursor::new(b"y\n".to_vec());
+        match confirm_and_delete(&mut out, &mut input, &artifacts, &options) {
+            Err(DeleteError::InputRequired { question }) => {
+                assert_eq!(question, "Delete 2 targets (18 B)?");
+            }
+            other => panic!("expected a confirmation to be required, got {other:?}"),
+        }
+        assert!(tmp.path().join("target").exists());
+        // Only the artifacts that would be asked about need input.
+        let confirmed = confirm_by_risk(
+            &mut out,
+            &mut input,
+            &artifacts[..1],
+            &options,
+            &[Risk::Output],
+        )
+        .unwrap();
+        assert_eq!(confirmed.len(), 1);
+        assert!(matches!(
+            confirm_by_risk(&mut out, &mut input, &artifacts, &options, &[Risk::Output]),
+            Err(DeleteError::InputRequired { .. })
+        ));
+
+        let options = DeleteOptions {
+            skip_confirm: true,
+            ..options
+        };
+        let removed = confirm_and_delete(&mut out, &mut input, &artifacts, &options)
+            .unwrap()
+            .removed();
+        assert_eq!(removed, 2);
+    }
+
+    #[test]
+    fn trash_prompt_names_the_trash() {
+        let tmp = TempDir::new().unwrap();
+        let artifacts = vec![make_test_artifact(&tmp, "target")];
+        let options = DeleteOptions {
+            trash: true,
+            ..Default::default()
+        };
+
+        let mut out = Vec::new();
+        let mut input = Cursor::new(b"n\n".to_vec());
+        let removed = confirm_and_delete(&mut out, &mut input, &artifacts, &options)
+            .unwrap()
+            .removed();
+
+        assert_eq!(removed, 0);
+        let output = String::from_utf8(out).unwrap();
+        assert!(output.contains("Move 1 targets (9 B) to the trash? [y/N]"));
+        assert!(tmp.path().join("target").exists());
+    }
+
+    #[test]
+    fn quarantine_moves_artifacts_into_a_run() {
+        let tmp = TempDir::new().unwrap();
+        let artifacts = vec![make_test_artifact(&tmp, "target")];
+        let dir = tmp.path().join("quarantine");
+        let options = DeleteOptions {
+            skip_confirm: true,
+            quarantine: Some(dir.clone()),
+            ..Default::default()
+        };
+
+        let mut out = Vec::new();
+        let mut input = Cursor::new(Vec::new());
+        let moved = confirm_and_delete(&mut out, &mut input, &artifacts, &options)
+            .unwrap()
+            .removed();
+
+        assert_eq!(moved, 1);
+        assert!(!tmp.path().join("target").exists());
+        let runs = Quarantine::new(&dir).runs().unwrap();
+        assert_eq!(runs.len(), 1);
+        let output = String::from_utf8(out).unwrap();
+        assert!(output.contains(&format!(
+            "Quarantined 1 of 1 artifact directories (9 B) as run {}",
+            runs[0]
+        )));
+        assert!(output.contains(&format!("clean-builds restore {}", runs[0])));
+    }
+
+    #[test]
+    fn empty_response_aborts() {
+        let tmp = TempDir::new().unwrap();
+        let artifacts = vec![make_test_artifact(&tmp, "target")];
+
+        let mut out = Vec::new();
+        let mut input = Cursor::new(b"\n".to_vec());
+        let deleted = confirm_and_delete(&mut out, &mut input, &artifacts, &prompt())
+            .unwrap()
+            .removed();
 
-        let mut response = String::new();
-        input.read_line(&mut response)?;
-        let response = response.trim().to_lowercase();
-        if response != "y" && response != "yes" {
-            writeln!(out, "Aborted.")?;
-            return Ok(0);
-        }
+        assert_eq!(deleted, 0);
+        assert!(tmp.path().join("target").exists());
     }
 
-    info!("Deleting {} artifact directories", artifacts.len());
-    let results: Vec<Result<(), DeleteError>> = artifacts
-        .par_iter()
-        .map(|artifact| {
-            debug!("Deleting {}", artifact.path.display());
-            delete_artifact(&artifact.path)
-        })
-        .collect();
+    #[test]
+    fn confirm_yes_full_word() {
+        let tmp = TempDir::new().unwrap();
+        let artifacts = vec![make_test_artifact(&tmp, "node_modules")];
 
-    let mut deleted = 0;
-    let mut errors = Vec::new();
-    for result in results {
-        match result {
-            Ok(()) => deleted += 1,
-            Err(e) => {
-                warn!("{e}");
-                errors.push(e);
-            }
-        }
-    }
+        let mut out = Vec::new();
+        let mut input = Cursor::new(b"yes\n".to_vec());
+        let deleted = confirm_and_delete(&mut out, &mut input, &artifacts, &prompt())
+            .unwrap()
+            .removed();
 
-    if !errors.is_empty() {
-        writeln!(out)?;
-        for err in &errors {
-            writeln!(out, "Error: {err}")?;
-        }
+        assert_eq!(deleted, 1);
     }
 
-    writeln!(
-        out,
-        "\nDeleted {deleted} of {} artifact directories ({}).",
-        artifacts.len(),
-        format_size(total_bytes)
-    )?;
+    #[test]
+    fn deletes_multiple_artifacts() {
+        let tmp = TempDir::new().unwrap();
+        let artifacts = vec![
+            make_test_artifact(&tmp, "target"),
+            make_test_artifact(&tmp, "build"),
+            make_test_artifact(&tmp, "node_modules"),
+        ];
 
-    Ok(deleted)
-}
+        let mut out = Vec::new();
+        let mut input = Cursor::new(Vec::new());
+        let deleted = confirm_and_delete(&mut out, &mut input, &artifacts, &yes())
+            .unwrap()
+            .removed();
 
-/// Delete a single artifact directory.
-fn delete_artifact(path: &Path) -> Result<(), DeleteError> {
-    std::fs::remove_dir_all(path).map_err(|e| DeleteError::RemoveDir {
-        path: path.display().to_string(),
-        source: e,
-    })
-}
+        assert_eq!(deleted, 3);
+        assert!(!tmp.path().join("target").exists());
+        assert!(!tmp.path().join("build").exists());
+        assert!(!tmp.path().join("node_modules").exists());
+    }
 
-#[cfg(test)]
-mod tests {
-    use super::*;
-    use std::fs;
-    use std::io::Cursor;
-    use tempfile::TempDir;
+    #[test]
+    fn deletes_file_artifact() {
+        let tmp = TempDir::new().unwrap();
+        let path = tmp.path().join(".ghc.environment.x86_64-linux-9.4.8");
+        fs::write(&path, "package-db").unwrap();
+        let artifacts = vec![Artifact {
+            artifact_dir: ".ghc.environment.*",
+            risk: Risk::Cache,
+            size_bytes: 10,
+            ..Artifact::for_test(path.clone(), "Haskell/Cabal")
+        }];
 
-    fn make_test_artifact(tmp: &TempDir, name: &'static str) -> Artifact {
-        let path = tmp.path().join(name);
-        fs::create_dir_all(&path).unwrap();
-        fs::write(path.join("file.txt"), "test data").unwrap();
-        Artifact {
-            path,
-            build_system: "Test",
-            artifact_dir: name,
-            size_bytes: 9,
-        }
+        let mut out = Vec::new();
+        let mut input = Cursor::new(Vec::new());
+        let deleted = confirm_and_delete(&mut out, &mut input, &artifacts, &yes())
+            .unwrap()
+            .removed();
+
+        assert_eq!(deleted, 1);
+        assert!(!path.exists());
     }
 
+    #[cfg(unix)]
     #[test]
-    fn confirm_yes_deletes() {
+    fn use_tool_runs_clean_command_in_project() {
         let tmp = TempDir::new().unwrap();
-        let artifacts = vec![make_test_artifact(&tmp, "target")];
+        let mut artifact = make_test_artifact(&tmp, "target");
+        artifact.clean = Some(&["sh", "-c", "rm -r target"]);
+        let options = DeleteOptions {
+            skip_confirm: true,
+            use_tool: true,
+            ..Default::default()
+        };
 
         let mut out = Vec::new();
-        let mut input = Cursor::new(b"y\n".to_vec());
-        let deleted = confirm_and_delete(&mut out, &mut input, &artifacts, false).unwrap();
+        let mut input = Cursor::new(Vec::new());
+        let removed = confirm_and_delete(&mut out, &mut input, &[artifact], &options)
+            .unwrap()
+            .removed();
 
-        assert_eq!(deleted, 1);
+        assert_eq!(removed, 1);
         assert!(!tmp.path().join("target").exists());
+        let output = String::from_utf8(out).unwrap();
+        assert!(
+            output.contains("1 removed by the build tool's clean command"),
+            "{output}"
+        );
     }
 
+    #[cfg(unix)]
     #[test]
-    fn confirm_no_aborts() {
+    fn use_tool_kills_a_tool_past_the_timeout() {
         let tmp = TempDir::new().unwrap();
-        let artifacts = vec![make_test_artifact(&tmp, "target")];
+        let mut artifact = make_test_artifact(&tmp, "target");
+        artifact.clean = Some(&["sleep", "10"]);
+        let options = DeleteOptions {
+            skip_confirm: true,
+            use_tool: true,
+            timeout: Some(Duration::from_millis(100)),
+            ..Default::default()
+        };
 
+        let started = Instant::now();
         let mut out = Vec::new();
-        let mut input = Cursor::new(b"n\n".to_vec());
-        let deleted = confirm_and_delete(&mut out, &mut input, &artifacts, false).unwrap();
+        let mut input = Cursor::new(Vec::new());
+        let report = confirm_and_delete(&mut out, &mut input, &[artifact], &options).unwrap();
 
-        assert_eq!(deleted, 0);
+        assert!(started.elapsed() < Duration::from_secs(5));
+        assert_eq!(report.removed(), 0);
+        let failures: Vec<_> = report.failures().collect();
+        assert_eq!(failures.len(), 1);
+        assert_eq!(failures[0].1.kind, FailureKind::TimedOut);
         assert!(tmp.path().join("target").exists());
         let output = String::from_utf8(out).unwrap();
-        assert!(output.contains("Aborted"));
+        assert!(output.contains("gave up on sleep 10"), "{output}");
     }
 
     #[test]
-    fn skip_confirm_deletes_immediately() {
+    fn use_tool_falls_back_when_tool_is_missing() {
         let tmp = TempDir::new().unwrap();
-        let artifacts = vec![make_test_artifact(&tmp, "build")];
+        let mut artifact = make_test_artifact(&tmp, "target");
+        artifact.clean = Some(&["clean-builds-no-such-tool", "clean"]);
+        let options = DeleteOptions {
+            skip_confirm: true,
+            use_tool: true,
+            ..Default::default()
+        };
 
         let mut out = Vec::new();
         let mut input = Cursor::new(Vec::new());
-        let deleted = confirm_and_delete(&mut out, &mut input, &artifacts, true).unwrap();
+        let removed = confirm_and_delete(&mut out, &mut input, &[artifact], &options)
+            .unwrap()
+            .removed();
 
-        assert_eq!(deleted, 1);
+        assert_eq!(removed, 1);
+        assert!(!tmp.path().join("target").exists());
+        let output = String::from_utf8(out).unwrap();
+        assert!(!output.contains("build tool"), "{output}");
+    }
+
+    #[test]
+    fn throttled_delete_removes_everything() {
+        let tmp = TempDir::new().unwrap();
+        let artifacts = vec![
+            make_test_artifact(&tmp, "target"),
+            make_test_artifact(&tmp, "build"),
+        ];
+        let options = DeleteOptions {
+            skip_confirm: true,
+            throttle: Some(Throttle {
+                batch: 1,
+                pause: std::time::Duration::from_millis(1),
+            }),
+            ..Default::default()
+        };
+
+        let mut out = Vec::new();
+        let mut input = Cursor::new(Vec::new());
+        let deleted = confirm_and_delete(&mut out, &mut input, &artifacts, &options)
+            .unwrap()
+            .removed();
+
+        assert_eq!(deleted, 2);
+        assert!(!tmp.path().join("target").exists());
         assert!(!tmp.path().join("build").exists());
     }
 
+    #[cfg(unix)]
     #[test]
-    fn empty_response_aborts() {
+    fn locked_artifacts_are_skipped_unless_forced() {
+        use std::os::fd::AsRawFd;
+
         let tmp = TempDir::new().unwrap();
         let artifacts = vec![make_test_artifact(&tmp, "target")];
+        let lock = tmp.path().join("target/.cargo-lock");
+        fs::write(&lock, "").unwrap();
+        let holder = fs::File::open(&lock).unwrap();
+        assert_eq!(unsafe { libc::flock(holder.as_raw_fd(), libc::LOCK_EX) }, 0);
 
+        let mut options = DeleteOptions {
+            skip_confirm: true,
+            ..Default::default()
+        };
         let mut out = Vec::new();
-        let mut input = Cursor::new(b"\n".to_vec());
-        let deleted = confirm_and_delete(&mut out, &mut input, &artifacts, false).unwrap();
-
+        let mut input = Cursor::new(Vec::new());
+        let deleted = confirm_and_delete(&mut out, &mut input, &artifacts, &options)
+            .unwrap()
+            .removed();
         assert_eq!(deleted, 0);
+        assert!(String::from_utf8(out).unwrap().contains("--force-in-use"));
         assert!(tmp.path().join("target").exists());
+
+        options.force_in_use = true;
+        let mut out = Vec::new();
+        let deleted = confirm_and_delete(&mut out, &mut input, &artifacts, &options)
+            .unwrap()
+            .removed();
+        assert_eq!(deleted, 1);
+        assert!(!tmp.path().join("target").exists());
     }
 
+    #[cfg(unix)]
     #[test]
-    fn confirm_yes_full_word() {
+    fn failing_before_delete_hook_keeps_artifact() {
         let tmp = TempDir::new().unwrap();
-        let artifacts = vec![make_test_artifact(&tmp, "node_modules")];
+        let artifacts = vec![
+            make_test_artifact(&tmp, "target"),
+            make_test_artifact(&tmp, "build"),
+        ];
+        let options = DeleteOptions {
+            skip_confirm: true,
+            hooks: Hooks {
+                before_delete: Some(
+                    "case $CLEAN_BUILDS_HOOK_PATH in */target) exit 1;; esac".into(),
+                ),
+                ..Default::default()
+            },
+            ..Default::default()
+        };
 
         let mut out = Vec::new();
-        let mut input = Cursor::new(b"yes\n".to_vec());
-        let deleted = confirm_and_delete(&mut out, &mut input, &artifacts, false).unwrap();
+        let mut input = Cursor::new(Vec::new());
+        let deleted = confirm_and_delete(&mut out, &mut input, &artifacts, &options)
+            .unwrap()
+            .removed();
 
         assert_eq!(deleted, 1);
+        assert!(tmp.path().join("target").exists());
+        assert!(!tmp.path().join("build").exists());
     }
 
     #[test]
-    fn deletes_multiple_artifacts() {
+    fn confirm_per_system_asks_for_each_system() {
+        let tmp = TempDir::new().unwrap();
+        let mut node = make_test_artifact(&tmp, "node_modules");
+        node.build_system = "Node.js";
+        let mut venv = make_test_artifact(&tmp, ".venv");
+        venv.build_system = "Python";
+        let artifacts = vec![node, venv];
+
+        let mut out = Vec::new();
+        let mut input = Cursor::new(b"y\nn\n".to_vec());
+        let confirmed =
+            confirm_per_system(&mut out, &mut input, &artifacts, &DeleteOptions::default())
+                .unwrap();
+
+        let output = String::from_utf8(out).unwrap();
+        assert!(output.contains("Delete 1 Node.js artifacts (9 B)? [y/N]"));
+        assert!(output.contains("Delete 1 Python artifacts (9 B)? [y/N]"));
+        assert_eq!(confirmed.len(), 1);
+        assert_eq!(confirmed[0].build_system, "Node.js");
+    }
+
+    #[test]
+    fn confirm_by_risk_asks_only_about_other_risks() {
+        let tmp = TempDir::new().unwrap();
+        let mut pycache = make_test_artifact(&tmp, "__pycache__");
+        pycache.risk = Risk::Cache;
+        let target = make_test_artifact(&tmp, "target");
+        let mut node = make_test_artifact(&tmp, "node_modules");
+        node.risk = Risk::Deps;
+        let artifacts = vec![pycache, target, node];
+        let auto = [Risk::Cache, Risk::Output];
+
+        let mut out = Vec::new();
+        let mut input = Cursor::new(b"n\n".to_vec());
+        let confirmed =
+            confirm_by_risk(&mut out, &mut input, &artifacts, &prompt(), &auto).unwrap();
+
+        let output = String::from_utf8(out).unwrap();
+        assert!(output.contains("2 cache/output artifacts (18 B) need no confirmation"));
+        assert!(output.contains("Delete 1 deps artifacts (9 B)? [y/N]"));
+        let names: Vec<_> = confirmed.iter().map(|a| a.artifact_dir).collect();
+        assert_eq!(names, ["__pycache__", "target"]);
+
+        let mut out = Vec::new();
+        let mut input = Cursor::new(b"y\n".to_vec());
+        let confirmed =
+            confirm_by_risk(&mut out, &mut input, &artifacts, &prompt(), &auto).unwrap();
+        assert_eq!(confirmed.len(), 3);
+    }
+
+    #[test]
+    fn confirm_by_risk_without_automatic_artifacts_can_abort() {
+        let tmp = TempDir::new().unwrap();
+        let artifacts = vec![make_test_artifact(&tmp, "target")];
+
+        let mut out = Vec::new();
+        let mut input = Cursor::new(b"n\n".to_vec());
+        let confirmed =
+            confirm_by_risk(&mut out, &mut input, &artifacts, &prompt(), &[Risk::Cache]).unwrap();
+
+        assert!(confirmed.is_empty());
+        let output = String::from_utf8(out).unwrap();
+        assert!(!output.contains("need no confirmation"));
+        assert!(output.contains("Aborted."));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn background_moves_artifacts_aside_for_the_worker() {
         let tmp = TempDir::new().unwrap();
         let artifacts = vec![
             make_test_artifact(&tmp, "target"),
             make_test_artifact(&tmp, "build"),
-            make_test_artifact(&tmp, "node_modules"),
         ];
+        let options = DeleteOptions {
+            skip_confirm: true,
+            background: Some(vec!["rm".into(), "-rf".into()]),
+            ..Default::default()
+        };
 
         let mut out = Vec::new();
         let mut input = Cursor::new(Vec::new());
-        let deleted = confirm_and_delete(&mut out, &mut input, &artifacts, true).unwrap();
+        let report = confirm_and_delete(&mut out, &mut input, &artifacts, &options).unwrap();
 
-        assert_eq!(deleted, 3);
+        assert_eq!(report.removed(), 2);
+        assert!(
+            report
+                .outcomes
+                .iter()
+                .all(|(_, o)| *o == Outcome::MovedAside)
+        );
+        assert!(
+            String::from_utf8(out)
+                .unwrap()
+                .contains("Moved aside 2 of 2")
+        );
         assert!(!tmp.path().join("target").exists());
         assert!(!tmp.path().join("build").exists());
-        assert!(!tmp.path().join("node_modules").exists());
+        let holding = tmp
+            .path()
+            .join(format!(".clean-builds-{}", std::process::id()));
+        for _ in 0..100 {
+            if !holding.exists() {
+                return;
+            }
+            std::thread::sleep(Duration::from_millis(50));
+        }
+        panic!("{} was not removed", holding.display());
+    }
+
+    #[test]
+    fn interrupted_delete_skips_remaining_artifacts() {
+        let tmp = TempDir::new().unwrap();
+        let artifacts = vec![
+            make_test_artifact(&tmp, "target"),
+            make_test_artifact(&tmp, "build"),
+        ];
+        let interrupted = AtomicBool::new(true);
+
+        let mut out = Vec::new();
+        let outcomes = delete_all(
+            &mut out,
+            &artifacts,
+            &DeleteOptions::default(),
+            &Cancel::new(&interrupted, &CancelToken::new()),
+            &Mutex::new(Vec::new()),
+        )
+        .unwrap();
+
+        assert_eq!(outcomes, vec![Outcome::Skipped, Outcome::Skipped]);
+        assert!(tmp.path().join("target").exists());
+        assert!(tmp.path().join("build").exists());
+    }
+
+    #[test]
+    fn cancelled_run_reports_what_it_skipped() {
+        let tmp = TempDir::new().unwrap();
+        let artifacts = vec![make_test_artifact(&tmp, "target")];
+        let cancel = CancelToken::new();
+        cancel.cancel();
+        let options = DeleteOptions { cancel, ..yes() };
+
+        let mut out = Vec::new();
+        let mut input = Cursor::new(Vec::new());
+        let report = confirm_and_delete(&mut out, &mut input, &artifacts, &options).unwrap();
+
+        assert!(report.interrupted);
+        assert_eq!(report.outcomes[0].1, Outcome::Skipped);
+        assert!(tmp.path().join("target").exists());
+    }
+
+    #[test]
+    fn watch_abandons_work_that_stalls() {
+        let timeout = Duration::from_millis(50);
+        let mut total = 0;
+
+        let steady = watch(
+            timeout,
+            |removed| {
+                for _ in 0..10 {
+                    std::thread::sleep(Duration::from_millis(10));
+                    removed(1);
+                }
+                "done"
+            },
+            &mut |bytes| total += bytes,
+        );
+        assert_eq!(steady, Some("done"));
+        assert_eq!(total, 10);
+
+        let stuck = watch(
+            timeout,
+            |_| std::thread::sleep(Duration::from_millis(500)),
+            &mut |_| {},
+        );
+        assert_eq!(stuck, None);
+    }
+
+    #[test]
+    fn timed_out_delete_still_succeeds_when_progressing() {
+        let tmp = TempDir::new().unwrap();
+        let artifacts = vec![make_test_artifact(&tmp, "target")];
+        let options = DeleteOptions {
+            skip_confirm: true,
+            timeout: Some(Duration::from_secs(5)),
+            ..Default::default()
+        };
+
+        let mut out = Vec::new();
+        let mut input = Cursor::new(Vec::new());
+        let deleted = confirm_and_delete(&mut out, &mut input, &artifacts, &options)
+            .unwrap()
+            .removed();
+
+        assert_eq!(deleted, 1);
+        assert!(!tmp.path().join("target").exists());
+    }
+
+    #[test]
+    fn timeout_error_names_the_stall() {
+        let err = DeleteError::TimedOut {
+            path: "/mnt/nfs/target".to_string(),
+            timeout: Duration::from_secs(120),
+        };
+        assert_eq!(
+            err.to_string(),
+            "gave up deleting /mnt/nfs/target: no progress for 2m"
+        );
+    }
+
+    #[test]
+    fn with_retry_retries_transient_errors() {
+        let retry = Retry {
+            retries: 3,
+            backoff: Duration::from_millis(1),
+        };
+        let busy = || std::io::Error::from(std::io::ErrorKind::ResourceBusy);
+
+        let mut calls = 0;
+        let result = with_retry(retry, |_| {
+            calls += 1;
+            if calls < 3 { Err(busy()) } else { Ok(()) }
+        });
+        assert_eq!(result.unwrap(), 2);
+
+        let (err, retries) = with_retry(retry, |_| Err(busy())).unwrap_err();
+        assert_eq!(err.kind(), std::io::ErrorKind::ResourceBusy);
+        assert_eq!(retries, 3);
+
+        let mut calls = 0;
+        let result = with_retry(retry, |_| {
+            calls += 1;
+            Err(std::io::Error::from(std::io::ErrorKind::InvalidInput))
+        });
+        assert_eq!(result.unwrap_err().1, 0);
+        assert_eq!(calls, 1);
+    }
+
+    #[test]
+    fn retry_count_appears_in_error() {
+        let err = DeleteError::RemoveDir {
+            path: "/dev/app/target".to_string(),
+            source: std::io::Error::from(std::io::ErrorKind::ResourceBusy),
+            retries: 4,
+        };
+        assert!(err.to_string().ends_with("(after 4 retries)"), "{err}");
+    }
+
+    #[test]
+    fn extended_length_prefixes_absolute_paths() {
+        assert_eq!(
+            extended_length(r"C:\dev\app\node_modules").as_deref(),
+            Some(r"\\?\C:\dev\app\node_modules")
+        );
+        assert_eq!(
+            extended_length("D:/dev/app").as_deref(),
+            Some(r"\\?\D:\dev\app")
+        );
+        assert_eq!(
+            extended_length(r"\\server\share\app").as_deref(),
+            Some(r"\\?\UNC\server\share\app")
+        );
+        assert_eq!(extended_length(r"\\?\C:\dev"), None);
+        assert_eq!(extended_length(r"dev\app"), None);
+        assert_eq!(extended_length("C:app"), None);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn force_permissions_removes_locked_directories() {
+        use std::os::unix::fs::PermissionsExt;
+        let tmp = TempDir::new().unwrap();
+        let root = tmp.path().join("bazel-out");
+        let locked = root.join("locked");
+        fs::create_dir_all(locked.join("unreadable")).unwrap();
+        fs::write(locked.join("file"), "x").unwrap();
+        fs::set_permissions(locked.join("unreadable"), fs::Permissions::from_mode(0o000)).unwrap();
+        fs::set_permissions(&locked, fs::Permissions::from_mode(0o500)).unwrap();
+
+        let options = DeleteOptions {
+            force_permissions: true,
+            ..Default::default()
+        };
+        remove_tree(&root, &options, &mut |_| {}).unwrap();
+        assert!(!root.exists());
+    }
+
+    #[test]
+    fn contents_only_keeps_directory_and_markers() {
+        let tmp = TempDir::new().unwrap();
+        let artifacts = vec![make_test_artifact(&tmp, "target")];
+        let target = tmp.path().join("target");
+        fs::create_dir_all(target.join("debug/deps")).unwrap();
+        fs::write(
+            target.join("CACHEDIR.TAG"),
+            "Signature: 8a477f597d28d172789f06886806bc55",
+        )
+        .unwrap();
+        fs::write(target.join(".gitkeep"), "").unwrap();
+        let options = DeleteOptions {
+            skip_confirm: true,
+            contents_only: true,
+            ..Default::default()
+        };
+
+        let mut out = Vec::new();
+        let mut input = Cursor::new(Vec::new());
+        let emptied = confirm_and_delete(&mut out, &mut input, &artifacts, &options)
+            .unwrap()
+            .removed();
+
+        assert_eq!(emptied, 1);
+        assert!(String::from_utf8(out).unwrap().contains("Emptied 1 of 1"));
+        let mut left: Vec<_> = fs::read_dir(&target)
+            .unwrap()
+            .map(|e| e.unwrap().file_name())
+            .collect();
+        left.sort();
+        assert_eq!(left, [".gitkeep", "CACHEDIR.TAG"]);
+    }
+
+    #[test]
+    fn shred_overwrites_contents() {
+        let tmp = TempDir::new().unwrap();
+        let file = tmp.path().join("credentials");
+        fs::write(&file, "token=hunter2").unwrap();
+
+        shred(&file, &fs::metadata(&file).unwrap()).unwrap();
+
+        assert_eq!(fs::read(&file).unwrap(), [0; 13]);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn shred_leaves_hard_linked_files_intact() {
+        let tmp = TempDir::new().unwrap();
+        let store = tmp.path().join("store.js");
+        fs::write(&store, "module.exports = {}").unwrap();
+        let link = tmp.path().join("node_modules/dep/index.js");
+        fs::create_dir_all(link.parent().unwrap()).unwrap();
+        fs::hard_link(&store, &link).unwrap();
+        let options = DeleteOptions {
+            shred: true,
+            ..Default::default()
+        };
+
+        remove_tree(&tmp.path().join("node_modules"), &options, &mut |_| {}).unwrap();
+
+        assert_eq!(fs::read_to_string(&store).unwrap(), "module.exports = {}");
+    }
+
+    #[test]
+    fn remove_tree_reports_file_sizes() {
+        let tmp = TempDir::new().unwrap();
+        let root = tmp.path().join("node_modules");
+        fs::create_dir_all(root.join("a/b/c")).unwrap();
+        fs::write(root.join("top.js"), "12345").unwrap();
+        fs::write(root.join("a/b/c/deep.js"), "123").unwrap();
+
+        let mut removed = Vec::new();
+        remove_tree(&root, &DeleteOptions::default(), &mut |bytes| {
+            removed.push(bytes)
+        })
+        .unwrap();
+
+        removed.sort_unstable();
+        assert_eq!(removed, [3, 5]);
+        assert!(!root.exists());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn deletes_symlink_without_following() {
+        let tmp = TempDir::new().unwrap();
+        let store = tmp.path().join("store");
+        fs::create_dir_all(&store).unwrap();
+        fs::write(store.join("bin"), "data").unwrap();
+        let link = tmp.path().join("result");
+        std::os::unix::fs::symlink(&store, &link).unwrap();
+        let artifacts = vec![Artifact {
+            artifact_dir: "result*",
+            ..Artifact::for_test(link.clone(), "Nix")
+        }];
+
+        let mut out = Vec::new();
+        let mut input = Cursor::new(Vec::new());
+        let deleted = confirm_and_delete(&mut out, &mut input, &artifacts, &yes())
+            .unwrap()
+            .removed();
+
+        assert_eq!(deleted, 1);
+        assert!(fs::symlink_metadata(&link).is_err());
+        assert!(store.join("bin").exists());
     }
 
     #[test]
@@ -204,9 +2631,11 @@ mod tests {
 
         let mut out = Vec::new();
         let mut input = Cursor::new(Vec::new());
-        confirm_and_delete(&mut out, &mut input, &artifacts, true).unwrap();
+        confirm_and_delete(&mut out, &mut input, &artifacts, &yes()).unwrap();
 
         let output = String::from_utf8(out).unwrap();
         assert!(output.contains("Deleted 1 of 1"));
+        #[cfg(unix)]
+        assert!(output.contains("of disk space (estimated 9 B)"));
     }
 }
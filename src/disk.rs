@@ -0,0 +1,365 @@
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+
+use log::debug;
+
+use crate::filter::{Filter, Verdict};
+use crate::scanner::Artifact;
+
+/// The filesystems holding a set of artifacts, for measuring how much space
+/// deleting them really frees. Hardlinks, compression and copy-on-write
+/// clones can make that differ a lot from the artifacts' apparent sizes.
+#[derive(Debug)]
+pub struct Volumes {
+    /// One directory on each filesystem that outlives the deletion.
+    probes: Vec<PathBuf>,
+}
+
+impl Volumes {
+    /// Find the filesystems of `artifacts`, probing each through the
+    /// project directory the artifact lives in.
+    pub fn of(artifacts: &[Artifact]) -> Self {
+        let mut seen = HashSet::new();
+        let probes = artifacts
+            .iter()
+            .filter_map(|a| a.path.parent())
+            .filter(|dir| volume_id(dir).is_some_and(|id| seen.insert(id)))
+            .map(Path::to_path_buf)
+            .collect();
+        Self { probes }
+    }
+
+    /// Bytes currently available across these filesystems, or `None` if
+    /// any of them cannot be queried.
+    pub fn free_bytes(&self) -> Option<u64> {
+        self.probes.iter().map(|p| available_bytes(p)).sum()
+    }
+}
+
+/// Identifies the filesystem holding `path` by its device ID. A symlink is
+/// on the filesystem of the directory holding it, not of its target.
+#[cfg(unix)]
+pub fn volume_id(path: &Path) -> Option<String> {
+    use std::os::unix::fs::MetadataExt;
+    std::fs::symlink_metadata(path)
+        .ok()
+        .map(|m| m.dev().to_string())
+}
+
+/// Identifies the filesystem holding `path` by its drive or share prefix.
+#[cfg(not(unix))]
+pub fn volume_id(path: &Path) -> Option<String> {
+    match path.components().next()? {
+        std::path::Component::Prefix(prefix) => {
+            Some(prefix.as_os_str().to_string_lossy().to_uppercase())
+        }
+        _ => None,
+    }
+}
+
+/// Where the filesystem holding `path` is mounted: its highest ancestor on
+/// the same filesystem.
+#[cfg(unix)]
+pub fn mount_point(path: &Path) -> Option<PathBuf> {
+    let id = volume_id(path)?;
+    let mount = path
+        .ancestors()
+        .take_while(|dir| volume_id(dir).as_ref() == Some(&id))
+        .last()?;
+    Some(mount.to_path_buf())
+}
+
+/// The root of the drive or share holding `path`.
+#[cfg(not(unix))]
+pub fn mount_point(path: &Path) -> Option<PathBuf> {
+    path.ancestors().last().map(Path::to_path_buf)
+}
+
+/// The total size of `artifacts` on each filesystem, largest first, with
+/// where it is mounted. Artifacts whose filesystem is unknown are left out.
+pub fn bytes_by_volume(artifacts: &[Artifact]) -> Vec<(PathBuf, u64)> {
+    let mut volumes: Vec<(&str, PathBuf, u64)> = Vec::new();
+    for artifact in artifacts {
+        let Some(id) = artifact.volume.as_deref() else {
+            continue;
+        };
+        match volumes.iter_mut().find(|(v, _, _)| *v == id) {
+            Some((_, _, bytes)) => *bytes += artifact.size_bytes,
+            None => {
+                let mount = mount_point(&artifact.path).unwrap_or_else(|| artifact.path.clone());
+                volumes.push((id, mount, artifact.size_bytes));
+            }
+        }
+    }
+    volumes.sort_by(|a, b| b.2.cmp(&a.2).then_with(|| a.1.cmp(&b.1)));
+    volumes
+        .into_iter()
+        .map(|(_, mount, bytes)| (mount, bytes))
+        .collect()
+}
+
+/// Error identifying the filesystem for `--on-volume`.
+#[derive(thiserror::Error, Debug)]
+#[error("cannot identify the filesystem holding {}", .0.display())]
+pub struct VolumeError(PathBuf);
+
+/// Keeps artifacts on the filesystem holding a path (`--on-volume`), such
+/// as a full disk to make room on with `--free`.
+#[derive(Debug)]
+pub struct VolumeFilter {
+    path: PathBuf,
+    id: String,
+}
+
+impl VolumeFilter {
+    /// Fails if the filesystem holding `path` cannot be identified.
+    pub fn new(path: &Path) -> Result<Self, VolumeError> {
+        let id = volume_id(path).ok_or_else(|| VolumeError(path.to_path_buf()))?;
+        Ok(Self {
+            path: path.to_path_buf(),
+            id,
+        })
+    }
+}
+
+impl Filter for VolumeFilter {
+    fn keep(&self, artifact: &Artifact) -> bool {
+        artifact.volume.as_ref() == Some(&self.id)
+    }
+
+    fn verdict(&self, artifact: &Artifact) -> Verdict {
+        if self.keep(artifact) {
+            Verdict::Keep(Some(format!(
+                "on the filesystem of {}",
+                self.path.display()
+            )))
+        } else {
+            Verdict::Drop(format!(
+                "not on the filesystem of {} (--on-volume)",
+                self.path.display()
+            ))
+        }
+    }
+}
+
+/// The entry of a `/proc/self/mounts`-formatted mount table for one
+/// filesystem.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct Mount<'a> {
+    pub point: PathBuf,
+    pub fs_type: &'a str,
+    pub options: &'a str,
+}
+
+/// Find the longest mount point in `/proc/self/mounts`-formatted text that
+/// contains `path`.
+#[cfg_attr(not(target_os = "linux"), allow(dead_code))]
+pub(crate) fn find_mount<'a>(mounts: &'a str, path: &Path) -> Option<Mount<'a>> {
+    mounts
+        .lines()
+        .filter_map(|line| {
+            let mut fields = line.split_whitespace();
+            let point = PathBuf::from(unescape_mount_field(fields.nth(1)?));
+            let fs_type = fields.next()?;
+            let options = fields.next()?;
+            Some(Mount {
+                point,
+                fs_type,
+                options,
+            })
+        })
+        .filter(|mount| path.starts_with(&mount.point))
+        .max_by_key(|mount| mount.point.components().count())
+}
+
+/// Decode the octal escapes (`\040` for a space, etc.) used in mount tables.
+fn unescape_mount_field(field: &str) -> String {
+    let mut out = String::with_capacity(field.len());
+    let mut rest = field;
+    while let Some(i) = rest.find('\\') {
+        out.push_str(&rest[..i]);
+        let code = rest
+            .get(i + 1..i + 4)
+            .and_then(|o| u8::from_str_radix(o, 8).ok());
+        match code {
+            Some(byte) => {
+                out.push(char::from(byte));
+                rest = &rest[i + 4..];
+            }
+            None => {
+                out.push('\\');
+                rest = &rest[i + 1..];
+            }
+        }
+    }
+    out.push_str(rest);
+    out
+}
+
+/// The type of the filesystem holding `path`, such as `ext4`, `apfs` or
+/// `nfs`, if it can be determined on this platform.
+#[cfg(target_os = "linux")]
+pub fn filesystem_type(path: &Path) -> Option<String> {
+    let mounts = std::fs::read_to_string("/proc/self/mounts").ok()?;
+    find_mount(&mounts, path).map(|m| m.fs_type.to_string())
+}
+
+/// The type of the filesystem holding `path`, such as `ext4`, `apfs` or
+/// `nfs`, if it can be determined on this platform.
+#[cfg(target_os = "macos")]
+pub fn filesystem_type(path: &Path) -> Option<String> {
+    use std::os::unix::ffi::OsStrExt;
+
+    let path = std::ffi::CString::new(path.as_os_str().as_bytes()).ok()?;
+    // SAFETY: an all-zero `statfs` struct is valid; statfs only fills it in.
+    let mut stats: libc::statfs = unsafe { std::mem::zeroed() };
+    // SAFETY: `path` is NUL-terminated and `stats` is exclusively borrowed.
+    if unsafe { libc::statfs(path.as_ptr(), &mut stats) } != 0 {
+        return None;
+    }
+    // SAFETY: the kernel NUL-terminates `f_fstypename`.
+    let name = unsafe { std::ffi::CStr::from_ptr(stats.f_fstypename.as_ptr()) };
+    Some(name.to_string_lossy().into_owned())
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "macos")))]
+pub fn filesystem_type(_path: &Path) -> Option<String> {
+    None
+}
+
+/// Bytes available to this user on the filesystem holding `dir`.
+#[cfg(unix)]
+fn available_bytes(dir: &Path) -> Option<u64> {
+    use std::os::unix::ffi::OsStrExt;
+
+    let path = std::ffi::CString::new(dir.as_os_str().as_bytes()).ok()?;
+    // SAFETY: an all-zero `statvfs` struct is valid; statvfs only fills it in.
+    let mut stats: libc::statvfs = unsafe { std::mem::zeroed() };
+    // SAFETY: `path` is NUL-terminated and `stats` is exclusively borrowed.
+    if unsafe { libc::statvfs(path.as_ptr(), &mut stats) } != 0 {
+        debug!(
+            "Cannot read free space of {}: {}",
+            dir.display(),
+            std::io::Error::last_os_error()
+        );
+        return None;
+    }
+    #[allow(clippy::useless_conversion)] // The field types vary by platform.
+    Some(u64::from(stats.f_bavail).saturating_mul(u64::from(stats.f_frsize)))
+}
+
+/// Bytes available to this user on the volume holding `dir`.
+#[cfg(windows)]
+fn available_bytes(dir: &Path) -> Option<u64> {
+    use std::os::windows::ffi::OsStrExt;
+    use windows_sys::Win32::Storage::FileSystem::GetDiskFreeSpaceExW;
+
+    let wide: Vec<u16> = dir.as_os_str().encode_wide().chain([0]).collect();
+    let mut available = 0u64;
+    // SAFETY: `wide` is NUL-terminated; the null totals are optional outputs.
+    let ok = unsafe {
+        GetDiskFreeSpaceExW(
+            wide.as_ptr(),
+            &mut available,
+            std::ptr::null_mut(),
+            std::ptr::null_mut(),
+        )
+    };
+    if ok == 0 {
+        debug!(
+            "Cannot read free space of {}: {}",
+            dir.display(),
+            std::io::Error::last_os_error()
+        );
+        return None;
+    }
+    Some(available)
+}
+
+#[cfg(not(any(unix, windows)))]
+fn available_bytes(_dir: &Path) -> Option<u64> {
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[cfg(unix)]
+    #[test]
+    fn artifacts_on_one_filesystem_share_a_volume() {
+        let tmp = TempDir::new().unwrap();
+        let artifacts = vec![
+            Artifact::for_test(tmp.path().join("a/target"), "Test"),
+            Artifact::for_test(tmp.path().join("b/target"), "Test"),
+        ];
+        std::fs::create_dir_all(tmp.path().join("a")).unwrap();
+        std::fs::create_dir_all(tmp.path().join("b")).unwrap();
+
+        let volumes = Volumes::of(&artifacts);
+        assert_eq!(volumes.probes.len(), 1);
+        assert!(volumes.free_bytes().is_some());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn bytes_are_totalled_per_volume_under_its_mount_point() {
+        let tmp = TempDir::new().unwrap();
+        let mut artifacts = Vec::new();
+        for (name, size) in [("a/target", 100), ("b/target", 50)] {
+            let path = tmp.path().join(name);
+            std::fs::create_dir_all(&path).unwrap();
+            let mut a = Artifact::for_test(path, "Test");
+            a.size_bytes = size;
+            a.volume = volume_id(&a.path);
+            artifacts.push(a);
+        }
+        // An artifact on another filesystem.
+        let mut elsewhere = Artifact::for_test(PathBuf::from("/elsewhere/target"), "Test");
+        elsewhere.size_bytes = 500;
+        elsewhere.volume = Some("other".to_string());
+        artifacts.push(elsewhere);
+
+        let volumes = bytes_by_volume(&artifacts);
+        assert_eq!(volumes.len(), 2);
+        assert_eq!(volumes[0].1, 500);
+        let (mount, bytes) = &volumes[1];
+        assert_eq!(*bytes, 150);
+        assert!(tmp.path().starts_with(mount), "{}", mount.display());
+        assert_eq!(volume_id(mount), volume_id(tmp.path()));
+
+        let filter = VolumeFilter::new(tmp.path()).unwrap();
+        let kept: Vec<_> = artifacts.iter().filter(|a| filter.keep(a)).collect();
+        assert_eq!(kept.len(), 2);
+        assert!(VolumeFilter::new(&tmp.path().join("missing")).is_err());
+    }
+
+    #[test]
+    fn find_mount_picks_longest_mount_point() {
+        let mounts = "\
+/dev/sda1 / ext4 rw,relatime 0 0
+server:/export /mnt/nfs nfs4 rw,noatime 0 0
+/dev/sdc1 /mnt/my\\040disk btrfs rw 0 0
+";
+        let mount = |p: &str| find_mount(mounts, Path::new(p)).map(|m| (m.point, m.fs_type));
+        assert_eq!(mount("/home/me"), Some((PathBuf::from("/"), "ext4")));
+        assert_eq!(
+            mount("/mnt/nfs/src"),
+            Some((PathBuf::from("/mnt/nfs"), "nfs4"))
+        );
+        assert_eq!(mount("/mnt/nfsx"), Some((PathBuf::from("/"), "ext4")));
+        assert_eq!(
+            mount("/mnt/my disk/src"),
+            Some((PathBuf::from("/mnt/my disk"), "btrfs"))
+        );
+        assert_eq!(find_mount("", Path::new("/")), None);
+    }
+
+    #[test]
+    fn unescape_mount_field_decodes_octal() {
+        assert_eq!(unescape_mount_field("/a\\040b\\011c"), "/a b\tc");
+        assert_eq!(unescape_mount_field("/plain"), "/plain");
+        assert_eq!(unescape_mount_field("/bad\\9"), "/bad\\9");
+    }
+}
@@ -0,0 +1,561 @@
+use std::fs;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use clap::parser::ValueSource;
+use clap::{ArgAction, ArgMatches, CommandFactory};
+
+use crate::age::atime_warning;
+use crate::cli::Cli;
+use crate::disk::{filesystem_type, mount_point, volume_id};
+use crate::filter::{ArtifactFilter, PatternError, read_pattern_file};
+use crate::preflight::read_only_mount;
+use crate::quarantine::{Quarantine, data_dir};
+use crate::rules::{all_rules, system_ids};
+use crate::{audit, cache, config, quarantine};
+
+/// How one check of `doctor` turned out.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Status {
+    Ok,
+    /// Works, but not the way the user may expect.
+    Warning,
+    /// Will make runs that need it fail.
+    Failed,
+}
+
+impl Status {
+    fn label(self) -> &'static str {
+        match self {
+            Status::Ok => "ok",
+            Status::Warning => "warn",
+            Status::Failed => "FAIL",
+        }
+    }
+}
+
+/// One thing `doctor` checked, e.g. that the size cache is writable.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Check {
+    pub status: Status,
+    pub subject: String,
+    pub detail: String,
+}
+
+impl Check {
+    fn new(status: Status, subject: impl Into<String>, detail: impl Into<String>) -> Self {
+        Self {
+            status,
+            subject: subject.into(),
+            detail: detail.into(),
+        }
+    }
+}
+
+/// What `doctor` found: the settings in effect and the checks, by section.
+#[derive(Debug, Default)]
+pub struct Report {
+    /// Options set on the command line or in the environment.
+    pub settings: Vec<String>,
+    pub sections: Vec<(&'static str, Vec<Check>)>,
+}
+
+impl Report {
+    /// Whether any check failed.
+    pub fn failed(&self) -> bool {
+        self.checks().any(|c| c.status == Status::Failed)
+    }
+
+    fn checks(&self) -> impl Iterator<Item = &Check> {
+        self.sections.iter().flat_map(|(_, checks)| checks)
+    }
+}
+
+/// Check the rules and pattern files `cli` uses, the directories runs write
+/// to, and the filesystems holding them and `path`.
+pub fn diagnose(cli: &Cli, matches: &ArgMatches, path: &Path) -> Report {
+    Report {
+        settings: settings(matches),
+        sections: vec![
+            ("Rules", check_rules(cli)),
+            ("Directories", check_directories(cli)),
+            ("Filesystems", check_filesystems(path)),
+        ],
+    }
+}
+
+/// Options in `matches` that were not left at their built-in defaults, each
+/// with its value and the environment variable or config file that set it,
+/// if one did.
+pub fn settings(matches: &ArgMatches) -> Vec<String> {
+    let mut settings = Vec::new();
+    for arg in Cli::command().get_arguments() {
+        let id = arg.get_id().as_str();
+        let Some(source) = matches.value_source(id) else {
+            continue;
+        };
+        // Defaults other than the built-in ones come from the config file.
+        let from_config = source == ValueSource::DefaultValue
+            && matches.get_raw(id).is_some_and(|values| {
+                !values.eq(arg
+                    .get_default_values()
+                    .iter()
+                    .map(AsRef::<std::ffi::OsStr>::as_ref))
+            });
+        if (source == ValueSource::DefaultValue && !from_config) || arg.is_hide_set() {
+            continue;
+        }
+        let name = match arg.get_long() {
+            Some(long) => format!("--{long}"),
+            None => arg.get_value_names().map_or(id.to_uppercase(), |names| {
+                names
+                    .iter()
+                    .map(|n| n.to_string())
+                    .collect::<Vec<_>>()
+                    .join(" ")
+            }),
+        };
+        // One entry per time the option was given, e.g. `cache,deps`.
+        let occurrences: Vec<String> = matches
+            .get_raw_occurrences(id)
+            .into_iter()
+            .flatten()
+            .map(|values| {
+                values
+                    .map(|v| v.to_string_lossy().into_owned())
+                    .collect::<Vec<_>>()
+                    .join(",")
+            })
+            .collect();
+        let from = match (source, arg.get_env()) {
+            (ValueSource::EnvVariable, Some(env)) => format!(" (from {})", env.to_string_lossy()),
+            _ if from_config => " (from the config file)".to_string(),
+            _ => String::new(),
+        };
+        match arg.get_action() {
+            ArgAction::SetTrue if occurrences.iter().any(|v| v == "false") => {}
+            ArgAction::SetTrue | ArgAction::SetFalse | ArgAction::Count => {
+                settings.push(format!("{name}{from}"));
+            }
+            _ if occurrences.iter().all(String::is_empty) => {
+                settings.push(format!("{name}{from}"));
+            }
+            _ => settings.extend(occurrences.iter().map(|v| format!("{name} {v}{from}"))),
+        }
+    }
+    settings
+}
+
+/// The built-in rules and the patterns `cli` filters with.
+fn check_rules(cli: &Cli) -> Vec<Check> {
+    let mut checks = vec![Check::new(
+        Status::Ok,
+        "built-in rules",
+        format!(
+            "{} rules for {} build systems",
+            all_rules().len(),
+            system_ids().len()
+        ),
+    )];
+    let files = cli.include_from.iter().chain(&cli.exclude_from);
+    for file in files {
+        checks.push(match read_pattern_file(file) {
+            Ok(patterns) => Check::new(
+                Status::Ok,
+                file.display().to_string(),
+                format!("{} patterns", patterns.len()),
+            ),
+            Err(e) => Check::new(Status::Failed, file.display().to_string(), e.to_string()),
+        });
+    }
+    let patterns = cli.include.len()
+        + cli.exclude.len()
+        + cli.include_regex.len()
+        + cli.exclude_regex.len()
+        + cli.include_from.len()
+        + cli.exclude_from.len();
+    if patterns > 0 {
+        let built = ArtifactFilter::with_pattern_files(
+            &cli.include,
+            &cli.include_from,
+            &cli.exclude,
+            &cli.exclude_from,
+        )
+        .and_then(|f| f.with_regexes(&cli.include_regex, &cli.exclude_regex));
+        match built {
+            Ok(_) => checks.push(Check::new(Status::Ok, "filter patterns", "valid")),
+            // Already reported with its file.
+            Err(PatternError::File { .. }) => {}
+            Err(e) => checks.push(Check::new(Status::Failed, "filter patterns", e.to_string())),
+        }
+    }
+    checks
+}
+
+/// Where sizes are cached, artifacts quarantined and trashed, and deletions
+/// logged, and whether each can be written.
+fn check_directories(cli: &Cli) -> Vec<Check> {
+    let no_home = "neither $XDG_DATA_HOME nor $HOME is set";
+    let cache = match cache::default_path() {
+        None => Check::new(
+            Status::Warning,
+            "size cache",
+            "neither $XDG_CACHE_HOME nor $HOME is set, so every run measures sizes afresh",
+        ),
+        Some(path) if cli.no_cache => Check::new(
+            Status::Ok,
+            "size cache",
+            format!("{} (not used: --no-cache)", path.display()),
+        ),
+        Some(path) => match writable(path.parent().unwrap_or(&path)) {
+            Ok(()) => Check::new(Status::Ok, "size cache", path.display().to_string()),
+            Err(e) => Check::new(
+                Status::Warning,
+                "size cache",
+                format!(
+                    "cannot write {}: {e}; sizes will not be cached",
+                    path.display()
+                ),
+            ),
+        },
+    };
+    let quarantine = match quarantine::default_dir() {
+        Err(_) => Check::new(Status::Failed, "quarantine", no_home),
+        Ok(dir) => {
+            let runs = Quarantine::new(&dir).runs().map_or(0, |r| r.len());
+            directory_check(
+                "quarantine",
+                &dir,
+                format!("{} ({runs} runs)", dir.display()),
+            )
+        }
+    };
+    let log = match audit::default_path() {
+        Err(_) => Check::new(Status::Failed, "deletion log", no_home),
+        Ok(path) => directory_check(
+            "deletion log",
+            path.parent().unwrap_or(&path),
+            path.display().to_string(),
+        ),
+    };
+    let config = match config::default_path() {
+        None => Check::new(
+            Status::Ok,
+            "config file",
+            "none (neither $XDG_CONFIG_HOME nor $HOME is set)",
+        ),
+        Some(path) if path.exists() => {
+            Check::new(Status::Ok, "config file", path.display().to_string())
+        }
+        Some(path) => Check::new(
+            Status::Ok,
+            "config file",
+            format!(
+                "none at {} (`clean-builds init` writes one)",
+                path.display()
+            ),
+        ),
+    };
+    vec![config, cache, quarantine, log, check_trash()]
+}
+
+/// `detail` if `dir` can be written, or why not.
+fn directory_check(subject: &str, dir: &Path, detail: String) -> Check {
+    match writable(dir) {
+        Ok(()) => Check::new(Status::Ok, subject, detail),
+        Err(e) => Check::new(
+            Status::Failed,
+            subject,
+            format!("cannot write {}: {e}", dir.display()),
+        ),
+    }
+}
+
+/// The freedesktop home trash, which `--trash` moves artifacts on the home
+/// filesystem into. Others go to a `.Trash-UID` directory on their own.
+#[cfg(all(unix, not(target_os = "macos")))]
+fn check_trash() -> Check {
+    let trash = std::env::var_os("XDG_DATA_HOME")
+        .filter(|d| !d.is_empty())
+        .map(PathBuf::from)
+        .or_else(|| {
+            std::env::var_os("HOME")
+                .filter(|h| !h.is_empty())
+                .map(|h| PathBuf::from(h).join(".local/share"))
+        })
+        .map(|d| d.join("Trash"));
+    match trash {
+        Some(dir) => directory_check("trash", &dir, dir.display().to_string()),
+        None => Check::new(
+            Status::Failed,
+            "trash",
+            "neither $XDG_DATA_HOME nor $HOME is set",
+        ),
+    }
+}
+
+/// The Finder's or Recycle Bin's trash, which is always there.
+#[cfg(not(all(unix, not(target_os = "macos"))))]
+fn check_trash() -> Check {
+    Check::new(Status::Ok, "trash", "the system trash")
+}
+
+/// Whether files can be created in `dir`, or in its nearest existing
+/// ancestor when `dir` is created on first use. Probes by creating and
+/// removing a file, since permission bits do not tell the whole story
+/// (read-only mounts, ACLs, root).
+fn writable(dir: &Path) -> std::io::Result<()> {
+    let existing = dir.ancestors().find(|d| d.is_dir()).unwrap_or(dir);
+    let probe = existing.join(format!(".clean-builds-doctor-{}", std::process::id()));
+    fs::OpenOptions::new()
+        .write(true)
+        .create_new(true)
+        .open(&probe)?;
+    fs::remove_file(&probe)
+}
+
+/// The filesystems holding `path` and the directories runs write to, with
+/// how each is mounted and what about it affects cleaning.
+fn check_filesystems(path: &Path) -> Vec<Check> {
+    let mut seen = Vec::new();
+    let mut checks = Vec::new();
+    let dirs = std::iter::once(path.to_path_buf())
+        .chain(cache::cache_dir())
+        .chain(data_dir());
+    for dir in dirs {
+        let Some(existing) = dir.ancestors().find(|d| d.exists()) else {
+            continue;
+        };
+        let Some(id) = volume_id(existing) else {
+            continue;
+        };
+        if seen.contains(&id) {
+            continue;
+        }
+        seen.push(id);
+        checks.push(check_filesystem(&dir, existing));
+    }
+    if !path.exists() {
+        checks.insert(
+            0,
+            Check::new(Status::Failed, path.display().to_string(), "does not exist"),
+        );
+    }
+    checks
+}
+
+/// How the filesystem holding `existing`, the deepest existing directory of
+/// `dir`, is mounted.
+fn check_filesystem(dir: &Path, existing: &Path) -> Check {
+    let fs_type = filesystem_type(existing);
+    let mut notes = vec![match (&fs_type, mount_point(existing)) {
+        (Some(t), Some(m)) => format!("{t} at {}", m.display()),
+        (None, Some(m)) => format!("mounted at {}", m.display()),
+        (Some(t), None) => t.clone(),
+        (None, None) => "unknown filesystem".to_string(),
+    }];
+    let mut status = Status::Ok;
+    if read_only_mount(existing) {
+        status = Status::Warning;
+        notes.push("read-only, so nothing on it can be deleted".to_string());
+    }
+    if let Some(quirk) = fs_type.as_deref().and_then(quirk) {
+        if quirk.warns {
+            status = Status::Warning;
+        }
+        notes.push(quirk.note.to_string());
+    }
+    if let Some(warning) = atime_warning(existing, Duration::MAX) {
+        notes.push(warning);
+    }
+    Check::new(status, dir.display().to_string(), notes.join("; "))
+}
+
+/// What sets a type of filesystem apart when cleaning it.
+#[derive(Debug, PartialEq, Eq)]
+struct Quirk {
+    note: &'static str,
+    /// Whether it makes runs slow or surprising rather than being a remark.
+    warns: bool,
+}
+
+/// The [`Quirk`] of filesystems of type `fs_type`, if it has one.
+fn quirk(fs_type: &str) -> Option<Quirk> {
+    let (note, warns) = match fs_type {
+        "nfs" | "nfs4" | "cifs" | "smb3" | "smbfs" | "afpfs" | "webdav" | "9p" | "fuse.sshfs" => (
+            "network filesystem: scans and deletions are slow and can stall (see --timeout)",
+            true,
+        ),
+        "overlay" | "aufs" => (
+            "overlay filesystem: deleting what a lower layer holds frees nothing",
+            true,
+        ),
+        "btrfs" | "xfs" | "apfs" | "zfs" | "bcachefs" | "refs" => (
+            "clones and compression can make apparent sizes overstate what deleting frees (see --du, --reclaimable)",
+            false,
+        ),
+        "tmpfs" | "ramfs" => ("in memory, so emptied on reboot anyway", false),
+        "vfat" | "msdos" | "exfat" => (
+            "no hard links or ownership, and modification times are only accurate to 2 seconds",
+            false,
+        ),
+        _ => return None,
+    };
+    Some(Quirk { note, warns })
+}
+
+/// Print `report` with a closing count of warnings and failures.
+pub fn print_report(out: &mut dyn Write, report: &Report) -> std::io::Result<()> {
+    writeln!(
+        out,
+        "clean-builds {}, {} threads, native walk {}",
+        env!("CARGO_PKG_VERSION"),
+        rayon::current_num_threads(),
+        if cfg!(feature = "native-walk") {
+            "on"
+        } else {
+            "off"
+        }
+    )?;
+    writeln!(out, "\nSettings:")?;
+    if report.settings.is_empty() {
+        writeln!(out, "  (all defaults)")?;
+    }
+    for setting in &report.settings {
+        writeln!(out, "  {setting}")?;
+    }
+    for (section, checks) in &report.sections {
+        writeln!(out, "\n{section}:")?;
+        for check in checks {
+            writeln!(
+                out,
+                "  {:<4}  {}: {}",
+                check.status.label(),
+                check.subject,
+                check.detail
+            )?;
+        }
+    }
+    let count = |status| report.checks().filter(|c| c.status == status).count();
+    let (warnings, failures) = (count(Status::Warning), count(Status::Failed));
+    if warnings + failures == 0 {
+        writeln!(out, "\nNo problems found.")
+    } else {
+        writeln!(out, "\n{warnings} warnings, {failures} failures.")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use clap::Parser;
+    use tempfile::TempDir;
+
+    #[test]
+    fn settings_lists_options_given_with_their_source() {
+        let matches = Cli::command()
+            .try_get_matches_from([
+                "clean-builds",
+                "--exclude",
+                "vendor",
+                "--exclude",
+                "dist",
+                "--du",
+                "--risk",
+                "cache,deps",
+                "src",
+            ])
+            .unwrap();
+        assert_eq!(
+            settings(&matches),
+            [
+                "PATH src",
+                "--exclude vendor",
+                "--exclude dist",
+                "--risk cache,deps",
+                "--du",
+            ]
+        );
+
+        let defaults = Cli::command()
+            .try_get_matches_from(["clean-builds"])
+            .unwrap();
+        assert!(settings(&defaults).is_empty());
+
+        let configured = crate::config::Config::parse("exclude = [\"old-*\"]\ndu = true")
+            .unwrap()
+            .apply(Cli::command())
+            .try_get_matches_from(["clean-builds"])
+            .unwrap();
+        assert_eq!(
+            settings(&configured),
+            [
+                "--exclude old-* (from the config file)",
+                "--du (from the config file)"
+            ]
+        );
+    }
+
+    #[test]
+    fn unreadable_pattern_files_fail() {
+        let tmp = TempDir::new().unwrap();
+        let good = tmp.path().join("exclude.txt");
+        fs::write(&good, "# keep vendored code\nvendor\ndist\n").unwrap();
+        let missing = tmp.path().join("missing.txt");
+        let cli = Cli::parse_from([
+            "clean-builds".as_ref(),
+            "--exclude-from".as_ref(),
+            good.as_os_str(),
+            "--exclude-from".as_ref(),
+            missing.as_os_str(),
+        ]);
+
+        let checks = check_rules(&cli);
+        assert_eq!(checks[0].subject, "built-in rules");
+        assert_eq!(checks[1].status, Status::Ok);
+        assert_eq!(checks[1].detail, "2 patterns");
+        assert_eq!(checks[2].status, Status::Failed);
+        assert!(checks[2].detail.contains("cannot read pattern file"));
+        assert_eq!(checks.len(), 3);
+    }
+
+    #[test]
+    fn writable_probes_the_nearest_existing_directory() {
+        let tmp = TempDir::new().unwrap();
+        assert!(writable(&tmp.path().join("not/yet/created")).is_ok());
+        assert!(!tmp.path().join("not").exists());
+        assert_eq!(fs::read_dir(tmp.path()).unwrap().count(), 0);
+    }
+
+    #[test]
+    fn network_and_overlay_filesystems_warn() {
+        assert!(quirk("nfs4").unwrap().warns);
+        assert!(quirk("overlay").unwrap().warns);
+        assert!(!quirk("btrfs").unwrap().warns);
+        assert_eq!(quirk("ext4"), None);
+    }
+
+    #[test]
+    fn report_counts_warnings_and_failures() {
+        let report = Report {
+            settings: vec!["--du (from CLEAN_BUILDS_DU)".to_string()],
+            sections: vec![(
+                "Directories",
+                vec![
+                    Check::new(Status::Ok, "size cache", "/c/sizes.toml"),
+                    Check::new(Status::Failed, "quarantine", "cannot write /q"),
+                ],
+            )],
+        };
+        let mut out = Vec::new();
+        print_report(&mut out, &report).unwrap();
+        let output = String::from_utf8(out).unwrap();
+
+        assert!(report.failed());
+        assert!(output.contains("Settings:\n  --du (from CLEAN_BUILDS_DU)\n"));
+        assert!(output.contains("  ok    size cache: /c/sizes.toml\n"));
+        assert!(output.contains("  FAIL  quarantine: cannot write /q\n"));
+        assert!(output.ends_with("0 warnings, 1 failures.\n"));
+    }
+}
@@ -0,0 +1,252 @@
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+use log::debug;
+use rayon::prelude::*;
+
+use crate::filter::{Filter, Verdict};
+use crate::rules::{EntryKind, MatchableRule, explain_marker, marker_anchor, matches_dir};
+use crate::scanner::{Artifact, match_path};
+
+/// What the filter stages decided about one artifact.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Explanation {
+    /// Reasons given by the stages that kept the artifact, in pipeline order.
+    pub kept_because: Vec<String>,
+    /// Reason given by the stage that dropped the artifact, if one did.
+    pub dropped_because: Option<String>,
+}
+
+/// Runs artifacts through filter stages, recording every verdict so that
+/// `--explain` can report why each artifact was kept or dropped.
+#[derive(Debug, Default)]
+pub struct Explainer {
+    entries: BTreeMap<PathBuf, Explanation>,
+}
+
+impl Explainer {
+    /// Run one filter stage, returning the artifacts it keeps.
+    ///
+    /// Verdicts are computed in parallel, since some filters (access times,
+    /// Git state) do I/O per artifact.
+    pub fn stage(&mut self, artifacts: Vec<Artifact>, filter: &dyn Filter) -> Vec<Artifact> {
+        let verdicts: Vec<Verdict> = artifacts.par_iter().map(|a| filter.verdict(a)).collect();
+        let mut kept = Vec::with_capacity(artifacts.len());
+        for (artifact, verdict) in artifacts.into_iter().zip(verdicts) {
+            let entry = self.entries.entry(artifact.path.clone()).or_default();
+            match verdict {
+                Verdict::Keep(reason) => {
+                    entry.kept_because.extend(reason);
+                    kept.push(artifact);
+                }
+                Verdict::Drop(reason) => {
+                    debug!("Filtered out: {} ({reason})", artifact.path.display());
+                    entry.dropped_because = Some(reason);
+                }
+            }
+        }
+        kept
+    }
+
+    /// What the stages decided about the artifact at `path`, if any saw it.
+    pub fn explanation(&self, path: &Path) -> Option<&Explanation> {
+        self.entries.get(path)
+    }
+
+    /// Every artifact seen by any stage, sorted by path.
+    pub fn explanations(&self) -> impl Iterator<Item = (&Path, &Explanation)> {
+        self.entries.iter().map(|(path, e)| (path.as_path(), e))
+    }
+}
+
+/// How a rule whose name pattern fits a path judged it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RuleCheck {
+    pub id: &'static str,
+    pub build_system: &'static str,
+    /// Whether the rule's marker holds.
+    pub matched: bool,
+    /// What was found, or what is missing.
+    pub marker: String,
+    /// Whether the run uses the rule, which `--system`, `--exclude-system`
+    /// and `--risk` can leave out.
+    pub active: bool,
+}
+
+/// What a run makes of one path (`clean-builds explain`).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PathReport {
+    pub path: PathBuf,
+    /// Rules whose name pattern fits the path, in the order they are tried.
+    pub checks: Vec<RuleCheck>,
+    /// Why the scan never reaches the path, if it does not.
+    pub unreached: Option<String>,
+    /// What the filters decided, if the scan found the path.
+    pub explanation: Option<Explanation>,
+    /// Whether the run selects the path for deletion.
+    pub selected: bool,
+}
+
+/// Explain what a run under `root` with the rules `active` (of all the
+/// `rules`) makes of `path`, given what its filter stages recorded in
+/// `explainer` and the artifacts it `selected`.
+pub fn report_path(
+    root: &Path,
+    path: &Path,
+    rules: &[MatchableRule],
+    active: &[MatchableRule],
+    explainer: &Explainer,
+    selected: &[Artifact],
+) -> PathReport {
+    PathReport {
+        path: path.to_path_buf(),
+        checks: check_rules(path, rules, active),
+        unreached: unreached(root, path, active),
+        explanation: explainer.explanation(path).cloned(),
+        selected: selected.iter().any(|a| a.path == path),
+    }
+}
+
+/// Try `path` against each of `rules` whose entry kind and name pattern fit
+/// it, as the scan would, recording whether its marker holds and why.
+fn check_rules(path: &Path, rules: &[MatchableRule], active: &[MatchableRule]) -> Vec<RuleCheck> {
+    let Some(name) = path.file_name().and_then(|n| n.to_str()) else {
+        return Vec::new();
+    };
+    let Some(kind) = entry_kind(path) else {
+        return Vec::new();
+    };
+    rules
+        .iter()
+        .filter(|r| r.entry == kind && matches_dir(name, &r.dir_match))
+        .filter_map(|r| {
+            let anchor = marker_anchor(path, &r.dir_match)?;
+            let (matched, marker) = explain_marker(anchor, path, &r.rule.marker);
+            Some(RuleCheck {
+                id: r.rule.id,
+                build_system: r.rule.build_system,
+                matched,
+                marker,
+                active: active.iter().any(|a| a.rule.id == r.rule.id),
+            })
+        })
+        .collect()
+}
+
+/// What kind of entry `path` is, as rules see it.
+pub fn entry_kind(path: &Path) -> Option<EntryKind> {
+    let file_type = path.symlink_metadata().ok()?.file_type();
+    if file_type.is_dir() {
+        Some(EntryKind::Dir)
+    } else if file_type.is_file() {
+        Some(EntryKind::File)
+    } else if file_type.is_symlink() {
+        Some(EntryKind::Symlink)
+    } else {
+        None
+    }
+}
+
+/// Why a scan of `root` with `rules` never reaches `path`: it is inside a
+/// `.git` directory, which the scan skips, or inside an artifact, which
+/// the scan does not look into. `None` if the scan reaches it.
+fn unreached(root: &Path, path: &Path, rules: &[MatchableRule]) -> Option<String> {
+    let Ok(relative) = path.strip_prefix(root) else {
+        return Some(format!("it is not under {}", root.display()));
+    };
+    let mut dir = root.to_path_buf();
+    let components: Vec<_> = relative.components().collect();
+    // Every directory between the root and the path, outermost first.
+    for component in components.iter().take(components.len().saturating_sub(1)) {
+        dir.push(component);
+        if dir.file_name().is_some_and(|name| name == ".git") {
+            return Some(format!(
+                "it is inside {}, and .git directories are skipped",
+                dir.display()
+            ));
+        }
+        if let Some(artifact) = match_path(&dir, rules) {
+            return Some(format!(
+                "it is inside {}, a {} artifact the scan does not look into",
+                dir.display(),
+                artifact.build_system
+            ));
+        }
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::filter::ArtifactFilter;
+    use crate::size::SizeFilter;
+
+    #[test]
+    fn reports_how_the_rules_and_scan_see_a_path() {
+        let tmp = tempfile::TempDir::new().unwrap();
+        let root = tmp.path().canonicalize().unwrap();
+        std::fs::create_dir_all(root.join("app/target/debug")).unwrap();
+        std::fs::write(root.join("app/Cargo.toml"), "").unwrap();
+        let rules = crate::rules::all_rules();
+        let target = root.join("app/target");
+
+        let report = report_path(&root, &target, &rules, &rules, &Explainer::default(), &[]);
+        let cargo = report.checks.iter().find(|c| c.id == "cargo").unwrap();
+        assert!(cargo.matched && cargo.active);
+        assert!(
+            report
+                .checks
+                .iter()
+                .filter(|c| c.id != "cargo")
+                .all(|c| !c.matched)
+        );
+        assert_eq!(report.unreached, None);
+        assert!(!report.selected);
+
+        let node_only =
+            crate::rules::filter_rules_by_system(rules.clone(), &["node".into()], &[]).unwrap();
+        let report = report_path(
+            &root,
+            &target,
+            &rules,
+            &node_only,
+            &Explainer::default(),
+            &[],
+        );
+        assert!(report.checks.iter().all(|c| !c.active));
+
+        let inner = root.join("app/target/debug");
+        let report = report_path(&root, &inner, &rules, &rules, &Explainer::default(), &[]);
+        let reason = report.unreached.unwrap();
+        assert!(reason.contains("Rust/Cargo artifact"), "{reason}");
+    }
+
+    #[test]
+    fn records_reasons_across_stages() {
+        let mut explainer = Explainer::default();
+        let artifacts = vec![
+            Artifact::for_test("/p/a/target", "Rust/Cargo"),
+            Artifact::for_test("/p/b/target", "Rust/Cargo"),
+        ];
+        let excludes = ArtifactFilter::new(&[], &["b".to_string()]).unwrap();
+        let artifacts = explainer.stage(artifacts, &excludes);
+        let artifacts = explainer.stage(artifacts, &|_: &Artifact| true);
+        let sizes = SizeFilter {
+            min: Some(0),
+            max: None,
+        };
+        let artifacts = explainer.stage(artifacts, &sizes);
+        assert_eq!(artifacts.len(), 1);
+
+        let explained: Vec<_> = explainer.explanations().collect();
+        assert_eq!(explained.len(), 2);
+        assert_eq!(explained[0].0, Path::new("/p/a/target"));
+        assert_eq!(explained[0].1.kept_because, ["size 0 B"]);
+        assert_eq!(explained[0].1.dropped_because, None);
+        assert_eq!(
+            explained[1].1.dropped_because.as_deref(),
+            Some("excluded by --exclude 'b'")
+        );
+    }
+}
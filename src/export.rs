@@ -0,0 +1,221 @@
+use std::io::Read;
+#[cfg(feature = "serde")]
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+
+use crate::rules::MatchableRule;
+use crate::scanner::{Artifact, match_path};
+
+/// Error reading an exported scan.
+#[derive(thiserror::Error, Debug)]
+pub enum ExportError {
+    #[error("cannot read {}: {source}", path.display())]
+    Io {
+        path: PathBuf,
+        source: std::io::Error,
+    },
+    #[error("invalid exported scan: {0}")]
+    Parse(#[from] serde_json::Error),
+}
+
+/// One artifact in an exported scan, as read back for `delete --from-json`:
+/// the fields of an [`Artifact`] that revalidating it needs, all but the
+/// path optional so that hand-written lists are accepted too.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ExportedArtifact {
+    pub path: PathBuf,
+    #[serde(default)]
+    pub project: Option<PathBuf>,
+    /// The build system it was detected as. When present, the artifact must
+    /// still be detected as the same one to be deleted.
+    #[serde(default)]
+    pub build_system: Option<String>,
+    #[serde(default)]
+    pub risk: Option<String>,
+    #[serde(default)]
+    pub size_bytes: Option<u64>,
+}
+
+/// The document `--json` prints.
+#[cfg(feature = "serde")]
+#[derive(Debug, Serialize)]
+struct Scan<'a> {
+    root: &'a Path,
+    artifacts: &'a [Artifact],
+}
+
+/// What `--from-json` accepts: the document `--json` prints, or just its
+/// list of artifacts (as `jq '.artifacts'` leaves it).
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum Import {
+    Scan { artifacts: Vec<ExportedArtifact> },
+    List(Vec<ExportedArtifact>),
+}
+
+/// Print `artifacts`, found under `root`, as a JSON document.
+#[cfg(feature = "serde")]
+pub fn write_json(out: &mut dyn Write, root: &Path, artifacts: &[Artifact]) -> std::io::Result<()> {
+    serde_json::to_writer_pretty(&mut *out, &Scan { root, artifacts })?;
+    writeln!(out)
+}
+
+/// Read an exported scan from `path`, or from standard input if it is `-`.
+pub fn read_json(path: &Path) -> Result<Vec<ExportedArtifact>, ExportError> {
+    let io = |source| ExportError::Io {
+        path: path.to_path_buf(),
+        source,
+    };
+    let mut text = String::new();
+    if path == Path::new("-") {
+        std::io::stdin().read_to_string(&mut text).map_err(io)?;
+    } else {
+        text = std::fs::read_to_string(path).map_err(io)?;
+    }
+    parse_json(&text)
+}
+
+/// Parse an exported scan in either form [`read_json`] accepts.
+pub fn parse_json(text: &str) -> Result<Vec<ExportedArtifact>, ExportError> {
+    Ok(match serde_json::from_str(text)? {
+        Import::Scan { artifacts } | Import::List(artifacts) => artifacts,
+    })
+}
+
+/// Match each exported artifact against `rules` again, as a scan would now.
+/// Returns the artifacts that still match, and the paths of those that don't
+/// with why. Sizes are left for `compute_sizes` to measure afresh.
+pub fn revalidate(
+    exported: &[ExportedArtifact],
+    rules: &[MatchableRule],
+) -> (Vec<Artifact>, Vec<(PathBuf, String)>) {
+    let mut artifacts: Vec<Artifact> = Vec::new();
+    let mut rejected = Vec::new();
+    for entry in exported {
+        let path = std::path::absolute(&entry.path).unwrap_or_else(|_| entry.path.clone());
+        if artifacts.iter().any(|a| a.path == path) {
+            continue;
+        }
+        if path.symlink_metadata().is_err() {
+            rejected.push((path, "no longer exists".to_string()));
+            continue;
+        }
+        match match_path(&path, rules) {
+            None => rejected.push((path, "no longer matches a known rule".to_string())),
+            Some(artifact) => match &entry.build_system {
+                Some(system) if system != artifact.build_system => rejected.push((
+                    path,
+                    format!("now matches {}, not {system}", artifact.build_system),
+                )),
+                _ => artifacts.push(artifact),
+            },
+        }
+    }
+    (artifacts, rejected)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rules::all_rules;
+    use std::fs;
+    use tempfile::TempDir;
+
+    fn rust_project(tmp: &TempDir) -> PathBuf {
+        let project = tmp.path().join("app");
+        fs::create_dir_all(project.join("target")).unwrap();
+        fs::write(project.join("Cargo.toml"), "").unwrap();
+        project
+    }
+
+    #[test]
+    #[cfg(feature = "serde")]
+    fn exported_scan_reads_back() {
+        let tmp = TempDir::new().unwrap();
+        let project = rust_project(&tmp);
+        let artifact = match_path(&project.join("target"), &all_rules()).unwrap();
+
+        let mut out = Vec::new();
+        write_json(&mut out, tmp.path(), std::slice::from_ref(&artifact)).unwrap();
+        let read = parse_json(&String::from_utf8(out).unwrap()).unwrap();
+
+        assert_eq!(read.len(), 1);
+        assert_eq!(read[0].path, artifact.path);
+        assert_eq!(read[0].build_system.as_deref(), Some(artifact.build_system));
+    }
+
+    #[test]
+    #[cfg(feature = "serde")]
+    fn exported_artifacts_deserialize_as_artifacts() {
+        let tmp = TempDir::new().unwrap();
+        let project = rust_project(&tmp);
+        let mut artifact = match_path(&project.join("target"), &all_rules()).unwrap();
+        crate::size::compute_sizes(std::slice::from_mut(&mut artifact));
+        // The export keeps whole seconds.
+        use std::time::{Duration, UNIX_EPOCH};
+        let stats = artifact.stats.as_mut().unwrap();
+        stats.newest = Some(UNIX_EPOCH + Duration::from_secs(1_709_210_096));
+
+        let mut out = Vec::new();
+        write_json(&mut out, tmp.path(), std::slice::from_ref(&artifact)).unwrap();
+        let scan: serde_json::Value = serde_json::from_slice(&out).unwrap();
+        assert_eq!(
+            scan["artifacts"][0]["stats"]["newest"],
+            serde_json::json!("2024-02-29T12:34:56Z")
+        );
+        let read: Vec<Artifact> = serde_json::from_value(scan["artifacts"].clone()).unwrap();
+
+        assert_eq!(read.len(), 1);
+        assert_eq!(read[0].path, artifact.path);
+        assert_eq!(read[0].project, artifact.project);
+        assert_eq!(read[0].build_system, artifact.build_system);
+        assert_eq!(read[0].artifact_dir, artifact.artifact_dir);
+        assert_eq!(read[0].risk, artifact.risk);
+        assert_eq!(read[0].stats, artifact.stats);
+        assert_eq!(read[0].clean, None);
+    }
+
+    #[test]
+    fn bare_list_of_paths_is_accepted() {
+        let read = parse_json(r#"[{"path": "/dev/app/target"}]"#).unwrap();
+        assert_eq!(read[0].path, PathBuf::from("/dev/app/target"));
+        assert!(read[0].build_system.is_none());
+    }
+
+    #[test]
+    fn revalidate_rejects_paths_that_no_longer_match() {
+        let tmp = TempDir::new().unwrap();
+        let project = rust_project(&tmp);
+        fs::create_dir_all(project.join("src")).unwrap();
+        let entry = |path: PathBuf, system: Option<&str>| ExportedArtifact {
+            path,
+            project: None,
+            build_system: system.map(str::to_string),
+            risk: None,
+            size_bytes: None,
+        };
+        let target = match_path(&project.join("target"), &all_rules()).unwrap();
+        let exported = [
+            entry(project.join("target"), Some("Not Rust")),
+            entry(project.join("src"), None),
+            entry(project.join("gone"), None),
+            entry(project.join("target"), None),
+        ];
+
+        let (artifacts, rejected) = revalidate(&exported, &all_rules());
+
+        assert_eq!(artifacts.len(), 1);
+        assert_eq!(artifacts[0].path, target.path);
+        let reasons: Vec<&str> = rejected.iter().map(|(_, r)| r.as_str()).collect();
+        assert_eq!(
+            reasons,
+            [
+                format!("now matches {}, not Not Rust", target.build_system).as_str(),
+                "no longer matches a known rule",
+                "no longer exists"
+            ]
+        );
+    }
+}
@@ -0,0 +1,574 @@
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+
+use globset::{Glob, GlobMatcher};
+
+use crate::age::parse_duration;
+use crate::filter::{Filter, Verdict};
+use crate::rules::{Risk, system_ids};
+use crate::scanner::Artifact;
+use crate::size::parse_size;
+
+/// Error from parsing a `--filter` expression.
+#[derive(thiserror::Error, Debug, PartialEq, Eq)]
+#[error("invalid filter expression at column {column}: {message}")]
+pub struct ExprError {
+    /// 1-based column of the offending token.
+    pub column: usize,
+    pub message: String,
+}
+
+/// An artifact property a comparison can test.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Field {
+    Size,
+    Age,
+    System,
+    Risk,
+    Path,
+    Project,
+}
+
+impl Field {
+    fn parse(name: &str) -> Option<Self> {
+        Some(match name {
+            "size" => Field::Size,
+            "age" => Field::Age,
+            "system" => Field::System,
+            "risk" => Field::Risk,
+            "path" => Field::Path,
+            "project" => Field::Project,
+            _ => return None,
+        })
+    }
+
+    fn is_ordered(self) -> bool {
+        matches!(self, Field::Size | Field::Age)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Op {
+    Lt,
+    Le,
+    Gt,
+    Ge,
+    Eq,
+    Ne,
+}
+
+impl Op {
+    fn as_str(self) -> &'static str {
+        match self {
+            Op::Lt => "<",
+            Op::Le => "<=",
+            Op::Gt => ">",
+            Op::Ge => ">=",
+            Op::Eq => "==",
+            Op::Ne => "!=",
+        }
+    }
+
+    fn compare<T: PartialOrd>(self, left: T, right: T) -> bool {
+        match self {
+            Op::Lt => left < right,
+            Op::Le => left <= right,
+            Op::Gt => left > right,
+            Op::Ge => left >= right,
+            Op::Eq => left == right,
+            Op::Ne => left != right,
+        }
+    }
+}
+
+/// The parsed right-hand side of a comparison.
+#[derive(Debug, Clone)]
+enum Value {
+    Size(u64),
+    Age(Duration),
+    /// A build system's display name, e.g. `Rust/Cargo`.
+    System(&'static str),
+    Risk(Risk),
+    Glob(GlobMatcher),
+}
+
+/// A parsed `--filter` expression.
+#[derive(Debug, Clone)]
+pub struct Expr(Node);
+
+#[derive(Debug, Clone)]
+enum Node {
+    Compare {
+        field: Field,
+        op: Op,
+        value: Value,
+        /// The value as written, for display.
+        text: String,
+    },
+    Not(Box<Node>),
+    And(Box<Node>, Box<Node>),
+    Or(Box<Node>, Box<Node>),
+}
+
+/// What an expression is evaluated against.
+struct Context<'a> {
+    artifact: &'a Artifact,
+    relative_path: &'a Path,
+    now: SystemTime,
+}
+
+impl Expr {
+    /// Parse an expression such as `size > 1GB && !(system == "python")`.
+    pub fn parse(text: &str) -> Result<Self, ExprError> {
+        let tokens = tokenize(text)?;
+        let mut parser = Parser {
+            tokens,
+            pos: 0,
+            end: text.chars().count() + 1,
+        };
+        let node = parser.or()?;
+        match parser.peek() {
+            None => Ok(Expr(node)),
+            Some(t) => Err(t.error("expected `&&`, `||`, or end of expression")),
+        }
+    }
+
+    /// Whether the expression tests `size`, so can only be evaluated once
+    /// sizes are computed.
+    pub fn needs_sizes(&self) -> bool {
+        self.0.needs_sizes()
+    }
+}
+
+impl fmt::Display for Expr {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        self.0.fmt(f)
+    }
+}
+
+impl Node {
+    fn eval(&self, cx: &Context) -> bool {
+        match self {
+            Node::Not(e) => !e.eval(cx),
+            Node::And(a, b) => a.eval(cx) && b.eval(cx),
+            Node::Or(a, b) => a.eval(cx) || b.eval(cx),
+            Node::Compare {
+                field, op, value, ..
+            } => {
+                let a = cx.artifact;
+                match (field, value) {
+                    (Field::Size, Value::Size(size)) => op.compare(a.size_bytes, *size),
+                    (Field::Age, Value::Age(age)) => {
+                        match std::fs::symlink_metadata(&a.path).and_then(|m| m.modified()) {
+                            Ok(mtime) => {
+                                op.compare(cx.now.duration_since(mtime).unwrap_or_default(), *age)
+                            }
+                            Err(_) => false,
+                        }
+                    }
+                    (Field::System, Value::System(name)) => op.compare(a.build_system, *name),
+                    (Field::Risk, Value::Risk(risk)) => op.compare(a.risk, *risk),
+                    (Field::Path, Value::Glob(glob)) => {
+                        (*op == Op::Eq) == glob.is_match(cx.relative_path)
+                    }
+                    (Field::Project, Value::Glob(glob)) => {
+                        let name = a.project.file_name().unwrap_or_default();
+                        (*op == Op::Eq) == glob.is_match(name)
+                    }
+                    _ => unreachable!("value kind is chosen by field at parse time"),
+                }
+            }
+        }
+    }
+
+    fn needs_sizes(&self) -> bool {
+        match self {
+            Node::Not(e) => e.needs_sizes(),
+            Node::And(a, b) | Node::Or(a, b) => a.needs_sizes() || b.needs_sizes(),
+            Node::Compare { field, .. } => *field == Field::Size,
+        }
+    }
+
+    /// The smallest part of a false expression that explains why: the false
+    /// side of an `&&`, or the whole expression otherwise.
+    fn culprit(&self, cx: &Context) -> &Node {
+        match self {
+            Node::And(a, _) if !a.eval(cx) => a.culprit(cx),
+            Node::And(_, b) => b.culprit(cx),
+            _ => self,
+        }
+    }
+}
+
+impl fmt::Display for Node {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Node::Compare {
+                field,
+                op,
+                text,
+                value,
+            } => {
+                let name = format!("{field:?}").to_lowercase();
+                match value {
+                    Value::Size(_) | Value::Age(_) => write!(f, "{name} {} {text}", op.as_str()),
+                    _ => write!(f, "{name} {} \"{text}\"", op.as_str()),
+                }
+            }
+            Node::Not(e) => write!(f, "!({e})"),
+            Node::And(a, b) => write!(f, "({a} && {b})"),
+            Node::Or(a, b) => write!(f, "({a} || {b})"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum TokenKind {
+    /// A bare word: a field name, or a size or duration such as `1.5GB`.
+    Word(String),
+    /// A quoted string.
+    Str(String),
+    Op(Op),
+    And,
+    Or,
+    Not,
+    LParen,
+    RParen,
+}
+
+#[derive(Debug, Clone)]
+struct Token {
+    kind: TokenKind,
+    column: usize,
+}
+
+impl Token {
+    fn error(&self, message: &str) -> ExprError {
+        ExprError {
+            column: self.column,
+            message: message.to_string(),
+        }
+    }
+}
+
+fn tokenize(text: &str) -> Result<Vec<Token>, ExprError> {
+    let chars: Vec<char> = text.chars().collect();
+    let mut tokens = Vec::new();
+    let mut i = 0;
+    while i < chars.len() {
+        let column = i + 1;
+        let c = chars[i];
+        let next = chars.get(i + 1).copied();
+        let (kind, len) = match (c, next) {
+            (c, _) if c.is_whitespace() => {
+                i += 1;
+                continue;
+            }
+            ('&', Some('&')) => (TokenKind::And, 2),
+            ('|', Some('|')) => (TokenKind::Or, 2),
+            ('<', Some('=')) => (TokenKind::Op(Op::Le), 2),
+            ('>', Some('=')) => (TokenKind::Op(Op::Ge), 2),
+            ('=', Some('=')) => (TokenKind::Op(Op::Eq), 2),
+            ('!', Some('=')) => (TokenKind::Op(Op::Ne), 2),
+            ('<', _) => (TokenKind::Op(Op::Lt), 1),
+            ('>', _) => (TokenKind::Op(Op::Gt), 1),
+            ('!', _) => (TokenKind::Not, 1),
+            ('(', _) => (TokenKind::LParen, 1),
+            (')', _) => (TokenKind::RParen, 1),
+            ('"' | '\'', _) => {
+                let Some(close) = chars[i + 1..].iter().position(|&ch| ch == c) else {
+                    return Err(ExprError {
+                        column,
+                        message: "unterminated string".to_string(),
+                    });
+                };
+                let s: String = chars[i + 1..i + 1 + close].iter().collect();
+                (TokenKind::Str(s), close + 2)
+            }
+            (c, _) if c.is_alphanumeric() || c == '.' || c == '_' => {
+                let len = chars[i..]
+                    .iter()
+                    .take_while(|ch| ch.is_alphanumeric() || **ch == '.' || **ch == '_')
+                    .count();
+                (TokenKind::Word(chars[i..i + len].iter().collect()), len)
+            }
+            (c, _) => {
+                return Err(ExprError {
+                    column,
+                    message: format!("unexpected character '{c}'"),
+                });
+            }
+        };
+        tokens.push(Token { kind, column });
+        i += len;
+    }
+    Ok(tokens)
+}
+
+struct Parser {
+    tokens: Vec<Token>,
+    pos: usize,
+    /// Column just past the end of the input, for "unexpected end" errors.
+    end: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.pos)
+    }
+
+    fn next(&mut self, expected: &str) -> Result<Token, ExprError> {
+        let token = self
+            .tokens
+            .get(self.pos)
+            .cloned()
+            .ok_or_else(|| ExprError {
+                column: self.end,
+                message: format!("expected {expected}, found end of expression"),
+            })?;
+        self.pos += 1;
+        Ok(token)
+    }
+
+    fn eat(&mut self, kind: &TokenKind) -> bool {
+        if self.peek().is_some_and(|t| t.kind == *kind) {
+            self.pos += 1;
+            true
+        } else {
+            false
+        }
+    }
+
+    fn or(&mut self) -> Result<Node, ExprError> {
+        let mut expr = self.and()?;
+        while self.eat(&TokenKind::Or) {
+            expr = Node::Or(Box::new(expr), Box::new(self.and()?));
+        }
+        Ok(expr)
+    }
+
+    fn and(&mut self) -> Result<Node, ExprError> {
+        let mut expr = self.unary()?;
+        while self.eat(&TokenKind::And) {
+            expr = Node::And(Box::new(expr), Box::new(self.unary()?));
+        }
+        Ok(expr)
+    }
+
+    fn unary(&mut self) -> Result<Node, ExprError> {
+        if self.eat(&TokenKind::Not) {
+            return Ok(Node::Not(Box::new(self.unary()?)));
+        }
+        if self.eat(&TokenKind::LParen) {
+            let expr = self.or()?;
+            let close = self.next("`)`")?;
+            if close.kind != TokenKind::RParen {
+                return Err(close.error("expected `)`"));
+            }
+            return Ok(expr);
+        }
+        self.comparison()
+    }
+
+    fn comparison(&mut self) -> Result<Node, ExprError> {
+        let token = self.next("a field name")?;
+        let field = match &token.kind {
+            TokenKind::Word(name) => Field::parse(name),
+            _ => None,
+        }
+        .ok_or_else(|| {
+            token.error("expected a field name (size, age, system, risk, path, or project)")
+        })?;
+
+        let token = self.next("a comparison operator")?;
+        let op = match token.kind {
+            TokenKind::Op(op) if field.is_ordered() || matches!(op, Op::Eq | Op::Ne) => op,
+            TokenKind::Op(_) => return Err(token.error("only `==` and `!=` apply to this field")),
+            _ => return Err(token.error("expected a comparison operator")),
+        };
+
+        let token = self.next("a value")?;
+        let text = match &token.kind {
+            TokenKind::Word(s) | TokenKind::Str(s) => s.clone(),
+            _ => return Err(token.error("expected a value")),
+        };
+        let invalid = |e: &dyn fmt::Display| token.error(&e.to_string());
+        let value = match field {
+            Field::Size => Value::Size(parse_size(&text).map_err(|e| invalid(&e))?),
+            Field::Age => Value::Age(parse_duration(&text).map_err(|e| invalid(&e))?),
+            Field::System => Value::System(
+                system_ids()
+                    .into_iter()
+                    .find(|(id, name)| id.eq_ignore_ascii_case(&text) || name == &text)
+                    .map(|(_, name)| name)
+                    .ok_or_else(|| invalid(&format!("unknown system '{text}'")))?,
+            ),
+            Field::Risk => Value::Risk(
+                <Risk as clap::ValueEnum>::from_str(&text, true)
+                    .map_err(|_| invalid(&format!("unknown risk '{text}'")))?,
+            ),
+            Field::Path | Field::Project => {
+                Value::Glob(Glob::new(&text).map_err(|e| invalid(&e))?.compile_matcher())
+            }
+        };
+        Ok(Node::Compare {
+            field,
+            op,
+            value,
+            text,
+        })
+    }
+}
+
+/// Keeps artifacts for which a `--filter` expression is true.
+///
+/// `size` needs sizes to be computed first; `age` is the artifact's own
+/// modification time, as for `--older-than`. `path` globs match the path
+/// relative to `root`, `project` globs the project directory name.
+#[derive(Debug)]
+pub struct ExprFilter {
+    expr: Expr,
+    root: PathBuf,
+    now: SystemTime,
+}
+
+impl ExprFilter {
+    pub fn new(expr: Expr, root: &Path) -> Self {
+        Self {
+            expr,
+            root: root.to_path_buf(),
+            now: SystemTime::now(),
+        }
+    }
+
+    fn context<'a>(&self, artifact: &'a Artifact) -> Context<'a> {
+        Context {
+            artifact,
+            relative_path: artifact
+                .path
+                .strip_prefix(&self.root)
+                .unwrap_or(&artifact.path),
+            now: self.now,
+        }
+    }
+}
+
+impl Filter for ExprFilter {
+    fn keep(&self, artifact: &Artifact) -> bool {
+        self.expr.0.eval(&self.context(artifact))
+    }
+
+    fn verdict(&self, artifact: &Artifact) -> Verdict {
+        let cx = self.context(artifact);
+        if self.expr.0.eval(&cx) {
+            Verdict::Keep(Some("matches --filter".to_string()))
+        } else {
+            Verdict::Drop(format!("--filter: {} is false", self.expr.0.culprit(&cx)))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn artifact(path: &str, system: &'static str, risk: Risk, size: u64) -> Artifact {
+        Artifact {
+            risk,
+            size_bytes: size,
+            ..Artifact::for_test(path, system)
+        }
+    }
+
+    fn keeps(expr: &str, artifact: &Artifact) -> bool {
+        ExprFilter::new(Expr::parse(expr).unwrap(), Path::new("/root")).keep(artifact)
+    }
+
+    #[test]
+    fn size_system_and_risk() {
+        let big_py = artifact("/root/app/.venv", "Python", Risk::Deps, 2 << 30);
+        let big_rs = artifact("/root/app/target", "Rust/Cargo", Risk::Output, 2 << 30);
+        let expr = r#"size > 1GB && system != "python""#;
+        assert!(!keeps(expr, &big_py));
+        assert!(keeps(expr, &big_rs));
+        assert!(keeps("risk == deps || size < 1KB", &big_py));
+        assert!(!keeps("!(risk == 'deps')", &big_py));
+        assert!(keeps("system == 'Rust/Cargo'", &big_rs));
+    }
+
+    #[test]
+    fn and_binds_tighter_than_or() {
+        let a = artifact("/root/app/target", "Rust/Cargo", Risk::Output, 0);
+        assert!(keeps("size > 1KB && risk == cache || risk == output", &a));
+        assert!(!keeps(
+            "size > 1KB && (risk == cache || risk == output)",
+            &a
+        ));
+    }
+
+    #[test]
+    fn path_and_project_globs() {
+        let a = artifact("/root/legacy/web/node_modules", "Node.js", Risk::Deps, 0);
+        assert!(keeps("path == 'legacy/**'", &a));
+        assert!(keeps("project != 'api*'", &a));
+        assert!(!keeps("project == 'api*'", &a));
+    }
+
+    #[test]
+    fn age_uses_modification_time() {
+        let tmp = tempfile::TempDir::new().unwrap();
+        let path = tmp.path().to_str().unwrap().to_string();
+        let a = artifact(&format!("{path}/target"), "Rust/Cargo", Risk::Output, 0);
+        std::fs::create_dir(&a.path).unwrap();
+        assert!(keeps("age < 1h", &a));
+        assert!(!keeps("age > 30d", &a));
+    }
+
+    #[test]
+    fn only_size_comparisons_need_sizes() {
+        let needs = |text| Expr::parse(text).unwrap().needs_sizes();
+        assert!(needs("size > 1GB"));
+        assert!(needs("system == cargo || !(size < 1KB)"));
+        assert!(!needs("age > 30d && (risk == deps || path == 'legacy/**')"));
+    }
+
+    #[test]
+    fn verdict_names_the_false_conjunct() {
+        let f = ExprFilter::new(
+            Expr::parse("size >= 1MB && system == cargo").unwrap(),
+            Path::new("/root"),
+        );
+        let small = artifact("/root/app/target", "Rust/Cargo", Risk::Output, 10);
+        assert_eq!(
+            f.verdict(&small),
+            Verdict::Drop("--filter: size >= 1MB is false".to_string())
+        );
+    }
+
+    #[test]
+    fn parse_errors_point_at_the_problem() {
+        let err = |text: &str| Expr::parse(text).unwrap_err();
+        assert_eq!(err("colour == red").column, 1);
+        assert_eq!(err("size > lots").column, 8);
+        assert!(err("size > lots").message.contains("invalid size"));
+        assert_eq!(err("system > cargo").column, 8);
+        assert!(err("system == nope").message.contains("unknown system"));
+        assert!(err("(size > 1KB").message.contains("end of expression"));
+        assert!(
+            err("size > 1KB risk == cache")
+                .message
+                .contains("expected `&&`")
+        );
+        assert!(
+            err("path == 'unterminated")
+                .message
+                .contains("unterminated")
+        );
+        assert!(
+            err("size > 1KB & age > 1d")
+                .message
+                .contains("unexpected character")
+        );
+    }
+}
@@ -1,14 +1,203 @@
-use std::path::Path;
+use std::path::{Path, PathBuf};
 
 use globset::{Glob, GlobSet, GlobSetBuilder};
 use log::debug;
+use rayon::prelude::*;
+use regex::RegexSet;
 
 use crate::scanner::Artifact;
 
 /// Error type for invalid filter patterns.
 #[derive(thiserror::Error, Debug)]
-#[error("invalid filter pattern: {0}")]
-pub struct PatternError(#[from] globset::Error);
+pub enum PatternError {
+    #[error("invalid filter pattern: {0}")]
+    Glob(#[from] globset::Error),
+    #[error("invalid filter regex: {0}")]
+    Regex(#[from] regex::Error),
+    #[error("cannot read pattern file {}: {source}", path.display())]
+    File {
+        path: PathBuf,
+        source: std::io::Error,
+    },
+}
+
+/// A filter's decision about one artifact, with a human-readable reason
+/// that `--explain` prints.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Verdict {
+    /// Kept. The reason names what selected the artifact, if anything did.
+    Keep(Option<String>),
+    /// Dropped, and why.
+    Drop(String),
+}
+
+impl Verdict {
+    pub fn is_keep(&self) -> bool {
+        matches!(self, Verdict::Keep(_))
+    }
+}
+
+/// A predicate deciding which detected artifacts to clean.
+///
+/// The built-in filters implement this, and so does any
+/// `Fn(&Artifact) -> bool`, so library consumers can plug their own checks
+/// (e.g., consulting an asset inventory) into a `FilterChain`.
+pub trait Filter: Send + Sync {
+    /// Whether to keep `artifact` for cleaning.
+    fn keep(&self, artifact: &Artifact) -> bool;
+
+    /// Decide about `artifact`, with a reason for `--explain`. The default
+    /// wraps `keep` and names the implementing type as the reason for a drop.
+    fn verdict(&self, artifact: &Artifact) -> Verdict {
+        if self.keep(artifact) {
+            Verdict::Keep(None)
+        } else {
+            Verdict::Drop(format!("rejected by {}", std::any::type_name::<Self>()))
+        }
+    }
+}
+
+impl<F> Filter for F
+where
+    F: Fn(&Artifact) -> bool + Send + Sync,
+{
+    fn keep(&self, artifact: &Artifact) -> bool {
+        self(artifact)
+    }
+}
+
+/// Filters applied in order; an artifact is kept only if every filter keeps it.
+#[derive(Default)]
+pub struct FilterChain {
+    filters: Vec<Box<dyn Filter>>,
+}
+
+impl FilterChain {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Append a filter to the end of the chain.
+    pub fn push(&mut self, filter: impl Filter + 'static) {
+        self.filters.push(Box::new(filter));
+    }
+
+    /// Keep the artifacts that every filter keeps, checking them in parallel.
+    pub fn apply(&self, artifacts: Vec<Artifact>) -> Vec<Artifact> {
+        artifacts
+            .into_par_iter()
+            .filter(|a| match self.verdict(a) {
+                Verdict::Keep(_) => true,
+                Verdict::Drop(reason) => {
+                    debug!("Filtered out: {} ({reason})", a.path.display());
+                    false
+                }
+            })
+            .collect()
+    }
+}
+
+impl Filter for FilterChain {
+    fn keep(&self, artifact: &Artifact) -> bool {
+        self.filters.iter().all(|f| f.keep(artifact))
+    }
+
+    /// The first filter's reason to drop, or every filter's reason to keep.
+    fn verdict(&self, artifact: &Artifact) -> Verdict {
+        let mut notes = Vec::new();
+        for filter in &self.filters {
+            match filter.verdict(artifact) {
+                Verdict::Keep(note) => notes.extend(note),
+                drop @ Verdict::Drop(_) => return drop,
+            }
+        }
+        Verdict::Keep((!notes.is_empty()).then(|| notes.join("; ")))
+    }
+}
+
+/// A pattern together with where it came from, e.g. `--exclude 'old-*'` or
+/// `'old-*' (excludes.txt:3)`.
+#[derive(Debug, Clone)]
+struct Pattern {
+    text: String,
+    label: String,
+}
+
+impl Pattern {
+    fn from_flag(flag: &str, text: &str) -> Self {
+        Self {
+            text: text.to_string(),
+            label: format!("{flag} '{text}'"),
+        }
+    }
+}
+
+/// The pattern that decided whether a path matches an `OrderedGlobs`.
+#[derive(Debug, Clone, Copy)]
+struct LastMatch<'a> {
+    label: &'a str,
+    negated: bool,
+}
+
+/// An ordered pattern list matched gitignore-style: the last pattern that
+/// matches a path decides, and a pattern written `!pattern` negates the match.
+/// A leading `\!` matches a literal `!`.
+#[derive(Debug)]
+struct OrderedGlobs {
+    set: GlobSet,
+    /// Index into `patterns` of each glob in `set`.
+    owners: Vec<usize>,
+    /// Label and negation of each pattern, in order.
+    patterns: Vec<(String, bool)>,
+}
+
+impl OrderedGlobs {
+    /// Compile `patterns`, turning each (after stripping any `!`) into the
+    /// globs returned by `expand`.
+    fn build(
+        patterns: &[Pattern],
+        expand: impl Fn(&str) -> Vec<String>,
+    ) -> Result<Self, globset::Error> {
+        let mut builder = GlobSetBuilder::new();
+        let mut owners = Vec::new();
+        let mut labels = Vec::new();
+        for (index, pat) in patterns.iter().enumerate() {
+            let text = pat.text.as_str();
+            let (negated, text) = match text.strip_prefix('!') {
+                Some(rest) => (true, rest),
+                None if text.starts_with("\\!") => (false, &text[1..]),
+                None => (false, text),
+            };
+            for glob in expand(text) {
+                builder.add(Glob::new(&glob)?);
+                owners.push(index);
+            }
+            labels.push((pat.label.clone(), negated));
+        }
+        Ok(Self {
+            set: builder.build()?,
+            owners,
+            patterns: labels,
+        })
+    }
+
+    /// Whether the list has patterns but every one is a negation, in which
+    /// case paths no pattern matches count as matched (as if the list started
+    /// with `**`).
+    fn only_negations(&self) -> bool {
+        !self.patterns.is_empty() && self.patterns.iter().all(|(_, negated)| *negated)
+    }
+
+    /// The last pattern matching `path`, if any.
+    fn last_match(&self, path: &Path) -> Option<LastMatch<'_>> {
+        let glob = *self.set.matches(path).iter().max()?;
+        let (label, negated) = &self.patterns[self.owners[glob]];
+        Some(LastMatch {
+            label,
+            negated: *negated,
+        })
+    }
+}
 
 /// Filters artifacts by include/exclude glob patterns.
 ///
@@ -18,20 +207,23 @@ pub struct PatternError(#[from] globset::Error);
 ///
 /// Patterns containing `/` are used as-is.
 ///
+/// Within the include list and the exclude list, the last matching pattern
+/// decides and `!PATTERN` negates, as in `.gitignore`.
+///
+/// Regexes (see `with_regexes`) are matched unanchored against the relative
+/// path with `/` separators, and combine with the globs: an artifact is
+/// included if any include glob or regex matches, and excluded if any exclude
+/// glob or regex matches.
+///
 /// Exclude takes precedence over include. If no includes are specified,
 /// all artifacts are included.
+#[derive(Debug)]
 pub struct ArtifactFilter {
-    includes: Option<GlobSet>,
-    excludes: GlobSet,
-}
-
-impl std::fmt::Debug for ArtifactFilter {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        f.debug_struct("ArtifactFilter")
-            .field("has_includes", &self.includes.is_some())
-            .field("excludes_len", &self.excludes.len())
-            .finish()
-    }
+    includes: Option<OrderedGlobs>,
+    excludes: OrderedGlobs,
+    include_regexes: Option<RegexSet>,
+    exclude_regexes: RegexSet,
+    root: Option<PathBuf>,
 }
 
 impl ArtifactFilter {
@@ -39,29 +231,137 @@ impl ArtifactFilter {
     pub fn new(
         include_patterns: &[String],
         exclude_patterns: &[String],
+    ) -> Result<Self, PatternError> {
+        let label = |flag: &str, patterns: &[String]| -> Vec<Pattern> {
+            patterns
+                .iter()
+                .map(|p| Pattern::from_flag(flag, p))
+                .collect()
+        };
+        Self::from_patterns(
+            label("--include", include_patterns),
+            label("--exclude", exclude_patterns),
+        )
+    }
+
+    fn from_patterns(
+        include_patterns: Vec<Pattern>,
+        exclude_patterns: Vec<Pattern>,
     ) -> Result<Self, PatternError> {
         let includes = if include_patterns.is_empty() {
             None
         } else {
-            Some(build_glob_set(include_patterns)?)
+            Some(OrderedGlobs::build(&include_patterns, path_globs)?)
         };
 
-        let excludes = build_glob_set(exclude_patterns)?;
+        let excludes = OrderedGlobs::build(&exclude_patterns, path_globs)?;
 
-        Ok(Self { includes, excludes })
+        Ok(Self {
+            includes,
+            excludes,
+            include_regexes: None,
+            exclude_regexes: RegexSet::empty(),
+            root: None,
+        })
     }
 
-    /// Test whether a single relative path matches the filter.
-    pub fn matches(&self, relative_path: &Path) -> bool {
-        if self.excludes.is_match(relative_path) {
-            return false;
+    /// Add include and exclude regexes to the filter.
+    pub fn with_regexes(
+        mut self,
+        include_regexes: &[String],
+        exclude_regexes: &[String],
+    ) -> Result<Self, PatternError> {
+        if !include_regexes.is_empty() {
+            self.include_regexes = Some(RegexSet::new(include_regexes)?);
+        }
+        self.exclude_regexes = RegexSet::new(exclude_regexes)?;
+        Ok(self)
+    }
+
+    /// Match artifact paths relative to `root` when used as a `Filter`.
+    /// Without a root, the full path is matched.
+    pub fn with_root(mut self, root: &Path) -> Self {
+        self.root = Some(root.to_path_buf());
+        self
+    }
+
+    /// Build a filter from CLI patterns merged with patterns read from files
+    /// (see `read_pattern_file`).
+    pub fn with_pattern_files(
+        include_patterns: &[String],
+        include_files: &[PathBuf],
+        exclude_patterns: &[String],
+        exclude_files: &[PathBuf],
+    ) -> Result<Self, PatternError> {
+        let merge = |flag: &str, patterns: &[String], files: &[PathBuf]| {
+            let mut merged: Vec<Pattern> = patterns
+                .iter()
+                .map(|p| Pattern::from_flag(flag, p))
+                .collect();
+            for file in files {
+                merged.extend(read_labeled_patterns(file)?);
+            }
+            Ok::<_, PatternError>(merged)
+        };
+        Self::from_patterns(
+            merge("--include", include_patterns, include_files)?,
+            merge("--exclude", exclude_patterns, exclude_files)?,
+        )
+    }
+
+    /// Decide whether to keep a single relative path, and why.
+    pub fn verdict(&self, relative_path: &Path) -> Verdict {
+        let path_str = relative_path.to_string_lossy().replace('\\', "/");
+        let spared = match self.excludes.last_match(relative_path) {
+            Some(m) if !m.negated => return Verdict::Drop(format!("excluded by {}", m.label)),
+            Some(m) => Some(format!("exclusion overridden by {}", m.label)),
+            None => None,
+        };
+        if let Some(re) = first_regex_match(&self.exclude_regexes, &path_str) {
+            return Verdict::Drop(format!("excluded by --exclude-regex '{re}'"));
         }
-        match &self.includes {
-            None => true,
-            Some(inc) => inc.is_match(relative_path),
+        match self.include_verdict(relative_path, &path_str) {
+            Verdict::Keep(note) => {
+                let notes: Vec<String> = spared.into_iter().chain(note).collect();
+                Verdict::Keep((!notes.is_empty()).then(|| notes.join(", ")))
+            }
+            drop => drop,
         }
     }
 
+    fn include_verdict(&self, relative_path: &Path, path_str: &str) -> Verdict {
+        if self.includes.is_none() && self.include_regexes.is_none() {
+            return Verdict::Keep(None);
+        }
+        let mut negated_by = None;
+        if let Some(includes) = &self.includes {
+            match includes.last_match(relative_path) {
+                Some(m) if !m.negated => {
+                    return Verdict::Keep(Some(format!("included by {}", m.label)));
+                }
+                Some(m) => negated_by = Some(m.label),
+                None if includes.only_negations() => return Verdict::Keep(None),
+                None => {}
+            }
+        }
+        if let Some(re) = self
+            .include_regexes
+            .as_ref()
+            .and_then(|set| first_regex_match(set, path_str))
+        {
+            return Verdict::Keep(Some(format!("included by --include-regex '{re}'")));
+        }
+        match negated_by {
+            Some(label) => Verdict::Drop(format!("excluded by {label}")),
+            None => Verdict::Drop("matched no include pattern".to_string()),
+        }
+    }
+
+    /// Test whether a single relative path matches the filter.
+    pub fn matches(&self, relative_path: &Path) -> bool {
+        self.verdict(relative_path).is_keep()
+    }
+
     /// Filter a list of artifacts, matching their paths relative to `root`.
     pub fn apply(&self, root: &Path, artifacts: Vec<Artifact>) -> Vec<Artifact> {
         let before = artifacts.len();
@@ -69,11 +369,13 @@ impl ArtifactFilter {
             .into_iter()
             .filter(|a| {
                 let rel = a.path.strip_prefix(root).unwrap_or(&a.path);
-                let matched = self.matches(rel);
-                if !matched {
-                    debug!("Filtered out: {}", rel.display());
+                match self.verdict(rel) {
+                    Verdict::Keep(_) => true,
+                    Verdict::Drop(reason) => {
+                        debug!("Filtered out: {} ({reason})", rel.display());
+                        false
+                    }
                 }
-                matched
             })
             .collect();
         let removed = before - filtered.len();
@@ -89,19 +391,213 @@ impl ArtifactFilter {
     }
 }
 
-/// Compile a list of pattern strings into a `GlobSet`, auto-enhancing bare
-/// patterns (those without `/`) into `**/PATTERN` and `**/PATTERN/**`.
-fn build_glob_set(patterns: &[String]) -> Result<GlobSet, globset::Error> {
-    let mut builder = GlobSetBuilder::new();
-    for pat in patterns {
-        if pat.contains('/') {
-            builder.add(Glob::new(pat)?);
+impl Filter for ArtifactFilter {
+    fn keep(&self, artifact: &Artifact) -> bool {
+        Filter::verdict(self, artifact).is_keep()
+    }
+
+    fn verdict(&self, artifact: &Artifact) -> Verdict {
+        let path = match &self.root {
+            Some(root) => artifact.path.strip_prefix(root).unwrap_or(&artifact.path),
+            None => &artifact.path,
+        };
+        ArtifactFilter::verdict(self, path)
+    }
+}
+
+/// Filters artifacts by the name of their project root (the directory holding
+/// the build system's marker), wherever it sits in the hierarchy.
+///
+/// Patterns are matched as-is against the bare directory name. Exclude takes
+/// precedence over include; with no includes, every project is included.
+#[derive(Debug)]
+pub struct ProjectFilter {
+    includes: Option<OrderedGlobs>,
+    excludes: OrderedGlobs,
+}
+
+impl ProjectFilter {
+    /// Build a filter from raw include and exclude project name globs.
+    pub fn new(
+        include_patterns: &[String],
+        exclude_patterns: &[String],
+    ) -> Result<Self, PatternError> {
+        let build = |flag: &str, patterns: &[String]| {
+            let patterns: Vec<Pattern> = patterns
+                .iter()
+                .map(|p| Pattern::from_flag(flag, p))
+                .collect();
+            OrderedGlobs::build(&patterns, |text| vec![text.to_string()])
+        };
+        let includes = if include_patterns.is_empty() {
+            None
         } else {
-            builder.add(Glob::new(&format!("**/{pat}"))?);
-            builder.add(Glob::new(&format!("**/{pat}/**"))?);
+            Some(build("--project", include_patterns)?)
+        };
+        Ok(Self {
+            includes,
+            excludes: build("--exclude-project", exclude_patterns)?,
+        })
+    }
+
+    /// Decide whether to keep artifacts of the project named `project_name`, and why.
+    pub fn verdict(&self, project_name: &str) -> Verdict {
+        let name = Path::new(project_name);
+        if let Some(m) = self.excludes.last_match(name).filter(|m| !m.negated) {
+            return Verdict::Drop(format!("project '{project_name}' excluded by {}", m.label));
+        }
+        let Some(includes) = &self.includes else {
+            return Verdict::Keep(None);
+        };
+        match includes.last_match(name) {
+            Some(m) if !m.negated => Verdict::Keep(Some(format!(
+                "project '{project_name}' included by {}",
+                m.label
+            ))),
+            Some(m) => Verdict::Drop(format!("project '{project_name}' excluded by {}", m.label)),
+            None if includes.only_negations() => Verdict::Keep(None),
+            None => Verdict::Drop(format!(
+                "project '{project_name}' matched no --project pattern"
+            )),
+        }
+    }
+
+    /// Test whether a project directory name matches the filter.
+    pub fn matches(&self, project_name: &str) -> bool {
+        self.verdict(project_name).is_keep()
+    }
+
+    /// Decide about an artifact by the name of its project root.
+    pub fn verdict_for(&self, artifact: &Artifact) -> Verdict {
+        let name = artifact.project.file_name().unwrap_or_default();
+        self.verdict(&name.to_string_lossy())
+    }
+
+    /// Filter a list of artifacts by the names of their project roots.
+    pub fn apply(&self, artifacts: Vec<Artifact>) -> Vec<Artifact> {
+        artifacts
+            .into_iter()
+            .filter(|a| match self.verdict_for(a) {
+                Verdict::Keep(_) => true,
+                Verdict::Drop(reason) => {
+                    debug!("Filtered out: {} ({reason})", a.path.display());
+                    false
+                }
+            })
+            .collect()
+    }
+}
+
+impl Filter for ProjectFilter {
+    fn keep(&self, artifact: &Artifact) -> bool {
+        self.verdict_for(artifact).is_keep()
+    }
+
+    fn verdict(&self, artifact: &Artifact) -> Verdict {
+        self.verdict_for(artifact)
+    }
+}
+
+/// Filters artifacts by depth below the scan root, counted in path
+/// components: `root/target` is at depth 1, `root/app/target` at depth 2.
+/// Both bounds are inclusive.
+#[derive(Debug)]
+pub struct DepthFilter {
+    root: PathBuf,
+    min: Option<usize>,
+    max: Option<usize>,
+}
+
+impl DepthFilter {
+    pub fn new(root: &Path, min: Option<usize>, max: Option<usize>) -> Self {
+        Self {
+            root: root.to_path_buf(),
+            min,
+            max,
+        }
+    }
+
+    /// Depth of `path` below the root. Paths outside the root count as depth 0.
+    pub fn depth(&self, path: &Path) -> usize {
+        path.strip_prefix(&self.root)
+            .map(|rel| rel.components().count())
+            .unwrap_or(0)
+    }
+}
+
+impl Filter for DepthFilter {
+    fn keep(&self, artifact: &Artifact) -> bool {
+        Filter::verdict(self, artifact).is_keep()
+    }
+
+    fn verdict(&self, artifact: &Artifact) -> Verdict {
+        let depth = self.depth(&artifact.path);
+        if let Some(min) = self.min.filter(|&min| depth < min) {
+            return Verdict::Drop(format!(
+                "at depth {depth}, shallower than --min-depth {min}"
+            ));
+        }
+        if let Some(max) = self.max.filter(|&max| depth > max) {
+            return Verdict::Drop(format!(
+                "at depth {depth}, deeper than --max-artifact-depth {max}"
+            ));
+        }
+        if self.min.is_none() && self.max.is_none() {
+            return Verdict::Keep(None);
         }
+        Verdict::Keep(Some(format!("at depth {depth}")))
+    }
+}
+
+/// The first regex in `set` that matches `text`.
+fn first_regex_match<'a>(set: &'a RegexSet, text: &str) -> Option<&'a str> {
+    let index = set.matches(text).iter().next()?;
+    Some(&set.patterns()[index])
+}
+
+/// Read glob patterns from a file: one per line, with blank lines and lines
+/// starting with `#` ignored. Surrounding whitespace is trimmed.
+pub fn read_pattern_file(path: &Path) -> Result<Vec<String>, PatternError> {
+    Ok(read_labeled_patterns(path)?
+        .into_iter()
+        .map(|p| p.text)
+        .collect())
+}
+
+/// Like `read_pattern_file`, labelling each pattern with its file and line.
+fn read_labeled_patterns(path: &Path) -> Result<Vec<Pattern>, PatternError> {
+    let text = std::fs::read_to_string(path).map_err(|source| PatternError::File {
+        path: path.to_path_buf(),
+        source,
+    })?;
+    Ok(parse_pattern_lines(&text)
+        .into_iter()
+        .map(|(line, text)| Pattern {
+            label: format!("'{text}' ({}:{line})", path.display()),
+            text,
+        })
+        .collect())
+}
+
+/// Non-comment lines of a pattern file, with 1-based line numbers.
+fn parse_pattern_lines(text: &str) -> Vec<(usize, String)> {
+    text.lines()
+        .enumerate()
+        .map(|(i, line)| (i + 1, line.trim()))
+        .filter(|(_, line)| !line.is_empty() && !line.starts_with('#'))
+        .map(|(i, line)| (i, line.to_owned()))
+        .collect()
+}
+
+/// Expand a path pattern into globs. Patterns without `/` match as a leaf
+/// (`**/PATTERN`) or ancestor (`**/PATTERN/**`) component; patterns with `/`
+/// are used as-is.
+fn path_globs(text: &str) -> Vec<String> {
+    if text.contains('/') {
+        vec![text.to_string()]
+    } else {
+        vec![format!("**/{text}"), format!("**/{text}/**")]
     }
-    builder.build()
 }
 
 #[cfg(test)]
@@ -117,15 +613,6 @@ mod tests {
         .unwrap()
     }
 
-    fn make_artifact(path: &str) -> Artifact {
-        Artifact {
-            path: PathBuf::from(path),
-            build_system: "Test",
-            artifact_dir: "target",
-            size_bytes: 0,
-        }
-    }
-
     #[test]
     fn no_patterns_matches_everything() {
         let f = filter(&[], &[]);
@@ -192,18 +679,165 @@ mod tests {
 
     #[test]
     fn invalid_pattern_returns_error() {
-        let result = ArtifactFilter::new(&[], &["[invalid".to_string()].to_vec());
+        let result = ArtifactFilter::new(&[], &["[invalid".to_string()]);
         assert!(result.is_err());
         let err = result.unwrap_err();
         assert!(err.to_string().contains("invalid filter pattern"));
     }
 
+    #[test]
+    fn pattern_lines_skip_comments_and_blanks() {
+        let patterns = parse_pattern_lines("# shared list\n\nwxyc*\n  old-*  \n#node_modules\n");
+        assert_eq!(
+            patterns,
+            vec![(3, "wxyc*".to_string()), (4, "old-*".to_string())]
+        );
+    }
+
+    #[test]
+    fn pattern_files_merge_with_cli_patterns() {
+        let tmp = tempfile::TempDir::new().unwrap();
+        let excludes = tmp.path().join("excludes.txt");
+        std::fs::write(&excludes, "# team list\nwxyc*\n").unwrap();
+        let f = ArtifactFilter::with_pattern_files(
+            &[],
+            &[],
+            &["old-*".to_string()],
+            std::slice::from_ref(&excludes),
+        )
+        .unwrap();
+        assert!(!f.matches(Path::new("wxyc-app/target")));
+        assert!(!f.matches(Path::new("old-app/target")));
+        assert!(f.matches(Path::new("my-app/target")));
+    }
+
+    #[test]
+    fn verdict_names_the_deciding_pattern() {
+        let tmp = tempfile::TempDir::new().unwrap();
+        let excludes = tmp.path().join("excludes.txt");
+        std::fs::write(&excludes, "# team list\nwxyc*\n").unwrap();
+        let f = ArtifactFilter::with_pattern_files(
+            &["target".to_string()],
+            &[],
+            &["old-*".to_string()],
+            std::slice::from_ref(&excludes),
+        )
+        .unwrap();
+        assert_eq!(
+            f.verdict(Path::new("old-app/target")),
+            Verdict::Drop("excluded by --exclude 'old-*'".to_string())
+        );
+        assert_eq!(
+            f.verdict(Path::new("wxyc-app/target")),
+            Verdict::Drop(format!("excluded by 'wxyc*' ({}:2)", excludes.display()))
+        );
+        assert_eq!(
+            f.verdict(Path::new("app/target")),
+            Verdict::Keep(Some("included by --include 'target'".to_string()))
+        );
+        assert_eq!(
+            f.verdict(Path::new("app/node_modules")),
+            Verdict::Drop("matched no include pattern".to_string())
+        );
+        assert_eq!(
+            filter(&[], &[]).verdict(Path::new("app/target")),
+            Verdict::Keep(None)
+        );
+    }
+
+    #[test]
+    fn missing_pattern_file_returns_error() {
+        let missing = PathBuf::from("/nonexistent/patterns.txt");
+        let err = ArtifactFilter::with_pattern_files(&[], &[missing], &[], &[]).unwrap_err();
+        assert!(err.to_string().contains("cannot read pattern file"));
+    }
+
+    fn regex_filter(includes: &[&str], excludes: &[&str]) -> ArtifactFilter {
+        filter(&[], &[])
+            .with_regexes(
+                &includes.iter().map(|s| s.to_string()).collect::<Vec<_>>(),
+                &excludes.iter().map(|s| s.to_string()).collect::<Vec<_>>(),
+            )
+            .unwrap()
+    }
+
+    #[test]
+    fn include_regex() {
+        let f = regex_filter(&[r"(^|/)JIRA-\d+/"], &[]);
+        assert!(f.matches(Path::new("work/JIRA-123/target")));
+        assert!(!f.matches(Path::new("work/JIRA-x/target")));
+    }
+
+    #[test]
+    fn exclude_regex_matches_date_buckets() {
+        let f = regex_filter(&[], &[r"^archive/\d{4}-\d{2}/"]);
+        assert!(!f.matches(Path::new("archive/2023-04/app/node_modules")));
+        assert!(f.matches(Path::new("archive/misc/app/node_modules")));
+    }
+
+    #[test]
+    fn regex_and_glob_includes_are_alternatives() {
+        let f = filter(&["node_modules"], &[])
+            .with_regexes(&[r"/target$".to_string()], &[])
+            .unwrap();
+        assert!(f.matches(Path::new("app/node_modules")));
+        assert!(f.matches(Path::new("app/target")));
+        assert!(!f.matches(Path::new("app/.venv")));
+    }
+
+    #[test]
+    fn exclude_regex_beats_include_glob() {
+        let f = filter(&["target"], &[])
+            .with_regexes(&[], &[r"^legacy".to_string()])
+            .unwrap();
+        assert!(!f.matches(Path::new("legacy-app/target")));
+        assert!(f.matches(Path::new("app/target")));
+    }
+
+    #[test]
+    fn invalid_regex_returns_error() {
+        let err = filter(&[], &[])
+            .with_regexes(&[], &["(unclosed".to_string()])
+            .unwrap_err();
+        assert!(err.to_string().contains("invalid filter regex"));
+    }
+
+    fn project_filter(includes: &[&str], excludes: &[&str]) -> ProjectFilter {
+        ProjectFilter::new(
+            &includes.iter().map(|s| s.to_string()).collect::<Vec<_>>(),
+            &excludes.iter().map(|s| s.to_string()).collect::<Vec<_>>(),
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn project_filter_matches_name_only() {
+        let f = project_filter(&["api-*"], &["api-legacy"]);
+        assert!(f.matches("api-gateway"));
+        assert!(!f.matches("api-legacy"));
+        assert!(!f.matches("web"));
+        assert!(project_filter(&[], &[]).matches("anything"));
+    }
+
+    #[test]
+    fn project_filter_ignores_depth() {
+        let f = project_filter(&[], &["shared"]);
+        let artifacts = vec![
+            Artifact::for_test("/root/shared/target", "Test"),
+            Artifact::for_test("/root/a/b/c/shared/target", "Test"),
+            Artifact::for_test("/root/shared/app/target", "Test"),
+        ];
+        let kept = f.apply(artifacts);
+        assert_eq!(kept.len(), 1);
+        assert_eq!(kept[0].path, PathBuf::from("/root/shared/app/target"));
+    }
+
     #[test]
     fn apply_strips_root_prefix() {
         let f = filter(&["node_modules"], &[]);
         let artifacts = vec![
-            make_artifact("/home/user/projects/app/node_modules"),
-            make_artifact("/home/user/projects/rust-app/target"),
+            Artifact::for_test("/home/user/projects/app/node_modules", "Test"),
+            Artifact::for_test("/home/user/projects/rust-app/target", "Test"),
         ];
         let root = Path::new("/home/user/projects");
         let filtered = f.apply(root, artifacts);
@@ -218,13 +852,124 @@ mod tests {
     fn apply_with_no_filters_keeps_all() {
         let f = filter(&[], &[]);
         let artifacts = vec![
-            make_artifact("/root/a/target"),
-            make_artifact("/root/b/node_modules"),
+            Artifact::for_test("/root/a/target", "Test"),
+            Artifact::for_test("/root/b/node_modules", "Test"),
         ];
         let filtered = f.apply(Path::new("/root"), artifacts);
         assert_eq!(filtered.len(), 2);
     }
 
+    #[test]
+    fn negated_include_carves_out_of_earlier_include() {
+        let f = filter(&["target", "!critical-service/**"], &[]);
+        assert!(f.matches(Path::new("app/target")));
+        assert!(!f.matches(Path::new("critical-service/target")));
+        assert_eq!(
+            f.verdict(Path::new("critical-service/target")),
+            Verdict::Drop("excluded by --include '!critical-service/**'".to_string())
+        );
+    }
+
+    #[test]
+    fn later_pattern_wins() {
+        let f = filter(&["!critical-service/**", "target"], &[]);
+        assert!(f.matches(Path::new("critical-service/target")));
+    }
+
+    #[test]
+    fn only_negated_includes_start_from_everything() {
+        let f = filter(&["!vendor"], &[]);
+        assert!(f.matches(Path::new("app/target")));
+        assert!(!f.matches(Path::new("vendor/lib/target")));
+    }
+
+    #[test]
+    fn negated_exclude_spares_a_path() {
+        let f = filter(&[], &["old-*", "!old-but-gold"]);
+        assert!(!f.matches(Path::new("old-app/target")));
+        assert_eq!(
+            f.verdict(Path::new("old-but-gold/target")),
+            Verdict::Keep(Some(
+                "exclusion overridden by --exclude '!old-but-gold'".to_string()
+            ))
+        );
+    }
+
+    #[test]
+    fn escaped_bang_is_literal() {
+        let f = filter(&[], &["\\!important"]);
+        assert!(!f.matches(Path::new("!important/target")));
+        assert!(f.matches(Path::new("important/target")));
+    }
+
+    #[test]
+    fn negated_exclude_in_pattern_file() {
+        let tmp = tempfile::TempDir::new().unwrap();
+        let excludes = tmp.path().join("excludes.txt");
+        std::fs::write(&excludes, "wxyc*\n!wxyc-keep\n").unwrap();
+        let f = ArtifactFilter::with_pattern_files(&[], &[], &[], &[excludes]).unwrap();
+        assert!(!f.matches(Path::new("wxyc-app/target")));
+        assert!(f.matches(Path::new("wxyc-keep/target")));
+    }
+
+    #[test]
+    fn project_filter_negation() {
+        let f = project_filter(&["api-*", "!api-legacy"], &[]);
+        assert!(f.matches("api-gateway"));
+        assert!(!f.matches("api-legacy"));
+    }
+
+    #[test]
+    fn depth_filter_counts_components_below_root() {
+        let f = DepthFilter::new(Path::new("/root"), Some(2), Some(3));
+        assert_eq!(f.depth(Path::new("/root/app/target")), 2);
+        assert!(!f.keep(&Artifact::for_test("/root/target", "Test")));
+        assert!(f.keep(&Artifact::for_test("/root/app/target", "Test")));
+        assert!(f.keep(&Artifact::for_test("/root/group/app/target", "Test")));
+        assert_eq!(
+            Filter::verdict(
+                &f,
+                &Artifact::for_test("/root/app/vendor/lib/target", "Test")
+            ),
+            Verdict::Drop("at depth 4, deeper than --max-artifact-depth 3".to_string())
+        );
+        assert_eq!(
+            Filter::verdict(&f, &Artifact::for_test("/root/target", "Test")),
+            Verdict::Drop("at depth 1, shallower than --min-depth 2".to_string())
+        );
+    }
+
+    #[test]
+    fn chain_combines_builtin_and_custom_filters() {
+        let mut chain = FilterChain::new();
+        chain.push(filter(&["target"], &[]).with_root(Path::new("/root")));
+        chain.push(|a: &Artifact| !a.path.starts_with("/root/critical"));
+        let kept = chain.apply(vec![
+            Artifact::for_test("/root/app/target", "Test"),
+            Artifact::for_test("/root/critical/target", "Test"),
+            Artifact::for_test("/root/app/node_modules", "Test"),
+        ]);
+        assert_eq!(kept.len(), 1);
+        assert_eq!(kept[0].path, PathBuf::from("/root/app/target"));
+        assert!(chain.keep(&Artifact::for_test("/root/app/target", "Test")));
+    }
+
+    #[test]
+    fn chain_verdict_reports_first_drop() {
+        let mut chain = FilterChain::new();
+        chain.push(filter(&["target"], &[]).with_root(Path::new("/root")));
+        chain.push(|_: &Artifact| false);
+        chain.push(filter(&[], &["critical"]).with_root(Path::new("/root")));
+        match chain.verdict(&Artifact::for_test("/root/critical/target", "Test")) {
+            Verdict::Drop(reason) => assert!(reason.starts_with("rejected by "), "{reason}"),
+            keep => panic!("expected drop, got {keep:?}"),
+        }
+        assert_eq!(
+            FilterChain::new().verdict(&Artifact::for_test("/root/app/target", "Test")),
+            Verdict::Keep(None)
+        );
+    }
+
     #[test]
     fn multiple_include_patterns() {
         let f = filter(&["node_modules", "target"], &[]);
@@ -0,0 +1,237 @@
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use log::{debug, warn};
+use rayon::prelude::*;
+
+use crate::filter::{Filter, Verdict};
+use crate::scanner::Artifact;
+
+/// Error from running `git` against a repository.
+#[derive(thiserror::Error, Debug)]
+pub enum GitError {
+    #[error("failed to run git: {0}")]
+    Spawn(#[from] std::io::Error),
+    #[error("git {args} failed in {repo}: {stderr}")]
+    Failed {
+        repo: PathBuf,
+        args: String,
+        stderr: String,
+    },
+}
+
+/// Return the nearest directory at or above `path` that contains `.git`
+/// (a directory, or a file for worktrees and submodules).
+pub fn repo_root(path: &Path) -> Option<&Path> {
+    path.ancestors().find(|dir| dir.join(".git").exists())
+}
+
+/// Whether a repository has work that exists only locally: commits on any
+/// branch that no remote-tracking branch contains, or stash entries.
+///
+/// A repository without remotes counts as unpushed as long as it has commits.
+pub fn has_unpushed_work(repo: &Path) -> Result<bool, GitError> {
+    let unpushed = git(
+        repo,
+        &["log", "--branches", "--not", "--remotes", "--oneline", "-1"],
+    )?;
+    if !unpushed.trim().is_empty() {
+        debug!("{}: unpushed commits", repo.display());
+        return Ok(true);
+    }
+    let stashes = git(repo, &["stash", "list", "-1"])?;
+    if !stashes.trim().is_empty() {
+        debug!("{}: stash entries", repo.display());
+        return Ok(true);
+    }
+    Ok(false)
+}
+
+/// Drop artifacts that live in a Git repository with unpushed commits or
+/// stashes. Artifacts outside any repository are kept. A repository whose
+/// state cannot be determined is treated as unpushed.
+pub fn skip_unpushed(artifacts: Vec<Artifact>) -> Vec<Artifact> {
+    let repos = UnpushedRepos::check(&artifacts);
+    artifacts
+        .into_iter()
+        .filter(|a| match Filter::verdict(&repos, a) {
+            Verdict::Keep(_) => true,
+            Verdict::Drop(reason) => {
+                debug!("Filtered out: {} ({reason})", a.path.display());
+                false
+            }
+        })
+        .collect()
+}
+
+/// The repositories containing a set of artifacts, checked once each for
+/// unpushed work (`--skip-unpushed`).
+#[derive(Debug)]
+pub struct UnpushedRepos {
+    unpushed: HashMap<PathBuf, bool>,
+}
+
+impl UnpushedRepos {
+    /// Check every repository containing one of `artifacts`, in parallel.
+    pub fn check(artifacts: &[Artifact]) -> Self {
+        let mut repos: Vec<&Path> = artifacts
+            .iter()
+            .filter_map(|a| repo_root(a.path.parent()?))
+            .collect();
+        repos.sort();
+        repos.dedup();
+
+        let unpushed = repos
+            .into_par_iter()
+            .map(|repo| {
+                let unpushed = has_unpushed_work(repo).unwrap_or_else(|e| {
+                    warn!("Cannot check {} for unpushed work: {e}", repo.display());
+                    true
+                });
+                (repo.to_path_buf(), unpushed)
+            })
+            .collect();
+        Self { unpushed }
+    }
+}
+
+impl Filter for UnpushedRepos {
+    fn keep(&self, artifact: &Artifact) -> bool {
+        Filter::verdict(self, artifact).is_keep()
+    }
+
+    /// Drop an artifact whose repository has unpushed work or was not checked.
+    fn verdict(&self, artifact: &Artifact) -> Verdict {
+        let Some(repo) = artifact.path.parent().and_then(repo_root) else {
+            return Verdict::Keep(None);
+        };
+        if self.unpushed.get(repo).copied().unwrap_or(true) {
+            Verdict::Drop(format!(
+                "unpushed commits or stashes in {} (--skip-unpushed)",
+                repo.display()
+            ))
+        } else {
+            Verdict::Keep(Some(format!("{} is fully pushed", repo.display())))
+        }
+    }
+}
+
+/// Run `git -C repo <args>` and return its stdout.
+fn git(repo: &Path, args: &[&str]) -> Result<String, GitError> {
+    let output = Command::new("git")
+        .arg("-C")
+        .arg(repo)
+        .args(args)
+        .output()?;
+    if !output.status.success() {
+        return Err(GitError::Failed {
+            repo: repo.to_path_buf(),
+            args: args.join(" "),
+            stderr: String::from_utf8_lossy(&output.stderr).trim().to_string(),
+        });
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use tempfile::TempDir;
+
+    /// Run git in `dir` with a fixed identity, panicking on failure.
+    fn run(dir: &Path, args: &[&str]) {
+        let out = Command::new("git")
+            .arg("-C")
+            .arg(dir)
+            .args(["-c", "user.name=Test", "-c", "user.email=test@example.com"])
+            .args(args)
+            .output()
+            .unwrap();
+        assert!(out.status.success(), "git {args:?}: {out:?}");
+    }
+
+    /// Create a repo at `dir` with one commit, pushed to a bare remote.
+    fn pushed_repo(tmp: &TempDir, name: &str) -> PathBuf {
+        let remote = tmp.path().join(format!("{name}.git"));
+        let repo = tmp.path().join(name);
+        fs::create_dir_all(&repo).unwrap();
+        run(
+            tmp.path(),
+            &["init", "-q", "--bare", remote.to_str().unwrap()],
+        );
+        run(&repo, &["init", "-q"]);
+        fs::write(repo.join("Cargo.toml"), "").unwrap();
+        run(&repo, &["add", "Cargo.toml"]);
+        run(&repo, &["commit", "-q", "-m", "init"]);
+        run(
+            &repo,
+            &["remote", "add", "origin", remote.to_str().unwrap()],
+        );
+        run(&repo, &["push", "-q", "origin", "HEAD"]);
+        repo
+    }
+
+    #[test]
+    fn repo_root_finds_nearest_git_dir() {
+        let tmp = TempDir::new().unwrap();
+        let repo = tmp.path().join("repo");
+        fs::create_dir_all(repo.join(".git")).unwrap();
+        fs::create_dir_all(repo.join("crates/a")).unwrap();
+        assert_eq!(repo_root(&repo.join("crates/a")), Some(repo.as_path()));
+        assert_eq!(repo_root(tmp.path()), None);
+    }
+
+    #[test]
+    fn pushed_repo_is_clean() {
+        let tmp = TempDir::new().unwrap();
+        let repo = pushed_repo(&tmp, "app");
+        assert!(!has_unpushed_work(&repo).unwrap());
+    }
+
+    #[test]
+    fn local_commit_is_unpushed() {
+        let tmp = TempDir::new().unwrap();
+        let repo = pushed_repo(&tmp, "app");
+        run(&repo, &["commit", "-q", "--allow-empty", "-m", "wip"]);
+        assert!(has_unpushed_work(&repo).unwrap());
+    }
+
+    #[test]
+    fn unpushed_branch_counts() {
+        let tmp = TempDir::new().unwrap();
+        let repo = pushed_repo(&tmp, "app");
+        run(&repo, &["checkout", "-q", "-b", "feature"]);
+        run(&repo, &["commit", "-q", "--allow-empty", "-m", "wip"]);
+        run(&repo, &["checkout", "-q", "-"]);
+        assert!(has_unpushed_work(&repo).unwrap());
+    }
+
+    #[test]
+    fn stash_is_unpushed() {
+        let tmp = TempDir::new().unwrap();
+        let repo = pushed_repo(&tmp, "app");
+        fs::write(repo.join("Cargo.toml"), "[package]").unwrap();
+        run(&repo, &["stash", "-q"]);
+        assert!(has_unpushed_work(&repo).unwrap());
+    }
+
+    #[test]
+    fn skip_unpushed_keeps_clean_and_non_repo_artifacts() {
+        let tmp = TempDir::new().unwrap();
+        let clean = pushed_repo(&tmp, "clean");
+        let dirty = pushed_repo(&tmp, "dirty");
+        run(&dirty, &["commit", "-q", "--allow-empty", "-m", "wip"]);
+        let loose = tmp.path().join("loose");
+        fs::create_dir_all(&loose).unwrap();
+
+        let kept = skip_unpushed(vec![
+            Artifact::for_test(clean.join("target"), "Rust/Cargo"),
+            Artifact::for_test(dirty.join("target"), "Rust/Cargo"),
+            Artifact::for_test(loose.join("target"), "Rust/Cargo"),
+        ]);
+        let paths: Vec<_> = kept.iter().map(|a| a.path.clone()).collect();
+        assert_eq!(paths, [clean.join("target"), loose.join("target")]);
+    }
+}
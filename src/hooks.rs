@@ -0,0 +1,202 @@
+use std::process::{Command, ExitStatus, Stdio};
+
+use log::debug;
+
+use crate::audit::Outcome;
+use crate::scanner::Artifact;
+
+/// Error from a hook command that could not run or exited unsuccessfully.
+#[derive(thiserror::Error, Debug)]
+pub enum HookError {
+    #[error("cannot run {hook} hook '{command}': {source}")]
+    Spawn {
+        hook: &'static str,
+        command: String,
+        source: std::io::Error,
+    },
+    #[error("{hook} hook '{command}' failed ({status})")]
+    Failed {
+        hook: &'static str,
+        command: String,
+        status: ExitStatus,
+    },
+}
+
+/// Shell commands run around a `--delete` run (`--before-run`,
+/// `--after-run`) and around each artifact (`--before-delete`,
+/// `--after-delete`). Details are passed in `CLEAN_BUILDS_HOOK_*`
+/// environment variables, apart from the `CLEAN_BUILDS_*` option defaults so
+/// that a `clean-builds` run from a hook does not read them as options.
+#[derive(Debug, Clone, Default)]
+pub struct Hooks {
+    pub before_run: Option<String>,
+    pub after_run: Option<String>,
+    pub before_delete: Option<String>,
+    pub after_delete: Option<String>,
+}
+
+impl Hooks {
+    /// Run the `--before-run` hook with the number and total size of the
+    /// artifacts about to be removed.
+    pub fn before_run(&self, artifacts: &[Artifact]) -> Result<(), HookError> {
+        let Some(command) = &self.before_run else {
+            return Ok(());
+        };
+        run("before-run", command, &run_env(artifacts))
+    }
+
+    /// Run the `--after-run` hook, adding how many artifacts were removed.
+    pub fn after_run(&self, artifacts: &[Artifact], removed: usize) -> Result<(), HookError> {
+        let Some(command) = &self.after_run else {
+            return Ok(());
+        };
+        let mut env = run_env(artifacts);
+        env.push(("CLEAN_BUILDS_HOOK_REMOVED", removed.to_string()));
+        run("after-run", command, &env)
+    }
+
+    /// Run the `--before-delete` hook for `artifact`. An error means the
+    /// artifact should be left alone.
+    pub fn before_delete(&self, artifact: &Artifact) -> Result<(), HookError> {
+        let Some(command) = &self.before_delete else {
+            return Ok(());
+        };
+        run("before-delete", command, &artifact_env(artifact))
+    }
+
+    /// Run the `--after-delete` hook for `artifact`, adding what happened to it.
+    pub fn after_delete(&self, artifact: &Artifact, outcome: &Outcome) -> Result<(), HookError> {
+        let Some(command) = &self.after_delete else {
+            return Ok(());
+        };
+        let mut env = artifact_env(artifact);
+        env.push(("CLEAN_BUILDS_HOOK_OUTCOME", outcome.label().to_string()));
+        if let Some(error) = outcome.error() {
+            env.push(("CLEAN_BUILDS_HOOK_ERROR", error.to_string()));
+        }
+        run("after-delete", command, &env)
+    }
+}
+
+fn run_env(artifacts: &[Artifact]) -> Vec<(&'static str, String)> {
+    let size: u64 = artifacts.iter().map(|a| a.size_bytes).sum();
+    vec![
+        ("CLEAN_BUILDS_HOOK_COUNT", artifacts.len().to_string()),
+        ("CLEAN_BUILDS_HOOK_SIZE", size.to_string()),
+    ]
+}
+
+fn artifact_env(artifact: &Artifact) -> Vec<(&'static str, String)> {
+    vec![
+        (
+            "CLEAN_BUILDS_HOOK_PATH",
+            artifact.path.display().to_string(),
+        ),
+        (
+            "CLEAN_BUILDS_HOOK_PROJECT",
+            artifact.project.display().to_string(),
+        ),
+        (
+            "CLEAN_BUILDS_HOOK_SYSTEM",
+            artifact.build_system.to_string(),
+        ),
+        ("CLEAN_BUILDS_HOOK_SIZE", artifact.size_bytes.to_string()),
+    ]
+}
+
+/// Run `command` through the platform shell with `env` added, letting it
+/// write to the terminal.
+fn run(hook: &'static str, command: &str, env: &[(&str, String)]) -> Result<(), HookError> {
+    debug!("Running {hook} hook: {command}");
+    let mut shell = if cfg!(windows) {
+        let mut shell = Command::new("cmd");
+        shell.arg("/C");
+        shell
+    } else {
+        let mut shell = Command::new("sh");
+        shell.arg("-c");
+        shell
+    };
+    let status = shell
+        .arg(command)
+        .envs(env.iter().map(|(k, v)| (k, v)))
+        .stdin(Stdio::null())
+        .status()
+        .map_err(|source| HookError::Spawn {
+            hook,
+            command: command.to_string(),
+            source,
+        })?;
+    if status.success() {
+        Ok(())
+    } else {
+        Err(HookError::Failed {
+            hook,
+            command: command.to_string(),
+            status,
+        })
+    }
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    fn artifact() -> Artifact {
+        Artifact {
+            size_bytes: 42,
+            ..Artifact::for_test("/dev/app/target", "Rust/Cargo")
+        }
+    }
+
+    #[test]
+    fn after_delete_sees_artifact_and_outcome() {
+        let tmp = TempDir::new().unwrap();
+        let log = tmp.path().join("hook.log");
+        let hooks = Hooks {
+            after_delete: Some(format!(
+                "echo \"$CLEAN_BUILDS_HOOK_PATH $CLEAN_BUILDS_HOOK_SYSTEM $CLEAN_BUILDS_HOOK_SIZE $CLEAN_BUILDS_HOOK_OUTCOME\" > {}",
+                log.display()
+            )),
+            ..Default::default()
+        };
+
+        hooks.after_delete(&artifact(), &Outcome::Deleted).unwrap();
+
+        let written = std::fs::read_to_string(&log).unwrap();
+        assert_eq!(written, "/dev/app/target Rust/Cargo 42 deleted\n");
+    }
+
+    #[test]
+    fn hook_variables_are_not_option_defaults() {
+        use clap::CommandFactory;
+
+        let hook_vars = run_env(&[artifact()])
+            .into_iter()
+            .chain(artifact_env(&artifact()));
+        for (name, _) in hook_vars {
+            assert!(name.starts_with("CLEAN_BUILDS_HOOK_"), "{name}");
+        }
+        for arg in crate::cli::Cli::command().get_arguments() {
+            if let Some(env) = arg.get_env() {
+                let env = env.to_string_lossy();
+                assert!(!env.starts_with("CLEAN_BUILDS_HOOK_"), "{env}");
+            }
+        }
+    }
+
+    #[test]
+    fn failing_hook_is_an_error() {
+        let hooks = Hooks {
+            before_delete: Some("exit 3".to_string()),
+            ..Default::default()
+        };
+        let err = hooks.before_delete(&artifact()).unwrap_err();
+        assert!(
+            err.to_string()
+                .starts_with("before-delete hook 'exit 3' failed")
+        );
+        assert!(Hooks::default().before_delete(&artifact()).is_ok());
+    }
+}
@@ -0,0 +1,360 @@
+use std::path::{Path, PathBuf};
+
+use log::{debug, warn};
+
+use crate::filter::{Filter, Verdict};
+use crate::scanner::Artifact;
+
+/// A directory a running process is working in.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ProcessDir {
+    pub pid: u32,
+    pub command: String,
+    pub dir: PathBuf,
+    /// Whether `dir` was named on the command line (e.g., `code ~/app`)
+    /// rather than being the working directory. A workspace covers every
+    /// project inside it; a working directory only the project containing it.
+    pub workspace: bool,
+}
+
+impl ProcessDir {
+    /// Whether this process is using `project`.
+    pub fn uses(&self, project: &Path) -> bool {
+        self.dir.starts_with(project) || (self.workspace && project.starts_with(&self.dir))
+    }
+}
+
+/// Directories used by every running process this user can inspect, except
+/// this process and its parent (the shell that launched it).
+pub fn process_dirs() -> Vec<ProcessDir> {
+    let own = [std::process::id(), parent_id()];
+    let mut dirs = list_process_dirs();
+    dirs.retain(|d| !own.contains(&d.pid));
+    debug!("Found {} process directories", dirs.len());
+    dirs
+}
+
+#[cfg(unix)]
+fn parent_id() -> u32 {
+    std::os::unix::process::parent_id()
+}
+
+#[cfg(not(unix))]
+fn parent_id() -> u32 {
+    0
+}
+
+/// Read working directories and directory arguments from `/proc`.
+#[cfg(target_os = "linux")]
+fn list_process_dirs() -> Vec<ProcessDir> {
+    let Ok(entries) = std::fs::read_dir("/proc") else {
+        warn!("Cannot read /proc; not checking for projects in use");
+        return Vec::new();
+    };
+    let mut dirs = Vec::new();
+    for entry in entries.flatten() {
+        let Some(pid) = entry.file_name().to_str().and_then(|s| s.parse().ok()) else {
+            continue;
+        };
+        let proc_dir = entry.path();
+        // Processes of other users are unreadable; skip them quietly.
+        let Ok(cwd) = std::fs::read_link(proc_dir.join("cwd")) else {
+            continue;
+        };
+        let command = std::fs::read_to_string(proc_dir.join("comm"))
+            .unwrap_or_default()
+            .trim()
+            .to_string();
+        let cmdline = std::fs::read(proc_dir.join("cmdline")).unwrap_or_default();
+        for arg in cmdline.split(|&b| b == 0).skip(1) {
+            let arg = Path::new(std::str::from_utf8(arg).unwrap_or_default());
+            if arg.is_absolute() && arg != Path::new("/") && arg.is_dir() {
+                dirs.push(ProcessDir {
+                    pid,
+                    command: command.clone(),
+                    dir: arg.to_path_buf(),
+                    workspace: true,
+                });
+            }
+        }
+        dirs.push(ProcessDir {
+            pid,
+            command,
+            dir: cwd,
+            workspace: false,
+        });
+    }
+    dirs
+}
+
+/// Ask `lsof` for the working directory of every process.
+#[cfg(all(unix, not(target_os = "linux")))]
+fn list_process_dirs() -> Vec<ProcessDir> {
+    match std::process::Command::new("lsof")
+        .args(["-a", "-d", "cwd", "-F", "pcn"])
+        .output()
+    {
+        Ok(output) => parse_lsof(&String::from_utf8_lossy(&output.stdout)),
+        Err(e) => {
+            warn!("Cannot run lsof ({e}); not checking for projects in use");
+            Vec::new()
+        }
+    }
+}
+
+#[cfg(not(unix))]
+fn list_process_dirs() -> Vec<ProcessDir> {
+    warn!("Checking for projects in use is not supported on this platform");
+    Vec::new()
+}
+
+/// Parse `lsof -F pcn` output: a `p<pid>` line starts each process, followed
+/// by `c<command>` and one `n<path>` line per matching file descriptor.
+#[cfg_attr(target_os = "linux", allow(dead_code))]
+fn parse_lsof(output: &str) -> Vec<ProcessDir> {
+    let mut dirs = Vec::new();
+    let mut pid = 0;
+    let mut command = String::new();
+    for line in output.lines() {
+        let Some((field, value)) = line.split_at_checked(1) else {
+            continue;
+        };
+        match field {
+            "p" => {
+                pid = value.parse().unwrap_or(0);
+                command.clear();
+            }
+            "c" => command = value.to_string(),
+            "n" => dirs.push(ProcessDir {
+                pid,
+                command: command.clone(),
+                dir: PathBuf::from(value),
+                workspace: false,
+            }),
+            _ => {}
+        }
+    }
+    dirs
+}
+
+/// Projects in use by running processes (`--skip-in-use`).
+#[derive(Debug)]
+pub struct InUseProjects {
+    dirs: Vec<ProcessDir>,
+}
+
+impl InUseProjects {
+    /// Snapshot the directories of running processes.
+    pub fn detect() -> Self {
+        Self {
+            dirs: process_dirs(),
+        }
+    }
+}
+
+impl InUseProjects {
+    /// Why `artifact` looks in use right now, if it does: a process's working
+    /// directory is in its project, or a build holds one of its lock files.
+    ///
+    /// Unlike `--skip-in-use`, directories named on a command line are not
+    /// counted, so an editor open on a whole workspace does not block every
+    /// project inside it.
+    pub fn reason(&self, artifact: &Artifact) -> Option<String> {
+        if let Some(d) = self
+            .dirs
+            .iter()
+            .find(|d| !d.workspace && d.uses(&artifact.project))
+        {
+            return Some(describe(d));
+        }
+        held_lock(&artifact.path)
+            .map(|lock| format!("{} is locked by a running build", lock.display()))
+    }
+
+    fn user(&self, project: &Path) -> Option<&ProcessDir> {
+        self.dirs.iter().find(|d| d.uses(project))
+    }
+}
+
+fn describe(d: &ProcessDir) -> String {
+    format!(
+        "project in use by {} (pid {}) in {}",
+        d.command,
+        d.pid,
+        d.dir.display()
+    )
+}
+
+impl Filter for InUseProjects {
+    fn keep(&self, artifact: &Artifact) -> bool {
+        Filter::verdict(self, artifact).is_keep()
+    }
+
+    /// Drop artifacts whose project a process is working in.
+    fn verdict(&self, artifact: &Artifact) -> Verdict {
+        match self.user(&artifact.project) {
+            Some(d) => Verdict::Drop(format!("{} (--skip-in-use)", describe(d))),
+            None => Verdict::Keep(None),
+        }
+    }
+}
+
+/// How deep below an artifact to look for lock files. Cargo's are two
+/// levels down (`target/debug/.cargo-lock`), Gradle's three
+/// (`.gradle/8.5/fileHashes/fileHashes.lock`).
+const LOCK_DEPTH: usize = 3;
+
+/// Stop looking for lock files after this many directory entries, so huge
+/// artifacts such as `node_modules` are not walked.
+const LOCK_SCAN_LIMIT: usize = 10_000;
+
+/// A lock file inside `artifact` that another process holds right now.
+///
+/// Lock files are those named `.cargo-lock` or ending in `.lock`; one is
+/// held if another process has an advisory lock on it (`flock` or `fcntl`).
+pub fn held_lock(artifact: &Path) -> Option<PathBuf> {
+    let mut dirs = vec![(artifact.to_path_buf(), 0)];
+    let mut seen = 0;
+    while let Some((dir, depth)) = dirs.pop() {
+        let Ok(entries) = std::fs::read_dir(&dir) else {
+            continue;
+        };
+        for entry in entries.flatten() {
+            seen += 1;
+            if seen > LOCK_SCAN_LIMIT {
+                debug!("Stopped looking for lock files in {}", artifact.display());
+                return None;
+            }
+            let Ok(file_type) = entry.file_type() else {
+                continue;
+            };
+            let path = entry.path();
+            if file_type.is_dir() {
+                if depth + 1 < LOCK_DEPTH {
+                    dirs.push((path, depth + 1));
+                }
+            } else if file_type.is_file() && is_lock_file(&path) && is_locked(&path) {
+                return Some(path);
+            }
+        }
+    }
+    None
+}
+
+fn is_lock_file(path: &Path) -> bool {
+    path.file_name().is_some_and(|n| n == ".cargo-lock")
+        || path.extension().is_some_and(|e| e == "lock")
+}
+
+/// Whether another process holds an advisory lock on `path`.
+#[cfg(unix)]
+fn is_locked(path: &Path) -> bool {
+    use std::os::fd::AsRawFd;
+
+    let Ok(file) = std::fs::File::open(path) else {
+        return false;
+    };
+    let fd = file.as_raw_fd();
+    // SAFETY: `fd` is a valid descriptor owned by `file` for this whole block,
+    // and the lock taken here is released when `file` is closed.
+    let flocked = unsafe { libc::flock(fd, libc::LOCK_SH | libc::LOCK_NB) } != 0
+        && std::io::Error::last_os_error().kind() == std::io::ErrorKind::WouldBlock;
+    if flocked {
+        return true;
+    }
+    // SAFETY: an all-zero `flock` struct is valid; F_GETLK only fills it in.
+    let mut query: libc::flock = unsafe { std::mem::zeroed() };
+    query.l_type = libc::F_WRLCK as _;
+    query.l_whence = libc::SEEK_SET as _;
+    // SAFETY: `query` is a valid, exclusively borrowed `flock` struct.
+    let rc = unsafe { libc::fcntl(fd, libc::F_GETLK, &mut query) };
+    rc == 0 && query.l_type != libc::F_UNLCK as libc::c_short
+}
+
+#[cfg(not(unix))]
+fn is_locked(_path: &Path) -> bool {
+    false
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn working_dir_uses_only_enclosing_project() {
+        let d = ProcessDir {
+            pid: 1,
+            command: "node".to_string(),
+            dir: PathBuf::from("/dev/web/src"),
+            workspace: false,
+        };
+        assert!(d.uses(Path::new("/dev/web")));
+        assert!(!d.uses(Path::new("/dev/web/src/packages/ui")));
+        assert!(!d.uses(Path::new("/dev/api")));
+    }
+
+    #[test]
+    fn workspace_uses_nested_projects() {
+        let d = ProcessDir {
+            pid: 1,
+            command: "code".to_string(),
+            dir: PathBuf::from("/dev/monorepo"),
+            workspace: true,
+        };
+        assert!(d.uses(Path::new("/dev/monorepo/packages/ui")));
+        assert!(!d.uses(Path::new("/dev/other")));
+    }
+
+    #[test]
+    fn parse_lsof_fields() {
+        let dirs = parse_lsof("p10\ncnode\nn/dev/web\np11\ncjava\nn/dev/api\n");
+        assert_eq!(dirs.len(), 2);
+        assert_eq!(dirs[0].pid, 10);
+        assert_eq!(dirs[0].command, "node");
+        assert_eq!(dirs[1].dir, PathBuf::from("/dev/api"));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn held_lock_finds_locked_cargo_lock() {
+        use std::os::fd::AsRawFd;
+
+        let tmp = tempfile::TempDir::new().unwrap();
+        let debug = tmp.path().join("target/debug");
+        std::fs::create_dir_all(&debug).unwrap();
+        let lock = debug.join(".cargo-lock");
+        std::fs::write(&lock, "").unwrap();
+        std::fs::write(debug.join("Cargo.lock.bak"), "").unwrap();
+        assert_eq!(held_lock(&tmp.path().join("target")), None);
+
+        // flock locks belong to the open file, so a second open conflicts.
+        let holder = std::fs::File::open(&lock).unwrap();
+        assert_eq!(unsafe { libc::flock(holder.as_raw_fd(), libc::LOCK_EX) }, 0);
+        assert_eq!(held_lock(&tmp.path().join("target")), Some(lock));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn detects_child_process_working_dir() {
+        let tmp = tempfile::TempDir::new().unwrap();
+        let project = tmp.path().canonicalize().unwrap();
+        let mut child = std::process::Command::new("sleep")
+            .arg("5")
+            .current_dir(&project)
+            .spawn()
+            .unwrap();
+
+        let in_use = InUseProjects::detect();
+        child.kill().unwrap();
+        child.wait().unwrap();
+
+        let artifact = Artifact {
+            risk: crate::rules::Risk::Deps,
+            ..Artifact::for_test(project.join("node_modules"), "Node.js")
+        };
+        match Filter::verdict(&in_use, &artifact) {
+            Verdict::Drop(reason) => assert!(reason.contains("sleep"), "{reason}"),
+            keep => panic!("expected drop, got {keep:?}"),
+        }
+    }
+}
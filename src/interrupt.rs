@@ -0,0 +1,111 @@
+use std::io;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use log::debug;
+use signal_hook::SigId;
+use signal_hook::consts::SIGINT;
+
+/// Exit status after a second Ctrl+C, as for a shell killed by SIGINT.
+const INTERRUPTED_STATUS: i32 = 130;
+
+/// A request to stop a run early, shared between whoever asks (a host
+/// application, say, on a Cancel button) and the scan, sizing and deletion
+/// that check it. They stop starting new work once it is cancelled and
+/// return what they had done by then: the artifacts found so far,
+/// artifacts left unsized, or removals not started reported as skipped.
+#[derive(Debug, Clone, Default)]
+pub struct CancelToken(Arc<AtomicBool>);
+
+impl CancelToken {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Ask every run holding this token to stop.
+    pub fn cancel(&self) {
+        self.0.store(true, Ordering::SeqCst);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.0.load(Ordering::SeqCst)
+    }
+}
+
+/// Ctrl+C handling for the duration of a deletion. The first Ctrl+C sets a
+/// flag that keeps new deletions from starting while those in flight finish;
+/// a second one exits immediately. Once the guard is dropped, Ctrl+C exits
+/// immediately again.
+#[derive(Debug)]
+pub struct InterruptGuard {
+    flag: Arc<AtomicBool>,
+    /// The handler that sets `flag`.
+    setter: SigId,
+}
+
+impl InterruptGuard {
+    pub fn install() -> io::Result<Self> {
+        let flag = Arc::new(AtomicBool::new(false));
+        // Registered first so that it sees the flag as it was before this
+        // Ctrl+C: it only exits once the other handler has already set it.
+        signal_hook::flag::register_conditional_shutdown(
+            SIGINT,
+            INTERRUPTED_STATUS,
+            Arc::clone(&flag),
+        )?;
+        let setter = signal_hook::flag::register(SIGINT, Arc::clone(&flag))?;
+        debug!("Installed Ctrl+C handler");
+        Ok(Self { flag, setter })
+    }
+
+    /// Set once Ctrl+C has been pressed.
+    pub fn flag(&self) -> &AtomicBool {
+        &self.flag
+    }
+
+    /// Whether Ctrl+C has been pressed.
+    pub fn interrupted(&self) -> bool {
+        self.flag.load(Ordering::SeqCst)
+    }
+
+    /// A token cancelled by the first Ctrl+C, for the scan and sizing.
+    /// Dropping the guard cancels it too, so it is only meaningful while the
+    /// guard is held.
+    pub fn cancel_token(&self) -> CancelToken {
+        CancelToken(Arc::clone(&self.flag))
+    }
+}
+
+impl Drop for InterruptGuard {
+    /// Unregistering every handler would leave Ctrl+C ignored rather than
+    /// restore the default, so the shutdown handler stays, armed.
+    fn drop(&mut self) {
+        signal_hook::low_level::unregister(self.setter);
+        self.flag.store(true, Ordering::SeqCst);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn cancelling_reaches_every_clone() {
+        let token = CancelToken::new();
+        let held = token.clone();
+        assert!(!held.is_cancelled());
+        token.cancel();
+        assert!(held.is_cancelled());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn first_interrupt_sets_flag() {
+        let guard = InterruptGuard::install().unwrap();
+        assert!(!guard.interrupted());
+        let token = guard.cancel_token();
+        signal_hook::low_level::raise(SIGINT).unwrap();
+        assert!(guard.interrupted());
+        assert!(token.is_cancelled());
+    }
+}
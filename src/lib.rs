@@ -1,7 +1,806 @@
+//! Find and delete build artifacts, recognized by the marker files of the
+//! build systems that produce them.
+//!
+//! [`CleanBuilds`] is the supported way to embed this: it runs the scan,
+//! filter, size and delete stages, and the `clean-builds` command runs
+//! through it too, set up with [`CleanBuilds::from_cli`]. The modules below
+//! are public for the command and for callers with needs it does not cover,
+//! but may change between releases.
+//!
+//! ```no_run
+//! use clean_builds::CleanBuilds;
+//! use clean_builds::rules::Risk;
+//!
+//! let report = CleanBuilds::new("/home/me/Developer")
+//!     .risks(&[Risk::Cache])
+//!     .min_size(100 << 20)
+//!     .report()?;
+//! println!("{} artifacts, {} bytes", report.artifacts.len(), report.bytes);
+//! # Ok::<(), clean_builds::Error>(())
+//! ```
+
+pub mod age;
+pub mod audit;
+pub mod breakdown;
+pub mod cache;
 pub mod cli;
+pub mod config;
 pub mod delete;
+pub mod disk;
+pub mod doctor;
+pub mod explain;
+pub mod export;
+pub mod expr;
 pub mod filter;
+pub mod git;
+pub mod hooks;
+pub mod in_use;
+pub mod interrupt;
+pub mod lint;
+pub mod lock;
+#[cfg(feature = "native-walk")]
+pub mod native_walk;
 pub mod output;
+pub mod owner;
+pub mod preflight;
+pub mod progress;
+pub mod quarantine;
 pub mod rules;
 pub mod scanner;
+pub mod schedule;
 pub mod size;
+pub mod throttle;
+pub mod tui;
+pub mod version;
+pub mod watch;
+
+use std::collections::HashSet;
+use std::io::{BufRead, BufReader, Write};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use log::{info, warn};
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+use crate::age::{AgeFilter, IdleProjects, LatestProjects, atime_warning};
+use crate::cache::SizeCache;
+use crate::cli::Cli;
+use crate::delete::{DeleteError, DeleteOptions, DeleteReport, confirm_and_delete};
+use crate::disk::{VolumeError, VolumeFilter};
+use crate::explain::Explainer;
+use crate::expr::{Expr, ExprFilter};
+use crate::filter::{
+    ArtifactFilter, DepthFilter, Filter, FilterChain, PatternError, ProjectFilter, Verdict,
+};
+use crate::git::UnpushedRepos;
+use crate::in_use::InUseProjects;
+use crate::interrupt::CancelToken;
+use crate::owner::{OwnerError, OwnerFilter};
+use crate::progress::{Observer, Progress};
+use crate::rules::{MatchableRule, Risk, SystemFilterError};
+use crate::scanner::{Artifact, scan_and_size, scan_with_progress};
+use crate::size::{FreeTarget, SizeFilter, SizeOptions, compute_sizes_with_progress, format_size};
+
+/// Error from a [`CleanBuilds`] run, or from setting one up with
+/// [`from_cli`](CleanBuilds::from_cli).
+#[derive(thiserror::Error, Debug)]
+pub enum Error {
+    #[error("cannot access '{}': {source}", path.display())]
+    Root {
+        path: PathBuf,
+        source: std::io::Error,
+    },
+    #[error(transparent)]
+    Systems(#[from] SystemFilterError),
+    #[error(transparent)]
+    Pattern(#[from] PatternError),
+    #[error(transparent)]
+    Owner(#[from] OwnerError),
+    #[error(transparent)]
+    Volume(#[from] VolumeError),
+    #[error(transparent)]
+    Delete(#[from] DeleteError),
+}
+
+/// A cleanup of one directory tree: which rules detect artifacts, which
+/// filters keep them, how they are sized and how they are deleted.
+///
+/// Built with [`new`](Self::new) and the setters, or from a command line
+/// with [`from_cli`](Self::from_cli), then run with [`scan`](Self::scan),
+/// [`report`](Self::report) or [`clean`](Self::clean). [`patterns`](Self::patterns)
+/// run first, then the filters and selections added with
+/// [`filter`](Self::filter), [`keep_latest`](Self::keep_latest),
+/// [`idle_for`](Self::idle_for), [`skip_unpushed`](Self::skip_unpushed) and
+/// [`skip_in_use`](Self::skip_in_use) in the order they were added, then the
+/// [`expr`](Self::expr) expressions that do not test sizes. Sizes are only
+/// measured for the artifacts these keep; [`min_size`](Self::min_size),
+/// [`max_size`](Self::max_size), the expressions that test sizes and
+/// [`free`](Self::free) run after.
+pub struct CleanBuilds {
+    root: PathBuf,
+    rules: Vec<MatchableRule>,
+    patterns: Option<ArtifactFilter>,
+    stages: Vec<Stage>,
+    expressions: Vec<Expr>,
+    sizes: SizeFilter,
+    free: Option<u64>,
+    size_options: SizeOptions,
+    single_pass: bool,
+    size_cache: Option<PathBuf>,
+    delete_options: DeleteOptions,
+    progress: Observer,
+    cancel: CancelToken,
+}
+
+/// A filter stage before sizing.
+enum Stage {
+    Filter(Box<dyn Filter>),
+    /// Drops the artifacts of the `n` most recently built projects of each
+    /// build system, among those reaching it.
+    KeepLatest(usize),
+    /// Keeps artifacts of projects whose sources are unchanged for this long.
+    IdleFor(Duration),
+    SkipUnpushed,
+    SkipInUse,
+}
+
+/// `patterns` matching artifact paths relative to `root`.
+struct Relative<'a> {
+    patterns: &'a ArtifactFilter,
+    root: &'a Path,
+}
+
+impl Filter for Relative<'_> {
+    fn keep(&self, artifact: &Artifact) -> bool {
+        Filter::verdict(self, artifact).is_keep()
+    }
+
+    fn verdict(&self, artifact: &Artifact) -> Verdict {
+        let path = artifact
+            .path
+            .strip_prefix(self.root)
+            .unwrap_or(&artifact.path);
+        self.patterns.verdict(path)
+    }
+}
+
+/// Artifacts found under a root, with totals by build system.
+#[derive(Debug, Clone, Default)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct Report {
+    /// The canonical root scanned.
+    pub root: PathBuf,
+    /// The artifacts kept by every filter, sized, sorted by path.
+    pub artifacts: Vec<Artifact>,
+    /// Total size of `artifacts`.
+    pub bytes: u64,
+    /// Artifact count and bytes per build system, sorted by name.
+    pub systems: Vec<SystemTotal>,
+}
+
+/// The artifacts of one build system in a [`Report`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct SystemTotal {
+    pub build_system: String,
+    pub count: usize,
+    pub bytes: u64,
+}
+
+impl CleanBuilds {
+    /// Clean up under `root` with every built-in rule, no filters, apparent
+    /// sizes, and plain deletion.
+    pub fn new(root: impl Into<PathBuf>) -> Self {
+        Self {
+            root: root.into(),
+            rules: rules::all_rules(),
+            patterns: None,
+            stages: Vec::new(),
+            expressions: Vec::new(),
+            sizes: SizeFilter::default(),
+            free: None,
+            size_options: SizeOptions::default(),
+            single_pass: false,
+            size_cache: None,
+            delete_options: DeleteOptions::default(),
+            progress: Observer::default(),
+            cancel: CancelToken::new(),
+        }
+    }
+
+    /// The cleanup the `clean-builds` command runs under `root` with the
+    /// options in `cli`: its rules, filters and sizing, with sizes cached in
+    /// the default cache unless `--no-cache`. Deleting is left to the
+    /// caller, since the command asks first.
+    pub fn from_cli(cli: &Cli, root: impl Into<PathBuf>) -> Result<Self, Error> {
+        let root = root.into();
+        let patterns = ArtifactFilter::with_pattern_files(
+            &cli.include,
+            &cli.include_from,
+            &cli.exclude,
+            &cli.exclude_from,
+        )?
+        .with_regexes(&cli.include_regex, &cli.exclude_regex)?;
+        if let Some(window) = cli.not_accessed_in {
+            if let Some(warning) = atime_warning(&root, window) {
+                warn!("{warning}");
+            }
+        }
+        let mut cleanup = Self::new(&root)
+            .systems(&cli.system, &cli.exclude_system)?
+            .risks(&cli.risk)
+            .patterns(patterns)
+            .filter(ProjectFilter::new(&cli.project, &cli.exclude_project)?);
+        if cli.min_depth.is_some() || cli.max_artifact_depth.is_some() {
+            cleanup = cleanup.filter(DepthFilter::new(
+                &root,
+                cli.min_depth,
+                cli.max_artifact_depth,
+            ));
+        }
+        if let Some(user) = &cli.owner {
+            cleanup = cleanup.filter(OwnerFilter::for_user(user)?);
+        } else if cli.only_mine {
+            cleanup = cleanup.filter(OwnerFilter::current_user()?);
+        }
+        if let Some(path) = &cli.on_volume {
+            cleanup = cleanup.filter(VolumeFilter::new(path)?);
+        }
+        if let Some(n) = cli.keep_latest {
+            cleanup = cleanup.keep_latest(n);
+        }
+        let ages = AgeFilter {
+            older_than: cli.older_than,
+            newer_than: cli.newer_than,
+            not_accessed_in: cli.not_accessed_in,
+        };
+        if !ages.is_empty() {
+            cleanup = cleanup.filter(ages);
+        }
+        if let Some(window) = cli.idle_for {
+            cleanup = cleanup.idle_for(window);
+        }
+        if cli.skip_unpushed {
+            cleanup = cleanup.skip_unpushed();
+        }
+        if cli.skip_in_use {
+            cleanup = cleanup.skip_in_use();
+        }
+        for expr in &cli.filter {
+            cleanup = cleanup.expr(expr.clone());
+        }
+        cleanup.sizes = SizeFilter {
+            min: cli.min_size,
+            max: cli.max_size,
+        };
+        cleanup.free = cli.free;
+        let size_cache = cache::default_path().filter(|_| !cli.no_cache);
+        // Sizing while scanning saves walking each artifact again, but sizes
+        // what filters drop and cannot reuse cached sizes or stop at --max-size.
+        let single_pass = cli.single_pass
+            || (size_cache.is_none()
+                && !cli.estimate
+                && !cli.link_targets
+                && cli.max_size.is_none()
+                && !cli.filters_before_sizing());
+        cleanup.size_cache = size_cache;
+        Ok(cleanup
+            .size_options(SizeOptions {
+                allocated: cli.du,
+                dedup_links: cli.dedup_links,
+                estimate: cli.estimate,
+                link_targets: cli.link_targets,
+                stop_above: cli.max_size,
+                reclaimable: cli.reclaimable,
+            })
+            .single_pass(single_pass))
+    }
+
+    /// Detect artifacts with `rules` instead of the built-in ones, e.g.
+    /// from [`parse_rules`](rules::parse_rules).
+    pub fn rules(mut self, rules: Vec<MatchableRule>) -> Self {
+        self.rules = rules;
+        self
+    }
+
+    /// Keep only the rules of the build systems in `include` (all if it is
+    /// empty) and not in `exclude`, by the IDs `--system` takes.
+    pub fn systems(
+        mut self,
+        include: &[String],
+        exclude: &[String],
+    ) -> Result<Self, SystemFilterError> {
+        self.rules = rules::filter_rules_by_system(self.rules, include, exclude)?;
+        Ok(self)
+    }
+
+    /// Keep only the rules whose artifacts are of one of `risks`.
+    pub fn risks(mut self, risks: &[Risk]) -> Self {
+        self.rules = rules::filter_rules_by_risk(self.rules, risks);
+        self
+    }
+
+    /// The rules artifacts are detected with.
+    pub fn active_rules(&self) -> &[MatchableRule] {
+        &self.rules
+    }
+
+    /// Keep only artifacts `patterns` includes, matching their paths
+    /// relative to the root, as `--include` and `--exclude` do.
+    pub fn patterns(mut self, patterns: ArtifactFilter) -> Self {
+        self.patterns = Some(patterns);
+        self
+    }
+
+    /// Keep only artifacts `filter` keeps, after those added before it.
+    pub fn filter(mut self, filter: impl Filter + 'static) -> Self {
+        self.stages.push(Stage::Filter(Box::new(filter)));
+        self
+    }
+
+    /// Leave alone the artifacts of the `n` most recently built projects of
+    /// each build system, among those the filters added before keep.
+    pub fn keep_latest(mut self, n: usize) -> Self {
+        self.stages.push(Stage::KeepLatest(n));
+        self
+    }
+
+    /// Keep only artifacts of projects whose source files are unchanged for
+    /// `window`, looking past every artifact the scan found.
+    pub fn idle_for(mut self, window: Duration) -> Self {
+        self.stages.push(Stage::IdleFor(window));
+        self
+    }
+
+    /// Skip artifacts in Git repositories with unpushed commits or stashes.
+    pub fn skip_unpushed(mut self) -> Self {
+        self.stages.push(Stage::SkipUnpushed);
+        self
+    }
+
+    /// Skip artifacts of projects that running processes are using.
+    pub fn skip_in_use(mut self) -> Self {
+        self.stages.push(Stage::SkipInUse);
+        self
+    }
+
+    /// Keep only artifacts for which `expr` is true. Expressions that test
+    /// sizes run once artifacts are sized; the rest run before.
+    pub fn expr(mut self, expr: Expr) -> Self {
+        self.expressions.push(expr);
+        self
+    }
+
+    /// Keep only artifacts of at least `bytes`.
+    pub fn min_size(mut self, bytes: u64) -> Self {
+        self.sizes.min = Some(bytes);
+        self
+    }
+
+    /// Keep only artifacts of at most `bytes`.
+    pub fn max_size(mut self, bytes: u64) -> Self {
+        self.sizes.max = Some(bytes);
+        self
+    }
+
+    /// Keep only the oldest artifacts needed to free `bytes`, after every
+    /// other filter.
+    pub fn free(mut self, bytes: u64) -> Self {
+        self.free = Some(bytes);
+        self
+    }
+
+    /// Measure artifacts as `options` says.
+    pub fn size_options(mut self, options: SizeOptions) -> Self {
+        self.size_options = options;
+        self
+    }
+
+    /// Size artifacts while scanning for them, in one walk, which also sizes
+    /// those the filters go on to drop (`--single-pass`).
+    pub fn single_pass(mut self, single_pass: bool) -> Self {
+        self.single_pass = single_pass;
+        self
+    }
+
+    /// Reuse the sizes of unchanged artifacts kept in the cache file at
+    /// `path` from earlier runs, and save the new ones there.
+    pub fn size_cache(mut self, path: impl Into<PathBuf>) -> Self {
+        self.size_cache = Some(path.into());
+        self
+    }
+
+    /// Delete as `options` says: to the trash, into quarantine, with hooks,
+    /// and so on. [`clean`](Self::clean) and [`delete`](Self::delete) never
+    /// ask for confirmation, so `skip_confirm` and `no_input` are ignored
+    /// there. `progress` gives way to [`progress`](Self::progress), and
+    /// `cancel` to [`cancel_with`](Self::cancel_with).
+    pub fn delete_options(mut self, options: DeleteOptions) -> Self {
+        self.delete_options = options;
+        self
+    }
+
+    /// Tell `progress` how the scan, sizing and deletion go, say to drive a
+    /// progress display.
+    pub fn progress(mut self, progress: impl Progress + 'static) -> Self {
+        self.progress = Observer::new(progress);
+        self
+    }
+
+    /// Stop once `cancel` is cancelled, from another thread: a scan returns
+    /// what it found until then, only partly sized, and a deletion reports
+    /// the artifacts it had not started on as skipped.
+    pub fn cancel_with(mut self, cancel: CancelToken) -> Self {
+        self.cancel = cancel;
+        self
+    }
+
+    /// Find the artifacts under the root that every filter keeps, sized and
+    /// sorted by path.
+    pub fn scan(&self) -> Result<Vec<Artifact>, Error> {
+        self.scan_explained().map(|(artifacts, _)| artifacts)
+    }
+
+    /// [`scan`](Self::scan), with what each filter stage decided about each
+    /// artifact, as `--explain` prints it.
+    pub fn scan_explained(&self) -> Result<(Vec<Artifact>, Explainer), Error> {
+        Ok(self.scan_root(&self.canonical_root()?))
+    }
+
+    fn canonical_root(&self) -> Result<PathBuf, Error> {
+        self.root.canonicalize().map_err(|source| Error::Root {
+            path: self.root.clone(),
+            source,
+        })
+    }
+
+    fn scan_root(&self, root: &Path) -> (Vec<Artifact>, Explainer) {
+        let mut artifacts = if self.single_pass {
+            info!("Scanning and sizing {}", root.display());
+            scan_and_size(
+                root,
+                &self.rules,
+                &self.size_options,
+                &self.progress,
+                &self.cancel,
+            )
+        } else {
+            info!("Scanning {}", root.display());
+            scan_with_progress(root, &self.rules, &self.progress, &self.cancel)
+        };
+        let idle = self.stages.iter().any(|s| matches!(s, Stage::IdleFor(_)));
+        let detected: HashSet<PathBuf> = if idle {
+            artifacts.iter().map(|a| a.path.clone()).collect()
+        } else {
+            HashSet::new()
+        };
+
+        info!("Filtering artifacts");
+        let mut explainer = Explainer::default();
+        if let Some(patterns) = &self.patterns {
+            artifacts = explainer.stage(artifacts, &Relative { patterns, root });
+        }
+        for stage in &self.stages {
+            artifacts = match stage {
+                Stage::Filter(filter) => explainer.stage(artifacts, &**filter),
+                Stage::KeepLatest(n) => {
+                    let latest = LatestProjects::find(&artifacts, *n);
+                    explainer.stage(artifacts, &latest)
+                }
+                Stage::IdleFor(window) => {
+                    let projects = IdleProjects::check(&artifacts, detected.clone(), *window);
+                    explainer.stage(artifacts, &projects)
+                }
+                Stage::SkipUnpushed => {
+                    let repos = UnpushedRepos::check(&artifacts);
+                    explainer.stage(artifacts, &repos)
+                }
+                Stage::SkipInUse => explainer.stage(artifacts, &InUseProjects::detect()),
+            };
+        }
+        // Expressions that do not test sizes narrow down what is sized.
+        let (sized_exprs, unsized_exprs): (Vec<_>, Vec<_>) =
+            self.expressions.iter().partition(|e| e.needs_sizes());
+        let expressions = |exprs: Vec<&Expr>| {
+            let mut chain = FilterChain::new();
+            for expr in exprs {
+                chain.push(ExprFilter::new(expr.clone(), root));
+            }
+            chain
+        };
+        artifacts = explainer.stage(artifacts, &expressions(unsized_exprs));
+
+        if !artifacts.is_empty() && !self.single_pass {
+            info!("Computing sizes for {} artifacts", artifacts.len());
+            match &self.size_cache {
+                Some(path) => {
+                    let mut cache = SizeCache::load(path);
+                    cache.compute_sizes_with_progress(
+                        &mut artifacts,
+                        &self.size_options,
+                        &*self.progress,
+                        &self.cancel,
+                    );
+                    if let Err(e) = cache.save(path) {
+                        warn!("{e}");
+                    }
+                }
+                None => compute_sizes_with_progress(
+                    &mut artifacts,
+                    &self.size_options,
+                    &*self.progress,
+                    &self.cancel,
+                ),
+            }
+        }
+        if !artifacts.is_empty() {
+            artifacts = explainer.stage(artifacts, &self.sizes);
+            artifacts = explainer.stage(artifacts, &expressions(sized_exprs));
+            if let Some(target) = self.free {
+                let free = FreeTarget::select(&artifacts, target);
+                if free.selected_bytes() < target {
+                    warn!(
+                        "Only {} of build artifacts match; --free {} cannot be reached",
+                        format_size(free.selected_bytes()),
+                        format_size(target)
+                    );
+                }
+                artifacts = explainer.stage(artifacts, &free);
+            }
+        }
+        artifacts.sort_by(|a, b| a.path.cmp(&b.path));
+        (artifacts, explainer)
+    }
+
+    /// [`scan`](Self::scan), with totals.
+    pub fn report(&self) -> Result<Report, Error> {
+        let root = self.canonical_root()?;
+        let (artifacts, _) = self.scan_root(&root);
+        let mut systems: Vec<SystemTotal> = Vec::new();
+        for artifact in &artifacts {
+            match systems
+                .iter_mut()
+                .find(|s| s.build_system == artifact.build_system)
+            {
+                Some(total) => {
+                    total.count += 1;
+                    total.bytes += artifact.size_bytes;
+                }
+                None => systems.push(SystemTotal {
+                    build_system: artifact.build_system.to_string(),
+                    count: 1,
+                    bytes: artifact.size_bytes,
+                }),
+            }
+        }
+        systems.sort_by(|a, b| a.build_system.cmp(&b.build_system));
+        Ok(Report {
+            root,
+            bytes: artifacts.iter().map(|a| a.size_bytes).sum(),
+            artifacts,
+            systems,
+        })
+    }
+
+    /// [`scan`](Self::scan), then delete every artifact found without
+    /// asking. To delete only some, scan and pass them to
+    /// [`delete`](Self::delete).
+    pub fn clean(&self) -> Result<DeleteReport, Error> {
+        let artifacts = self.scan()?;
+        self.delete(&artifacts)
+    }
+
+    /// Delete `artifacts`, found by an earlier [`scan`](Self::scan), without
+    /// asking. Artifacts that cannot be removed are reported in the
+    /// [`DeleteReport`] rather than failing the call.
+    pub fn delete(&self, artifacts: &[Artifact]) -> Result<DeleteReport, Error> {
+        let options = DeleteOptions {
+            skip_confirm: true,
+            no_input: true,
+            ..self.run_delete_options()
+        };
+        let mut input = BufReader::new(std::io::empty());
+        Ok(confirm_and_delete(
+            &mut std::io::sink(),
+            &mut input,
+            artifacts,
+            &options,
+        )?)
+    }
+
+    /// [`delete`](Self::delete), but first asking on `out` and `input` as
+    /// the command does, unless the delete options skip confirmation.
+    pub fn confirm_and_delete(
+        &self,
+        out: &mut dyn Write,
+        input: &mut dyn BufRead,
+        artifacts: &[Artifact],
+    ) -> Result<DeleteReport, DeleteError> {
+        confirm_and_delete(out, input, artifacts, &self.run_delete_options())
+    }
+
+    fn run_delete_options(&self) -> DeleteOptions {
+        DeleteOptions {
+            progress: self.progress.clone(),
+            cancel: self.cancel.clone(),
+            ..self.delete_options.clone()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use tempfile::TempDir;
+
+    /// A Rust project with a 100-byte `target` and a Python project with a
+    /// 10-byte `__pycache__`.
+    fn projects() -> TempDir {
+        let tmp = TempDir::new().unwrap();
+        let rust = tmp.path().join("app");
+        fs::create_dir_all(rust.join("target/debug")).unwrap();
+        fs::write(rust.join("Cargo.toml"), "").unwrap();
+        fs::write(rust.join("target/debug/app"), [0u8; 100]).unwrap();
+        let python = tmp.path().join("tool");
+        fs::create_dir_all(python.join("__pycache__")).unwrap();
+        fs::write(python.join("pyproject.toml"), "").unwrap();
+        fs::write(python.join("__pycache__/m.pyc"), [0u8; 10]).unwrap();
+        tmp
+    }
+
+    #[test]
+    fn report_totals_what_the_filters_keep() {
+        let tmp = projects();
+        let report = CleanBuilds::new(tmp.path()).report().unwrap();
+        assert_eq!(report.root, tmp.path().canonicalize().unwrap());
+        assert_eq!(report.artifacts.len(), 2);
+        assert_eq!(report.bytes, 110);
+        assert_eq!(
+            report.systems,
+            [
+                SystemTotal {
+                    build_system: "Python".to_string(),
+                    count: 1,
+                    bytes: 10
+                },
+                SystemTotal {
+                    build_system: "Rust/Cargo".to_string(),
+                    count: 1,
+                    bytes: 100
+                },
+            ]
+        );
+
+        let excluded = ArtifactFilter::new(&[], &["tool/**".to_string()]).unwrap();
+        let scanned = CleanBuilds::new(tmp.path())
+            .patterns(excluded)
+            .scan()
+            .unwrap();
+        assert_eq!(scanned.len(), 1);
+        assert_eq!(scanned[0].build_system, "Rust/Cargo");
+
+        let large = CleanBuilds::new(tmp.path()).min_size(50).scan().unwrap();
+        assert_eq!(large.len(), 1);
+        let none = CleanBuilds::new(tmp.path())
+            .filter(|a: &Artifact| a.risk == Risk::Deps)
+            .scan()
+            .unwrap();
+        assert!(none.is_empty());
+        let rust = CleanBuilds::new(tmp.path())
+            .systems(&["cargo".to_string()], &[])
+            .unwrap()
+            .scan()
+            .unwrap();
+        assert_eq!(rust.len(), 1);
+    }
+
+    #[test]
+    fn clean_deletes_without_asking() {
+        let tmp = projects();
+        let report = CleanBuilds::new(tmp.path())
+            .risks(&[Risk::Cache])
+            .clean()
+            .unwrap();
+        assert!(report.confirmed);
+        assert_eq!(report.removed(), 1);
+        assert!(!tmp.path().join("tool/__pycache__").exists());
+        assert!(tmp.path().join("app/target").exists());
+    }
+
+    #[test]
+    fn progress_hears_of_every_stage() {
+        use crate::audit::Outcome;
+        use crate::size::SizingProgress;
+        use std::sync::{Arc, Mutex};
+
+        #[derive(Clone, Default)]
+        struct Events(Arc<Mutex<Vec<String>>>);
+        impl Events {
+            fn push(&self, event: String) {
+                self.0.lock().unwrap().push(event);
+            }
+        }
+        impl Progress for Events {
+            fn on_dir_visited(&self, _: &Path) {
+                self.push("visited".to_string());
+            }
+            fn on_artifact_found(&self, artifact: &Artifact) {
+                self.push(format!("found {}", artifact.artifact_dir));
+            }
+            fn on_sized(&self, artifact: &Artifact, bytes: u64, progress: SizingProgress) {
+                let total = progress.total;
+                self.push(format!(
+                    "sized {} {bytes} of {total}",
+                    artifact.artifact_dir
+                ));
+            }
+            fn on_deleted(&self, artifact: &Artifact, outcome: &Outcome) {
+                self.push(format!("{outcome:?} {}", artifact.artifact_dir));
+            }
+        }
+
+        let tmp = projects();
+        let events = Events::default();
+        CleanBuilds::new(tmp.path())
+            .risks(&[Risk::Cache])
+            .progress(events.clone())
+            .clean()
+            .unwrap();
+        let events = events.0.lock().unwrap();
+        assert!(events.contains(&"visited".to_string()));
+        let rest: Vec<&str> = events
+            .iter()
+            .filter(|e| *e != "visited")
+            .map(String::as_str)
+            .collect();
+        assert_eq!(
+            rest,
+            [
+                "found __pycache__",
+                "sized __pycache__ 10 of 1",
+                "Deleted __pycache__"
+            ]
+        );
+    }
+
+    #[test]
+    fn from_cli_runs_the_command_stages() {
+        use clap::Parser;
+
+        let tmp = projects();
+        let cli = Cli::parse_from([
+            "clean-builds",
+            "--no-cache",
+            "--exclude-project",
+            "nothing",
+            "--filter",
+            "size > 50",
+        ]);
+        let cleanup = CleanBuilds::from_cli(&cli, tmp.path()).unwrap();
+        let (artifacts, explainer) = cleanup.scan_explained().unwrap();
+        assert_eq!(artifacts.len(), 1);
+        assert_eq!(artifacts[0].build_system, "Rust/Cargo");
+        assert_eq!(artifacts[0].size_bytes, 100);
+        let pycache = tmp.path().canonicalize().unwrap().join("tool/__pycache__");
+        let dropped = explainer.explanation(&pycache).unwrap();
+        assert!(dropped.dropped_because.is_some());
+
+        let cli = Cli::parse_from(["clean-builds", "--system", "nonesuch"]);
+        assert!(matches!(
+            CleanBuilds::from_cli(&cli, tmp.path()),
+            Err(Error::Systems(_))
+        ));
+    }
+
+    #[test]
+    fn missing_root_is_an_error() {
+        let tmp = TempDir::new().unwrap();
+        let missing = tmp.path().join("missing");
+        match CleanBuilds::new(&missing).scan() {
+            Err(Error::Root { path, .. }) => assert_eq!(path, missing),
+            other => panic!("expected a root error, got {other:?}"),
+        }
+    }
+}
@@ -0,0 +1,339 @@
+use std::io::Write;
+use std::path::Path;
+
+use crate::rules::{DirMatch, MarkerKind, MatchableRule, parse_rules_each};
+use crate::scanner::Artifact;
+
+/// How serious a lint finding is.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Severity {
+    /// The file cannot be used.
+    Error,
+    /// The file works, but a rule in it does nothing.
+    Warning,
+}
+
+/// Something wrong with a rule file.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Finding {
+    pub severity: Severity,
+    /// 1-based line the finding is about, if known.
+    pub line: Option<usize>,
+    pub message: String,
+}
+
+/// The result of checking a rule file.
+#[derive(Debug, Default)]
+pub struct Lint {
+    /// The valid rules, in order.
+    pub rules: Vec<MatchableRule>,
+    pub findings: Vec<Finding>,
+}
+
+impl Lint {
+    /// Whether the file has errors.
+    pub fn failed(&self) -> bool {
+        self.findings.iter().any(|f| f.severity == Severity::Error)
+    }
+}
+
+/// Check the rule file `text`: report syntax errors and invalid rules with
+/// their line numbers, and warn about rules that can never match because a
+/// rule in `builtin`, or an earlier one in the file, already matches
+/// everything they do.
+pub fn lint(text: &str, builtin: &[MatchableRule]) -> Lint {
+    let parsed = match parse_rules_each(text) {
+        Ok(parsed) => parsed,
+        Err(e) => {
+            return Lint {
+                rules: Vec::new(),
+                findings: vec![Finding {
+                    severity: Severity::Error,
+                    line: e.span().map(|span| line_of(text, span.start)),
+                    message: e.message().trim_end().to_string(),
+                }],
+            };
+        }
+    };
+    let mut lint = Lint::default();
+    for (i, (span, rule)) in parsed.into_iter().enumerate() {
+        let line = Some(line_of(text, span.start));
+        let rule = match rule {
+            Ok(rule) => rule,
+            Err(e) => {
+                lint.findings.push(Finding {
+                    severity: Severity::Error,
+                    line,
+                    message: e.to_string(),
+                });
+                continue;
+            }
+        };
+        let shadow = builtin
+            .iter()
+            .find(|b| shadows(b, &rule))
+            .map(|b| format!("built-in rule `{}` ({})", b.rule.id, b.rule.build_system))
+            .or_else(|| {
+                lint.rules
+                    .iter()
+                    .find(|earlier| shadows(earlier, &rule))
+                    .map(|earlier| format!("earlier rule `{}`", earlier.rule.id))
+            });
+        if let Some(shadow) = shadow {
+            lint.findings.push(Finding {
+                severity: Severity::Warning,
+                line,
+                message: format!(
+                    "rule {} ({}): never matches, as {shadow} matches everything it does first",
+                    i + 1,
+                    rule.rule.id
+                ),
+            });
+        }
+        lint.rules.push(rule);
+    }
+    lint
+}
+
+/// 1-based line of byte `offset` in `text`.
+fn line_of(text: &str, offset: usize) -> usize {
+    text[..offset.min(text.len())].matches('\n').count() + 1
+}
+
+/// Whether `first`, tried before `rule`, matches every entry `rule` would.
+/// Only certain cases are recognized, so a rule that goes unreported can
+/// still be shadowed in practice.
+fn shadows(first: &MatchableRule, rule: &MatchableRule) -> bool {
+    first.entry == rule.entry
+        && covers(&first.dir_match, &rule.dir_match)
+        && implies(&rule.rule.marker, &first.rule.marker)
+}
+
+/// Whether every name matching `narrow` also matches `wide`.
+fn covers(wide: &DirMatch, narrow: &DirMatch) -> bool {
+    match (wide, narrow) {
+        (DirMatch::Any, _) => true,
+        (DirMatch::Exact(a), DirMatch::Exact(b)) => a == b,
+        (DirMatch::Suffix(suffix), DirMatch::Exact(name) | DirMatch::Suffix(name)) => {
+            name.ends_with(suffix)
+        }
+        (DirMatch::Regex(re), DirMatch::Exact(name)) => re.is_match(name),
+        (DirMatch::Regex(a), DirMatch::Regex(b)) => a.as_str() == b.as_str(),
+        (DirMatch::Path(a), DirMatch::Path(b)) => a == b,
+        _ => false,
+    }
+}
+
+/// Whether `marker` holding means `other` holds too.
+fn implies(marker: &MarkerKind, other: &MarkerKind) -> bool {
+    match (marker, other) {
+        (_, MarkerKind::Always) => true,
+        (_, MarkerKind::Any(others)) if others.iter().any(|o| implies(marker, o)) => true,
+        (_, MarkerKind::All(others)) => others.iter().all(|o| implies(marker, o)),
+        (MarkerKind::All(markers), _) => markers.iter().any(|m| implies(m, other)),
+        (MarkerKind::Any(markers), _) => markers.iter().all(|m| implies(m, other)),
+        (MarkerKind::Files(a), MarkerKind::Files(b) | MarkerKind::Ancestor(b)) => {
+            a.iter().all(|name| b.contains(name))
+        }
+        (MarkerKind::Ancestor(a), MarkerKind::Ancestor(b)) => a.iter().all(|name| b.contains(name)),
+        (MarkerKind::GlobSuffix(a), MarkerKind::GlobSuffix(b)) => a.ends_with(b),
+        (MarkerKind::Glob(a), MarkerKind::Glob(b)) => a.glob().glob() == b.glob().glob(),
+        (MarkerKind::InsideDir(a), MarkerKind::InsideDir(b)) => a == b,
+        (
+            MarkerKind::FileContains { file, needle },
+            MarkerKind::FileContains {
+                file: other_file,
+                needle: other_needle,
+            },
+        ) => file == other_file && needle.contains(other_needle),
+        (MarkerKind::FileContains { file, .. }, MarkerKind::Files(names)) => names.contains(file),
+        _ => false,
+    }
+}
+
+/// Print the findings for the rule file `name`, then a summary line.
+pub fn print_lint(out: &mut dyn Write, name: &str, lint: &Lint) -> std::io::Result<()> {
+    for finding in &lint.findings {
+        let location = match finding.line {
+            Some(line) => format!("{name}:{line}"),
+            None => name.to_string(),
+        };
+        let severity = match finding.severity {
+            Severity::Error => "error",
+            Severity::Warning => "warning",
+        };
+        writeln!(out, "{location}: {severity}: {}", finding.message)?;
+    }
+    let count = |n: usize, what: &str| format!("{n} {what}{}", if n == 1 { "" } else { "s" });
+    let findings = |severity| {
+        lint.findings
+            .iter()
+            .filter(|f| f.severity == severity)
+            .count()
+    };
+    writeln!(
+        out,
+        "{name}: {}, {}, {}",
+        count(lint.rules.len(), "valid rule"),
+        count(findings(Severity::Error), "error"),
+        count(findings(Severity::Warning), "warning")
+    )
+}
+
+/// Print what the rules of a linted file matched in the sample directory
+/// `root`, relative to it.
+pub fn print_sample(
+    out: &mut dyn Write,
+    root: &Path,
+    artifacts: &[Artifact],
+) -> std::io::Result<()> {
+    if artifacts.is_empty() {
+        return writeln!(out, "\nNothing under {} matches.", root.display());
+    }
+    writeln!(out, "\nMatches under {}:", root.display())?;
+    for artifact in artifacts {
+        let path = artifact.path.strip_prefix(root).unwrap_or(&artifact.path);
+        writeln!(out, "  {}  ({})", path.display(), artifact.build_system)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rules::{BUILTIN_RULES, all_rules, parse_rules};
+
+    fn messages(lint: &Lint) -> Vec<(Severity, Option<usize>, &str)> {
+        lint.findings
+            .iter()
+            .map(|f| (f.severity, f.line, f.message.as_str()))
+            .collect()
+    }
+
+    #[test]
+    fn reports_every_invalid_rule_with_its_line() {
+        let text = r#"# My rules
+[[rule]]
+id = "bad-regex"
+system = "Bad"
+regex = "(unclosed"
+artifact = "x"
+marker = "always"
+risk = "cache"
+
+[[rule]]
+id = "ok"
+system = "Zig"
+dir = "zig-cache"
+marker = { files = ["build.zig"] }
+risk = "cache"
+
+[[rule]]
+id = "two-matches"
+system = "Bad"
+dir = "out"
+suffix = ".out"
+marker = "always"
+risk = "output"
+"#;
+        let lint = lint(text, &[]);
+        assert!(lint.failed());
+        assert_eq!(lint.rules.len(), 1);
+        let found = messages(&lint);
+        assert_eq!(found.len(), 2);
+        assert_eq!(found[0].1, Some(2));
+        assert!(found[0].2.starts_with("rule 1 (bad-regex): invalid regex"));
+        assert_eq!(found[1].1, Some(17));
+        assert!(found[1].2.contains("exactly one of"));
+    }
+
+    #[test]
+    fn reports_syntax_errors_with_their_line() {
+        let lint = lint("[[rule]]\nid = \"x\"\nsytem = \"X\"\n", &[]);
+        let found = messages(&lint);
+        assert_eq!(found.len(), 1);
+        assert_eq!(found[0].0, Severity::Error);
+        assert_eq!(found[0].1, Some(3));
+        assert!(found[0].2.contains("sytem"), "{}", found[0].2);
+    }
+
+    #[test]
+    fn warns_about_shadowed_rules() {
+        let text = r#"
+[[rule]]
+id = "my-cargo"
+system = "My Cargo"
+dir = "target"
+marker = { files = ["Cargo.toml"] }
+risk = "output"
+
+[[rule]]
+id = "my-pycache"
+system = "My Python"
+dir = "__pycache__"
+marker = { files = ["setup.py"] }
+risk = "cache"
+
+[[rule]]
+id = "frob"
+system = "Frob"
+dir = "frob-out"
+marker = { files = ["Frobfile"] }
+risk = "output"
+
+[[rule]]
+id = "frob-again"
+system = "Frob"
+dir = "frob-out"
+marker = { all = [{ files = ["Frobfile"] }, { files = ["Frobfile.lock"] }] }
+risk = "output"
+"#;
+        let lint = lint(text, &all_rules());
+        assert!(!lint.failed());
+        let found = messages(&lint);
+        assert_eq!(found.len(), 3, "{found:?}");
+        assert_eq!(found[0].1, Some(2));
+        assert!(found[0].2.contains("built-in rule `cargo`"));
+        assert!(found[1].2.contains("built-in rule `python`"));
+        assert_eq!(found[2].1, Some(23));
+        assert!(found[2].2.contains("earlier rule `frob`"));
+    }
+
+    #[test]
+    fn builtin_rules_lint_cleanly() {
+        let lint = lint(BUILTIN_RULES, &[]);
+        assert_eq!(messages(&lint), []);
+        assert_eq!(lint.rules.len(), parse_rules(BUILTIN_RULES).unwrap().len());
+    }
+
+    #[test]
+    fn prints_findings_and_a_summary() {
+        let lint = lint("[[rule]]\nid = \"x\"\n", &[]);
+        let mut out = Vec::new();
+        print_lint(&mut out, "my.toml", &lint).unwrap();
+        let output = String::from_utf8(out).unwrap();
+        assert!(output.starts_with("my.toml:1: error: "), "{output}");
+        assert!(output.ends_with("my.toml: 0 valid rules, 1 error, 0 warnings\n"));
+    }
+
+    #[test]
+    fn rules_the_scanner_cannot_use_are_errors() {
+        let text = r#"[[rule]]
+id = "empty-needle"
+system = "X"
+dir = "build"
+marker = { file_contains = { file = "setup.cfg", needle = "" } }
+risk = "output"
+"#;
+        let lint = lint(text, &[]);
+        assert!(lint.failed());
+        assert!(lint.rules.is_empty());
+        let found = messages(&lint);
+        assert_eq!(found.len(), 1);
+        assert!(
+            found[0].2.contains("needs a `file` and a `needle`"),
+            "{}",
+            found[0].2
+        );
+    }
+}
@@ -0,0 +1,172 @@
+use std::fs::{self, File, OpenOptions};
+use std::io::{Read, Write};
+use std::path::{Path, PathBuf};
+
+use crate::cache::cache_dir;
+
+/// Error taking the run lock.
+#[derive(thiserror::Error, Debug)]
+pub enum LockError {
+    #[error("cannot lock {}: {source}", path.display())]
+    Io {
+        path: PathBuf,
+        source: std::io::Error,
+    },
+    #[error(
+        "another clean-builds run{} is already deleting under {}; wait for it to finish or pass --no-lock",
+        holder.map(|pid| format!(" (process {pid})")).unwrap_or_default(),
+        root.display()
+    )]
+    Held { root: PathBuf, holder: Option<u32> },
+}
+
+/// An advisory lock on a scan root, held until dropped, so that two runs
+/// deleting under the same root (say, cron and a person at a terminal) do
+/// not race to remove the same directories. Other roots, even nested ones,
+/// are not locked against.
+#[derive(Debug)]
+pub struct RunLock {
+    _file: File,
+}
+
+impl RunLock {
+    /// Lock `root`, which should be canonical, with a lock file under
+    /// `locks` in the [cache directory](cache_dir). `None` if there is no
+    /// cache directory to keep it in.
+    pub fn acquire(root: &Path) -> Option<Result<Self, LockError>> {
+        cache_dir().map(|dir| Self::acquire_in(&dir.join("locks"), root))
+    }
+
+    /// Lock `root` with a lock file in `dir`, named after a hash of the
+    /// root. Fails at once, rather than waiting, if another run holds it.
+    pub fn acquire_in(dir: &Path, root: &Path) -> Result<Self, LockError> {
+        let path = dir.join(format!("{:016x}.lock", path_hash(root)));
+        let io = |source| LockError::Io {
+            path: path.clone(),
+            source,
+        };
+        fs::create_dir_all(dir).map_err(io)?;
+        let mut file = open_exclusive(&path)
+            .map_err(io)?
+            .ok_or_else(|| LockError::Held {
+                root: root.to_path_buf(),
+                holder: holder(&path),
+            })?;
+        // Record who holds the lock for the message a second run prints.
+        file.set_len(0).map_err(io)?;
+        writeln!(file, "{}\n{}", std::process::id(), root.display()).map_err(io)?;
+        Ok(Self { _file: file })
+    }
+}
+
+/// Hash of `path` naming its lock file, the same in every version of the
+/// tool.
+fn path_hash(path: &Path) -> u64 {
+    fnv1a(path.as_os_str().as_encoded_bytes())
+}
+
+/// FNV-1a of `bytes`: stable across builds and platforms, unlike
+/// `DefaultHasher`.
+pub(crate) fn fnv1a(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0xcbf2_9ce4_8422_2325, |hash, &byte| {
+        (hash ^ u64::from(byte)).wrapping_mul(0x0100_0000_01b3)
+    })
+}
+
+/// The process ID recorded in the lock file at `path`, if it can be read.
+fn holder(path: &Path) -> Option<u32> {
+    let mut text = String::new();
+    File::open(path).ok()?.read_to_string(&mut text).ok()?;
+    text.lines().next()?.trim().parse().ok()
+}
+
+/// Open the lock file at `path` and lock it with `flock`, which the system
+/// releases when the process exits however it exits. `None` if another
+/// open file holds the lock.
+#[cfg(unix)]
+fn open_exclusive(path: &Path) -> std::io::Result<Option<File>> {
+    use std::os::fd::AsRawFd;
+
+    let file = OpenOptions::new()
+        .read(true)
+        .write(true)
+        .create(true)
+        .truncate(false)
+        .open(path)?;
+    // SAFETY: `file` is an open descriptor for the duration of the call.
+    if unsafe { libc::flock(file.as_raw_fd(), libc::LOCK_EX | libc::LOCK_NB) } == 0 {
+        return Ok(Some(file));
+    }
+    let e = std::io::Error::last_os_error();
+    if e.kind() == std::io::ErrorKind::WouldBlock {
+        Ok(None)
+    } else {
+        Err(e)
+    }
+}
+
+/// Open the lock file at `path` without sharing, which keeps every other
+/// open out until the process closes it or exits. `None` if another
+/// process has it open.
+#[cfg(windows)]
+fn open_exclusive(path: &Path) -> std::io::Result<Option<File>> {
+    use std::os::windows::fs::OpenOptionsExt;
+    const ERROR_SHARING_VIOLATION: i32 = 32;
+
+    match OpenOptions::new()
+        .read(true)
+        .write(true)
+        .create(true)
+        .truncate(false)
+        .share_mode(0)
+        .open(path)
+    {
+        Ok(file) => Ok(Some(file)),
+        Err(e) if e.raw_os_error() == Some(ERROR_SHARING_VIOLATION) => Ok(None),
+        Err(e) => Err(e),
+    }
+}
+
+#[cfg(not(any(unix, windows)))]
+fn open_exclusive(path: &Path) -> std::io::Result<Option<File>> {
+    OpenOptions::new()
+        .write(true)
+        .create(true)
+        .truncate(false)
+        .open(path)
+        .map(Some)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn second_lock_on_a_root_fails_until_the_first_is_dropped() {
+        let tmp = TempDir::new().unwrap();
+        let locks = tmp.path().join("locks");
+        let root = Path::new("/home/me/Developer");
+
+        let first = RunLock::acquire_in(&locks, root).unwrap();
+        match RunLock::acquire_in(&locks, root) {
+            Err(LockError::Held { root: held, holder }) => {
+                assert_eq!(held, root);
+                assert_eq!(holder, Some(std::process::id()));
+            }
+            other => panic!("expected the lock to be held, got {other:?}"),
+        }
+        // Other roots are not held up.
+        RunLock::acquire_in(&locks, Path::new("/home/me/Other")).unwrap();
+
+        drop(first);
+        RunLock::acquire_in(&locks, root).unwrap();
+    }
+
+    #[test]
+    fn path_hash_is_stable() {
+        assert_eq!(path_hash(Path::new("")), 0xcbf2_9ce4_8422_2325);
+        assert_eq!(path_hash(Path::new("a")), 0xaf63_dc4c_8601_ec8c);
+        assert_ne!(path_hash(Path::new("/a/b")), path_hash(Path::new("/a/c")));
+    }
+}
@@ -1,19 +1,458 @@
-use std::io;
+use std::env;
+use std::ffi::OsString;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
 use std::process;
+use std::sync::Arc;
+use std::time::SystemTime;
 
-use clap::Parser;
-use log::info;
+use clap::{CommandFactory, FromArgMatches};
+use console::Term;
+use log::{debug, warn};
 
-use clean_builds::cli::Cli;
-use clean_builds::delete::confirm_and_delete;
-use clean_builds::filter::ArtifactFilter;
-use clean_builds::output::{print_dry_run_footer, print_summary, print_systems};
-use clean_builds::rules::{all_rules, filter_rules_by_system};
-use clean_builds::scanner::scan;
+use clean_builds::CleanBuilds;
+use clean_builds::audit;
+use clean_builds::breakdown::breakdown;
+use clean_builds::cli::{Cli, Command, RulesAction, ScheduleAction};
+use clean_builds::config::{self, Config, ConfigError};
+use clean_builds::delete::{
+    DeleteError, DeleteOptions, DeleteReport, LargeDeletion, confirm_and_delete, confirm_by_risk,
+    confirm_per_system, finish_background,
+};
+use clean_builds::disk::bytes_by_volume;
+use clean_builds::doctor;
+use clean_builds::explain::{Explainer, report_path};
+use clean_builds::export::{read_json, revalidate, write_json};
+use clean_builds::git::repo_root;
+use clean_builds::hooks::Hooks;
+use clean_builds::interrupt::{CancelToken, InterruptGuard};
+use clean_builds::lint::{lint, print_lint, print_sample};
+use clean_builds::lock::RunLock;
+use clean_builds::output::{
+    print_breakdowns, print_dry_run_footer, print_explanations, print_path_report,
+    print_predicted_failures, print_summary, print_systems, print_volumes,
+};
+use clean_builds::preflight::predict_failures;
+use clean_builds::progress::SizingBar;
+use clean_builds::quarantine::{Quarantine, default_dir};
+use clean_builds::rules::{BUILTIN_RULES, MatchableRule, all_rules};
+use clean_builds::scanner::{Artifact, scan};
+use clean_builds::schedule::{Frequency, Schedule, ScheduleError, Scheduler};
 use clean_builds::size::compute_sizes;
+use clean_builds::throttle::Throttle;
+use clean_builds::tui::{self, Done, Review};
+use clean_builds::version::{capabilities, print_version};
+use clean_builds::watch::{WatchOptions, watch};
+
+// Exit statuses beyond 0 (success) and 1 (error); see `--help`.
+/// Some, but not all, deletions failed.
+const EXIT_PARTIAL: i32 = 3;
+/// Nothing was deleted: every deletion failed or the prompt was declined.
+const EXIT_NONE_DELETED: i32 = 4;
+/// A dry run with `--exit-code` found artifacts.
+const EXIT_FOUND: i32 = 5;
+/// A confirmation was needed, but `--no-input` forbade asking.
+const EXIT_INPUT_REQUIRED: i32 = 6;
+/// Interrupted by Ctrl+C, as shells report death by SIGINT.
+const EXIT_INTERRUPTED: i32 = 130;
+
+/// The command `--background` starts to finish deleting: this executable's
+/// hidden `background-worker` subcommand.
+fn background_worker() -> Vec<OsString> {
+    let exe = env::current_exe().unwrap_or_else(|e| {
+        eprintln!("Error: cannot locate clean-builds to run in the background: {e}");
+        process::exit(1);
+    });
+    vec![exe.into_os_string(), OsString::from("background-worker")]
+}
+
+/// Exit with the status for a deletion run meant to remove `wanted`
+/// artifacts, or return if it removed them all. Unless `strict`, artifacts
+/// skipped on read-only filesystems are not counted as wanted.
+fn exit_after_delete(result: Result<DeleteReport, DeleteError>, wanted: usize, strict: bool) {
+    match result {
+        Ok(report) if report.interrupted => process::exit(EXIT_INTERRUPTED),
+        Ok(report) if !strict && report.removed() == wanted - report.read_only() => {}
+        Ok(report) if report.removed() == wanted => {}
+        Ok(report) if report.removed() == 0 => process::exit(EXIT_NONE_DELETED),
+        Ok(_) => process::exit(EXIT_PARTIAL),
+        Err(e) => exit_on_delete_error(&e),
+    }
+}
+
+/// Exit with the status for a deletion run that failed with `e`.
+fn exit_on_delete_error(e: &DeleteError) -> ! {
+    if let DeleteError::InputRequired { .. } = e {
+        eprintln!("Error: {e}");
+        process::exit(EXIT_INPUT_REQUIRED);
+    }
+    eprintln!("Error during deletion: {e}");
+    process::exit(1);
+}
+
+/// `clean-builds delete --from-json FILE`: delete the artifacts of an
+/// exported scan that still match a rule, skipping the rest.
+fn delete_from_json(path: &Path, yes: bool, no_input: bool) {
+    if path == Path::new("-") && !yes {
+        eprintln!("Error: pass --yes to delete a list read from standard input");
+        process::exit(1);
+    }
+    let exported = match read_json(path) {
+        Ok(exported) => exported,
+        Err(e) => {
+            eprintln!("Error: {e}");
+            process::exit(1);
+        }
+    };
+    let (mut artifacts, rejected) = revalidate(&exported, &all_rules());
+    for (path, reason) in &rejected {
+        warn!("Skipping {}: {reason}", path.display());
+    }
+    if artifacts.is_empty() {
+        println!("No build artifacts found.");
+        if !rejected.is_empty() {
+            process::exit(EXIT_NONE_DELETED);
+        }
+        return;
+    }
+    compute_sizes(&mut artifacts);
+
+    let stdout = io::stdout();
+    let mut out = stdout.lock();
+    if let Err(e) = print_summary(&mut out, &artifacts, false) {
+        eprintln!("Error writing output: {e}");
+        process::exit(1);
+    }
+    let options = DeleteOptions {
+        skip_confirm: yes,
+        no_input,
+        handle_interrupt: true,
+        ..Default::default()
+    };
+    let stdin = io::stdin();
+    let result = confirm_and_delete(&mut out, &mut stdin.lock(), &artifacts, &options);
+    exit_after_delete(result, artifacts.len() + rejected.len(), false);
+}
+
+/// `clean-builds rules lint [FILE]`: check a rule file, or the built-in
+/// rules, and list what its rules match under `sample`.
+fn lint_rules(file: Option<&Path>, sample: Option<&Path>) {
+    let (name, text) = match file {
+        None => ("built-in rules".to_string(), BUILTIN_RULES.to_string()),
+        Some(path) => {
+            let text = if path == Path::new("-") {
+                io::read_to_string(io::stdin())
+            } else {
+                std::fs::read_to_string(path)
+            };
+            let text = text.unwrap_or_else(|e| {
+                eprintln!("Error: cannot read {}: {e}", path.display());
+                process::exit(1);
+            });
+            let name = if path == Path::new("-") {
+                "<stdin>".to_string()
+            } else {
+                path.display().to_string()
+            };
+            (name, text)
+        }
+    };
+    if let Some(root) = sample.filter(|root| !root.is_dir()) {
+        eprintln!("Error: {} is not a directory", root.display());
+        process::exit(1);
+    }
+    // The built-in rules would shadow themselves.
+    let builtin = if file.is_some() {
+        all_rules()
+    } else {
+        Vec::new()
+    };
+    let lint = lint(&text, &builtin);
+
+    let stdout = io::stdout();
+    let mut out = stdout.lock();
+    let result = print_lint(&mut out, &name, &lint).and_then(|()| match sample {
+        Some(root) => print_sample(&mut out, root, &scan(root, &lint.rules)),
+        None => Ok(()),
+    });
+    if let Err(e) = result {
+        eprintln!("Error writing output: {e}");
+        process::exit(1);
+    }
+    if lint.failed() {
+        process::exit(1);
+    }
+}
+
+/// `clean-builds schedule`: install, remove or show a scheduled cleanup.
+fn schedule(action: &ScheduleAction) {
+    let scheduler = Scheduler::for_platform().unwrap_or_else(|e| {
+        eprintln!("Error: {e}");
+        process::exit(1);
+    });
+    let stdout = io::stdout();
+    let mut out = stdout.lock();
+    let result = match action {
+        ScheduleAction::Install {
+            path,
+            daily,
+            weekly: _,
+            monthly,
+            profile,
+            print,
+        } => {
+            let canonical = |path: &Path| {
+                path.canonicalize().unwrap_or_else(|e| {
+                    eprintln!("Error: cannot resolve {}: {e}", path.display());
+                    process::exit(1);
+                })
+            };
+            let exe = env::current_exe().unwrap_or_else(|e| {
+                eprintln!("Error: cannot locate clean-builds to schedule: {e}");
+                process::exit(1);
+            });
+            let schedule = Schedule {
+                exe: canonical(&exe),
+                root: canonical(path),
+                frequency: if *daily {
+                    Frequency::Daily
+                } else if *monthly {
+                    Frequency::Monthly
+                } else {
+                    Frequency::Weekly
+                },
+                profile: *profile,
+            };
+            if *print {
+                scheduler
+                    .files(&schedule)
+                    .iter()
+                    .try_for_each(|(path, contents)| {
+                        writeln!(out, "# {}\n{contents}", path.display())
+                    })
+                    .map_err(ScheduleError::from)
+            } else {
+                scheduler.install(&mut out, &schedule)
+            }
+        }
+        ScheduleAction::Remove => scheduler.remove(&mut out),
+        ScheduleAction::Status => scheduler.status(&mut out),
+    };
+    if let Err(e) = result {
+        eprintln!("Error: {e}");
+        process::exit(1);
+    }
+}
+
+/// Lock `root` against other runs deleting under it (see [`RunLock`]),
+/// exiting if one is. `None` if there is nowhere to keep the lock.
+fn lock_root(root: &Path) -> Option<RunLock> {
+    match RunLock::acquire(root) {
+        Some(Ok(lock)) => Some(lock),
+        Some(Err(e)) => {
+            eprintln!("Error: {e}");
+            process::exit(1);
+        }
+        None => {
+            debug!("No cache directory to keep a run lock in; not locking");
+            None
+        }
+    }
+}
+
+/// Let the user pick artifacts found under `root` in the terminal UI
+/// (`tui`), then delete those they confirmed.
+fn review_and_delete(root: &Path, artifacts: Vec<Artifact>) {
+    let term = Term::stdout();
+    if !term.is_term() {
+        eprintln!("Error: tui needs a terminal");
+        process::exit(1);
+    }
+    let mut review = Review::new(root, artifacts, SystemTime::now());
+    match tui::run(&mut review) {
+        Ok(Done::Delete) => {}
+        Ok(Done::Quit) => return,
+        Err(e) => {
+            eprintln!("Error: {e}");
+            process::exit(1);
+        }
+    }
+    let selected = review.selected();
+    let _lock = lock_root(root);
+    let options = DeleteOptions {
+        // Confirmed in the UI.
+        skip_confirm: true,
+        handle_interrupt: true,
+        ..Default::default()
+    };
+    let stdout = io::stdout();
+    let stdin = io::stdin();
+    let result = confirm_and_delete(&mut stdout.lock(), &mut stdin.lock(), &selected, &options);
+    exit_after_delete(result, selected.len(), false);
+}
+
+/// How `--delete` removes artifacts, as the options in `cli` say. Exits if
+/// the quarantine or the deletion log has nowhere to go.
+fn delete_options(cli: &Cli) -> DeleteOptions {
+    let quarantine = if cli.quarantine {
+        match default_dir() {
+            Ok(dir) => Some(dir),
+            Err(e) => {
+                eprintln!("Error: {e}");
+                process::exit(1);
+            }
+        }
+    } else {
+        None
+    };
+    let log_file = match cli.log_file.clone() {
+        Some(Some(path)) => Some(path),
+        Some(None) => match audit::default_path() {
+            Ok(path) => Some(path),
+            Err(e) => {
+                eprintln!("Error: {e}");
+                process::exit(1);
+            }
+        },
+        None => None,
+    };
+    let default_large = LargeDeletion::default();
+    DeleteOptions {
+        skip_confirm: cli.yes || cli.confirm_per_system || !cli.yes_for.is_empty(),
+        no_input: cli.no_input,
+        trash: cli.trash,
+        quarantine,
+        use_tool: cli.use_tool,
+        shred: cli.shred,
+        background: cli.background.then(background_worker),
+        contents_only: cli.contents_only,
+        throttle: cli.nice.then(Throttle::default),
+        force_permissions: cli.force_permissions,
+        timeout: cli.timeout,
+        log_file,
+        handle_interrupt: true,
+        force_in_use: cli.force_in_use,
+        hooks: Hooks {
+            before_run: cli.before_run.clone(),
+            after_run: cli.after_run.clone(),
+            before_delete: cli.before_delete.clone(),
+            after_delete: cli.after_delete.clone(),
+        },
+        large: LargeDeletion {
+            bytes: cli.strict_confirm_size.unwrap_or(default_large.bytes),
+            count: cli.strict_confirm_count.unwrap_or(default_large.count),
+        },
+        ..Default::default()
+    }
+}
+
+/// The command line parser, with the defaults in the config file.
+fn command() -> clap::Command {
+    let Some(path) = config::default_path() else {
+        return Cli::command();
+    };
+    match Config::load(&path) {
+        Ok(Some(config)) => config.apply(Cli::command()),
+        Ok(None) => Cli::command(),
+        Err(e) => {
+            eprintln!("Error: {e}");
+            process::exit(1);
+        }
+    }
+}
+
+/// Write the starter config file (`init`), or print it.
+fn init_config(path: &Path, exclude: &[String], force: bool, print: bool) {
+    let root = match path.canonicalize() {
+        Ok(p) => p,
+        Err(e) => {
+            eprintln!("Error: cannot access '{}': {e}", path.display());
+            process::exit(1);
+        }
+    };
+    let text = config::starter(&root, exclude);
+    if print {
+        print!("{text}");
+        return;
+    }
+    let written = config::default_path()
+        .ok_or(ConfigError::NoHome)
+        .and_then(|file| config::write(&file, &text, force).map(|()| file));
+    match written {
+        Ok(file) => println!("Wrote {}", file.display()),
+        Err(e) => {
+            eprintln!("Error: {e}");
+            process::exit(1);
+        }
+    }
+}
+
+/// For `clean-builds explain PATH RUN...`, the run to explain, parsed from
+/// RUN, and PATH; otherwise `cli` itself. The run never deletes.
+fn explained_run(mut cli: Cli) -> (Cli, Option<PathBuf>) {
+    match cli.command.take() {
+        Some(Command::Explain { path, run }) => {
+            let args = std::iter::once(OsString::from("clean-builds")).chain(run);
+            let mut run = command()
+                .try_get_matches_from(args)
+                .and_then(|matches| Cli::from_arg_matches(&matches))
+                .unwrap_or_else(|e| e.exit());
+            if run.command.is_some() {
+                eprintln!("Error: explain takes the root and options of a scan, not a subcommand");
+                process::exit(2);
+            }
+            run.delete = false;
+            (run, Some(path))
+        }
+        command => {
+            cli.command = command;
+            (cli, None)
+        }
+    }
+}
+
+/// Print what the run under `root`, with the `active` rules, made of
+/// `path`, given the stages recorded in `explainer` and the `selected`
+/// artifacts.
+fn explain_path_in_run(
+    root: &Path,
+    path: &Path,
+    active: &[MatchableRule],
+    explainer: &Explainer,
+    selected: &[Artifact],
+) {
+    // Resolve the parent only, so a symlink is explained as one.
+    let absolute = match (path.parent(), path.file_name()) {
+        (Some(parent), Some(name)) => {
+            let parent = if parent.as_os_str().is_empty() {
+                Path::new(".")
+            } else {
+                parent
+            };
+            parent.canonicalize().map(|p| p.join(name))
+        }
+        _ => path.canonicalize(),
+    };
+    let path = match absolute.and_then(|p| p.symlink_metadata().map(|_| p)) {
+        Ok(p) => p,
+        Err(e) => {
+            eprintln!("Error: cannot access '{}': {e}", path.display());
+            process::exit(1);
+        }
+    };
+    let report = report_path(root, &path, &all_rules(), active, explainer, selected);
+    let stdout = io::stdout();
+    if let Err(e) = print_path_report(&mut stdout.lock(), root, &report) {
+        eprintln!("Error writing output: {e}");
+        process::exit(1);
+    }
+}
 
 fn main() {
-    let cli = Cli::parse();
+    // Kept to tell `doctor` which options were set and where.
+    let matches = command().get_matches();
+    let cli = Cli::from_arg_matches(&matches).unwrap_or_else(|e| e.exit());
+    let (cli, explain_path) = explained_run(cli);
 
     env_logger::Builder::new()
         .filter_level(if cli.verbose {
@@ -25,6 +464,15 @@ fn main() {
         .format_target(false)
         .init();
 
+    if let Some(jobs) = cli.jobs {
+        if let Err(e) = rayon::ThreadPoolBuilder::new()
+            .num_threads(jobs.get())
+            .build_global()
+        {
+            warn!("cannot limit threads to {jobs}: {e}");
+        }
+    }
+
     if cli.list_systems {
         let stdout = io::stdout();
         let mut out = stdout.lock();
@@ -35,63 +483,297 @@ fn main() {
         return;
     }
 
-    let rules = match filter_rules_by_system(all_rules(), &cli.system, &cli.exclude_system) {
-        Ok(r) => r,
-        Err(e) => {
+    if let Some(Command::BackgroundWorker { paths }) = &cli.command {
+        finish_background(paths);
+        return;
+    }
+
+    if let Some(Command::Delete {
+        from_json,
+        yes,
+        no_input,
+    }) = &cli.command
+    {
+        delete_from_json(from_json, *yes, *no_input);
+        return;
+    }
+
+    if let Some(Command::Watch {
+        path,
+        interval,
+        delete,
+        scans,
+    }) = &cli.command
+    {
+        let exe = env::current_exe().unwrap_or_else(|e| {
+            eprintln!("Error: cannot locate clean-builds to rescan with: {e}");
+            process::exit(1);
+        });
+        let options = WatchOptions {
+            interval: *interval,
+            delete: *delete,
+            scans: *scans,
+        };
+        let stdout = io::stdout();
+        if let Err(e) = watch(&mut stdout.lock(), &[exe.into_os_string()], path, &options) {
+            eprintln!("Error writing output: {e}");
+            process::exit(1);
+        }
+        return;
+    }
+
+    if let Some(Command::Rules {
+        action: RulesAction::Lint { file, sample },
+    }) = &cli.command
+    {
+        lint_rules(file.as_deref(), sample.as_deref());
+        return;
+    }
+
+    if let Some(Command::Schedule { action }) = &cli.command {
+        schedule(action);
+        return;
+    }
+
+    if let Some(Command::Init {
+        path,
+        exclude,
+        force,
+        print,
+    }) = &cli.command
+    {
+        init_config(path, exclude, *force, *print);
+        return;
+    }
+
+    if let Some(Command::Version { json }) = &cli.command {
+        let stdout = io::stdout();
+        if let Err(e) = print_version(&mut stdout.lock(), &capabilities(), *json) {
+            eprintln!("Error writing output: {e}");
+            process::exit(1);
+        }
+        return;
+    }
+
+    if let Some(Command::Doctor { path }) = &cli.command {
+        let report = doctor::diagnose(&cli, &matches, path);
+        let stdout = io::stdout();
+        if let Err(e) = doctor::print_report(&mut stdout.lock(), &report) {
+            eprintln!("Error writing output: {e}");
+            process::exit(1);
+        }
+        if report.failed() {
+            process::exit(1);
+        }
+        return;
+    }
+
+    let tui = matches!(cli.command, Some(Command::Tui { .. }));
+    if let Some(command) = cli.command.as_ref().filter(|_| !tui) {
+        let quarantine = match default_dir() {
+            Ok(dir) => Quarantine::new(&dir),
+            Err(e) => {
+                eprintln!("Error: {e}");
+                process::exit(1);
+            }
+        };
+        let stdout = io::stdout();
+        let mut out = stdout.lock();
+        let result = match command {
+            Command::Restore { run } => quarantine.restore(&mut out, run.as_deref()),
+            Command::Purge { run } => quarantine.purge(&mut out, run.as_deref()),
+            Command::Delete { .. }
+            | Command::Doctor { .. }
+            | Command::Tui { .. }
+            | Command::Watch { .. }
+            | Command::Version { .. }
+            | Command::Schedule { .. }
+            | Command::Rules { .. }
+            | Command::Explain { .. }
+            | Command::Init { .. }
+            | Command::BackgroundWorker { .. } => {
+                unreachable!("handled above")
+            }
+        };
+        if let Err(e) = result {
             eprintln!("Error: {e}");
             process::exit(1);
         }
-    };
+        return;
+    }
 
-    let root = match cli.path.canonicalize() {
+    let delete_options = delete_options(&cli);
+
+    let path = match &cli.command {
+        Some(Command::Tui { path }) => path,
+        _ => &cli.path,
+    };
+    let root = match path.canonicalize() {
         Ok(p) => p,
         Err(e) => {
-            eprintln!("Error: cannot access '{}': {e}", cli.path.display());
+            eprintln!("Error: cannot access '{}': {e}", path.display());
             process::exit(1);
         }
     };
+    let root = if cli.git_root {
+        match repo_root(&root) {
+            Some(top) => top.to_path_buf(),
+            None => {
+                eprintln!("Error: '{}' is not inside a Git repository", path.display());
+                process::exit(1);
+            }
+        }
+    } else {
+        root
+    };
+    // Held until the process exits, which releases it.
+    let _lock = (cli.delete && !cli.no_lock).then(|| lock_root(&root));
 
-    let filter = match ArtifactFilter::new(&cli.include, &cli.exclude) {
-        Ok(f) => f,
+    let cleanup = match CleanBuilds::from_cli(&cli, &root) {
+        Ok(cleanup) => cleanup,
+        Err(e) => {
+            eprintln!("Error: {e}");
+            process::exit(1);
+        }
+    };
+    // The first Ctrl+C stops the scan and sizing, and what they found so far
+    // is reported without deleting anything; a second one exits.
+    let interrupt = InterruptGuard::install()
+        .map_err(|e| warn!("Cannot handle Ctrl+C: {e}"))
+        .ok();
+    let cancel = interrupt
+        .as_ref()
+        .map_or_else(CancelToken::new, InterruptGuard::cancel_token);
+    let bar = Arc::new(SizingBar::new());
+    let cleanup = cleanup
+        .progress(Arc::clone(&bar))
+        .cancel_with(cancel)
+        .delete_options(delete_options.clone());
+    let (mut artifacts, explainer) = match cleanup.scan_explained() {
+        Ok(scanned) => scanned,
         Err(e) => {
             eprintln!("Error: {e}");
             process::exit(1);
         }
     };
+    bar.finish();
+    let interrupted = interrupt.as_ref().is_some_and(InterruptGuard::interrupted);
+    drop(interrupt);
+    if interrupted {
+        warn!("Interrupted; the results below are partial and nothing will be deleted");
+    }
+    // Dropping the guard armed its token; deletion handles Ctrl+C itself.
+    let cleanup = cleanup.cancel_with(CancelToken::new());
+
+    if let Some(path) = explain_path {
+        explain_path_in_run(&root, &path, cleanup.active_rules(), &explainer, &artifacts);
+        return;
+    }
+
+    if tui && !interrupted {
+        if artifacts.is_empty() {
+            println!("No build artifacts found.");
+            return;
+        }
+        review_and_delete(&root, artifacts);
+        return;
+    }
 
-    info!("Scanning {}", root.display());
-    let mut artifacts = scan(&root, &rules);
+    if cli.explain {
+        let stdout = io::stdout();
+        if let Err(e) = print_explanations(&mut stdout.lock(), &root, &explainer) {
+            eprintln!("Error writing output: {e}");
+            process::exit(1);
+        }
+    }
 
-    info!("Filtering artifacts");
-    artifacts = filter.apply(&root, artifacts);
+    if cli.json {
+        let stdout = io::stdout();
+        if let Err(e) = write_json(&mut stdout.lock(), &root, &artifacts) {
+            eprintln!("Error writing output: {e}");
+            process::exit(1);
+        }
+        if interrupted {
+            process::exit(EXIT_INTERRUPTED);
+        }
+        if cli.exit_code && !artifacts.is_empty() {
+            process::exit(EXIT_FOUND);
+        }
+        return;
+    }
 
     if artifacts.is_empty() {
         println!("No build artifacts found.");
+        if interrupted {
+            process::exit(EXIT_INTERRUPTED);
+        }
         return;
     }
 
-    info!("Computing sizes for {} artifacts", artifacts.len());
-    compute_sizes(&mut artifacts);
-
     let stdout = io::stdout();
     let mut out = stdout.lock();
 
-    if let Err(e) = print_summary(&mut out, &artifacts, cli.verbose) {
+    if let Err(e) = print_summary(&mut out, &artifacts, cli.verbose)
+        .and_then(|()| print_volumes(&mut out, &bytes_by_volume(&artifacts)))
+    {
         eprintln!("Error writing output: {e}");
         process::exit(1);
     }
 
-    if cli.delete {
+    if let Some(top) = cli.breakdown {
+        let mut largest: Vec<_> = artifacts.iter().collect();
+        largest.sort_by_key(|a| std::cmp::Reverse(a.size_bytes));
+        largest.truncate(top.unwrap_or(usize::MAX));
+        let breakdowns: Vec<_> = largest
+            .into_iter()
+            .map(|a| (a, breakdown(&a.path)))
+            .collect();
+        if let Err(e) = print_breakdowns(&mut out, &breakdowns) {
+            eprintln!("Error writing output: {e}");
+            process::exit(1);
+        }
+    }
+
+    if cli.delete && !interrupted {
         let stdin = io::stdin();
         let mut input = stdin.lock();
-        match confirm_and_delete(&mut out, &mut input, &artifacts, cli.yes) {
-            Ok(_) => {}
-            Err(e) => {
-                eprintln!("Error during deletion: {e}");
+        if cli.confirm_per_system {
+            artifacts = match confirm_per_system(&mut out, &mut input, &artifacts, &delete_options)
+            {
+                Ok(confirmed) if confirmed.is_empty() => process::exit(EXIT_NONE_DELETED),
+                Ok(confirmed) => confirmed,
+                Err(e) => exit_on_delete_error(&e),
+            };
+        }
+        if !cli.yes_for.is_empty() {
+            artifacts = match confirm_by_risk(
+                &mut out,
+                &mut input,
+                &artifacts,
+                &delete_options,
+                &cli.yes_for,
+            ) {
+                Ok(confirmed) if confirmed.is_empty() => process::exit(EXIT_NONE_DELETED),
+                Ok(confirmed) => confirmed,
+                Err(e) => exit_on_delete_error(&e),
+            };
+        }
+        let result = cleanup.confirm_and_delete(&mut out, &mut input, &artifacts);
+        exit_after_delete(result, artifacts.len(), cli.strict);
+    } else {
+        if cli.check {
+            let failures = predict_failures(&artifacts);
+            if let Err(e) = print_predicted_failures(&mut out, &root, &failures) {
+                eprintln!("Error writing output: {e}");
                 process::exit(1);
             }
         }
-    } else {
         let _ = print_dry_run_footer(&mut out);
+        if interrupted {
+            process::exit(EXIT_INTERRUPTED);
+        }
+        if cli.exit_code {
+            process::exit(EXIT_FOUND);
+        }
     }
 }
@@ -0,0 +1,477 @@
+use std::io;
+use std::path::Path;
+use std::time::SystemTime;
+
+/// One entry found by [`walk`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct NativeEntry {
+    pub is_dir: bool,
+    pub is_file: bool,
+    pub is_symlink: bool,
+    /// Apparent length; only read for files and symlinks unless every entry
+    /// is statted.
+    pub len: u64,
+    /// Space allocated on disk, where the listing reports it.
+    pub allocated: Option<u64>,
+    /// Device and inode of a file with more than one hard link, where the
+    /// listing reports link counts.
+    pub link: Option<(u64, u64)>,
+    pub modified: Option<SystemTime>,
+}
+
+/// Walk everything below the directory `root` with the platform's batched
+/// directory APIs, without following symlinks, calling `visit` for each
+/// entry. Files and symlinks are always statted; other entries only if
+/// `stat_all`.
+/// Directories that cannot be read are skipped. Fails, having visited
+/// nothing, if `root` cannot be read or the platform has no native walk.
+pub fn walk(root: &Path, stat_all: bool, visit: &mut dyn FnMut(&NativeEntry)) -> io::Result<()> {
+    imp::walk(root, stat_all, visit)
+}
+
+/// `getdents64` reads a whole buffer of entries per call, and `statx`
+/// fetches only the fields sizing needs, without syncing network
+/// filesystems.
+#[cfg(all(target_os = "linux", target_env = "gnu"))]
+mod imp {
+    use std::ffi::{CStr, CString};
+    use std::io;
+    use std::os::fd::{AsRawFd, FromRawFd, OwnedFd};
+    use std::os::unix::ffi::OsStrExt;
+    use std::path::Path;
+    use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+    use super::NativeEntry;
+
+    pub fn walk(
+        root: &Path,
+        stat_all: bool,
+        visit: &mut dyn FnMut(&NativeEntry),
+    ) -> io::Result<()> {
+        let root = CString::new(root.as_os_str().as_bytes())?;
+        let dir = open_dir(libc::AT_FDCWD, &root)?;
+        let mut buf = vec![0u8; 64 * 1024];
+        walk_dir(&dir, stat_all, &mut buf, visit)
+    }
+
+    fn open_dir(parent: libc::c_int, name: &CStr) -> io::Result<OwnedFd> {
+        let flags = libc::O_RDONLY | libc::O_DIRECTORY | libc::O_NOFOLLOW | libc::O_CLOEXEC;
+        // SAFETY: `name` is NUL-terminated and `parent` is a directory fd or AT_FDCWD.
+        let fd = unsafe { libc::openat(parent, name.as_ptr(), flags) };
+        if fd < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        // SAFETY: `fd` was just opened and nothing else owns it.
+        Ok(unsafe { OwnedFd::from_raw_fd(fd) })
+    }
+
+    /// Visit the entries of `dir`, then walk its subdirectories. Only the
+    /// names of subdirectories are held while listing, so one buffer serves
+    /// every level.
+    fn walk_dir(
+        dir: &OwnedFd,
+        stat_all: bool,
+        buf: &mut [u8],
+        visit: &mut dyn FnMut(&NativeEntry),
+    ) -> io::Result<()> {
+        let mut subdirs = Vec::new();
+        loop {
+            // SAFETY: `buf` is writable for its whole length.
+            let read = unsafe {
+                libc::syscall(
+                    libc::SYS_getdents64,
+                    dir.as_raw_fd(),
+                    buf.as_mut_ptr(),
+                    buf.len(),
+                )
+            };
+            if read < 0 {
+                return Err(io::Error::last_os_error());
+            }
+            if read == 0 {
+                break;
+            }
+            // Each record is a linux_dirent64: d_ino (8 bytes), d_off (8),
+            // d_reclen (2), d_type (1), then the NUL-terminated name.
+            let mut pos = 0;
+            while pos < read as usize {
+                let record = &buf[pos..];
+                let reclen = usize::from(u16::from_ne_bytes([record[16], record[17]]));
+                let d_type = record[18];
+                pos += reclen;
+                let Ok(name) = CStr::from_bytes_until_nul(&record[19..reclen]) else {
+                    continue;
+                };
+                if name == c"." || name == c".." {
+                    continue;
+                }
+                let mut entry = NativeEntry {
+                    is_dir: d_type == libc::DT_DIR,
+                    is_file: d_type == libc::DT_REG,
+                    is_symlink: d_type == libc::DT_LNK,
+                    ..Default::default()
+                };
+                // Some filesystems leave the type to a stat.
+                if stat_all || entry.is_file || entry.is_symlink || d_type == libc::DT_UNKNOWN {
+                    match stat(dir, name) {
+                        Some(statted) => entry = statted,
+                        None if d_type == libc::DT_UNKNOWN => continue,
+                        None => {}
+                    }
+                }
+                if entry.is_dir {
+                    subdirs.push(name.to_owned());
+                }
+                visit(&entry);
+            }
+        }
+        for name in subdirs {
+            if let Ok(subdir) = open_dir(dir.as_raw_fd(), &name) {
+                let _ = walk_dir(&subdir, stat_all, buf, visit);
+            }
+        }
+        Ok(())
+    }
+
+    fn stat(dir: &OwnedFd, name: &CStr) -> Option<NativeEntry> {
+        let mask = libc::STATX_TYPE
+            | libc::STATX_NLINK
+            | libc::STATX_INO
+            | libc::STATX_SIZE
+            | libc::STATX_BLOCKS
+            | libc::STATX_MTIME;
+        let flags = libc::AT_SYMLINK_NOFOLLOW | libc::AT_STATX_DONT_SYNC;
+        // SAFETY: statx fills the zeroed struct; `name` is NUL-terminated.
+        let mut st: libc::statx = unsafe { std::mem::zeroed() };
+        let rc = unsafe { libc::statx(dir.as_raw_fd(), name.as_ptr(), flags, mask, &mut st) };
+        if rc != 0 {
+            return None;
+        }
+        let kind = u32::from(st.stx_mode) & libc::S_IFMT;
+        let is_dir = kind == libc::S_IFDIR;
+        let seconds = Duration::from_secs(st.stx_mtime.tv_sec.unsigned_abs());
+        let modified = if st.stx_mtime.tv_sec >= 0 {
+            UNIX_EPOCH.checked_add(seconds)
+        } else {
+            UNIX_EPOCH.checked_sub(seconds)
+        }
+        .and_then(|t: SystemTime| t.checked_add(Duration::from_nanos(st.stx_mtime.tv_nsec.into())));
+        Some(NativeEntry {
+            is_dir,
+            is_file: kind == libc::S_IFREG,
+            is_symlink: kind == libc::S_IFLNK,
+            len: st.stx_size,
+            allocated: Some(st.stx_blocks * 512),
+            link: (st.stx_nlink > 1 && !is_dir).then(|| {
+                let dev = libc::makedev(st.stx_dev_major, st.stx_dev_minor);
+                (dev, st.stx_ino)
+            }),
+            modified,
+        })
+    }
+}
+
+/// `getattrlistbulk` returns the name, type and sizes of many entries per
+/// call, with no separate stat.
+#[cfg(target_os = "macos")]
+mod imp {
+    use std::ffi::{CStr, CString};
+    use std::io;
+    use std::mem::size_of;
+    use std::os::fd::{AsRawFd, FromRawFd, OwnedFd};
+    use std::os::unix::ffi::OsStrExt;
+    use std::path::Path;
+    use std::time::{Duration, UNIX_EPOCH};
+
+    use super::NativeEntry;
+
+    // From `enum vtype` in <sys/vnode.h>.
+    const VREG: u32 = 1;
+    const VDIR: u32 = 2;
+    const VLNK: u32 = 5;
+
+    pub fn walk(
+        root: &Path,
+        _stat_all: bool,
+        visit: &mut dyn FnMut(&NativeEntry),
+    ) -> io::Result<()> {
+        let root = CString::new(root.as_os_str().as_bytes())?;
+        let dir = open_dir(libc::AT_FDCWD, &root)?;
+        let mut buf = vec![0u8; 256 * 1024];
+        walk_dir(&dir, &mut buf, visit)
+    }
+
+    fn open_dir(parent: libc::c_int, name: &CStr) -> io::Result<OwnedFd> {
+        let flags = libc::O_RDONLY | libc::O_DIRECTORY | libc::O_NOFOLLOW | libc::O_CLOEXEC;
+        // SAFETY: `name` is NUL-terminated and `parent` is a directory fd or AT_FDCWD.
+        let fd = unsafe { libc::openat(parent, name.as_ptr(), flags) };
+        if fd < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        // SAFETY: `fd` was just opened and nothing else owns it.
+        Ok(unsafe { OwnedFd::from_raw_fd(fd) })
+    }
+
+    /// Read a `T` at `offset` in `record`; attributes are only 4-byte aligned.
+    fn read<T: Copy>(record: &[u8], offset: usize) -> T {
+        assert!(offset + size_of::<T>() <= record.len());
+        // SAFETY: the bounds are checked above and T is plain data.
+        unsafe { std::ptr::read_unaligned(record.as_ptr().add(offset).cast()) }
+    }
+
+    fn walk_dir(
+        dir: &OwnedFd,
+        buf: &mut [u8],
+        visit: &mut dyn FnMut(&NativeEntry),
+    ) -> io::Result<()> {
+        let mut attrs = libc::attrlist {
+            bitmapcount: libc::ATTR_BIT_MAP_COUNT,
+            reserved: 0,
+            commonattr: libc::ATTR_CMN_RETURNED_ATTRS
+                | libc::ATTR_CMN_NAME
+                | libc::ATTR_CMN_DEVID
+                | libc::ATTR_CMN_OBJTYPE
+                | libc::ATTR_CMN_MODTIME
+                | libc::ATTR_CMN_FILEID,
+            volattr: 0,
+            dirattr: 0,
+            fileattr: libc::ATTR_FILE_LINKCOUNT
+                | libc::ATTR_FILE_ALLOCSIZE
+                | libc::ATTR_FILE_DATALENGTH,
+            forkattr: 0,
+        };
+        let mut subdirs = Vec::new();
+        loop {
+            // SAFETY: `attrs` and `buf` are valid for the sizes passed.
+            let count = unsafe {
+                libc::getattrlistbulk(
+                    dir.as_raw_fd(),
+                    (&raw mut attrs).cast(),
+                    buf.as_mut_ptr().cast(),
+                    buf.len(),
+                    u64::from(libc::FSOPT_PACK_INVAL_ATTRS),
+                )
+            };
+            if count < 0 {
+                return Err(io::Error::last_os_error());
+            }
+            if count == 0 {
+                break;
+            }
+            // With FSOPT_PACK_INVAL_ATTRS every record has every requested
+            // attribute, in bit order: its length, the returned set, then
+            // name, device, type, mtime and file ID, then link count,
+            // allocated size and length.
+            let mut pos = 0;
+            for _ in 0..count {
+                let record = &buf[pos..];
+                let length = read::<u32>(record, 0) as usize;
+                pos += length;
+                let record = &record[..length];
+                let mut at = 4 + size_of::<libc::attribute_set_t>();
+                let name_ref: libc::attrreference_t = read(record, at);
+                let name_start = at + name_ref.attr_dataoffset as usize;
+                at += size_of::<libc::attrreference_t>();
+                let dev: libc::dev_t = read(record, at);
+                at += size_of::<libc::dev_t>();
+                let kind: u32 = read(record, at);
+                at += size_of::<u32>();
+                let mtime: libc::timespec = read(record, at);
+                at += size_of::<libc::timespec>();
+                let ino: u64 = read(record, at);
+                at += size_of::<u64>();
+                let links: u32 = read(record, at);
+                at += size_of::<u32>();
+                let allocated: libc::off_t = read(record, at);
+                at += size_of::<libc::off_t>();
+                let len: libc::off_t = read(record, at);
+
+                let Some(name) = record
+                    .get(name_start..name_start + name_ref.attr_length as usize)
+                    .and_then(|n| CStr::from_bytes_until_nul(n).ok())
+                else {
+                    continue;
+                };
+                let is_dir = kind == VDIR;
+                let modified = u64::try_from(mtime.tv_sec).ok().map(|secs| {
+                    UNIX_EPOCH + Duration::new(secs, mtime.tv_nsec.clamp(0, 999_999_999) as u32)
+                });
+                let entry = NativeEntry {
+                    is_dir,
+                    is_file: kind == VREG,
+                    is_symlink: kind == VLNK,
+                    len: len.max(0) as u64,
+                    allocated: Some(allocated.max(0) as u64),
+                    link: (links > 1 && !is_dir).then_some((dev as u32 as u64, ino)),
+                    modified,
+                };
+                if is_dir {
+                    subdirs.push(name.to_owned());
+                }
+                visit(&entry);
+            }
+        }
+        for name in subdirs {
+            if let Ok(subdir) = open_dir(dir.as_raw_fd(), &name) {
+                let _ = walk_dir(&subdir, buf, visit);
+            }
+        }
+        Ok(())
+    }
+}
+
+/// `FindFirstFileExW` with `FIND_FIRST_EX_LARGE_FETCH` lists a directory
+/// with each entry's attributes, length and times in few round trips, which
+/// matters most over SMB. Link counts are not listed, so hard links are
+/// counted per name.
+#[cfg(windows)]
+mod imp {
+    use std::ffi::OsString;
+    use std::io;
+    use std::os::windows::ffi::{OsStrExt, OsStringExt};
+    use std::path::{Path, PathBuf};
+    use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+    use windows_sys::Win32::Foundation::{FILETIME, INVALID_HANDLE_VALUE};
+    use windows_sys::Win32::Storage::FileSystem::{
+        FILE_ATTRIBUTE_COMPRESSED, FILE_ATTRIBUTE_DIRECTORY, FILE_ATTRIBUTE_REPARSE_POINT,
+        FILE_ATTRIBUTE_SPARSE_FILE, FIND_FIRST_EX_LARGE_FETCH, FindClose, FindExInfoBasic,
+        FindExSearchNameMatch, FindFirstFileExW, FindNextFileW, WIN32_FIND_DATAW,
+    };
+
+    use super::NativeEntry;
+
+    pub fn walk(
+        root: &Path,
+        stat_all: bool,
+        visit: &mut dyn FnMut(&NativeEntry),
+    ) -> io::Result<()> {
+        walk_dir(root, stat_all, visit)
+    }
+
+    /// With `stat_all`, compressed and sparse files are asked for the space
+    /// they occupy, which the listing does not carry.
+    fn walk_dir(dir: &Path, stat_all: bool, visit: &mut dyn FnMut(&NativeEntry)) -> io::Result<()> {
+        let pattern: Vec<u16> = dir.join("*").as_os_str().encode_wide().chain([0]).collect();
+        // SAFETY: WIN32_FIND_DATAW is plain data, filled in by the calls below.
+        let mut data: WIN32_FIND_DATAW = unsafe { std::mem::zeroed() };
+        // SAFETY: `pattern` is NUL-terminated and `data` is the basic info struct.
+        let handle = unsafe {
+            FindFirstFileExW(
+                pattern.as_ptr(),
+                FindExInfoBasic,
+                (&raw mut data).cast(),
+                FindExSearchNameMatch,
+                std::ptr::null(),
+                FIND_FIRST_EX_LARGE_FETCH,
+            )
+        };
+        if handle == INVALID_HANDLE_VALUE {
+            return Err(io::Error::last_os_error());
+        }
+        let mut subdirs: Vec<PathBuf> = Vec::new();
+        loop {
+            let end = data.cFileName.iter().position(|&c| c == 0);
+            let name = OsString::from_wide(&data.cFileName[..end.unwrap_or(data.cFileName.len())]);
+            if name != "." && name != ".." {
+                // Symlinks and junctions are reparse points, not followed.
+                let reparse = data.dwFileAttributes & FILE_ATTRIBUTE_REPARSE_POINT != 0;
+                let directory = data.dwFileAttributes & FILE_ATTRIBUTE_DIRECTORY != 0;
+                let packed = data.dwFileAttributes
+                    & (FILE_ATTRIBUTE_COMPRESSED | FILE_ATTRIBUTE_SPARSE_FILE)
+                    != 0;
+                let allocated = if stat_all && packed && !directory && !reparse {
+                    crate::size::compressed_size(&dir.join(&name))
+                } else {
+                    None
+                };
+                let entry = NativeEntry {
+                    is_dir: directory && !reparse,
+                    is_file: !directory && !reparse,
+                    is_symlink: reparse,
+                    len: (u64::from(data.nFileSizeHigh) << 32) | u64::from(data.nFileSizeLow),
+                    allocated,
+                    link: None,
+                    modified: system_time(data.ftLastWriteTime),
+                };
+                if entry.is_dir {
+                    subdirs.push(dir.join(name));
+                }
+                visit(&entry);
+            }
+            // SAFETY: `handle` is an open search handle and `data` is writable.
+            if unsafe { FindNextFileW(handle, &mut data) } == 0 {
+                break;
+            }
+        }
+        // SAFETY: `handle` is an open search handle, closed once.
+        unsafe { FindClose(handle) };
+        for subdir in subdirs {
+            let _ = walk_dir(&subdir, stat_all, visit);
+        }
+        Ok(())
+    }
+
+    /// A FILETIME counts 100ns intervals since 1601.
+    fn system_time(time: FILETIME) -> Option<SystemTime> {
+        const UNIX_EPOCH_IN_FILETIME: u64 = 116_444_736_000_000_000;
+        let ticks = (u64::from(time.dwHighDateTime) << 32) | u64::from(time.dwLowDateTime);
+        let since_unix = ticks.checked_sub(UNIX_EPOCH_IN_FILETIME)?;
+        Some(UNIX_EPOCH + Duration::from_nanos(since_unix) * 100)
+    }
+}
+
+#[cfg(not(any(
+    all(target_os = "linux", target_env = "gnu"),
+    target_os = "macos",
+    windows
+)))]
+mod imp {
+    use std::io;
+    use std::path::Path;
+
+    use super::NativeEntry;
+
+    pub fn walk(
+        _root: &Path,
+        _stat_all: bool,
+        _visit: &mut dyn FnMut(&NativeEntry),
+    ) -> io::Result<()> {
+        Err(io::ErrorKind::Unsupported.into())
+    }
+}
+
+#[cfg(all(test, target_os = "linux", target_env = "gnu"))]
+mod tests {
+    use super::*;
+    use std::fs;
+    use tempfile::TempDir;
+
+    #[test]
+    fn walk_visits_every_entry_below_the_root() {
+        let tmp = TempDir::new().unwrap();
+        fs::create_dir_all(tmp.path().join("a/b")).unwrap();
+        fs::write(tmp.path().join("a/one"), "1").unwrap();
+        fs::write(tmp.path().join("a/b/two"), "22").unwrap();
+        fs::hard_link(tmp.path().join("a/b/two"), tmp.path().join("three")).unwrap();
+        std::os::unix::fs::symlink(tmp.path().join("a"), tmp.path().join("link")).unwrap();
+
+        let mut entries = Vec::new();
+        walk(tmp.path(), false, &mut |e| entries.push(*e)).unwrap();
+
+        let dirs = entries.iter().filter(|e| e.is_dir).count();
+        let files: Vec<_> = entries.iter().filter(|e| e.is_file).collect();
+        assert_eq!(entries.len(), 6, "{entries:?}");
+        assert_eq!(dirs, 2);
+        assert_eq!(files.iter().map(|e| e.len).sum::<u64>(), 5);
+        assert_eq!(files.iter().filter(|e| e.link.is_some()).count(), 2);
+        assert!(files.iter().all(|e| e.modified.is_some()));
+    }
+
+    #[test]
+    fn walk_fails_on_a_missing_root() {
+        let tmp = TempDir::new().unwrap();
+        let result = walk(&tmp.path().join("missing"), false, &mut |_| {});
+        assert!(result.is_err());
+    }
+}
@@ -1,8 +1,11 @@
 use std::collections::BTreeMap;
 use std::io::Write;
+use std::path::{Path, PathBuf};
 
+use crate::breakdown::Category;
+use crate::explain::{Explainer, PathReport};
 use crate::scanner::Artifact;
-use crate::size::format_size;
+use crate::size::{SizeFormat, format_size};
 
 /// Summary entry for a build system group.
 struct GroupSummary {
@@ -46,7 +49,7 @@ pub fn print_summary(
     // Calculate column widths.
     let system_width = groups.keys().map(|k| k.len()).max().unwrap_or(12).max(12);
     let count_width = 5;
-    let size_width = 10;
+    let size_width = SizeFormat::default().width();
 
     // Header
     writeln!(
@@ -79,11 +82,26 @@ pub fn print_summary(
         if verbose {
             if let Some(paths) = paths_by_system.get(system) {
                 for artifact in paths {
+                    let margin = match artifact.stats.and_then(|s| s.margin) {
+                        Some(margin) => format!(" ±{}", format_size(margin)),
+                        None => String::new(),
+                    };
+                    let outside = match artifact.stats.map_or(0, |s| s.outside_bytes) {
+                        0 => String::new(),
+                        bytes => format!(", links to {} outside", format_size(bytes)),
+                    };
+                    let frees = match artifact.stats.and_then(|s| s.reclaimable) {
+                        Some(bytes) if bytes < artifact.size_bytes => {
+                            format!(", frees {}", format_size(bytes))
+                        }
+                        _ => String::new(),
+                    };
                     writeln!(
                         out,
-                        "  {} ({})",
+                        "  {} ({}{margin}, {}{outside}{frees})",
                         artifact.path.display(),
-                        format_size(artifact.size_bytes)
+                        format_size(artifact.size_bytes),
+                        artifact.risk
                     )?;
                 }
             }
@@ -106,6 +124,66 @@ pub fn print_summary(
         format_size(total_bytes),
     )?;
 
+    let outside: u64 = artifacts
+        .iter()
+        .filter_map(|a| a.stats)
+        .map(|s| s.outside_bytes)
+        .sum();
+    if outside > 0 {
+        writeln!(
+            out,
+            "Symlinks in these artifacts point to {} outside them, not counted above.",
+            format_size(outside)
+        )?;
+    }
+
+    let reclaimable: Option<u64> = artifacts
+        .iter()
+        .map(|a| a.stats.and_then(|s| s.reclaimable))
+        .sum();
+    if let Some(reclaimable) = reclaimable.filter(|&r| r < total_bytes) {
+        writeln!(
+            out,
+            "Deleting these frees at least {}; the rest is hard-linked or cloned elsewhere.",
+            format_size(reclaimable)
+        )?;
+    }
+
+    // Independent errors add in quadrature.
+    let margins: Vec<f64> = artifacts
+        .iter()
+        .filter_map(|a| a.stats.and_then(|s| s.margin))
+        .map(|m| m as f64)
+        .collect();
+    if !margins.is_empty() {
+        let margin = margins.iter().map(|m| m * m).sum::<f64>().sqrt();
+        writeln!(
+            out,
+            "Sizes are estimated (--estimate); the total is within ±{} at 95% confidence.",
+            format_size(margin.round() as u64)
+        )?;
+    }
+
+    Ok(())
+}
+
+/// Print how much of the artifacts' total is on each filesystem, as
+/// [`bytes_by_volume`](crate::disk::bytes_by_volume) gives it, if they
+/// are on more than one.
+pub fn print_volumes(out: &mut dyn Write, volumes: &[(PathBuf, u64)]) -> std::io::Result<()> {
+    if volumes.len() < 2 {
+        return Ok(());
+    }
+    let size_width = SizeFormat::default().width();
+    writeln!(out, "By volume:")?;
+    for (mount, bytes) in volumes {
+        writeln!(
+            out,
+            "  {:>size_width$}  on {}",
+            format_size(*bytes),
+            mount.display()
+        )?;
+    }
     Ok(())
 }
 
@@ -138,6 +216,159 @@ pub fn print_systems(out: &mut dyn Write) -> std::io::Result<()> {
     Ok(())
 }
 
+/// Print the `--explain` report: one line per detected artifact, relative to
+/// `root`, saying whether it was kept or dropped and why.
+pub fn print_explanations(
+    out: &mut dyn Write,
+    root: &Path,
+    explainer: &Explainer,
+) -> std::io::Result<()> {
+    for (path, explanation) in explainer.explanations() {
+        let rel = path.strip_prefix(root).unwrap_or(path);
+        match &explanation.dropped_because {
+            Some(reason) => writeln!(out, "drop  {}  ({reason})", rel.display())?,
+            None if explanation.kept_because.is_empty() => {
+                writeln!(out, "keep  {}  (no filter applies)", rel.display())?
+            }
+            None => writeln!(
+                out,
+                "keep  {}  ({})",
+                rel.display(),
+                explanation.kept_because.join("; ")
+            )?,
+        }
+    }
+    writeln!(out)
+}
+
+/// Print the `explain` report for one path, relative to `root`: each rule
+/// tried on it, whether the scan finds it, and what the filters decided.
+pub fn print_path_report(
+    out: &mut dyn Write,
+    root: &Path,
+    report: &PathReport,
+) -> std::io::Result<()> {
+    let rel = report.path.strip_prefix(root).unwrap_or(&report.path);
+    let name = report
+        .path
+        .file_name()
+        .map(|n| n.to_string_lossy())
+        .unwrap_or_default();
+    writeln!(out, "{}", rel.display())?;
+    let winner = report.checks.iter().position(|c| c.matched && c.active);
+    if report.checks.is_empty() {
+        writeln!(out, "  Rules:   none applies to an entry named `{name}`")?;
+    } else {
+        writeln!(out, "  Rules, in the order they are tried:")?;
+    }
+    for (i, check) in report.checks.iter().enumerate() {
+        let verdict = match winner {
+            _ if !check.matched => "no",
+            _ if !check.active => "matches, but --system, --exclude-system or --risk leave it out",
+            Some(w) if w < i => "matches, but an earlier rule matched first",
+            _ => "matches",
+        };
+        writeln!(
+            out,
+            "    {} ({}): {verdict}; {}",
+            check.id, check.build_system, check.marker
+        )?;
+    }
+    match (&report.unreached, winner) {
+        (Some(reason), _) => writeln!(out, "  Scan:    never reaches it, as {reason}")?,
+        (None, Some(w)) if report.explanation.is_some() => writeln!(
+            out,
+            "  Scan:    finds it ({})",
+            report.checks[w].build_system
+        )?,
+        (None, None) => writeln!(out, "  Scan:    not an artifact of the rules the run uses")?,
+        (None, Some(_)) => writeln!(out, "  Scan:    does not report it")?,
+    }
+    if let Some(explanation) = &report.explanation {
+        match &explanation.dropped_because {
+            Some(reason) => writeln!(out, "  Filters: drop ({reason})")?,
+            None if explanation.kept_because.is_empty() => {
+                writeln!(out, "  Filters: keep (no filter applies)")?
+            }
+            None => writeln!(
+                out,
+                "  Filters: keep ({})",
+                explanation.kept_because.join("; ")
+            )?,
+        }
+    }
+    if report.selected {
+        writeln!(
+            out,
+            "  Result:  selected; with --delete the run would delete it"
+        )
+    } else {
+        writeln!(out, "  Result:  not selected")
+    }
+}
+
+/// Print the `--check` report: each artifact predicted to fail deletion,
+/// relative to `root`, and why.
+pub fn print_predicted_failures(
+    out: &mut dyn Write,
+    root: &Path,
+    failures: &[(PathBuf, String)],
+) -> std::io::Result<()> {
+    writeln!(out)?;
+    if failures.is_empty() {
+        return writeln!(out, "Every artifact looks deletable.");
+    }
+    writeln!(out, "Predicted failures ({}):", failures.len())?;
+    for (path, problem) in failures {
+        let rel = path.strip_prefix(root).unwrap_or(path);
+        writeln!(out, "  {}  ({problem})", rel.display())?;
+    }
+    Ok(())
+}
+
+/// Categories listed per artifact by [`print_breakdowns`]; the rest are
+/// summed as "other".
+const BREAKDOWN_CATEGORIES: usize = 5;
+
+/// Print the `--breakdown` report: for each artifact, the share of its bytes
+/// in each category.
+pub fn print_breakdowns(
+    out: &mut dyn Write,
+    breakdowns: &[(&Artifact, Vec<Category>)],
+) -> std::io::Result<()> {
+    for (artifact, categories) in breakdowns {
+        writeln!(out)?;
+        writeln!(
+            out,
+            "{} ({})",
+            artifact.path.display(),
+            format_size(artifact.size_bytes)
+        )?;
+        let total: u64 = categories.iter().map(|c| c.bytes).sum();
+        if total == 0 {
+            writeln!(out, "  (empty)")?;
+            continue;
+        }
+        let shown = categories.len().min(BREAKDOWN_CATEGORIES);
+        let other: u64 = categories[shown..].iter().map(|c| c.bytes).sum();
+        let size_width = SizeFormat::default().width();
+        let rows = categories[..shown]
+            .iter()
+            .map(|c| (c.name.as_str(), c.bytes))
+            .chain((other > 0).then_some(("other", other)));
+        for (name, bytes) in rows {
+            writeln!(
+                out,
+                "  {:>3}%  {:<16}  {:>size_width$}",
+                bytes * 100 / total,
+                name,
+                format_size(bytes)
+            )?;
+        }
+    }
+    Ok(())
+}
+
 /// Print the dry-run footer message.
 pub fn print_dry_run_footer(out: &mut dyn Write) -> std::io::Result<()> {
     writeln!(out)?;
@@ -147,14 +378,15 @@ pub fn print_dry_run_footer(out: &mut dyn Write) -> std::io::Result<()> {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::filter::ArtifactFilter;
+    use crate::size::{SizeFilter, SizeStats};
     use std::path::PathBuf;
 
     fn make_artifact(system: &'static str, dir: &'static str, path: &str, size: u64) -> Artifact {
         Artifact {
-            path: PathBuf::from(path),
-            build_system: system,
             artifact_dir: dir,
             size_bytes: size,
+            ..Artifact::for_test(PathBuf::from(path), system)
         }
     }
 
@@ -188,6 +420,110 @@ mod tests {
         assert!(output.contains("3"));
     }
 
+    #[test]
+    fn breakdown_lists_top_categories_and_other() {
+        let artifact = make_artifact("Node.js", "node_modules", "/a/node_modules", 1000);
+        let categories = [".map", ".js", ".ts", ".json", ".md", ".css", ".png"]
+            .iter()
+            .zip([400, 300, 100, 100, 50, 30, 20])
+            .map(|(name, bytes)| Category {
+                name: name.to_string(),
+                bytes,
+            })
+            .collect();
+        let mut buf = Vec::new();
+        print_breakdowns(&mut buf, &[(&artifact, categories)]).unwrap();
+        let output = String::from_utf8(buf).unwrap();
+        assert!(output.contains("/a/node_modules (1000 B)"), "{output}");
+        assert!(output.contains("   40%  .map"), "{output}");
+        assert!(output.contains("    5%  .md"), "{output}");
+        assert!(output.contains("    5%  other"), "{output}");
+        assert!(!output.contains(".css"), "{output}");
+    }
+
+    #[test]
+    fn estimated_sizes_show_their_margin() {
+        let mut artifacts = vec![
+            make_artifact("Node.js", "node_modules", "/a/node_modules", 10_000),
+            make_artifact("Node.js", "node_modules", "/b/node_modules", 10_000),
+        ];
+        for artifact in &mut artifacts {
+            artifact.stats = Some(SizeStats {
+                bytes: 10_000,
+                margin: Some(300),
+                ..Default::default()
+            });
+        }
+        let mut buf = Vec::new();
+        print_summary(&mut buf, &artifacts, true).unwrap();
+        let output = String::from_utf8(buf).unwrap();
+        assert!(output.contains("(9.8 KB ±300 B, output)"), "{output}");
+        assert!(output.contains("within ±424 B at 95%"), "{output}");
+    }
+
+    #[test]
+    fn link_targets_outside_are_reported_apart() {
+        let mut artifact = make_artifact("Bazel", "bazel-out", "/a/bazel-out", 100);
+        artifact.stats = Some(SizeStats {
+            bytes: 100,
+            outside_bytes: 2048,
+            ..Default::default()
+        });
+        let mut buf = Vec::new();
+        print_summary(&mut buf, &[artifact], true).unwrap();
+        let output = String::from_utf8(buf).unwrap();
+        assert!(
+            output.contains("(100 B, output, links to 2.0 KB outside)"),
+            "{output}"
+        );
+        assert!(output.contains("point to 2.0 KB outside them"), "{output}");
+    }
+
+    #[test]
+    fn reclaimable_bytes_are_reported_when_less_than_the_size() {
+        let mut artifact = make_artifact("Node.js", "node_modules", "/a/node_modules", 3072);
+        artifact.stats = Some(SizeStats {
+            bytes: 3072,
+            reclaimable: Some(1024),
+            ..Default::default()
+        });
+        let mut whole = make_artifact("Node.js", "node_modules", "/b/node_modules", 1024);
+        whole.stats = Some(SizeStats {
+            bytes: 1024,
+            reclaimable: Some(1024),
+            ..Default::default()
+        });
+        let mut buf = Vec::new();
+        print_summary(&mut buf, &[artifact, whole], true).unwrap();
+        let output = String::from_utf8(buf).unwrap();
+        assert!(
+            output.contains("(3.0 KB, output, frees 1.0 KB)"),
+            "{output}"
+        );
+        assert!(output.contains("(1.0 KB, output)"), "{output}");
+        assert!(
+            output.contains("Deleting these frees at least 2.0 KB"),
+            "{output}"
+        );
+    }
+
+    #[test]
+    fn volumes_are_listed_only_when_there_are_several() {
+        let mut buf = Vec::new();
+        print_volumes(&mut buf, &[(PathBuf::from("/"), 2048)]).unwrap();
+        assert!(buf.is_empty());
+
+        let volumes = [
+            (PathBuf::from("/mnt/data"), 140 * 1024 * 1024),
+            (PathBuf::from("/"), 12 * 1024),
+        ];
+        print_volumes(&mut buf, &volumes).unwrap();
+        assert_eq!(
+            String::from_utf8(buf).unwrap(),
+            "By volume:\n   140.0 MB  on /mnt/data\n    12.0 KB  on /\n"
+        );
+    }
+
     #[test]
     fn verbose_shows_paths() {
         let artifacts = vec![make_artifact(
@@ -200,6 +536,28 @@ mod tests {
         print_summary(&mut buf, &artifacts, true).unwrap();
         let output = String::from_utf8(buf).unwrap();
         assert!(output.contains("/projects/foo/target"));
+        assert!(output.contains("1.0 KB, output"));
+    }
+
+    #[test]
+    fn predicted_failures_relative_to_root() {
+        let failures = vec![(
+            PathBuf::from("/root/a/bazel-out"),
+            "directory /root/a/bazel-out/x is not writable".to_string(),
+        )];
+        let mut buf = Vec::new();
+        print_predicted_failures(&mut buf, Path::new("/root"), &failures).unwrap();
+        let output = String::from_utf8(buf).unwrap();
+        assert!(output.contains("Predicted failures (1):"));
+        assert!(output.contains("  a/bazel-out  (directory /root/a/bazel-out/x is not writable)"));
+
+        let mut buf = Vec::new();
+        print_predicted_failures(&mut buf, Path::new("/root"), &[]).unwrap();
+        assert!(
+            String::from_utf8(buf)
+                .unwrap()
+                .contains("Every artifact looks deletable.")
+        );
     }
 
     #[test]
@@ -210,6 +568,33 @@ mod tests {
         assert!(output.contains("Run with --delete"));
     }
 
+    #[test]
+    fn explanations_relative_to_root() {
+        let mut explainer = Explainer::default();
+        let root = Path::new("/root");
+        let artifacts = explainer.stage(
+            vec![
+                make_artifact("Rust/Cargo", "target", "/root/a/target", 1024),
+                make_artifact("Rust/Cargo", "target", "/root/b/target", 0),
+                make_artifact("Rust/Cargo", "target", "/root/c/target", 0),
+            ],
+            &ArtifactFilter::new(&[], &["b".to_string()])
+                .unwrap()
+                .with_root(root),
+        );
+        let size_filter = SizeFilter {
+            min: Some(1),
+            max: None,
+        };
+        explainer.stage(artifacts, &size_filter);
+        let mut buf = Vec::new();
+        print_explanations(&mut buf, root, &explainer).unwrap();
+        let output = String::from_utf8(buf).unwrap();
+        assert!(output.contains("keep  a/target  (size 1.0 KB)"));
+        assert!(output.contains("drop  b/target  (excluded by --exclude 'b')"));
+        assert!(output.contains("drop  c/target  (0 B is below --min-size 1 B)"));
+    }
+
     #[test]
     fn print_systems_shows_ids() {
         let mut buf = Vec::new();
@@ -0,0 +1,148 @@
+use std::path::Path;
+
+use crate::filter::{Filter, Verdict};
+use crate::scanner::Artifact;
+
+/// Error resolving the user for `--owner` or `--only-mine`.
+#[derive(thiserror::Error, Debug)]
+pub enum OwnerError {
+    #[error("unknown user: {0}")]
+    UnknownUser(String),
+    #[error("filtering by owner is not supported on this platform")]
+    Unsupported,
+}
+
+/// Resolve a user name or numeric UID to a UID.
+#[cfg(unix)]
+pub fn resolve_user(user: &str) -> Result<u32, OwnerError> {
+    if let Ok(uid) = user.parse() {
+        return Ok(uid);
+    }
+    let unknown = || OwnerError::UnknownUser(user.to_string());
+    let name = std::ffi::CString::new(user).map_err(|_| unknown())?;
+    let mut buf: Vec<libc::c_char> = vec![0; 1024];
+    loop {
+        // SAFETY: `passwd` is plain data that getpwnam_r fills in; its string
+        // fields point into `buf`, which outlives every read of them.
+        let mut pwd: libc::passwd = unsafe { std::mem::zeroed() };
+        let mut result = std::ptr::null_mut();
+        let rc = unsafe {
+            libc::getpwnam_r(
+                name.as_ptr(),
+                &mut pwd,
+                buf.as_mut_ptr(),
+                buf.len(),
+                &mut result,
+            )
+        };
+        if rc == libc::ERANGE && buf.len() < 1 << 20 {
+            buf.resize(buf.len() * 2, 0);
+            continue;
+        }
+        if rc != 0 || result.is_null() {
+            return Err(unknown());
+        }
+        return Ok(pwd.pw_uid);
+    }
+}
+
+#[cfg(not(unix))]
+pub fn resolve_user(_user: &str) -> Result<u32, OwnerError> {
+    Err(OwnerError::Unsupported)
+}
+
+/// The UID of the user running this process.
+#[cfg(unix)]
+pub fn current_uid() -> Result<u32, OwnerError> {
+    // SAFETY: getuid has no preconditions and cannot fail.
+    Ok(unsafe { libc::getuid() })
+}
+
+#[cfg(not(unix))]
+pub fn current_uid() -> Result<u32, OwnerError> {
+    Err(OwnerError::Unsupported)
+}
+
+/// The UID owning `path` itself (not following symlinks).
+#[cfg(unix)]
+pub fn owner_of(path: &Path) -> std::io::Result<u32> {
+    use std::os::unix::fs::MetadataExt;
+    Ok(std::fs::symlink_metadata(path)?.uid())
+}
+
+#[cfg(not(unix))]
+pub fn owner_of(_path: &Path) -> std::io::Result<u32> {
+    Err(std::io::Error::from(std::io::ErrorKind::Unsupported))
+}
+
+/// Keeps only artifacts owned by one user (`--owner`, `--only-mine`).
+#[derive(Debug)]
+pub struct OwnerFilter {
+    uid: u32,
+    /// How the user was given on the command line, for `--explain`.
+    flag: String,
+}
+
+impl OwnerFilter {
+    /// Filter for the user given to `--owner`, by name or UID.
+    pub fn for_user(user: &str) -> Result<Self, OwnerError> {
+        Ok(Self {
+            uid: resolve_user(user)?,
+            flag: format!("--owner {user}"),
+        })
+    }
+
+    /// Filter for the user running this process (`--only-mine`).
+    pub fn current_user() -> Result<Self, OwnerError> {
+        Ok(Self {
+            uid: current_uid()?,
+            flag: "--only-mine".to_string(),
+        })
+    }
+}
+
+impl Filter for OwnerFilter {
+    fn keep(&self, artifact: &Artifact) -> bool {
+        Filter::verdict(self, artifact).is_keep()
+    }
+
+    fn verdict(&self, artifact: &Artifact) -> Verdict {
+        match owner_of(&artifact.path) {
+            Ok(uid) if uid == self.uid => Verdict::Keep(Some(format!("owned by uid {uid}"))),
+            Ok(uid) => Verdict::Drop(format!("owned by uid {uid}, not {}", self.flag)),
+            Err(e) => Verdict::Drop(format!("cannot read owner: {e}")),
+        }
+    }
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn resolve_user_accepts_names_and_uids() {
+        assert_eq!(resolve_user("root").unwrap(), 0);
+        assert_eq!(resolve_user("1234").unwrap(), 1234);
+        assert!(matches!(
+            resolve_user("no-such-user-here"),
+            Err(OwnerError::UnknownUser(_))
+        ));
+    }
+
+    #[test]
+    fn only_mine_keeps_own_artifacts() {
+        let tmp = TempDir::new().unwrap();
+        let a = Artifact::for_test(tmp.path(), "Rust/Cargo");
+        assert!(OwnerFilter::current_user().unwrap().keep(&a));
+
+        let other = OwnerFilter {
+            uid: current_uid().unwrap() + 1,
+            flag: "--owner someone".to_string(),
+        };
+        match Filter::verdict(&other, &a) {
+            Verdict::Drop(reason) => assert!(reason.ends_with("not --owner someone"), "{reason}"),
+            keep => panic!("expected drop, got {keep:?}"),
+        }
+    }
+}
@@ -0,0 +1,167 @@
+use std::path::{Path, PathBuf};
+
+use jwalk::{Parallelism, WalkDir};
+use rayon::prelude::*;
+
+use crate::scanner::Artifact;
+
+/// Check, without changing anything, whether each artifact looks deletable
+/// (`--check`). Returns the path and predicted problem of each that does not.
+pub fn predict_failures(artifacts: &[Artifact]) -> Vec<(PathBuf, String)> {
+    artifacts
+        .par_iter()
+        .filter_map(|a| problem(&a.path).map(|p| (a.path.clone(), p)))
+        .collect()
+}
+
+/// The first reason found that deleting `path` would fail: its filesystem
+/// is mounted read-only, its parent or a directory inside it is not
+/// writable, or one of those directories is immutable. Directories inside
+/// are checked, but not every file, so this is a prediction, not a promise.
+pub fn problem(path: &Path) -> Option<String> {
+    let parent = path.parent()?;
+    if read_only_mount(parent) {
+        return Some("on a read-only filesystem".to_string());
+    }
+    if let Some(problem) = dir_problem(parent) {
+        return Some(format!("parent directory {}", problem));
+    }
+    if immutable(path) {
+        return Some(format!("{} is immutable", path.display()));
+    }
+    let meta = std::fs::symlink_metadata(path).ok()?;
+    if !meta.is_dir() {
+        return None;
+    }
+    WalkDir::new(path)
+        .parallelism(Parallelism::Serial)
+        .follow_links(false)
+        .skip_hidden(false)
+        .into_iter()
+        .filter_map(|e| e.ok())
+        .filter(|e| e.file_type().is_dir())
+        .find_map(|e| dir_problem(&e.path()).map(|p| format!("directory {p}")))
+}
+
+/// Why entries of directory `dir` cannot be removed, if they cannot.
+fn dir_problem(dir: &Path) -> Option<String> {
+    if !writable(dir) {
+        Some(format!("{} is not writable", dir.display()))
+    } else if immutable(dir) {
+        Some(format!("{} is immutable", dir.display()))
+    } else {
+        None
+    }
+}
+
+/// Whether this user may add and remove entries in directory `dir`.
+#[cfg(unix)]
+fn writable(dir: &Path) -> bool {
+    use std::os::unix::ffi::OsStrExt;
+    let Ok(path) = std::ffi::CString::new(dir.as_os_str().as_bytes()) else {
+        return true;
+    };
+    // SAFETY: `path` is a valid NUL-terminated string.
+    unsafe { libc::access(path.as_ptr(), libc::W_OK | libc::X_OK) == 0 }
+}
+
+/// Whether directory `dir` is writable; Windows only marks files read-only,
+/// and deletion clears that, so directories are always taken as writable.
+#[cfg(not(unix))]
+fn writable(_dir: &Path) -> bool {
+    true
+}
+
+/// Whether the filesystem holding `dir` is mounted read-only.
+#[cfg(unix)]
+pub fn read_only_mount(dir: &Path) -> bool {
+    use std::os::unix::ffi::OsStrExt;
+    let Ok(path) = std::ffi::CString::new(dir.as_os_str().as_bytes()) else {
+        return false;
+    };
+    // SAFETY: an all-zero `statvfs` struct is valid; statvfs only fills it in.
+    let mut stats: libc::statvfs = unsafe { std::mem::zeroed() };
+    // SAFETY: `path` is NUL-terminated and `stats` is exclusively borrowed.
+    let rc = unsafe { libc::statvfs(path.as_ptr(), &mut stats) };
+    rc == 0 && stats.f_flag & libc::ST_RDONLY != 0
+}
+
+#[cfg(not(unix))]
+pub fn read_only_mount(_dir: &Path) -> bool {
+    false
+}
+
+/// Whether `path` has the immutable or append-only attribute (`chattr +i`,
+/// `chattr +a`), which even root cannot delete through.
+#[cfg(target_os = "linux")]
+fn immutable(path: &Path) -> bool {
+    use std::os::fd::AsRawFd;
+    const FS_IMMUTABLE_FL: libc::c_long = 0x10;
+    const FS_APPEND_FL: libc::c_long = 0x20;
+
+    // Attributes can only be read through an open file; skip symlinks.
+    if std::fs::symlink_metadata(path).is_ok_and(|m| m.file_type().is_symlink()) {
+        return false;
+    }
+    let Ok(file) = std::fs::File::open(path) else {
+        return false;
+    };
+    let mut flags: libc::c_long = 0;
+    // SAFETY: FS_IOC_GETFLAGS writes one `long` through the pointer.
+    let rc = unsafe { libc::ioctl(file.as_raw_fd(), libc::FS_IOC_GETFLAGS, &mut flags) };
+    rc == 0 && flags & (FS_IMMUTABLE_FL | FS_APPEND_FL) != 0
+}
+
+/// Whether `path` has the user or system immutable flag (`chflags uchg`,
+/// `chflags schg`).
+#[cfg(target_os = "macos")]
+fn immutable(path: &Path) -> bool {
+    use std::os::macos::fs::MetadataExt;
+    std::fs::symlink_metadata(path).is_ok_and(|m| {
+        m.st_flags() & (libc::UF_IMMUTABLE | libc::SF_IMMUTABLE | libc::UF_APPEND | libc::SF_APPEND)
+            != 0
+    })
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "macos")))]
+fn immutable(_path: &Path) -> bool {
+    false
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn ordinary_artifact_has_no_problem() {
+        let tmp = TempDir::new().unwrap();
+        let target = tmp.path().join("target/debug");
+        std::fs::create_dir_all(&target).unwrap();
+        std::fs::write(target.join("app"), "binary").unwrap();
+        assert_eq!(problem(&tmp.path().join("target")), None);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn read_only_directory_inside_is_predicted() {
+        use std::os::unix::fs::PermissionsExt;
+
+        // Root may write anywhere, so there is nothing to predict.
+        if unsafe { libc::geteuid() } == 0 {
+            return;
+        }
+        let tmp = TempDir::new().unwrap();
+        let locked = tmp.path().join("bazel-out/external");
+        std::fs::create_dir_all(&locked).unwrap();
+        std::fs::set_permissions(&locked, std::fs::Permissions::from_mode(0o555)).unwrap();
+
+        let problem = problem(&tmp.path().join("bazel-out"));
+
+        std::fs::set_permissions(&locked, std::fs::Permissions::from_mode(0o755)).unwrap();
+        assert_eq!(
+            problem,
+            Some(format!("directory {} is not writable", locked.display()))
+        );
+    }
+}
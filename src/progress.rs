@@ -0,0 +1,323 @@
+use std::fmt;
+use std::io::IsTerminal;
+use std::path::Path;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use indicatif::{MultiProgress, ProgressBar, ProgressDrawTarget, ProgressState, ProgressStyle};
+use log::warn;
+
+use crate::audit::Outcome;
+use crate::scanner::Artifact;
+use crate::size::{SizeFormat, SizingProgress, format_size};
+
+/// Observer of a run as it goes, for a UI to follow the scan, sizing and
+/// deletion without reading the log. Methods are called from worker
+/// threads, in no particular order between threads, and should return
+/// quickly. Each does nothing by default, except [`on_error`], which logs
+/// a warning.
+///
+/// [`on_error`]: Progress::on_error
+pub trait Progress: Send + Sync {
+    /// The scan listed `dir`.
+    fn on_dir_visited(&self, dir: &Path) {
+        let _ = dir;
+    }
+
+    /// The scan matched `artifact`, before any filter has seen it.
+    fn on_artifact_found(&self, artifact: &Artifact) {
+        let _ = artifact;
+    }
+
+    /// `artifact` was measured at `bytes`, bringing the sizing pass to
+    /// `progress`. `done` and `bytes` in `progress` only grow.
+    fn on_sized(&self, artifact: &Artifact, bytes: u64, progress: SizingProgress) {
+        let _ = (artifact, bytes, progress);
+    }
+
+    /// The deletion run settled `artifact` with `outcome`.
+    fn on_deleted(&self, artifact: &Artifact, outcome: &Outcome) {
+        let _ = (artifact, outcome);
+    }
+
+    /// `path` could not be read, so the run goes on without it.
+    fn on_error(&self, path: &Path, error: &dyn std::error::Error) {
+        warn!("Cannot read {}: {error}", path.display());
+    }
+}
+
+/// Observes nothing, and logs errors.
+impl Progress for () {}
+
+/// A shared observer, for a caller to keep a handle on, e.g. to clear a
+/// display once the run is done.
+impl<P: Progress + ?Sized> Progress for Arc<P> {
+    fn on_dir_visited(&self, dir: &Path) {
+        (**self).on_dir_visited(dir);
+    }
+
+    fn on_artifact_found(&self, artifact: &Artifact) {
+        (**self).on_artifact_found(artifact);
+    }
+
+    fn on_sized(&self, artifact: &Artifact, bytes: u64, progress: SizingProgress) {
+        (**self).on_sized(artifact, bytes, progress);
+    }
+
+    fn on_deleted(&self, artifact: &Artifact, outcome: &Outcome) {
+        (**self).on_deleted(artifact, outcome);
+    }
+
+    fn on_error(&self, path: &Path, error: &dyn std::error::Error) {
+        (**self).on_error(path, error);
+    }
+}
+
+/// A shared [`Progress`] observer, which the scan hands to its threads.
+/// Defaults to `()`.
+#[derive(Clone)]
+pub struct Observer(pub Arc<dyn Progress>);
+
+impl Observer {
+    pub fn new(progress: impl Progress + 'static) -> Self {
+        Self(Arc::new(progress))
+    }
+}
+
+impl Default for Observer {
+    fn default() -> Self {
+        Self::new(())
+    }
+}
+
+impl fmt::Debug for Observer {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("Observer(..)")
+    }
+}
+
+impl std::ops::Deref for Observer {
+    type Target = dyn Progress;
+
+    fn deref(&self) -> &Self::Target {
+        &*self.0
+    }
+}
+
+/// Progress display for a deletion run: one bar for the whole run and one
+/// for each artifact in flight, drawn on stderr. Hidden when stderr is not
+/// a terminal, so piped and scripted runs print nothing extra.
+#[derive(Debug)]
+pub struct DeletionBars {
+    multi: MultiProgress,
+    overall: ProgressBar,
+    done: AtomicUsize,
+    count: usize,
+}
+
+impl DeletionBars {
+    /// Start a display for removing `artifacts`; `verb` labels the bars
+    /// (e.g., "Deleting").
+    pub fn new(artifacts: &[Artifact], verb: &str) -> Self {
+        let target = if std::io::stderr().is_terminal() {
+            ProgressDrawTarget::stderr()
+        } else {
+            ProgressDrawTarget::hidden()
+        };
+        Self::with_draw_target(artifacts, verb, target)
+    }
+
+    fn with_draw_target(artifacts: &[Artifact], verb: &str, target: ProgressDrawTarget) -> Self {
+        let multi = MultiProgress::with_draw_target(target);
+        let total: u64 = artifacts.iter().map(|a| a.size_bytes).sum();
+        let overall = ProgressBar::new(total)
+            .with_style(
+                style("{prefix} [{bar:30}] {size}/{total} ({msg}, {elapsed})")
+                    .progress_chars("=> "),
+            )
+            .with_prefix(verb.to_string())
+            .with_message(format!("0/{} artifacts", artifacts.len()));
+        let overall = multi.add(overall);
+        Self {
+            multi,
+            overall,
+            done: AtomicUsize::new(0),
+            count: artifacts.len(),
+        }
+    }
+
+    /// Add a bar for one artifact about to be removed.
+    pub fn start(&self, artifact: &Artifact) -> ArtifactProgress<'_> {
+        let bar = ProgressBar::new(artifact.size_bytes)
+            .with_style(style(&format!(
+                "  {{size:>{width}}}/{{total:<{width}}} {{wide_msg}}",
+                width = SizeFormat::default().width()
+            )))
+            .with_message(artifact.path.display().to_string());
+        let bar = self.multi.insert_before(&self.overall, bar);
+        ArtifactProgress {
+            progress: self,
+            bar,
+            removed: 0,
+            size: artifact.size_bytes,
+        }
+    }
+
+    /// Print a line above the bars (nothing when hidden).
+    pub fn println(&self, line: &str) {
+        let _ = self.multi.println(line);
+    }
+
+    /// Remove the display from the terminal.
+    pub fn finish(&self) {
+        self.overall.finish_and_clear();
+        let _ = self.multi.clear();
+    }
+}
+
+/// Progress display for computing sizes: artifacts measured, bytes found
+/// so far, and an estimate of the time left, on stderr. Hidden when stderr
+/// is not a terminal.
+#[derive(Debug)]
+pub struct SizingBar {
+    bar: ProgressBar,
+}
+
+impl SizingBar {
+    /// Start a display for sizing, drawn once the first artifact is sized.
+    pub fn new() -> Self {
+        let target = if std::io::stderr().is_terminal() {
+            ProgressDrawTarget::stderr()
+        } else {
+            ProgressDrawTarget::hidden()
+        };
+        Self::with_draw_target(target)
+    }
+
+    fn with_draw_target(target: ProgressDrawTarget) -> Self {
+        let bar = ProgressBar::with_draw_target(None, target)
+            .with_style(
+                style("Sizing [{bar:30}] {pos}/{len} artifacts, {msg} found (ETA {eta})")
+                    .progress_chars("=> "),
+            )
+            .with_message(format_size(0));
+        Self { bar }
+    }
+
+    /// Show `progress`, as reported by `compute_sizes_with_progress`.
+    pub fn update(&self, progress: SizingProgress) {
+        self.bar.set_length(progress.total as u64);
+        self.bar.set_position(progress.done as u64);
+        self.bar.set_message(format_size(progress.bytes));
+    }
+
+    /// Remove the display from the terminal.
+    pub fn finish(&self) {
+        self.bar.finish_and_clear();
+    }
+}
+
+impl Default for SizingBar {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// The command line's observer while sizing.
+impl Progress for SizingBar {
+    fn on_sized(&self, _: &Artifact, _: u64, progress: SizingProgress) {
+        self.update(progress);
+    }
+}
+
+/// A bar style whose `{size}` and `{total}` keys use [`format_size`].
+fn style(template: &str) -> ProgressStyle {
+    ProgressStyle::with_template(template)
+        .expect("valid progress template")
+        .with_key("size", |state: &ProgressState, w: &mut dyn fmt::Write| {
+            let _ = write!(w, "{}", format_size(state.pos()));
+        })
+        .with_key("total", |state: &ProgressState, w: &mut dyn fmt::Write| {
+            let _ = write!(w, "{}", format_size(state.len().unwrap_or(0)));
+        })
+}
+
+/// The bar of one artifact being removed; counts towards the overall bar.
+#[derive(Debug)]
+pub struct ArtifactProgress<'a> {
+    progress: &'a DeletionBars,
+    bar: ProgressBar,
+    removed: u64,
+    size: u64,
+}
+
+impl ArtifactProgress<'_> {
+    /// Record `bytes` more removed.
+    pub fn inc(&mut self, bytes: u64) {
+        // Files may have grown since sizes were computed; never overshoot.
+        let bytes = bytes.min(self.size.saturating_sub(self.removed));
+        self.removed += bytes;
+        self.bar.inc(bytes);
+        self.progress.overall.inc(bytes);
+    }
+
+    /// Finish this artifact, printing `status` (e.g., "deleted") above the
+    /// bars and crediting any bytes not reported through `inc`.
+    pub fn finish(mut self, status: &str) {
+        self.inc(u64::MAX);
+        self.bar.finish_and_clear();
+        let progress = self.progress;
+        progress.println(&format!(
+            "{status:>11} {} ({})",
+            self.bar.message(),
+            format_size(self.size)
+        ));
+        let done = progress.done.fetch_add(1, Ordering::Relaxed) + 1;
+        progress
+            .overall
+            .set_message(format!("{done}/{} artifacts", progress.count));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn artifact(size_bytes: u64) -> Artifact {
+        Artifact {
+            size_bytes,
+            ..Artifact::for_test("/dev/app/target", "Rust/Cargo")
+        }
+    }
+
+    #[test]
+    fn sizing_bar_follows_progress() {
+        let bar = SizingBar::with_draw_target(ProgressDrawTarget::hidden());
+        bar.update(SizingProgress {
+            done: 3,
+            total: 4,
+            bytes: 2048,
+        });
+        assert_eq!(bar.bar.position(), 3);
+        assert_eq!(bar.bar.length(), Some(4));
+        assert_eq!(bar.bar.message(), "2.0 KB");
+    }
+
+    #[test]
+    fn artifact_progress_is_capped_and_completed() {
+        let artifacts = [artifact(100), artifact(50)];
+        let progress =
+            DeletionBars::with_draw_target(&artifacts, "Deleting", ProgressDrawTarget::hidden());
+        assert_eq!(progress.overall.length(), Some(150));
+
+        let mut bar = progress.start(&artifacts[0]);
+        bar.inc(60);
+        bar.inc(60);
+        assert_eq!(progress.overall.position(), 100);
+        bar.finish("deleted");
+
+        progress.start(&artifacts[1]).finish("deleted");
+        assert_eq!(progress.overall.position(), 150);
+        assert_eq!(progress.overall.message(), "2/2 artifacts");
+    }
+}
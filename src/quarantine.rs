@@ -0,0 +1,466 @@
+use std::fs;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+use log::{debug, warn};
+use serde::{Deserialize, Serialize};
+
+use crate::age::UtcTime;
+use crate::scanner::Artifact;
+use crate::size::format_size;
+
+const MANIFEST: &str = "manifest.toml";
+
+/// Error type for quarantine operations.
+#[derive(thiserror::Error, Debug)]
+pub enum QuarantineError {
+    #[error("cannot locate the quarantine: neither XDG_DATA_HOME nor HOME is set")]
+    NoHome,
+    #[error("{}: {source}", path.display())]
+    Io {
+        path: PathBuf,
+        source: std::io::Error,
+    },
+    #[error("invalid quarantine manifest {}: {source}", path.display())]
+    Manifest {
+        path: PathBuf,
+        source: toml::de::Error,
+    },
+    #[error("cannot write quarantine manifest: {0}")]
+    ManifestWrite(#[from] toml::ser::Error),
+    #[error("no quarantined run named '{0}'")]
+    UnknownRun(String),
+}
+
+impl QuarantineError {
+    fn io(path: &Path) -> impl FnOnce(std::io::Error) -> Self + '_ {
+        move |source| QuarantineError::Io {
+            path: path.to_path_buf(),
+            source,
+        }
+    }
+}
+
+/// The record of one quarantine run, stored as `manifest.toml` in its directory.
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct Manifest {
+    #[serde(default, rename = "artifact")]
+    pub artifacts: Vec<ManifestEntry>,
+}
+
+/// One quarantined artifact.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ManifestEntry {
+    /// Where the artifact was, and where `restore` puts it back.
+    pub original: PathBuf,
+    /// Where the artifact is now, relative to the run directory.
+    pub stored: PathBuf,
+    pub build_system: String,
+    pub size_bytes: u64,
+}
+
+/// Per-user data directory: `$XDG_DATA_HOME/clean-builds`, falling back to
+/// `~/.local/share/clean-builds`.
+pub fn data_dir() -> Option<PathBuf> {
+    let data_home = std::env::var_os("XDG_DATA_HOME")
+        .filter(|d| !d.is_empty())
+        .map(PathBuf::from)
+        .or_else(|| {
+            std::env::var_os("HOME")
+                .filter(|h| !h.is_empty())
+                .map(|h| PathBuf::from(h).join(".local/share"))
+        })?;
+    Some(data_home.join("clean-builds"))
+}
+
+/// Default quarantine directory, `quarantine` under [`data_dir`].
+pub fn default_dir() -> Result<PathBuf, QuarantineError> {
+    data_dir()
+        .map(|d| d.join("quarantine"))
+        .ok_or(QuarantineError::NoHome)
+}
+
+/// A quarantine directory holding one subdirectory per run.
+#[derive(Debug, Clone)]
+pub struct Quarantine {
+    dir: PathBuf,
+}
+
+/// Outcome of quarantining a set of artifacts.
+#[derive(Debug)]
+pub struct QuarantinedRun {
+    pub id: String,
+    pub moved: usize,
+    pub errors: Vec<QuarantineError>,
+}
+
+impl Quarantine {
+    pub fn new(dir: &Path) -> Self {
+        Self {
+            dir: dir.to_path_buf(),
+        }
+    }
+
+    /// Run IDs, oldest first. A run interrupted before its manifest was
+    /// first written has none, but is listed so that it can be purged.
+    pub fn runs(&self) -> Result<Vec<String>, QuarantineError> {
+        let entries = match fs::read_dir(&self.dir) {
+            Ok(entries) => entries,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(e) => return Err(QuarantineError::io(&self.dir)(e)),
+        };
+        let mut runs: Vec<String> = entries
+            .flatten()
+            .filter(|e| e.file_type().is_ok_and(|t| t.is_dir()))
+            .filter_map(|e| e.file_name().into_string().ok())
+            .collect();
+        runs.sort();
+        Ok(runs)
+    }
+
+    /// Move `artifacts` into a new run directory, recording a manifest.
+    ///
+    /// Each artifact is added to the manifest before it is moved, so that a
+    /// run cut short still records everything it moved; entries for
+    /// artifacts that were never moved are skipped by `restore`.
+    /// Artifacts are renamed, not copied, so each must be on the same
+    /// filesystem as the quarantine; those that are not are reported in
+    /// `errors` and left in place. If none could be moved, no run is created.
+    pub fn quarantine(&self, artifacts: &[Artifact]) -> Result<QuarantinedRun, QuarantineError> {
+        let (id, run_dir) = self.new_run_dir()?;
+        let mut manifest = Manifest::default();
+        let mut errors = Vec::new();
+        for (index, artifact) in artifacts.iter().enumerate() {
+            let name = artifact.path.file_name().unwrap_or_default();
+            let stored = PathBuf::from(index.to_string()).join(name);
+            let dest = run_dir.join(&stored);
+            debug!(
+                "Quarantining {} to {}",
+                artifact.path.display(),
+                dest.display()
+            );
+            manifest.artifacts.push(ManifestEntry {
+                original: artifact.path.clone(),
+                stored,
+                build_system: artifact.build_system.to_string(),
+                size_bytes: artifact.size_bytes,
+            });
+            write_manifest(&run_dir, &manifest)?;
+            let moved = fs::create_dir_all(dest.parent().unwrap_or(&run_dir))
+                .and_then(|()| fs::rename(&artifact.path, &dest));
+            if let Err(source) = moved {
+                manifest.artifacts.pop();
+                write_manifest(&run_dir, &manifest)?;
+                let e = QuarantineError::Io {
+                    path: artifact.path.clone(),
+                    source,
+                };
+                warn!("{e}");
+                errors.push(e);
+            }
+        }
+        if manifest.artifacts.is_empty() {
+            fs::remove_dir_all(&run_dir).map_err(QuarantineError::io(&run_dir))?;
+        }
+        Ok(QuarantinedRun {
+            id,
+            moved: manifest.artifacts.len(),
+            errors,
+        })
+    }
+
+    /// Move the artifacts of `run` (default: the most recent) back to where
+    /// they came from. Artifacts whose original path exists again are left in
+    /// quarantine. The run is removed once it is empty; anything in it that
+    /// its manifest does not account for is left there.
+    pub fn restore(&self, out: &mut dyn Write, run: Option<&str>) -> Result<(), QuarantineError> {
+        let Some(id) = self.resolve(run)? else {
+            writeln!(out, "Quarantine is empty.").map_err(QuarantineError::io(&self.dir))?;
+            return Ok(());
+        };
+        let run_dir = self.dir.join(&id);
+        let mut manifest = read_manifest(&run_dir)?;
+        // Recorded by a run that stopped before moving them.
+        manifest
+            .artifacts
+            .retain(|entry| fs::symlink_metadata(run_dir.join(&entry.stored)).is_ok());
+        let total = manifest.artifacts.len();
+        let mut remaining = Manifest::default();
+        let mut report = Vec::new();
+        for entry in manifest.artifacts {
+            let result = if entry.original.exists() {
+                Err(format!("{} already exists", entry.original.display()))
+            } else {
+                entry
+                    .original
+                    .parent()
+                    .map_or(Ok(()), fs::create_dir_all)
+                    .and_then(|()| fs::rename(run_dir.join(&entry.stored), &entry.original))
+                    .map_err(|e| format!("cannot restore {}: {e}", entry.original.display()))
+            };
+            if let Err(message) = result {
+                report.push(message);
+                remaining.artifacts.push(entry);
+            }
+        }
+
+        let write = |out: &mut dyn Write| -> std::io::Result<()> {
+            for message in &report {
+                writeln!(out, "Error: {message}")?;
+            }
+            writeln!(
+                out,
+                "Restored {} of {total} artifact directories from run {id}.",
+                total - remaining.artifacts.len()
+            )
+        };
+        write(out).map_err(QuarantineError::io(&run_dir))?;
+
+        if remaining.artifacts.is_empty() {
+            remove_emptied_run(&run_dir)
+        } else {
+            write_manifest(&run_dir, &remaining)
+        }
+    }
+
+    /// Permanently delete `run`, or every run if `None`.
+    pub fn purge(&self, out: &mut dyn Write, run: Option<&str>) -> Result<(), QuarantineError> {
+        let runs = match run {
+            Some(id) => vec![self.resolve(Some(id))?.unwrap_or_default()],
+            None => self.runs()?,
+        };
+        let mut bytes = 0;
+        for id in &runs {
+            let run_dir = self.dir.join(id);
+            bytes += read_manifest(&run_dir)
+                .map(|m| m.artifacts.iter().map(|a| a.size_bytes).sum())
+                .unwrap_or(0);
+            fs::remove_dir_all(&run_dir).map_err(QuarantineError::io(&run_dir))?;
+        }
+        writeln!(
+            out,
+            "Purged {} quarantined run{} ({}).",
+            runs.len(),
+            if runs.len() == 1 { "" } else { "s" },
+            format_size(bytes)
+        )
+        .map_err(QuarantineError::io(&self.dir))
+    }
+
+    /// The named run, or the most recent one; `None` if there are no runs.
+    fn resolve(&self, run: Option<&str>) -> Result<Option<String>, QuarantineError> {
+        let runs = self.runs()?;
+        match run {
+            Some(id) if runs.iter().any(|r| r == id) => Ok(Some(id.to_string())),
+            Some(id) => Err(QuarantineError::UnknownRun(id.to_string())),
+            None => Ok(runs.last().cloned()),
+        }
+    }
+
+    /// Create a directory for a new run, named after the current UTC time.
+    fn new_run_dir(&self) -> Result<(String, PathBuf), QuarantineError> {
+        fs::create_dir_all(&self.dir).map_err(QuarantineError::io(&self.dir))?;
+        let stamp = run_timestamp(SystemTime::now());
+        for n in 1.. {
+            let id = if n == 1 {
+                stamp.clone()
+            } else {
+                format!("{stamp}-{n}")
+            };
+            let dir = self.dir.join(&id);
+            match fs::create_dir(&dir) {
+                Ok(()) => return Ok((id, dir)),
+                Err(e) if e.kind() == std::io::ErrorKind::AlreadyExists => continue,
+                Err(e) => return Err(QuarantineError::io(&dir)(e)),
+            }
+        }
+        unreachable!("run directory suffixes are unbounded")
+    }
+}
+
+/// The manifest of the run in `run_dir`; empty if the run never wrote one.
+fn read_manifest(run_dir: &Path) -> Result<Manifest, QuarantineError> {
+    let path = run_dir.join(MANIFEST);
+    let text = match fs::read_to_string(&path) {
+        Ok(text) => text,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Manifest::default()),
+        Err(e) => return Err(QuarantineError::io(&path)(e)),
+    };
+    toml::from_str(&text).map_err(|source| QuarantineError::Manifest { path, source })
+}
+
+/// Replace the manifest in `run_dir` by renaming a new one over it, so that
+/// it is never left half written.
+fn write_manifest(run_dir: &Path, manifest: &Manifest) -> Result<(), QuarantineError> {
+    let path = run_dir.join(MANIFEST);
+    let partial = run_dir.join(format!("{MANIFEST}.partial"));
+    fs::write(&partial, toml::to_string(manifest)?).map_err(QuarantineError::io(&partial))?;
+    fs::rename(&partial, &path).map_err(QuarantineError::io(&path))
+}
+
+/// Remove the run directory `run_dir` once everything in its manifest has
+/// been restored: the manifest and the emptied directories artifacts were
+/// stored in. Anything else is left, with a warning.
+fn remove_emptied_run(run_dir: &Path) -> Result<(), QuarantineError> {
+    let entries = fs::read_dir(run_dir).map_err(QuarantineError::io(run_dir))?;
+    for entry in entries.flatten() {
+        let path = entry.path();
+        if entry.file_name() == MANIFEST {
+            fs::remove_file(&path).map_err(QuarantineError::io(&path))?;
+        } else {
+            // Fails, leaving it, unless it is an emptied storage directory.
+            let _ = fs::remove_dir(&path);
+        }
+    }
+    if fs::remove_dir(run_dir).is_err() {
+        warn!(
+            "{} holds files its manifest does not list; left in place (purge removes them)",
+            run_dir.display()
+        );
+    }
+    Ok(())
+}
+
+/// Format a time as a sortable UTC run ID, e.g. `20261016-101500`.
+fn run_timestamp(time: SystemTime) -> String {
+    let t = UtcTime::of(time);
+    format!(
+        "{:04}{:02}{:02}-{:02}{:02}{:02}",
+        t.year, t.month, t.day, t.hour, t.minute, t.second
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::{Duration, UNIX_EPOCH};
+    use tempfile::TempDir;
+
+    fn artifact(tmp: &TempDir, project: &str, name: &'static str) -> Artifact {
+        let project = tmp.path().join(project);
+        let path = project.join(name);
+        fs::create_dir_all(&path).unwrap();
+        fs::write(path.join("file.txt"), "test data").unwrap();
+        Artifact {
+            size_bytes: 9,
+            ..Artifact::for_test(path, "Test")
+        }
+    }
+
+    #[test]
+    fn run_timestamp_is_utc_civil_time() {
+        assert_eq!(run_timestamp(UNIX_EPOCH), "19700101-000000");
+        let t = UNIX_EPOCH + Duration::from_secs(1_709_210_096);
+        assert_eq!(run_timestamp(t), "20240229-123456");
+    }
+
+    #[test]
+    fn quarantine_then_restore_round_trips() {
+        let tmp = TempDir::new().unwrap();
+        let q = Quarantine::new(&tmp.path().join("q"));
+        let artifacts = vec![artifact(&tmp, "a", "target"), artifact(&tmp, "b", "target")];
+
+        let run = q.quarantine(&artifacts).unwrap();
+        assert_eq!(run.moved, 2);
+        assert!(run.errors.is_empty());
+        assert!(!artifacts[0].path.exists());
+        assert_eq!(q.runs().unwrap(), vec![run.id.clone()]);
+
+        let mut out = Vec::new();
+        q.restore(&mut out, None).unwrap();
+        assert!(artifacts[0].path.join("file.txt").exists());
+        assert!(artifacts[1].path.join("file.txt").exists());
+        assert!(q.runs().unwrap().is_empty());
+        let output = String::from_utf8(out).unwrap();
+        assert!(output.contains(&format!(
+            "Restored 2 of 2 artifact directories from run {}",
+            run.id
+        )));
+    }
+
+    #[test]
+    fn restore_keeps_entries_whose_original_exists() {
+        let tmp = TempDir::new().unwrap();
+        let q = Quarantine::new(&tmp.path().join("q"));
+        let artifacts = vec![artifact(&tmp, "a", "target")];
+        let run = q.quarantine(&artifacts).unwrap();
+        fs::create_dir_all(&artifacts[0].path).unwrap();
+
+        let mut out = Vec::new();
+        q.restore(&mut out, Some(&run.id)).unwrap();
+        let output = String::from_utf8(out).unwrap();
+        assert!(output.contains("already exists"));
+        assert!(output.contains("Restored 0 of 1"));
+        assert_eq!(q.runs().unwrap(), [run.id]);
+    }
+
+    #[test]
+    fn runs_cut_short_can_be_restored_and_purged() {
+        let tmp = TempDir::new().unwrap();
+        let q = Quarantine::new(&tmp.path().join("q"));
+        let moved = artifact(&tmp, "a", "target");
+        let unmoved = artifact(&tmp, "b", "target");
+        let run = q.quarantine(std::slice::from_ref(&moved)).unwrap();
+        // As if the run had died after recording `unmoved`, before moving it.
+        let run_dir = tmp.path().join("q").join(&run.id);
+        let mut manifest = read_manifest(&run_dir).unwrap();
+        manifest.artifacts.push(ManifestEntry {
+            original: unmoved.path.clone(),
+            stored: PathBuf::from("1/target"),
+            build_system: "Test".to_string(),
+            size_bytes: 9,
+        });
+        write_manifest(&run_dir, &manifest).unwrap();
+        // And a run that died before writing a manifest at all.
+        let bare = tmp.path().join("q/20000101-000000");
+        fs::create_dir_all(bare.join("0/target")).unwrap();
+        assert_eq!(q.runs().unwrap(), ["20000101-000000", run.id.as_str()]);
+
+        let mut out = Vec::new();
+        q.restore(&mut out, Some(&run.id)).unwrap();
+        assert!(String::from_utf8(out).unwrap().contains("Restored 1 of 1"));
+        assert!(moved.path.join("file.txt").exists());
+        assert!(unmoved.path.join("file.txt").exists());
+
+        let mut out = Vec::new();
+        q.purge(&mut out, None).unwrap();
+        assert!(q.runs().unwrap().is_empty());
+        assert!(!bare.exists());
+    }
+
+    #[test]
+    fn purge_removes_runs() {
+        let tmp = TempDir::new().unwrap();
+        let q = Quarantine::new(&tmp.path().join("q"));
+        q.quarantine(&[artifact(&tmp, "a", "target")]).unwrap();
+        q.quarantine(&[artifact(&tmp, "b", "target")]).unwrap();
+        assert_eq!(q.runs().unwrap().len(), 2);
+
+        let mut out = Vec::new();
+        q.purge(&mut out, None).unwrap();
+        assert!(q.runs().unwrap().is_empty());
+        assert!(
+            String::from_utf8(out)
+                .unwrap()
+                .contains("Purged 2 quarantined runs (18 B)")
+        );
+    }
+
+    #[test]
+    fn unknown_run_errors() {
+        let tmp = TempDir::new().unwrap();
+        let q = Quarantine::new(tmp.path());
+        let mut out = Vec::new();
+        assert!(matches!(
+            q.restore(&mut out, Some("nope")),
+            Err(QuarantineError::UnknownRun(_))
+        ));
+        q.restore(&mut out, None).unwrap();
+        assert!(
+            String::from_utf8(out)
+                .unwrap()
+                .contains("Quarantine is empty.")
+        );
+    }
+}
@@ -1,7 +1,17 @@
-use std::collections::BTreeSet;
-use std::path::Path;
+#[cfg(any(feature = "serde", test))]
+use std::collections::HashSet;
+use std::collections::{BTreeSet, HashMap};
+use std::io::Read;
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, OnceLock};
 
+use globset::{Glob, GlobMatcher};
 use log::warn;
+use regex::Regex;
+use serde::Deserialize;
+#[cfg(feature = "serde")]
+use serde::Serialize;
 
 /// Describes a build artifact directory and how to identify it.
 #[derive(Debug, Clone)]
@@ -11,6 +21,42 @@ pub struct ArtifactRule {
     pub build_system: &'static str,
     pub artifact_dir: &'static str,
     pub marker: MarkerKind,
+    pub risk: Risk,
+    /// The build tool's own clean command, run in the project directory by
+    /// `--use-tool` (e.g., `cargo clean`).
+    pub clean: Option<&'static [&'static str]>,
+}
+
+/// How costly it is to get an artifact back after deleting it.
+#[derive(
+    Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, clap::ValueEnum, Deserialize,
+)]
+#[cfg_attr(feature = "serde", derive(Serialize))]
+#[serde(rename_all = "lowercase")]
+pub enum Risk {
+    /// Instantly regenerable caches (e.g., `__pycache__`, `.gradle`).
+    Cache,
+    /// Installed dependencies that need a reinstall (e.g., `node_modules`, `.venv`).
+    Deps,
+    /// Build products that need a rebuild (e.g., `target`, `build`).
+    Output,
+}
+
+impl Risk {
+    /// Lowercase name used on the command line and in output.
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Risk::Cache => "cache",
+            Risk::Deps => "deps",
+            Risk::Output => "output",
+        }
+    }
+}
+
+impl std::fmt::Display for Risk {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
 }
 
 /// How to confirm that an artifact directory belongs to a known build system.
@@ -20,15 +66,45 @@ pub enum MarkerKind {
     Files(&'static [&'static str]),
     /// Parent directory must contain a file matching a glob suffix (e.g., `.csproj`).
     GlobSuffix(&'static str),
+    /// Parent directory must contain an entry whose name matches a full glob
+    /// (e.g., `*.xcodeproj`, `Makefile.*`). Compiled once when the rule is parsed.
+    Glob(GlobMatcher),
+    /// The candidate directory itself must contain this file or directory
+    /// (e.g., `pyvenv.cfg`, or Gradle's `tmp`).
+    InsideDir(&'static str),
+    /// The parent directory or one of its ancestors (up to
+    /// `MAX_ANCESTOR_DEPTH` levels, and no further up than a `src` directory)
+    /// must contain one of these files. Used for modules of multi-project
+    /// builds where only the root has the marker.
+    Ancestor(&'static [&'static str]),
+    /// Parent directory must contain `file`, and the first `CONTENT_PEEK_BYTES`
+    /// of it must contain `needle` (e.g., `package.json` mentioning `"next"`).
+    FileContains {
+        file: &'static str,
+        needle: &'static str,
+    },
+    /// Every one of these markers must hold (e.g., `pubspec.yaml` and `lib/main.dart`).
+    All(&'static [MarkerKind]),
+    /// At least one of these markers must hold.
+    Any(&'static [MarkerKind]),
     /// No marker needed -- always matches (e.g., `__pycache__`).
     Always,
 }
 
-/// Whether the artifact directory name is an exact match or a suffix glob.
+/// Whether the artifact directory name is an exact match, a suffix glob, or a regex.
 #[derive(Debug, Clone)]
 pub enum DirMatch {
     Exact(&'static str),
     Suffix(&'static str),
+    /// Compiled once when the rule is constructed. Patterns should be anchored
+    /// (`^...$`) unless a partial match is intended.
+    Regex(Regex),
+    /// A multi-segment relative path such as `vendor/bundle` or `.yarn/cache`.
+    /// Markers are looked up in the directory containing the first segment.
+    Path(&'static [&'static str]),
+    /// Any directory name -- only useful with a marker that inspects the
+    /// directory itself (`MarkerKind::InsideDir`).
+    Any,
 }
 
 /// A rule with its matching strategy.
@@ -36,8 +112,27 @@ pub enum DirMatch {
 pub struct MatchableRule {
     pub rule: ArtifactRule,
     pub dir_match: DirMatch,
+    pub entry: EntryKind,
 }
 
+/// What kind of filesystem entry a rule matches.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum EntryKind {
+    /// A directory, pruned from the scan once matched.
+    Dir,
+    /// A regular file (e.g., `.ghc.environment.*`).
+    File,
+    /// A symbolic link, which is never followed (e.g., Nix `result` links).
+    Symlink,
+}
+
+/// How many directories `MarkerKind::Ancestor` looks at, starting with the parent.
+pub const MAX_ANCESTOR_DEPTH: usize = 8;
+
+/// How much of a marker file `MarkerKind::FileContains` reads.
+pub const CONTENT_PEEK_BYTES: u64 = 64 * 1024;
+
 /// Error from invalid system IDs in `--system` or `--exclude-system`.
 #[derive(thiserror::Error, Debug)]
 #[error("unknown build system: {id}\nValid systems: {valid}")]
@@ -46,163 +141,243 @@ pub struct SystemFilterError {
     valid: String,
 }
 
+/// Built-in rules, embedded at compile time. See the header of the file for
+/// the format.
+pub const BUILTIN_RULES: &str = include_str!("rules.toml");
+
 /// Returns the full set of artifact rules, ordered so that more specific markers
 /// come first (helps with disambiguation of `target/`, `build/`, etc.).
+///
+/// The rules are parsed from `rules.toml` on first use and cached.
 pub fn all_rules() -> Vec<MatchableRule> {
-    vec![
-        // Java/Maven
-        mr("maven", "Java/Maven", "target", &["pom.xml"]),
-        // Rust/Cargo
-        mr("cargo", "Rust/Cargo", "target", &["Cargo.toml"]),
-        // Scala/SBT
-        mr("sbt", "Scala/SBT", "target", &["build.sbt"]),
-        // Node.js
-        mr("node", "Node.js", "node_modules", &["package.json"]),
-        mr("node", "Node.js", ".next", &["package.json"]),
-        mr("node", "Node.js", ".nuxt", &["package.json"]),
-        mr("node", "Node.js", ".output", &["package.json"]),
-        // Swift/SPM
-        mr("spm", "Swift/SPM", ".build", &["Package.swift"]),
-        // Python -- no-marker variants
-        MatchableRule {
-            rule: ArtifactRule {
-                id: "python",
-                build_system: "Python",
-                artifact_dir: "__pycache__",
-                marker: MarkerKind::Always,
-            },
-            dir_match: DirMatch::Exact("__pycache__"),
-        },
-        MatchableRule {
-            rule: ArtifactRule {
-                id: "python",
-                build_system: "Python",
-                artifact_dir: ".mypy_cache",
-                marker: MarkerKind::Always,
-            },
-            dir_match: DirMatch::Exact(".mypy_cache"),
-        },
-        MatchableRule {
-            rule: ArtifactRule {
-                id: "python",
-                build_system: "Python",
-                artifact_dir: ".pytest_cache",
-                marker: MarkerKind::Always,
-            },
-            dir_match: DirMatch::Exact(".pytest_cache"),
-        },
-        // Python -- marker variants
-        mr_multi(
-            "python",
-            "Python",
-            ".venv",
-            &["pyproject.toml", "setup.py", "requirements.txt"],
-        ),
-        mr_multi(
-            "python",
-            "Python",
-            "venv",
-            &["pyproject.toml", "setup.py", "requirements.txt"],
-        ),
-        mr_multi(
-            "python",
-            "Python",
-            ".tox",
-            &["pyproject.toml", "setup.py", "requirements.txt"],
-        ),
-        // Python egg-info (suffix match)
-        MatchableRule {
-            rule: ArtifactRule {
-                id: "python",
-                build_system: "Python",
-                artifact_dir: "*.egg-info",
-                marker: MarkerKind::Files(&["pyproject.toml", "setup.py", "requirements.txt"]),
-            },
-            dir_match: DirMatch::Suffix(".egg-info"),
+    static RULES: OnceLock<Vec<MatchableRule>> = OnceLock::new();
+    RULES
+        .get_or_init(|| parse_rules(BUILTIN_RULES).expect("built-in rules are valid"))
+        .clone()
+}
+
+/// Identifies the built-in rule set: a hash of `rules.toml`, which changes
+/// whenever a rule does.
+pub fn rules_revision() -> String {
+    format!("{:016x}", crate::lock::fnv1a(BUILTIN_RULES.as_bytes()))
+}
+
+/// Error from a malformed rule file.
+#[derive(thiserror::Error, Debug)]
+pub enum RuleFileError {
+    #[error("invalid rule file: {0}")]
+    Parse(#[from] toml::de::Error),
+    #[error("rule {index} ({id}): {reason}")]
+    Invalid {
+        index: usize,
+        id: String,
+        reason: String,
+    },
+}
+
+/// Top level of a rule file: an ordered list of `[[rule]]` tables, each
+/// with where it is in the file.
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct RuleFile {
+    #[serde(default)]
+    rule: Vec<toml::Spanned<RuleSpec>>,
+}
+
+/// One `[[rule]]` table. Exactly one of `dir`, `suffix`, `regex`, `path`, and
+/// `any` must be set.
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct RuleSpec {
+    id: String,
+    system: String,
+    risk: Risk,
+    marker: MarkerSpec,
+    dir: Option<String>,
+    suffix: Option<String>,
+    regex: Option<String>,
+    path: Option<String>,
+    #[serde(default)]
+    any: bool,
+    artifact: Option<String>,
+    entry: Option<EntryKind>,
+    clean: Option<Vec<String>>,
+}
+
+/// Serialized form of `MarkerKind`.
+#[derive(Deserialize)]
+#[serde(rename_all = "snake_case", deny_unknown_fields)]
+enum MarkerSpec {
+    Files(Vec<String>),
+    GlobSuffix(String),
+    Glob(String),
+    InsideDir(String),
+    Ancestor(Vec<String>),
+    FileContains { file: String, needle: String },
+    All(Vec<MarkerSpec>),
+    Any(Vec<MarkerSpec>),
+    Always,
+}
+
+/// Parse rules in the `rules.toml` format, preserving their order.
+///
+/// Strings are leaked so that parsed rules share the `'static` types of the
+/// rest of the crate; callers are expected to parse a rule file once per run.
+pub fn parse_rules(text: &str) -> Result<Vec<MatchableRule>, RuleFileError> {
+    parse_rules_each(text)?
+        .into_iter()
+        .map(|(_, rule)| rule)
+        .collect()
+}
+
+/// One rule of a file read by [`parse_rules_each`]: the byte range of its
+/// table, and the rule or why it is invalid.
+pub type ParsedRule = (Range<usize>, Result<MatchableRule, RuleFileError>);
+
+/// Parse rules like [`parse_rules`], but build each one on its own, so that
+/// every invalid rule is reported rather than just the first.
+pub fn parse_rules_each(text: &str) -> Result<Vec<ParsedRule>, toml::de::Error> {
+    let file: RuleFile = toml::from_str(text)?;
+    Ok(file
+        .rule
+        .into_iter()
+        .enumerate()
+        .map(|(i, spec)| {
+            let span = spec.span();
+            let rule =
+                build_rule(spec.into_inner()).map_err(|(id, reason)| RuleFileError::Invalid {
+                    index: i + 1,
+                    id,
+                    reason,
+                });
+            (span, rule)
+        })
+        .collect())
+}
+
+/// Turn a parsed `[[rule]]` table into a rule, or `(id, reason)` if it is invalid.
+fn build_rule(spec: RuleSpec) -> Result<MatchableRule, (String, String)> {
+    let fail = |reason: String| (spec.id.clone(), reason);
+
+    if spec.id.is_empty() || spec.system.is_empty() {
+        return Err(fail("`id` and `system` must not be empty".into()));
+    }
+    let set = [
+        spec.dir.is_some(),
+        spec.suffix.is_some(),
+        spec.regex.is_some(),
+        spec.path.is_some(),
+        spec.any,
+    ];
+    if set.iter().filter(|&&s| s).count() != 1 {
+        return Err(fail(
+            "exactly one of `dir`, `suffix`, `regex`, `path`, `any` must be set".into(),
+        ));
+    }
+
+    let (dir_match, derived) = if let Some(name) = &spec.dir {
+        (DirMatch::Exact(leak(name.clone())), Some(name.clone()))
+    } else if let Some(suffix) = &spec.suffix {
+        (
+            DirMatch::Suffix(leak(suffix.clone())),
+            Some(format!("*{suffix}")),
+        )
+    } else if let Some(pattern) = &spec.regex {
+        let re = Regex::new(pattern).map_err(|e| fail(format!("invalid regex: {e}")))?;
+        (DirMatch::Regex(re), None)
+    } else if let Some(path) = &spec.path {
+        let segments: Vec<String> = path.split('/').map(str::to_owned).collect();
+        if segments.len() < 2 || segments.iter().any(String::is_empty) {
+            return Err(fail(format!("`path` needs two or more segments: {path}")));
+        }
+        (DirMatch::Path(leak_all(segments)), Some(path.clone()))
+    } else {
+        (DirMatch::Any, Some("*".to_string()))
+    };
+    let Some(artifact_dir) = spec.artifact.clone().or(derived) else {
+        return Err(fail("`regex` rules must set `artifact`".into()));
+    };
+
+    let marker = build_marker(spec.marker).map_err(fail)?;
+    if spec.clean.as_ref().is_some_and(Vec::is_empty) {
+        return Err(fail("`clean` needs a command".into()));
+    }
+
+    Ok(MatchableRule {
+        rule: ArtifactRule {
+            id: leak(spec.id),
+            build_system: leak(spec.system),
+            artifact_dir: leak(artifact_dir),
+            marker,
+            risk: spec.risk,
+            clean: spec.clean.map(leak_all),
         },
-        // Android/Gradle
-        mr_multi(
-            "gradle",
-            "Android/Gradle",
-            "build",
-            &["build.gradle", "build.gradle.kts"],
-        ),
-        mr_multi(
-            "gradle",
-            "Android/Gradle",
-            ".gradle",
-            &["build.gradle", "build.gradle.kts"],
+        dir_match,
+        entry: spec.entry.unwrap_or(EntryKind::Dir),
+    })
+}
+
+/// Turn a parsed marker into a `MarkerKind`, rejecting empty `all`/`any` lists
+/// and an empty `file_contains` file or needle.
+fn build_marker(spec: MarkerSpec) -> Result<MarkerKind, String> {
+    let build_all = |specs: Vec<MarkerSpec>, key: &str| {
+        if specs.is_empty() {
+            return Err(format!("`{key}` needs at least one marker"));
+        }
+        let markers = specs
+            .into_iter()
+            .map(build_marker)
+            .collect::<Result<Box<[_]>, _>>()?;
+        Ok::<_, String>(&*Box::leak(markers))
+    };
+    Ok(match spec {
+        MarkerSpec::Files(names) => MarkerKind::Files(leak_all(names)),
+        MarkerSpec::GlobSuffix(suffix) => MarkerKind::GlobSuffix(leak(suffix)),
+        MarkerSpec::Glob(pattern) => MarkerKind::Glob(
+            Glob::new(&pattern)
+                .map_err(|e| format!("invalid glob: {e}"))?
+                .compile_matcher(),
         ),
-        // C/C++/CMake
-        mr("cmake", "C/C++/CMake", "build", &["CMakeLists.txt"]),
-        mr("cmake", "C/C++/CMake", "CMakeFiles", &["CMakeLists.txt"]),
-        // .NET/C#
-        MatchableRule {
-            rule: ArtifactRule {
-                id: "dotnet",
-                build_system: ".NET/C#",
-                artifact_dir: "bin",
-                marker: MarkerKind::GlobSuffix(".csproj"),
-            },
-            dir_match: DirMatch::Exact("bin"),
-        },
-        MatchableRule {
-            rule: ArtifactRule {
-                id: "dotnet",
-                build_system: ".NET/C#",
-                artifact_dir: "obj",
-                marker: MarkerKind::GlobSuffix(".csproj"),
-            },
-            dir_match: DirMatch::Exact("obj"),
-        },
-        // .NET/C# -- .sln marker
-        MatchableRule {
-            rule: ArtifactRule {
-                id: "dotnet",
-                build_system: ".NET/C#",
-                artifact_dir: "bin",
-                marker: MarkerKind::GlobSuffix(".sln"),
-            },
-            dir_match: DirMatch::Exact("bin"),
-        },
-        MatchableRule {
-            rule: ArtifactRule {
-                id: "dotnet",
-                build_system: ".NET/C#",
-                artifact_dir: "obj",
-                marker: MarkerKind::GlobSuffix(".sln"),
-            },
-            dir_match: DirMatch::Exact("obj"),
-        },
-        // Elixir/Mix
-        mr("mix", "Elixir/Mix", "_build", &["mix.exs"]),
-        mr("mix", "Elixir/Mix", "deps", &["mix.exs"]),
-        // Haskell/Stack
-        mr("stack", "Haskell/Stack", ".stack-work", &["stack.yaml"]),
-        // Haskell/Cabal
-        MatchableRule {
-            rule: ArtifactRule {
-                id: "cabal",
-                build_system: "Haskell/Cabal",
-                artifact_dir: "dist-newstyle",
-                marker: MarkerKind::GlobSuffix(".cabal"),
-            },
-            dir_match: DirMatch::Exact("dist-newstyle"),
-        },
-        // Dart/Flutter
-        mr("flutter", "Dart/Flutter", ".dart_tool", &["pubspec.yaml"]),
-        mr("flutter", "Dart/Flutter", "build", &["pubspec.yaml"]),
-        // Zig
-        mr("zig", "Zig", "zig-out", &["build.zig"]),
-        mr("zig", "Zig", "zig-cache", &["build.zig"]),
-        // PHP/Composer
-        mr("composer", "PHP/Composer", "vendor", &["composer.json"]),
-        // CocoaPods
-        mr("cocoapods", "CocoaPods", "Pods", &["Podfile"]),
-        // Ruby/Bundler -- special: matches `bundle` inside a `vendor/` directory.
-        // The scanner checks the grandparent for `Gemfile`.
-        mr("bundler", "Ruby/Bundler", "bundle", &["Gemfile"]),
-    ]
+        MarkerSpec::InsideDir(name) => MarkerKind::InsideDir(leak(name)),
+        MarkerSpec::Ancestor(names) => MarkerKind::Ancestor(leak_all(names)),
+        MarkerSpec::FileContains { file, needle } => {
+            if file.is_empty() || needle.is_empty() {
+                return Err("`file_contains` needs a `file` and a `needle`".into());
+            }
+            MarkerKind::FileContains {
+                file: leak(file),
+                needle: leak(needle),
+            }
+        }
+        MarkerSpec::All(specs) => MarkerKind::All(build_all(specs, "all")?),
+        MarkerSpec::Any(specs) => MarkerKind::Any(build_all(specs, "any")?),
+        MarkerSpec::Always => MarkerKind::Always,
+    })
+}
+
+fn leak(s: String) -> &'static str {
+    Box::leak(s.into_boxed_str())
+}
+
+fn leak_all(items: Vec<String>) -> &'static [&'static str] {
+    Box::leak(items.into_iter().map(leak).collect::<Box<[_]>>())
+}
+
+/// `s` as a `'static` string, leaked only the first time it is seen, for
+/// rule names read back from serialized artifacts.
+#[cfg(any(feature = "serde", test))]
+pub(crate) fn intern(s: String) -> &'static str {
+    static INTERNED: OnceLock<Mutex<HashSet<&'static str>>> = OnceLock::new();
+    let mut interned = INTERNED
+        .get_or_init(Default::default)
+        .lock()
+        .unwrap_or_else(|e| e.into_inner());
+    if let Some(&s) = interned.get(s.as_str()) {
+        return s;
+    }
+    let s = leak(s);
+    interned.insert(s);
+    s
 }
 
 /// Returns sorted, deduplicated `(id, display_name)` pairs for all build systems.
@@ -265,51 +440,183 @@ pub fn filter_rules_by_system(
     }
 }
 
-/// Shorthand for an exact-match rule with a single-file marker set.
-fn mr(
-    id: &'static str,
-    build_system: &'static str,
-    artifact_dir: &'static str,
-    markers: &'static [&'static str],
-) -> MatchableRule {
-    MatchableRule {
-        rule: ArtifactRule {
-            id,
-            build_system,
-            artifact_dir,
-            marker: MarkerKind::Files(markers),
-        },
-        dir_match: DirMatch::Exact(artifact_dir),
+/// Filter rules to the given risk categories. An empty slice keeps all rules.
+pub fn filter_rules_by_risk(rules: Vec<MatchableRule>, risks: &[Risk]) -> Vec<MatchableRule> {
+    if risks.is_empty() {
+        return rules;
     }
+    rules
+        .into_iter()
+        .filter(|r| risks.contains(&r.rule.risk))
+        .collect()
 }
 
-/// Shorthand for an exact-match rule with multiple marker files.
-fn mr_multi(
-    id: &'static str,
-    build_system: &'static str,
-    artifact_dir: &'static str,
-    markers: &'static [&'static str],
-) -> MatchableRule {
-    mr(id, build_system, artifact_dir, markers)
-}
-
-/// Check if a parent directory contains any file matching the given marker.
-pub fn has_marker(parent: &Path, marker: &MarkerKind) -> bool {
+/// Check if a candidate artifact directory satisfies the given marker.
+///
+/// Most markers are looked up in `parent` (the project directory); `InsideDir`
+/// markers are looked up in `dir`, the candidate directory itself.
+pub fn has_marker(parent: &Path, dir: &Path, marker: &MarkerKind) -> bool {
     match marker {
         MarkerKind::Always => true,
+        MarkerKind::All(markers) => markers.iter().all(|m| has_marker(parent, dir, m)),
+        MarkerKind::Any(markers) => markers.iter().any(|m| has_marker(parent, dir, m)),
+        MarkerKind::InsideDir(name) => dir.join(name).exists(),
+        MarkerKind::FileContains { file, needle } => {
+            file_head(&parent.join(file)).is_some_and(|head| {
+                head.windows(needle.len())
+                    .any(|window| window == needle.as_bytes())
+            })
+        }
         MarkerKind::Files(names) => names.iter().any(|name| parent.join(name).exists()),
+        MarkerKind::Ancestor(names) => {
+            marker_ancestors(parent).any(|dir| names.iter().any(|name| dir.join(name).exists()))
+        }
+        MarkerKind::GlobSuffix(suffix) => any_entry(parent, |name| {
+            name.to_str().is_some_and(|name| name.ends_with(suffix))
+        }),
+        MarkerKind::Glob(glob) => any_entry(parent, |name| glob.is_match(name)),
+    }
+}
+
+/// [`has_marker`], with what was found, or for a marker that does not hold,
+/// what is missing (e.g. `Cargo.toml in /p/app`, `no pom.xml in /p/app`).
+pub fn explain_marker(parent: &Path, dir: &Path, marker: &MarkerKind) -> (bool, String) {
+    let names = |names: &[&str]| match names {
+        [name] => format!("no {name}"),
+        names => format!("none of {}", names.join(", ")),
+    };
+    let holds = has_marker(parent, dir, marker);
+    let text = match marker {
+        MarkerKind::Always => "no marker needed".to_string(),
+        MarkerKind::All(markers) => {
+            let explained: Vec<_> = markers
+                .iter()
+                .map(|m| explain_marker(parent, dir, m))
+                .collect();
+            let shown: Vec<_> = explained
+                .iter()
+                .filter(|(found, _)| *found == holds)
+                .map(|(_, text)| text.as_str())
+                .collect();
+            shown.join(if holds { " and " } else { "; " })
+        }
+        MarkerKind::Any(markers) => {
+            let explained: Vec<_> = markers
+                .iter()
+                .map(|m| explain_marker(parent, dir, m))
+                .collect();
+            match explained.iter().find(|(found, _)| *found) {
+                Some((_, text)) => text.clone(),
+                None => explained
+                    .iter()
+                    .map(|(_, text)| text.as_str())
+                    .collect::<Vec<_>>()
+                    .join("; "),
+            }
+        }
+        MarkerKind::InsideDir(name) if holds => format!("{name} inside {}", dir.display()),
+        MarkerKind::InsideDir(name) => format!("no {name} inside {}", dir.display()),
+        MarkerKind::FileContains { file, needle } if holds => {
+            format!("{} mentions {needle:?}", parent.join(file).display())
+        }
+        MarkerKind::FileContains { file, needle } => {
+            format!("no {} mentioning {needle:?}", parent.join(file).display())
+        }
+        MarkerKind::Files(list) => match list.iter().find(|name| parent.join(name).exists()) {
+            Some(name) => format!("{name} in {}", parent.display()),
+            None => format!("{} in {}", names(list), parent.display()),
+        },
+        MarkerKind::Ancestor(list) => {
+            let found = marker_ancestors(parent).find_map(|dir| {
+                list.iter()
+                    .find(|name| dir.join(name).exists())
+                    .map(|name| dir.join(name))
+            });
+            match found {
+                Some(path) => path.display().to_string(),
+                None => format!(
+                    "{} in {} or the directories above it, up to {} levels or a src directory",
+                    names(list),
+                    parent.display(),
+                    MAX_ANCESTOR_DEPTH - 1
+                ),
+            }
+        }
+        MarkerKind::GlobSuffix(suffix) if holds => {
+            format!("a file ending in {suffix} in {}", parent.display())
+        }
         MarkerKind::GlobSuffix(suffix) => {
-            let Ok(entries) = std::fs::read_dir(parent) else {
-                warn!("Cannot read directory: {}", parent.display());
-                return false;
-            };
-            entries.filter_map(|e| e.ok()).any(|e| {
-                e.file_name()
-                    .to_str()
-                    .is_some_and(|name| name.ends_with(suffix))
-            })
+            format!("no file ending in {suffix} in {}", parent.display())
+        }
+        MarkerKind::Glob(glob) if holds => {
+            format!("an entry matching {} in {}", glob.glob(), parent.display())
+        }
+        MarkerKind::Glob(glob) => {
+            format!("no entry matching {} in {}", glob.glob(), parent.display())
         }
+    };
+    (holds, text)
+}
+
+/// The directories an [`MarkerKind::Ancestor`] marker is looked for in:
+/// `parent` and those above it, up to [`MAX_ANCESTOR_DEPTH`] in all. The walk
+/// stops below a `src` directory, so that a package directory in a source
+/// tree (`src/main/java/com/acme/build`) is never taken for the output of a
+/// build whose root is further up.
+fn marker_ancestors(parent: &Path) -> impl Iterator<Item = &Path> {
+    parent
+        .ancestors()
+        .take(MAX_ANCESTOR_DEPTH)
+        .take_while(|dir| dir.file_name().is_none_or(|name| name != "src"))
+}
+
+/// Check whether any entry name in `dir` satisfies `pred`.
+fn any_entry(dir: &Path, pred: impl Fn(&std::ffi::OsStr) -> bool) -> bool {
+    let Ok(entries) = std::fs::read_dir(dir) else {
+        warn!("Cannot read directory: {}", dir.display());
+        return false;
+    };
+    entries.filter_map(|e| e.ok()).any(|e| pred(&e.file_name()))
+}
+
+/// Marker file heads keyed by path; `None` records an unreadable file.
+type ContentCache = Mutex<HashMap<PathBuf, Option<Arc<[u8]>>>>;
+
+/// Per-file cache of marker file heads, shared across `FileContains` checks so
+/// that e.g. `package.json` is read at most once per scan.
+fn content_cache() -> &'static ContentCache {
+    static CACHE: OnceLock<ContentCache> = OnceLock::new();
+    CACHE.get_or_init(Default::default)
+}
+
+/// Drop all cached marker file contents. Called at the start of each scan so
+/// that edits between scans in the same process are picked up.
+pub fn clear_content_cache() {
+    content_cache().lock().unwrap().clear();
+}
+
+/// Read (or fetch from cache) the first `CONTENT_PEEK_BYTES` of a file.
+fn file_head(path: &Path) -> Option<Arc<[u8]>> {
+    if let Some(cached) = content_cache().lock().unwrap().get(path) {
+        return cached.clone();
     }
+
+    let head = std::fs::File::open(path).ok().and_then(|f| {
+        let mut buf = Vec::new();
+        match f.take(CONTENT_PEEK_BYTES).read_to_end(&mut buf) {
+            Ok(_) => Some(Arc::from(buf)),
+            Err(e) => {
+                warn!("Cannot read marker file {}: {e}", path.display());
+                None
+            }
+        }
+    });
+
+    content_cache()
+        .lock()
+        .unwrap()
+        .insert(path.to_path_buf(), head.clone());
+    head
 }
 
 /// Check if a directory name matches a rule's pattern.
@@ -317,6 +624,30 @@ pub fn matches_dir(dir_name: &str, dir_match: &DirMatch) -> bool {
     match dir_match {
         DirMatch::Exact(name) => dir_name == *name,
         DirMatch::Suffix(suffix) => dir_name.ends_with(suffix),
+        DirMatch::Regex(re) => re.is_match(dir_name),
+        DirMatch::Path(segments) => segments.last() == Some(&dir_name),
+        DirMatch::Any => true,
+    }
+}
+
+/// Return the directory in which a rule's markers should be looked up.
+///
+/// For single-name matches this is the parent of `path`. For `DirMatch::Path`
+/// it is the directory above the first segment, or `None` if the leading
+/// segments of the rule don't match the trailing components of `path`.
+pub fn marker_anchor<'a>(path: &'a Path, dir_match: &DirMatch) -> Option<&'a Path> {
+    match dir_match {
+        DirMatch::Path(segments) => {
+            let mut current = path;
+            for segment in segments.iter().rev() {
+                if current.file_name()? != *segment {
+                    return None;
+                }
+                current = current.parent()?;
+            }
+            Some(current)
+        }
+        _ => path.parent(),
     }
 }
 
@@ -375,16 +706,26 @@ mod tests {
             "bundler",
             "cabal",
             "cargo",
+            "carthage",
+            "clojure",
             "cmake",
             "cocoapods",
             "composer",
             "dotnet",
+            "elm",
             "flutter",
+            "godot",
             "gradle",
+            "latex",
             "maven",
+            "mill",
             "mix",
+            "nix",
             "node",
+            "purescript",
             "python",
+            "qmake",
+            "rescript",
             "sbt",
             "spm",
             "stack",
@@ -457,6 +798,44 @@ mod tests {
         assert!(ids.contains("node"));
     }
 
+    #[test]
+    fn filter_by_risk_keeps_only_selected() {
+        let filtered = filter_rules_by_risk(all_rules(), &[Risk::Cache]);
+        assert!(!filtered.is_empty());
+        assert!(filtered.iter().all(|r| r.rule.risk == Risk::Cache));
+        assert!(
+            filtered
+                .iter()
+                .any(|r| r.rule.artifact_dir == "__pycache__")
+        );
+        assert!(
+            !filtered
+                .iter()
+                .any(|r| r.rule.artifact_dir == "node_modules")
+        );
+    }
+
+    #[test]
+    fn filter_by_risk_empty_returns_all() {
+        let total = all_rules().len();
+        assert_eq!(filter_rules_by_risk(all_rules(), &[]).len(), total);
+    }
+
+    #[test]
+    fn risk_classification_examples() {
+        let risk_of = |dir: &str| {
+            all_rules()
+                .into_iter()
+                .find(|r| r.rule.artifact_dir == dir)
+                .map(|r| r.rule.risk)
+        };
+        assert_eq!(risk_of("__pycache__"), Some(Risk::Cache));
+        assert_eq!(risk_of(".gradle"), Some(Risk::Cache));
+        assert_eq!(risk_of("node_modules"), Some(Risk::Deps));
+        assert_eq!(risk_of(".venv"), Some(Risk::Deps));
+        assert_eq!(risk_of("target"), Some(Risk::Output));
+    }
+
     #[test]
     fn matches_dir_exact() {
         assert!(matches_dir(
@@ -475,37 +854,131 @@ mod tests {
         assert!(!matches_dir("foo.egg", &DirMatch::Suffix(".egg-info")));
     }
 
+    #[test]
+    fn matches_dir_regex() {
+        let re = DirMatch::Regex(Regex::new(r"^build-.*-(Debug|Release)$").unwrap());
+        assert!(matches_dir("build-app-Desktop-Debug", &re));
+        assert!(matches_dir("build-app-Release", &re));
+        assert!(!matches_dir("build", &re));
+        assert!(!matches_dir("build-app-Debug.bak", &re));
+    }
+
+    #[test]
+    fn matches_dir_path_checks_last_segment() {
+        let dm = DirMatch::Path(&["vendor", "bundle"]);
+        assert!(matches_dir("bundle", &dm));
+        assert!(!matches_dir("vendor", &dm));
+    }
+
+    #[test]
+    fn marker_anchor_single_segment_is_parent() {
+        let path = Path::new("/proj/node_modules");
+        let anchor = marker_anchor(path, &DirMatch::Exact("node_modules"));
+        assert_eq!(anchor, Some(Path::new("/proj")));
+    }
+
+    #[test]
+    fn marker_anchor_multi_segment() {
+        let dm = DirMatch::Path(&[".yarn", "cache"]);
+        assert_eq!(
+            marker_anchor(Path::new("/proj/.yarn/cache"), &dm),
+            Some(Path::new("/proj"))
+        );
+        assert_eq!(marker_anchor(Path::new("/proj/other/cache"), &dm), None);
+        assert_eq!(marker_anchor(Path::new("cache"), &dm), None);
+    }
+
+    #[test]
+    fn matches_dir_any() {
+        assert!(matches_dir("whatever", &DirMatch::Any));
+    }
+
     #[test]
     fn has_marker_always() {
         let tmp = TempDir::new().unwrap();
-        assert!(has_marker(tmp.path(), &MarkerKind::Always));
+        assert!(has_marker(
+            tmp.path(),
+            &tmp.path().join("x"),
+            &MarkerKind::Always
+        ));
     }
 
     #[test]
     fn has_marker_files_present() {
         let tmp = TempDir::new().unwrap();
         fs::write(tmp.path().join("Cargo.toml"), "").unwrap();
-        assert!(has_marker(tmp.path(), &MarkerKind::Files(&["Cargo.toml"])));
+        assert!(has_marker(
+            tmp.path(),
+            &tmp.path().join("x"),
+            &MarkerKind::Files(&["Cargo.toml"])
+        ));
     }
 
     #[test]
     fn has_marker_files_absent() {
         let tmp = TempDir::new().unwrap();
-        assert!(!has_marker(tmp.path(), &MarkerKind::Files(&["Cargo.toml"])));
+        assert!(!has_marker(
+            tmp.path(),
+            &tmp.path().join("x"),
+            &MarkerKind::Files(&["Cargo.toml"])
+        ));
+    }
+
+    #[test]
+    fn explain_marker_says_what_was_found_or_missing() {
+        let tmp = TempDir::new().unwrap();
+        let dir = tmp.path().join("target");
+        fs::write(tmp.path().join("Cargo.toml"), "").unwrap();
+        let (found, text) = explain_marker(tmp.path(), &dir, &MarkerKind::Files(&["Cargo.toml"]));
+        assert!(found);
+        assert_eq!(text, format!("Cargo.toml in {}", tmp.path().display()));
+
+        let (found, text) = explain_marker(tmp.path(), &dir, &MarkerKind::Files(&["pom.xml"]));
+        assert!(!found);
+        assert_eq!(text, format!("no pom.xml in {}", tmp.path().display()));
+
+        let both = MarkerKind::All(&[
+            MarkerKind::Files(&["Cargo.toml"]),
+            MarkerKind::Files(&["a", "b"]),
+        ]);
+        let (found, text) = explain_marker(tmp.path(), &dir, &both);
+        assert!(!found);
+        assert_eq!(text, format!("none of a, b in {}", tmp.path().display()));
     }
 
     #[test]
     fn has_marker_glob_suffix_present() {
         let tmp = TempDir::new().unwrap();
         fs::write(tmp.path().join("MyProject.csproj"), "").unwrap();
-        assert!(has_marker(tmp.path(), &MarkerKind::GlobSuffix(".csproj")));
+        assert!(has_marker(
+            tmp.path(),
+            &tmp.path().join("x"),
+            &MarkerKind::GlobSuffix(".csproj")
+        ));
     }
 
     #[test]
     fn has_marker_glob_suffix_absent() {
         let tmp = TempDir::new().unwrap();
         fs::write(tmp.path().join("MyProject.txt"), "").unwrap();
-        assert!(!has_marker(tmp.path(), &MarkerKind::GlobSuffix(".csproj")));
+        assert!(!has_marker(
+            tmp.path(),
+            &tmp.path().join("x"),
+            &MarkerKind::GlobSuffix(".csproj")
+        ));
+    }
+
+    #[test]
+    fn has_marker_glob_matches_whole_name() {
+        let tmp = TempDir::new().unwrap();
+        fs::create_dir(tmp.path().join("App.xcodeproj")).unwrap();
+        fs::write(tmp.path().join("Makefile.am"), "").unwrap();
+        let glob = |p: &str| MarkerKind::Glob(Glob::new(p).unwrap().compile_matcher());
+        let dir = tmp.path().join("build");
+        assert!(has_marker(tmp.path(), &dir, &glob("*.xcodeproj")));
+        assert!(has_marker(tmp.path(), &dir, &glob("Makefile.*")));
+        assert!(!has_marker(tmp.path(), &dir, &glob("*.xcworkspace")));
+        assert!(!has_marker(tmp.path(), &dir, &glob("Makefile")));
     }
 
     #[test]
@@ -514,10 +987,167 @@ mod tests {
         fs::write(tmp.path().join("setup.py"), "").unwrap();
         assert!(has_marker(
             tmp.path(),
+            &tmp.path().join("x"),
             &MarkerKind::Files(&["pyproject.toml", "setup.py", "requirements.txt"])
         ));
     }
 
+    #[test]
+    fn has_marker_file_contains_match() {
+        let tmp = TempDir::new().unwrap();
+        fs::write(
+            tmp.path().join("package.json"),
+            r#"{"dependencies": {"next": "14.0.0"}}"#,
+        )
+        .unwrap();
+        let marker = MarkerKind::FileContains {
+            file: "package.json",
+            needle: "\"next\"",
+        };
+        assert!(has_marker(tmp.path(), &tmp.path().join(".next"), &marker));
+    }
+
+    #[test]
+    fn has_marker_file_contains_no_match() {
+        let tmp = TempDir::new().unwrap();
+        fs::write(
+            tmp.path().join("package.json"),
+            r#"{"dependencies": {"react": "18.0.0"}}"#,
+        )
+        .unwrap();
+        let marker = MarkerKind::FileContains {
+            file: "package.json",
+            needle: "\"next\"",
+        };
+        assert!(!has_marker(tmp.path(), &tmp.path().join(".next"), &marker));
+    }
+
+    #[test]
+    fn has_marker_file_contains_missing_file() {
+        let tmp = TempDir::new().unwrap();
+        let marker = MarkerKind::FileContains {
+            file: "package.json",
+            needle: "next",
+        };
+        assert!(!has_marker(tmp.path(), &tmp.path().join(".next"), &marker));
+    }
+
+    #[test]
+    fn has_marker_file_contains_is_bounded() {
+        let tmp = TempDir::new().unwrap();
+        let mut contents = " ".repeat(CONTENT_PEEK_BYTES as usize);
+        contents.push_str("needle");
+        fs::write(tmp.path().join("big.txt"), contents).unwrap();
+        let marker = MarkerKind::FileContains {
+            file: "big.txt",
+            needle: "needle",
+        };
+        assert!(!has_marker(tmp.path(), &tmp.path().join("x"), &marker));
+    }
+
+    #[test]
+    fn has_marker_ancestor_finds_root_marker() {
+        let tmp = TempDir::new().unwrap();
+        fs::write(tmp.path().join("build.sbt"), "").unwrap();
+        let module = tmp.path().join("modules").join("core");
+        fs::create_dir_all(&module).unwrap();
+        assert!(has_marker(
+            &module,
+            &module.join("target"),
+            &MarkerKind::Ancestor(&["build.sbt"])
+        ));
+    }
+
+    #[test]
+    fn has_marker_ancestor_stops_at_src() {
+        let tmp = TempDir::new().unwrap();
+        fs::write(tmp.path().join("settings.gradle"), "").unwrap();
+        let package = tmp.path().join("app/src/main/java/com/acme");
+        fs::create_dir_all(&package).unwrap();
+        assert!(!has_marker(
+            &package,
+            &package.join("build"),
+            &MarkerKind::Ancestor(&["settings.gradle"])
+        ));
+        let module = tmp.path().join("app");
+        assert!(has_marker(
+            &module,
+            &module.join("build"),
+            &MarkerKind::Ancestor(&["settings.gradle"])
+        ));
+    }
+
+    #[test]
+    fn has_marker_ancestor_is_bounded() {
+        let tmp = TempDir::new().unwrap();
+        fs::write(tmp.path().join("build.sbt"), "").unwrap();
+        let mut deep = tmp.path().to_path_buf();
+        for i in 0..MAX_ANCESTOR_DEPTH {
+            deep = deep.join(format!("d{i}"));
+        }
+        fs::create_dir_all(&deep).unwrap();
+        assert!(!has_marker(
+            &deep,
+            &deep.join("target"),
+            &MarkerKind::Ancestor(&["build.sbt"])
+        ));
+    }
+
+    #[test]
+    fn has_marker_inside_dir_present() {
+        let tmp = TempDir::new().unwrap();
+        let dir = tmp.path().join("env");
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(dir.join("pyvenv.cfg"), "").unwrap();
+        assert!(has_marker(
+            tmp.path(),
+            &dir,
+            &MarkerKind::InsideDir("pyvenv.cfg")
+        ));
+    }
+
+    #[test]
+    fn has_marker_inside_dir_ignores_parent() {
+        let tmp = TempDir::new().unwrap();
+        let dir = tmp.path().join("env");
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(tmp.path().join("pyvenv.cfg"), "").unwrap();
+        assert!(!has_marker(
+            tmp.path(),
+            &dir,
+            &MarkerKind::InsideDir("pyvenv.cfg")
+        ));
+    }
+
+    #[test]
+    fn has_marker_all_needs_every_marker() {
+        let tmp = TempDir::new().unwrap();
+        let dir = tmp.path().join("build");
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(tmp.path().join("CMakeLists.txt"), "").unwrap();
+        let marker = MarkerKind::All(&[
+            MarkerKind::Files(&["CMakeLists.txt"]),
+            MarkerKind::InsideDir("CMakeCache.txt"),
+        ]);
+        assert!(!has_marker(tmp.path(), &dir, &marker));
+        fs::write(dir.join("CMakeCache.txt"), "").unwrap();
+        assert!(has_marker(tmp.path(), &dir, &marker));
+    }
+
+    #[test]
+    fn has_marker_any_needs_one_marker() {
+        let tmp = TempDir::new().unwrap();
+        let dir = tmp.path().join("out");
+        let marker = MarkerKind::Any(&[
+            MarkerKind::GlobSuffix(".tex"),
+            MarkerKind::All(&[MarkerKind::Files(&["a"]), MarkerKind::Files(&["b"])]),
+        ]);
+        fs::write(tmp.path().join("a"), "").unwrap();
+        assert!(!has_marker(tmp.path(), &dir, &marker));
+        fs::write(tmp.path().join("b"), "").unwrap();
+        assert!(has_marker(tmp.path(), &dir, &marker));
+    }
+
     #[test]
     fn rule_count_covers_all_build_systems() {
         let rules = all_rules();
@@ -528,6 +1158,7 @@ mod tests {
             "Java/Maven",
             "Rust/Cargo",
             "Scala/SBT",
+            "Scala/Mill",
             "Node.js",
             "Swift/SPM",
             "Python",
@@ -541,10 +1172,315 @@ mod tests {
             "Zig",
             "PHP/Composer",
             "CocoaPods",
+            "Carthage",
+            "Clojure",
+            "Elm",
+            "Godot",
+            "LaTeX",
+            "Qt/qmake",
+            "ReScript",
+            "PureScript",
+            "Nix",
             "Ruby/Bundler",
         ];
         for sys in &expected {
             assert!(systems.contains(sys), "Missing build system: {sys}");
         }
     }
+
+    #[test]
+    fn parse_rules_reads_every_match_kind() {
+        let rules = parse_rules(
+            r#"
+            [[rule]]
+            id = "a"
+            system = "A"
+            dir = "target"
+            marker = { files = ["a.toml"] }
+            risk = "output"
+
+            [[rule]]
+            id = "b"
+            system = "B"
+            suffix = ".egg-info"
+            marker = "always"
+            risk = "cache"
+
+            [[rule]]
+            id = "c"
+            system = "C"
+            regex = '^out-\d+$'
+            artifact = "out-N"
+            entry = "file"
+            marker = { file_contains = { file = "c.json", needle = "c" } }
+            risk = "deps"
+
+            [[rule]]
+            id = "d"
+            system = "D"
+            path = "vendor/bundle"
+            marker = { ancestor = ["D"] }
+            risk = "deps"
+
+            [[rule]]
+            id = "e"
+            system = "E"
+            any = true
+            marker = { inside_dir = "e.cfg" }
+            risk = "output"
+            "#,
+        )
+        .unwrap();
+
+        let dirs: Vec<&str> = rules.iter().map(|r| r.rule.artifact_dir).collect();
+        assert_eq!(
+            dirs,
+            ["target", "*.egg-info", "out-N", "vendor/bundle", "*"]
+        );
+        assert!(matches!(rules[0].dir_match, DirMatch::Exact("target")));
+        assert!(matches!(rules[1].dir_match, DirMatch::Suffix(".egg-info")));
+        assert!(matches_dir("out-42", &rules[2].dir_match));
+        assert!(!matches_dir("out-x", &rules[2].dir_match));
+        assert_eq!(rules[2].entry, EntryKind::File);
+        assert_eq!(rules[2].rule.risk, Risk::Deps);
+        assert!(matches!(
+            rules[3].dir_match,
+            DirMatch::Path(["vendor", "bundle"])
+        ));
+        assert!(matches!(
+            rules[4].rule.marker,
+            MarkerKind::InsideDir("e.cfg")
+        ));
+        assert_eq!(rules[0].entry, EntryKind::Dir);
+    }
+
+    #[test]
+    fn parse_rules_requires_exactly_one_match_kind() {
+        let err = parse_rules(
+            r#"
+            [[rule]]
+            id = "a"
+            system = "A"
+            dir = "target"
+            suffix = ".out"
+            marker = "always"
+            risk = "output"
+            "#,
+        )
+        .unwrap_err();
+        assert!(err.to_string().contains("exactly one of"), "{err}");
+    }
+
+    #[test]
+    fn parse_rules_requires_artifact_for_regex() {
+        let err = parse_rules(
+            r#"
+            [[rule]]
+            id = "a"
+            system = "A"
+            regex = "^x$"
+            marker = "always"
+            risk = "output"
+            "#,
+        )
+        .unwrap_err();
+        assert!(err.to_string().contains("rule 1 (a)"), "{err}");
+    }
+
+    #[test]
+    fn parse_rules_rejects_invalid_regex_and_unknown_keys() {
+        let bad_regex = r#"
+            [[rule]]
+            id = "a"
+            system = "A"
+            regex = "("
+            artifact = "x"
+            marker = "always"
+            risk = "output"
+        "#;
+        assert!(matches!(
+            parse_rules(bad_regex),
+            Err(RuleFileError::Invalid { .. })
+        ));
+
+        let unknown_key = r#"
+            [[rule]]
+            id = "a"
+            system = "A"
+            dir = "x"
+            marker = "always"
+            risk = "output"
+            priority = 1
+        "#;
+        assert!(matches!(
+            parse_rules(unknown_key),
+            Err(RuleFileError::Parse(_))
+        ));
+    }
+
+    #[test]
+    fn parse_rules_reads_composite_markers() {
+        let rules = parse_rules(
+            r#"
+            [[rule]]
+            id = "a"
+            system = "A"
+            dir = "build"
+            marker = { all = [{ files = ["a"] }, { any = [{ inside_dir = "b" }, "always"] }] }
+            risk = "output"
+            "#,
+        )
+        .unwrap();
+        let MarkerKind::All([MarkerKind::Files(["a"]), MarkerKind::Any(inner)]) =
+            &rules[0].rule.marker
+        else {
+            panic!("unexpected marker: {:?}", rules[0].rule.marker);
+        };
+        assert!(matches!(
+            inner,
+            [MarkerKind::InsideDir("b"), MarkerKind::Always]
+        ));
+    }
+
+    #[test]
+    fn parse_rules_compiles_glob_markers() {
+        let text = |glob: &str| {
+            format!(
+                "[[rule]]\nid = \"a\"\nsystem = \"A\"\ndir = \"build\"\n\
+                 marker = {{ glob = '{glob}' }}\nrisk = \"output\"\n"
+            )
+        };
+        let rules = parse_rules(&text("*-workspace.code-workspace")).unwrap();
+        let MarkerKind::Glob(glob) = &rules[0].rule.marker else {
+            panic!("unexpected marker: {:?}", rules[0].rule.marker);
+        };
+        assert!(glob.is_match("app-workspace.code-workspace"));
+        assert!(!glob.is_match("app.code-workspace"));
+
+        let err = parse_rules(&text("[unclosed")).unwrap_err();
+        assert!(err.to_string().contains("invalid glob"), "{err}");
+    }
+
+    #[test]
+    fn parse_rules_rejects_empty_composite() {
+        let err = parse_rules(
+            r#"
+            [[rule]]
+            id = "a"
+            system = "A"
+            dir = "build"
+            marker = { any = [] }
+            risk = "output"
+            "#,
+        )
+        .unwrap_err();
+        assert!(
+            err.to_string().contains("`any` needs at least one"),
+            "{err}"
+        );
+    }
+
+    #[test]
+    fn parse_rules_rejects_empty_file_contains() {
+        for marker in [
+            r#"{ file_contains = { file = "setup.cfg", needle = "" } }"#,
+            r#"{ file_contains = { file = "", needle = "[tool]" } }"#,
+        ] {
+            let err = parse_rules(&format!(
+                r#"
+                [[rule]]
+                id = "a"
+                system = "A"
+                dir = "build"
+                marker = {marker}
+                risk = "output"
+                "#
+            ))
+            .unwrap_err();
+            assert!(
+                err.to_string().contains("needs a `file` and a `needle`"),
+                "{err}"
+            );
+        }
+    }
+
+    #[test]
+    fn builtin_clean_commands_remove_only_their_artifact() {
+        let cleaned: Vec<(&str, &str)> = all_rules()
+            .iter()
+            .filter(|r| r.rule.clean.is_some())
+            .map(|r| (r.rule.id, r.rule.artifact_dir))
+            .collect();
+        assert_eq!(
+            cleaned,
+            [("maven", "target"), ("cargo", "target"), ("mill", "out")]
+        );
+        let cargo = all_rules().into_iter().find(|r| r.rule.id == "cargo");
+        assert_eq!(
+            cargo.unwrap().rule.clean.unwrap(),
+            ["cargo", "clean", "--target-dir", "target"]
+        );
+    }
+
+    #[test]
+    fn parse_rules_reads_clean_command() {
+        let text = |clean: &str| {
+            format!(
+                "[[rule]]\nid = \"a\"\nsystem = \"A\"\ndir = \"target\"\n\
+                 marker = \"always\"\nrisk = \"output\"\nclean = {clean}\n"
+            )
+        };
+        let rules = parse_rules(&text(r#"["cargo", "clean"]"#)).unwrap();
+        assert_eq!(rules[0].rule.clean, Some(&["cargo", "clean"][..]));
+
+        let err = parse_rules(&text("[]")).unwrap_err();
+        assert!(err.to_string().contains("`clean` needs a command"), "{err}");
+    }
+
+    #[test]
+    fn builtin_rule_file_parses() {
+        parse_rules(BUILTIN_RULES).expect("built-in rules are valid");
+    }
+
+    mod prop {
+        use super::*;
+        use proptest::prelude::*;
+
+        /// Names built from the characters that appear in real artifact dirs,
+        /// biased toward existing rule names so matches actually occur.
+        fn dir_name() -> impl Strategy<Value = String> {
+            let known: Vec<String> = all_rules()
+                .iter()
+                .map(|r| r.rule.artifact_dir.to_string())
+                .collect();
+            prop_oneof![
+                "[a-zA-Z0-9._-]{0,12}",
+                proptest::sample::select(known.clone()),
+                (proptest::sample::select(known), "[a-z._-]{0,4}")
+                    .prop_map(|(k, extra)| format!("{extra}{k}")),
+            ]
+        }
+
+        proptest! {
+            #[test]
+            fn regex_agrees_with_exact(name in dir_name(), exact in dir_name()) {
+                let exact: &'static str = Box::leak(exact.into_boxed_str());
+                let re = Regex::new(&format!("^{}$", regex::escape(exact))).unwrap();
+                prop_assert_eq!(
+                    matches_dir(&name, &DirMatch::Regex(re)),
+                    matches_dir(&name, &DirMatch::Exact(exact))
+                );
+            }
+
+            #[test]
+            fn regex_agrees_with_suffix(name in dir_name(), suffix in dir_name()) {
+                let suffix: &'static str = Box::leak(suffix.into_boxed_str());
+                let re = Regex::new(&format!("{}$", regex::escape(suffix))).unwrap();
+                prop_assert_eq!(
+                    matches_dir(&name, &DirMatch::Regex(re)),
+                    matches_dir(&name, &DirMatch::Suffix(suffix))
+                );
+            }
+        }
+    }
 }
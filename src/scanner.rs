@@ -1,19 +1,105 @@
+use std::collections::HashMap;
 use std::path::{Path, PathBuf};
 use std::sync::{Arc, Mutex};
 
-use jwalk::WalkDir;
-use log::{debug, warn};
+use jwalk::{DirEntry, WalkDirGeneric};
+use log::debug;
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Deserializer, Serialize};
 
-use crate::rules::{MatchableRule, has_marker, matches_dir};
+use crate::disk::volume_id;
+use crate::interrupt::CancelToken;
+use crate::progress::Observer;
+use crate::rules::{
+    EntryKind, MatchableRule, Risk, clear_content_cache, has_marker, marker_anchor, matches_dir,
+};
+use crate::size::{SizeOptions, SizeStats, SizingProgress, Tally, assign_tallies};
 
 /// A detected build artifact.
+///
+/// Serialized as `--json` prints it. Deserializing one does not check that
+/// it still matches a rule; see [`revalidate`](crate::export::revalidate).
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize))]
 pub struct Artifact {
     pub path: PathBuf,
+    /// Directory the rule's marker was found in -- the project root.
+    pub project: PathBuf,
     pub build_system: &'static str,
     pub artifact_dir: &'static str,
+    pub risk: Risk,
     /// Computed later by `size.rs`.
     pub size_bytes: u64,
+    /// Everything else the size pass counted while walking the artifact;
+    /// `None` until it has been sized.
+    #[cfg_attr(feature = "serde", serde(skip_serializing_if = "Option::is_none"))]
+    pub stats: Option<SizeStats>,
+    /// Identifies the filesystem holding the artifact, as
+    /// [`volume_id`](crate::disk::volume_id) does; `None` if it could not
+    /// be read.
+    #[cfg_attr(feature = "serde", serde(skip_serializing_if = "Option::is_none"))]
+    pub volume: Option<String>,
+    /// The rule's clean command, for `--use-tool`. Not serialized, as it
+    /// belongs to the rule rather than the artifact.
+    #[cfg_attr(feature = "serde", serde(skip))]
+    pub clean: Option<&'static [&'static str]>,
+}
+
+// By hand, as a derive would borrow the `&'static str` names from the input
+// and so only read from `'static` data.
+#[cfg(feature = "serde")]
+impl<'de> Deserialize<'de> for Artifact {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        #[derive(Deserialize)]
+        struct Fields {
+            path: PathBuf,
+            project: PathBuf,
+            build_system: String,
+            artifact_dir: String,
+            risk: Risk,
+            size_bytes: u64,
+            #[serde(default)]
+            stats: Option<SizeStats>,
+            #[serde(default)]
+            volume: Option<String>,
+        }
+        let fields = Fields::deserialize(deserializer)?;
+        Ok(Artifact {
+            path: fields.path,
+            project: fields.project,
+            build_system: crate::rules::intern(fields.build_system),
+            artifact_dir: crate::rules::intern(fields.artifact_dir),
+            risk: fields.risk,
+            size_bytes: fields.size_bytes,
+            stats: fields.stats,
+            volume: fields.volume,
+            clean: None,
+        })
+    }
+}
+
+#[cfg(test)]
+impl Artifact {
+    /// An unsized `system` output at `path`, in the project above it, for
+    /// tests to adjust with struct update syntax.
+    pub(crate) fn for_test(path: impl Into<PathBuf>, system: &'static str) -> Self {
+        let path = path.into();
+        Artifact {
+            project: path.parent().map(Path::to_path_buf).unwrap_or_default(),
+            build_system: system,
+            artifact_dir: crate::rules::intern(
+                path.file_name()
+                    .map(|name| name.to_string_lossy().into_owned())
+                    .unwrap_or_default(),
+            ),
+            risk: Risk::Output,
+            size_bytes: 0,
+            stats: None,
+            volume: None,
+            clean: None,
+            path,
+        }
+    }
 }
 
 /// Scan `root` for build artifacts using parallel directory traversal.
@@ -24,28 +110,134 @@ pub struct Artifact {
 /// The caller provides the set of rules to match against, allowing pre-filtering
 /// by build system before any filesystem work is done.
 pub fn scan(root: &Path, rules: &[MatchableRule]) -> Vec<Artifact> {
-    let rules = rules.to_vec();
-    let artifacts = Arc::new(Mutex::new(Vec::new()));
-    let artifacts_ref = Arc::clone(&artifacts);
+    scan_with_progress(root, rules, &Observer::default(), &CancelToken::new())
+}
+
+/// [`scan`], telling `progress` of each directory listed, each artifact
+/// matched, and each entry that could not be read. Once `cancel` is
+/// cancelled, no more directories are read, and the artifacts found so far
+/// are returned.
+pub fn scan_with_progress(
+    root: &Path,
+    rules: &[MatchableRule],
+    progress: &Observer,
+    cancel: &CancelToken,
+) -> Vec<Artifact> {
+    let (artifacts, _) = walk(root, rules, None, progress, cancel);
+    artifacts
+}
+
+/// [`scan`] and size the artifacts found in the same traversal, as
+/// [`compute_sizes_with`](crate::size::compute_sizes_with) would, instead of
+/// walking each artifact again afterwards (`--single-pass`). Matched
+/// directories are descended into to count what they hold, but not matched
+/// against rules. Every artifact is sized, including those filters drop
+/// later. `options.estimate`, `options.link_targets` and
+/// `options.stop_above` have no effect. `progress` hears of the scan as it
+/// goes, then of each artifact's size once the traversal is done. Stopped
+/// by `cancel`, it returns the artifacts found so far, sized by what was
+/// counted of them.
+pub fn scan_and_size(
+    root: &Path,
+    rules: &[MatchableRule],
+    options: &SizeOptions,
+    progress: &Observer,
+    cancel: &CancelToken,
+) -> Vec<Artifact> {
+    let (mut artifacts, tallies) = walk(root, rules, Some(*options), progress, cancel);
+    assign_tallies(&mut artifacts, tallies, options);
+    let mut sized = SizingProgress {
+        total: artifacts.len(),
+        ..Default::default()
+    };
+    for artifact in &artifacts {
+        sized.done += 1;
+        sized.bytes += artifact.size_bytes;
+        progress.on_sized(artifact, artifact.size_bytes, sized);
+    }
+    artifacts
+}
 
-    let walker = WalkDir::new(root)
+/// jwalk's client state for [`walk`]: per directory read, the index of the
+/// artifact the directory is inside; nothing per entry.
+type WalkState = (Option<usize>, ());
+
+/// What a [`walk`] has found so far.
+#[derive(Debug, Default)]
+struct Found {
+    artifacts: Vec<Artifact>,
+    /// When sizing, the sizes counted so far for each of `artifacts`.
+    tallies: Vec<Tally>,
+    /// When sizing, the index in `artifacts` of each directory artifact, so
+    /// reading it can tell that what it holds belongs to the artifact.
+    dirs: HashMap<PathBuf, usize>,
+}
+
+/// Scan `root`, and with `sizing`, size the artifacts found. Each directory
+/// read inside an artifact carries the artifact's index as its jwalk read
+/// state, which the directories it holds inherit.
+fn walk(
+    root: &Path,
+    rules: &[MatchableRule],
+    sizing: Option<SizeOptions>,
+    progress: &Observer,
+    cancel: &CancelToken,
+) -> (Vec<Artifact>, Vec<Tally>) {
+    clear_content_cache();
+    let rules_for = |kind: EntryKind| -> Vec<MatchableRule> {
+        rules.iter().filter(|r| r.entry == kind).cloned().collect()
+    };
+    let dir_rules = rules_for(EntryKind::Dir);
+    let file_rules = rules_for(EntryKind::File);
+    let symlink_rules = rules_for(EntryKind::Symlink);
+    let found = Arc::new(Mutex::new(Found::default()));
+    let found_ref = Arc::clone(&found);
+    let progress = progress.clone();
+    let cancel = cancel.clone();
+
+    let walker = WalkDirGeneric::<WalkState>::new(root)
         .follow_links(false)
         .skip_hidden(false)
-        .process_read_dir(move |_depth, _path, _read_dir_state, children| {
+        .process_read_dir(move |_depth, path, inside, children| {
+            if cancel.is_cancelled() {
+                // Nothing more to match or descend into.
+                children.clear();
+                return;
+            }
+            progress.on_dir_visited(path);
+            if let Some(options) = &sizing {
+                if inside.is_none() {
+                    *inside = lock(&found_ref).dirs.get(path).copied();
+                }
+                if let Some(i) = *inside {
+                    count_children(&found_ref, i, children, options);
+                    return;
+                }
+            }
+
             for entry_result in children.iter_mut() {
                 let Ok(entry) = entry_result.as_mut() else {
                     if let Err(e) = entry_result {
-                        warn!("Failed to read directory entry: {e}");
+                        progress.on_error(e.path().unwrap_or(path), e);
                     }
                     continue;
                 };
 
-                if !entry.file_type.is_dir() {
+                let rules = if entry.file_type.is_dir() {
+                    &dir_rules
+                } else if entry.file_type.is_file() {
+                    &file_rules
+                } else if entry.file_type.is_symlink() {
+                    &symlink_rules
+                } else {
+                    continue;
+                };
+                if rules.is_empty() {
                     continue;
                 }
 
                 let name = entry.file_name.to_string_lossy();
-                if name == ".git" {
+                if entry.file_type.is_dir() && name == ".git" {
                     debug!(
                         "Skipping .git directory: {}",
                         entry.parent_path.join(&entry.file_name).display()
@@ -60,61 +252,115 @@ pub fn scan(root: &Path, rules: &[MatchableRule]) -> Vec<Artifact> {
                     None => continue,
                 };
 
-                if let Some(artifact) = try_match(&path, dir_name, &rules) {
+                if let Some(artifact) = try_match(&path, dir_name, rules) {
                     debug!(
                         "Found artifact: {} ({})",
                         artifact.path.display(),
                         artifact.build_system
                     );
-                    artifacts_ref.lock().unwrap().push(artifact);
-                    entry.read_children_path = None;
+                    let tally = sizing.as_ref().and_then(|options| {
+                        let meta = std::fs::symlink_metadata(&path).ok()?;
+                        Some(Tally::new(&path, &meta, options))
+                    });
+                    if tally.is_none() {
+                        entry.read_children_path = None;
+                        // Gone since it was listed.
+                        if sizing.is_some() {
+                            continue;
+                        }
+                    }
+                    progress.on_artifact_found(&artifact);
+                    let mut found = lock(&found_ref);
+                    // Keep descending, to size what the artifact holds.
+                    if let Some(tally) = tally {
+                        if entry.file_type.is_dir() {
+                            let index = found.artifacts.len();
+                            found.dirs.insert(path, index);
+                        }
+                        found.tallies.push(tally);
+                    }
+                    found.artifacts.push(artifact);
                 }
             }
         });
 
     for _ in walker {}
 
-    let mut result = artifacts.lock().unwrap();
-    let artifacts = std::mem::take(&mut *result);
-    debug!("Scan complete: found {} artifacts", artifacts.len());
-    artifacts
+    let found = std::mem::take(&mut *lock(&found));
+    debug!("Scan complete: found {} artifacts", found.artifacts.len());
+    (found.artifacts, found.tallies)
 }
 
-/// Try to match a directory against all rules. Returns the first match.
-fn try_match(path: &Path, dir_name: &str, rules: &[MatchableRule]) -> Option<Artifact> {
-    let parent = path.parent()?;
+fn lock(found: &Mutex<Found>) -> std::sync::MutexGuard<'_, Found> {
+    found.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+/// Count the entries of a directory read inside the artifact at `index`
+/// into its tally. Metadata is read before taking the lock.
+fn count_children(
+    found: &Mutex<Found>,
+    index: usize,
+    children: &[jwalk::Result<DirEntry<WalkState>>],
+    options: &SizeOptions,
+) {
+    let entries: Vec<_> = children
+        .iter()
+        .filter_map(|e| e.as_ref().ok())
+        .map(|e| {
+            let meta = Tally::needs_metadata(e.file_type, options)
+                .then(|| e.metadata().ok())
+                .flatten();
+            (e, meta)
+        })
+        .collect();
+    let mut found = lock(found);
+    let tally = &mut found.tallies[index];
+    for (entry, meta) in entries {
+        tally.count(entry.file_type.is_dir(), meta.as_ref(), options, || {
+            entry.path()
+        });
+    }
+}
 
+/// Match a single path against `rules` as `scan` would if it came across it.
+/// Returns `None` if the path is missing or matches no rule.
+pub fn match_path(path: &Path, rules: &[MatchableRule]) -> Option<Artifact> {
+    let file_type = path.symlink_metadata().ok()?.file_type();
+    let kind = if file_type.is_dir() {
+        EntryKind::Dir
+    } else if file_type.is_file() {
+        EntryKind::File
+    } else if file_type.is_symlink() {
+        EntryKind::Symlink
+    } else {
+        return None;
+    };
+    let rules: Vec<MatchableRule> = rules.iter().filter(|r| r.entry == kind).cloned().collect();
+    try_match(path, path.file_name()?.to_str()?, &rules)
+}
+
+/// Try to match a directory (or file) against all rules. Returns the first match.
+fn try_match(path: &Path, dir_name: &str, rules: &[MatchableRule]) -> Option<Artifact> {
     for mr in rules {
         if !matches_dir(dir_name, &mr.dir_match) {
             continue;
         }
 
-        // Special case: Ruby/Bundler requires parent to be named "vendor"
-        // and marker (Gemfile) in grandparent.
-        if mr.rule.build_system == "Ruby/Bundler" {
-            let parent_name = parent.file_name().and_then(|n| n.to_str());
-            if parent_name != Some("vendor") {
-                continue;
-            }
-            let grandparent = parent.parent()?;
-            if !has_marker(grandparent, &mr.rule.marker) {
-                continue;
-            }
-            return Some(Artifact {
-                path: path.to_path_buf(),
-                build_system: mr.rule.build_system,
-                artifact_dir: mr.rule.artifact_dir,
-                size_bytes: 0,
-            });
-        }
+        let Some(anchor) = marker_anchor(path, &mr.dir_match) else {
+            continue;
+        };
 
-        // Normal case: check marker in parent directory.
-        if has_marker(parent, &mr.rule.marker) {
+        if has_marker(anchor, path, &mr.rule.marker) {
             return Some(Artifact {
                 path: path.to_path_buf(),
+                project: anchor.to_path_buf(),
                 build_system: mr.rule.build_system,
                 artifact_dir: mr.rule.artifact_dir,
+                risk: mr.rule.risk,
                 size_bytes: 0,
+                stats: None,
+                volume: volume_id(path),
+                clean: mr.rule.clean,
             });
         }
     }
@@ -160,6 +406,44 @@ mod tests {
         assert_eq!(artifacts[0].build_system, "Node.js");
     }
 
+    #[test]
+    fn detects_node_framework_caches() {
+        let tmp = TempDir::new().unwrap();
+        let project = tmp.path().join("web");
+        fs::create_dir_all(&project).unwrap();
+        fs::write(project.join("package.json"), "{}").unwrap();
+        for dir in [
+            ".parcel-cache",
+            ".svelte-kit",
+            ".astro",
+            "coverage",
+            "storybook-static",
+        ] {
+            fs::create_dir_all(project.join(dir)).unwrap();
+        }
+        let artifacts = scan(tmp.path(), &all_rules());
+        assert_eq!(artifacts.len(), 5);
+        assert!(artifacts.iter().all(|a| a.build_system == "Node.js"));
+    }
+
+    #[test]
+    fn node_modules_cache_only_when_node_modules_filtered_out() {
+        let tmp = TempDir::new().unwrap();
+        let project = tmp.path().join("web");
+        let cache = project.join("node_modules").join(".cache");
+        fs::create_dir_all(&cache).unwrap();
+        fs::write(project.join("package.json"), "{}").unwrap();
+
+        let artifacts = scan(tmp.path(), &all_rules());
+        assert_eq!(artifacts.len(), 1);
+        assert_eq!(artifacts[0].path, project.join("node_modules"));
+
+        let cache_rules = crate::rules::filter_rules_by_risk(all_rules(), &[Risk::Cache]);
+        let artifacts = scan(tmp.path(), &cache_rules);
+        assert_eq!(artifacts.len(), 1);
+        assert_eq!(artifacts[0].path, cache);
+    }
+
     #[test]
     fn detects_maven_target() {
         let tmp = TempDir::new().unwrap();
@@ -169,6 +453,30 @@ mod tests {
         assert_eq!(artifacts[0].build_system, "Java/Maven");
     }
 
+    #[test]
+    fn detects_clojure_artifacts() {
+        let tmp = TempDir::new().unwrap();
+        let project = tmp.path().join("clj-app");
+        fs::create_dir_all(project.join("target")).unwrap();
+        fs::create_dir_all(project.join(".cpcache")).unwrap();
+        fs::write(project.join("deps.edn"), "{}").unwrap();
+        let artifacts = scan(tmp.path(), &all_rules());
+        assert_eq!(artifacts.len(), 2);
+        assert!(artifacts.iter().all(|a| a.build_system == "Clojure"));
+    }
+
+    #[test]
+    fn clojure_target_not_shadowed_by_generated_pom() {
+        let tmp = TempDir::new().unwrap();
+        let project = tmp.path().join("lein-app");
+        fs::create_dir_all(project.join("target")).unwrap();
+        fs::write(project.join("project.clj"), "").unwrap();
+        fs::write(project.join("pom.xml"), "").unwrap();
+        let artifacts = scan(tmp.path(), &all_rules());
+        assert_eq!(artifacts.len(), 1);
+        assert_eq!(artifacts[0].build_system, "Clojure");
+    }
+
     #[test]
     fn detects_python_pycache_without_marker() {
         let tmp = TempDir::new().unwrap();
@@ -194,6 +502,51 @@ mod tests {
         assert_eq!(artifacts[0].artifact_dir, ".venv");
     }
 
+    #[test]
+    fn detects_python_tool_caches() {
+        let tmp = TempDir::new().unwrap();
+        let project = tmp.path().join("pyproject");
+        fs::create_dir_all(&project).unwrap();
+        fs::write(project.join("pyproject.toml"), "").unwrap();
+        fs::write(project.join("noxfile.py"), "").unwrap();
+        for dir in [
+            ".ruff_cache",
+            ".nox",
+            ".eggs",
+            "build",
+            "dist",
+            "htmlcov",
+            ".hypothesis",
+        ] {
+            fs::create_dir_all(project.join(dir)).unwrap();
+        }
+        let artifacts = scan(tmp.path(), &all_rules());
+        assert_eq!(artifacts.len(), 7);
+        assert!(artifacts.iter().all(|a| a.build_system == "Python"));
+    }
+
+    #[test]
+    fn python_build_dir_defers_to_cmake() {
+        let tmp = TempDir::new().unwrap();
+        let project = tmp.path().join("ext");
+        fs::create_dir_all(project.join("build")).unwrap();
+        fs::write(project.join("pyproject.toml"), "").unwrap();
+        fs::write(project.join("CMakeLists.txt"), "").unwrap();
+        let artifacts = scan(tmp.path(), &all_rules());
+        assert_eq!(artifacts.len(), 1);
+        assert_eq!(artifacts[0].build_system, "C/C++/CMake");
+    }
+
+    #[test]
+    fn ignores_dist_with_only_requirements_txt() {
+        let tmp = TempDir::new().unwrap();
+        let project = tmp.path().join("scripts");
+        fs::create_dir_all(project.join("dist")).unwrap();
+        fs::write(project.join("requirements.txt"), "").unwrap();
+        let artifacts = scan(tmp.path(), &all_rules());
+        assert!(artifacts.is_empty());
+    }
+
     #[test]
     fn ignores_venv_without_marker() {
         let tmp = TempDir::new().unwrap();
@@ -203,6 +556,91 @@ mod tests {
         assert!(artifacts.is_empty());
     }
 
+    #[test]
+    fn detects_sbt_extra_artifacts() {
+        let tmp = TempDir::new().unwrap();
+        let project = tmp.path().join("scala-app");
+        fs::create_dir_all(project.join("project").join("target")).unwrap();
+        fs::create_dir_all(project.join(".bloop")).unwrap();
+        fs::create_dir_all(project.join(".metals")).unwrap();
+        fs::write(project.join("build.sbt"), "").unwrap();
+        let artifacts = scan(tmp.path(), &all_rules());
+        assert_eq!(artifacts.len(), 3);
+        assert!(artifacts.iter().all(|a| a.build_system == "Scala/SBT"));
+    }
+
+    #[test]
+    fn detects_sbt_nested_module_target() {
+        let tmp = TempDir::new().unwrap();
+        let project = tmp.path().join("scala-app");
+        let module = project.join("modules").join("core");
+        fs::create_dir_all(module.join("target")).unwrap();
+        fs::create_dir_all(module.join("src/main/scala")).unwrap();
+        let bare = project.join("modules").join("macros");
+        fs::create_dir_all(bare.join("target/streams")).unwrap();
+        // Neither a module nor sbt output.
+        fs::create_dir_all(project.join("docs/target")).unwrap();
+        fs::write(project.join("build.sbt"), "").unwrap();
+        let mut artifacts = scan(tmp.path(), &all_rules());
+        artifacts.sort_by(|a, b| a.path.cmp(&b.path));
+        assert_eq!(artifacts.len(), 2, "{artifacts:?}");
+        assert_eq!(artifacts[0].path, module.join("target"));
+        assert_eq!(artifacts[1].path, bare.join("target"));
+        assert!(artifacts.iter().all(|a| a.build_system == "Scala/SBT"));
+    }
+
+    #[test]
+    fn ignores_target_packages_in_sbt_source_trees() {
+        let tmp = TempDir::new().unwrap();
+        let project = tmp.path().join("scala-app");
+        let package = project.join("core/src/main/scala/com/acme/target");
+        fs::create_dir_all(package.join("streams")).unwrap();
+        fs::write(package.join("Target.scala"), "").unwrap();
+        fs::write(project.join("build.sbt"), "").unwrap();
+        let artifacts = scan(tmp.path(), &all_rules());
+        assert!(artifacts.is_empty(), "unexpected: {artifacts:?}");
+    }
+
+    #[test]
+    fn nested_cargo_target_in_sbt_repo_stays_cargo() {
+        let tmp = TempDir::new().unwrap();
+        let project = tmp.path().join("scala-app");
+        let crate_dir = project.join("native");
+        fs::create_dir_all(crate_dir.join("target")).unwrap();
+        fs::write(project.join("build.sbt"), "").unwrap();
+        fs::write(crate_dir.join("Cargo.toml"), "").unwrap();
+        let artifacts = scan(tmp.path(), &all_rules());
+        assert_eq!(artifacts.len(), 1);
+        assert_eq!(artifacts[0].build_system, "Rust/Cargo");
+    }
+
+    #[test]
+    fn detects_mill_out() {
+        let tmp = TempDir::new().unwrap();
+        set_up_project(&tmp, "build.mill", "out");
+        let artifacts = scan(tmp.path(), &all_rules());
+        assert_eq!(artifacts.len(), 1);
+        assert_eq!(artifacts[0].build_system, "Scala/Mill");
+    }
+
+    #[test]
+    fn ignores_unrelated_out_dirs() {
+        let tmp = TempDir::new().unwrap();
+        // No marker at all.
+        fs::create_dir_all(tmp.path().join("plain").join("out")).unwrap();
+        // Near-miss marker names.
+        let near = tmp.path().join("near");
+        fs::create_dir_all(near.join("out")).unwrap();
+        fs::write(near.join("build.sc.bak"), "").unwrap();
+        fs::write(near.join("build.scala"), "").unwrap();
+        // Marker one level too high.
+        let nested = tmp.path().join("mill-root");
+        fs::write(tmp.path().join("build.sc"), "").unwrap();
+        fs::create_dir_all(nested.join("sub").join("out")).unwrap();
+        let artifacts = scan(tmp.path(), &all_rules());
+        assert!(artifacts.is_empty(), "unexpected: {artifacts:?}");
+    }
+
     #[test]
     fn detects_gradle_build() {
         let tmp = TempDir::new().unwrap();
@@ -212,6 +650,70 @@ mod tests {
         assert_eq!(artifacts[0].build_system, "Android/Gradle");
     }
 
+    #[test]
+    fn detects_gradle_subproject_build_via_settings() {
+        let tmp = TempDir::new().unwrap();
+        let root = tmp.path().join("android-app");
+        let sub = root.join("feature").join("login");
+        fs::create_dir_all(sub.join("build/tmp/compileKotlin")).unwrap();
+        // No sign of Gradle output.
+        fs::create_dir_all(root.join("docs/build")).unwrap();
+        fs::write(
+            root.join("settings.gradle.kts"),
+            "include(\":feature:login\")",
+        )
+        .unwrap();
+        let artifacts = scan(tmp.path(), &all_rules());
+        assert_eq!(artifacts.len(), 1);
+        assert_eq!(artifacts[0].path, sub.join("build"));
+        assert_eq!(artifacts[0].build_system, "Android/Gradle");
+    }
+
+    #[test]
+    fn ignores_build_packages_in_gradle_source_trees() {
+        let tmp = TempDir::new().unwrap();
+        let root = tmp.path().join("android-app");
+        let package = root.join("app/core/src/main/java/com/acme/build");
+        fs::create_dir_all(package.join("generated")).unwrap();
+        fs::write(root.join("settings.gradle"), "").unwrap();
+        fs::write(package.join("Builder.java"), "").unwrap();
+        let kotlin = root.join("app/core/src/main/kotlin/build");
+        fs::create_dir_all(kotlin.join("tmp")).unwrap();
+        let artifacts = scan(tmp.path(), &all_rules());
+        assert!(artifacts.is_empty(), "unexpected: {artifacts:?}");
+    }
+
+    #[test]
+    fn cmake_build_inside_gradle_project_stays_cmake() {
+        let tmp = TempDir::new().unwrap();
+        let root = tmp.path().join("android-app");
+        let native = root.join("native");
+        fs::create_dir_all(native.join("build")).unwrap();
+        fs::write(root.join("settings.gradle"), "").unwrap();
+        fs::write(native.join("CMakeLists.txt"), "").unwrap();
+        let artifacts = scan(tmp.path(), &all_rules());
+        assert_eq!(artifacts.len(), 1);
+        assert_eq!(artifacts[0].build_system, "C/C++/CMake");
+    }
+
+    #[test]
+    fn flutter_build_needs_app_entry_point() {
+        let tmp = TempDir::new().unwrap();
+        let package = tmp.path().join("dart_pkg");
+        fs::create_dir_all(package.join("build")).unwrap();
+        fs::write(package.join("pubspec.yaml"), "").unwrap();
+        let app = tmp.path().join("flutter_app");
+        fs::create_dir_all(app.join("build")).unwrap();
+        fs::create_dir_all(app.join("lib")).unwrap();
+        fs::write(app.join("pubspec.yaml"), "").unwrap();
+        fs::write(app.join("lib/main.dart"), "").unwrap();
+
+        let artifacts = scan(tmp.path(), &all_rules());
+        assert_eq!(artifacts.len(), 1);
+        assert_eq!(artifacts[0].path, app.join("build"));
+        assert_eq!(artifacts[0].build_system, "Dart/Flutter");
+    }
+
     #[test]
     fn detects_cmake_build() {
         let tmp = TempDir::new().unwrap();
@@ -221,6 +723,46 @@ mod tests {
         assert_eq!(artifacts[0].build_system, "C/C++/CMake");
     }
 
+    #[test]
+    fn detects_clion_and_qt_shadow_builds() {
+        let tmp = TempDir::new().unwrap();
+        let cmake_proj = tmp.path().join("engine");
+        fs::create_dir_all(cmake_proj.join("cmake-build-debug")).unwrap();
+        fs::create_dir_all(cmake_proj.join("build-engine-Desktop_Qt_6_5_GCC_64bit-Release"))
+            .unwrap();
+        fs::write(cmake_proj.join("CMakeLists.txt"), "").unwrap();
+        let qt_proj = tmp.path().join("viewer");
+        fs::create_dir_all(qt_proj.join("build-viewer-Desktop-Debug")).unwrap();
+        fs::write(qt_proj.join("viewer.pro"), "").unwrap();
+
+        let artifacts = scan(tmp.path(), &all_rules());
+        assert_eq!(artifacts.len(), 3);
+        let qt: Vec<_> = artifacts
+            .iter()
+            .filter(|a| a.build_system == "Qt/qmake")
+            .collect();
+        assert_eq!(qt.len(), 1);
+        assert_eq!(qt[0].path, qt_proj.join("build-viewer-Desktop-Debug"));
+    }
+
+    #[test]
+    fn shadow_build_patterns_avoid_false_positives() {
+        let tmp = TempDir::new().unwrap();
+        let project = tmp.path().join("engine");
+        // Names that look similar but aren't shadow builds.
+        fs::create_dir_all(project.join("build-scripts")).unwrap();
+        fs::create_dir_all(project.join("build-tools-Debugging")).unwrap();
+        fs::create_dir_all(project.join("cmake-build-")).unwrap();
+        fs::write(project.join("CMakeLists.txt"), "").unwrap();
+        // Correct names without a marker next to them.
+        let other = tmp.path().join("other");
+        fs::create_dir_all(other.join("cmake-build-release")).unwrap();
+        fs::create_dir_all(other.join("build-app-Desktop-Debug")).unwrap();
+
+        let artifacts = scan(tmp.path(), &all_rules());
+        assert!(artifacts.is_empty(), "unexpected: {artifacts:?}");
+    }
+
     #[test]
     fn detects_dotnet_with_csproj() {
         let tmp = TempDir::new().unwrap();
@@ -320,6 +862,8 @@ mod tests {
         let artifacts = scan(tmp.path(), &all_rules());
         assert_eq!(artifacts.len(), 1);
         assert_eq!(artifacts[0].build_system, "Ruby/Bundler");
+        // The project is where the marker lives, not the artifact's parent.
+        assert_eq!(artifacts[0].project, project);
     }
 
     #[test]
@@ -334,6 +878,37 @@ mod tests {
         assert!(artifacts.is_empty());
     }
 
+    #[test]
+    fn ignores_bundle_outside_vendor() {
+        let tmp = TempDir::new().unwrap();
+        let project = tmp.path().join("app");
+        fs::create_dir_all(&project).unwrap();
+        fs::write(project.join("Gemfile"), "").unwrap();
+        fs::create_dir_all(project.join("bundle")).unwrap();
+        fs::create_dir_all(project.join("lib").join("bundle")).unwrap();
+        let artifacts = scan(tmp.path(), &all_rules());
+        assert!(artifacts.is_empty());
+    }
+
+    #[test]
+    fn detects_next_output_only_for_next_projects() {
+        let tmp = TempDir::new().unwrap();
+        let next_app = tmp.path().join("next-app");
+        fs::create_dir_all(next_app.join(".next")).unwrap();
+        fs::write(
+            next_app.join("package.json"),
+            r#"{"dependencies": {"next": "14.0.0"}}"#,
+        )
+        .unwrap();
+        let other = tmp.path().join("other-app");
+        fs::create_dir_all(other.join(".next")).unwrap();
+        fs::write(other.join("package.json"), "{}").unwrap();
+
+        let artifacts = scan(tmp.path(), &all_rules());
+        assert_eq!(artifacts.len(), 1);
+        assert_eq!(artifacts[0].path, next_app.join(".next"));
+    }
+
     #[test]
     fn detects_swift_spm_build() {
         let tmp = TempDir::new().unwrap();
@@ -356,6 +931,215 @@ mod tests {
         assert!(artifacts.iter().all(|a| a.build_system == "Elixir/Mix"));
     }
 
+    #[test]
+    fn detects_arbitrarily_named_venv() {
+        let tmp = TempDir::new().unwrap();
+        let env = tmp.path().join("project").join("my-env");
+        fs::create_dir_all(env.join("lib")).unwrap();
+        fs::write(env.join("pyvenv.cfg"), "home = /usr/bin").unwrap();
+        let artifacts = scan(tmp.path(), &all_rules());
+        assert_eq!(artifacts.len(), 1);
+        assert_eq!(artifacts[0].path, env);
+        assert_eq!(artifacts[0].build_system, "Python");
+    }
+
+    #[test]
+    fn detects_out_of_tree_cmake_build() {
+        let tmp = TempDir::new().unwrap();
+        let build = tmp.path().join("out-debug");
+        fs::create_dir_all(&build).unwrap();
+        fs::write(build.join("CMakeCache.txt"), "").unwrap();
+        let artifacts = scan(tmp.path(), &all_rules());
+        assert_eq!(artifacts.len(), 1);
+        assert_eq!(artifacts[0].path, build);
+        assert_eq!(artifacts[0].build_system, "C/C++/CMake");
+    }
+
+    #[test]
+    fn ignores_dir_without_inside_marker() {
+        let tmp = TempDir::new().unwrap();
+        let dir = tmp.path().join("env");
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(dir.join("config.cfg"), "").unwrap();
+        let artifacts = scan(tmp.path(), &all_rules());
+        assert!(artifacts.is_empty());
+    }
+
+    #[test]
+    fn detects_haskell_cabal_extras() {
+        let tmp = TempDir::new().unwrap();
+        let project = tmp.path().join("hs-lib");
+        fs::create_dir_all(project.join("dist")).unwrap();
+        fs::create_dir_all(project.join(".hie")).unwrap();
+        fs::write(project.join("hs-lib.cabal"), "").unwrap();
+        fs::write(project.join(".ghc.environment.x86_64-linux-9.4.8"), "").unwrap();
+        let artifacts = scan(tmp.path(), &all_rules());
+        assert_eq!(artifacts.len(), 3);
+        assert!(artifacts.iter().all(|a| a.build_system == "Haskell/Cabal"));
+        assert!(
+            artifacts
+                .iter()
+                .any(|a| a.path == project.join(".ghc.environment.x86_64-linux-9.4.8"))
+        );
+    }
+
+    #[test]
+    fn detects_haskell_stack_extras() {
+        let tmp = TempDir::new().unwrap();
+        let project = tmp.path().join("hs-app");
+        fs::create_dir_all(project.join(".hie")).unwrap();
+        fs::write(project.join("stack.yaml"), "").unwrap();
+        fs::write(project.join(".ghc.environment.aarch64-darwin-9.6.3"), "").unwrap();
+        let artifacts = scan(tmp.path(), &all_rules());
+        assert_eq!(artifacts.len(), 2);
+        assert!(artifacts.iter().all(|a| a.build_system == "Haskell/Stack"));
+    }
+
+    #[test]
+    fn ignores_dist_without_cabal_file() {
+        let tmp = TempDir::new().unwrap();
+        let project = tmp.path().join("web");
+        fs::create_dir_all(project.join("dist")).unwrap();
+        fs::write(project.join(".ghc.environment.x86_64-linux-9.4.8"), "").unwrap();
+        let artifacts = scan(tmp.path(), &all_rules());
+        assert!(artifacts.is_empty());
+    }
+
+    #[test]
+    fn detects_modern_zig_cache() {
+        let tmp = TempDir::new().unwrap();
+        let project = tmp.path().join("zig-app");
+        fs::create_dir_all(project.join(".zig-cache")).unwrap();
+        fs::create_dir_all(project.join("zig-out")).unwrap();
+        fs::write(project.join("build.zig.zon"), "").unwrap();
+        let artifacts = scan(tmp.path(), &all_rules());
+        assert_eq!(artifacts.len(), 2);
+        assert!(artifacts.iter().all(|a| a.build_system == "Zig"));
+    }
+
+    #[test]
+    fn detects_carthage() {
+        let tmp = TempDir::new().unwrap();
+        let project = tmp.path().join("ios-app");
+        fs::create_dir_all(project.join("Carthage").join("Build")).unwrap();
+        fs::create_dir_all(project.join("Carthage").join("Checkouts")).unwrap();
+        fs::write(project.join("Cartfile"), "").unwrap();
+        let artifacts = scan(tmp.path(), &all_rules());
+        assert_eq!(artifacts.len(), 2);
+        assert!(artifacts.iter().all(|a| a.build_system == "Carthage"));
+    }
+
+    #[test]
+    fn ignores_carthage_without_cartfile() {
+        let tmp = TempDir::new().unwrap();
+        let project = tmp.path().join("ios-app");
+        fs::create_dir_all(project.join("Carthage").join("Build")).unwrap();
+        let artifacts = scan(tmp.path(), &all_rules());
+        assert!(artifacts.is_empty());
+    }
+
+    #[test]
+    fn detects_latex_output() {
+        let tmp = TempDir::new().unwrap();
+        let paper = tmp.path().join("paper");
+        fs::create_dir_all(paper.join("build")).unwrap();
+        fs::create_dir_all(paper.join("_minted-main")).unwrap();
+        fs::write(paper.join("main.tex"), "").unwrap();
+        let thesis = tmp.path().join("thesis");
+        fs::create_dir_all(thesis.join("out")).unwrap();
+        fs::write(thesis.join("latexmkrc"), "").unwrap();
+        let artifacts = scan(tmp.path(), &all_rules());
+        assert_eq!(artifacts.len(), 3);
+        assert!(artifacts.iter().all(|a| a.build_system == "LaTeX"));
+    }
+
+    #[test]
+    fn ignores_out_dir_without_tex() {
+        let tmp = TempDir::new().unwrap();
+        let project = tmp.path().join("tool");
+        fs::create_dir_all(project.join("out")).unwrap();
+        fs::create_dir_all(project.join("_minted-notes")).unwrap();
+        fs::write(project.join("notes.txt"), "").unwrap();
+        let artifacts = scan(tmp.path(), &all_rules());
+        assert!(artifacts.is_empty());
+    }
+
+    #[test]
+    fn detects_elm_stuff() {
+        let tmp = TempDir::new().unwrap();
+        set_up_project(&tmp, "elm.json", "elm-stuff");
+        let artifacts = scan(tmp.path(), &all_rules());
+        assert_eq!(artifacts.len(), 1);
+        assert_eq!(artifacts[0].build_system, "Elm");
+    }
+
+    #[test]
+    fn detects_rescript_lib_bs() {
+        let tmp = TempDir::new().unwrap();
+        let project = tmp.path().join("res-app");
+        fs::create_dir_all(project.join("lib").join("bs")).unwrap();
+        fs::write(project.join("rescript.json"), "{}").unwrap();
+        let artifacts = scan(tmp.path(), &all_rules());
+        assert_eq!(artifacts.len(), 1);
+        assert_eq!(artifacts[0].path, project.join("lib").join("bs"));
+        assert_eq!(artifacts[0].build_system, "ReScript");
+    }
+
+    #[test]
+    fn detects_purescript_output() {
+        let tmp = TempDir::new().unwrap();
+        set_up_project(&tmp, "spago.yaml", "output");
+        let artifacts = scan(tmp.path(), &all_rules());
+        assert_eq!(artifacts.len(), 1);
+        assert_eq!(artifacts[0].build_system, "PureScript");
+    }
+
+    #[test]
+    fn detects_godot_import_caches() {
+        let tmp = TempDir::new().unwrap();
+        let game = tmp.path().join("game");
+        fs::create_dir_all(game.join(".godot")).unwrap();
+        fs::create_dir_all(game.join(".import")).unwrap();
+        fs::write(game.join("project.godot"), "").unwrap();
+        let artifacts = scan(tmp.path(), &all_rules());
+        assert_eq!(artifacts.len(), 2);
+        assert!(artifacts.iter().all(|a| a.build_system == "Godot"));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn detects_nix_result_symlinks() {
+        let tmp = TempDir::new().unwrap();
+        let store = tmp.path().join("store").join("abc123-hello");
+        fs::create_dir_all(&store).unwrap();
+        let project = tmp.path().join("flake");
+        fs::create_dir_all(&project).unwrap();
+        fs::write(project.join("flake.nix"), "").unwrap();
+        std::os::unix::fs::symlink(&store, project.join("result")).unwrap();
+        std::os::unix::fs::symlink(&store, project.join("result-dev")).unwrap();
+
+        let artifacts = scan(tmp.path(), &all_rules());
+        assert_eq!(artifacts.len(), 2);
+        assert!(artifacts.iter().all(|a| a.build_system == "Nix"));
+        assert!(artifacts.iter().all(|a| a.path.starts_with(&project)));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn ignores_result_dir_and_unmarked_symlink() {
+        let tmp = TempDir::new().unwrap();
+        let project = tmp.path().join("flake");
+        // A real directory named `result` is not a Nix output link.
+        fs::create_dir_all(project.join("result")).unwrap();
+        fs::write(project.join("flake.nix"), "").unwrap();
+        let other = tmp.path().join("other");
+        fs::create_dir_all(&other).unwrap();
+        std::os::unix::fs::symlink(&project, other.join("result")).unwrap();
+
+        let artifacts = scan(tmp.path(), &all_rules());
+        assert!(artifacts.is_empty());
+    }
+
     #[test]
     fn detects_cocoapods() {
         let tmp = TempDir::new().unwrap();
@@ -364,4 +1148,63 @@ mod tests {
         assert_eq!(artifacts.len(), 1);
         assert_eq!(artifacts[0].build_system, "CocoaPods");
     }
+
+    #[test]
+    fn cancelled_scan_reads_no_further() {
+        let tmp = TempDir::new().unwrap();
+        set_up_project(&tmp, "Cargo.toml", "target");
+        let cancel = CancelToken::new();
+        let rules = all_rules();
+        assert_eq!(
+            scan_with_progress(tmp.path(), &rules, &Observer::default(), &cancel).len(),
+            1
+        );
+        cancel.cancel();
+        assert!(scan_with_progress(tmp.path(), &rules, &Observer::default(), &cancel).is_empty());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn scan_and_size_matches_scanning_then_sizing() {
+        use crate::size::compute_sizes_with;
+
+        let tmp = TempDir::new().unwrap();
+        let project = set_up_project(&tmp, "Cargo.toml", "target");
+        let target = project.join("target");
+        fs::create_dir_all(target.join("debug/.fingerprint/app")).unwrap();
+        fs::write(target.join("debug/.fingerprint/app/hash"), "0123").unwrap();
+        // Not an artifact of its own inside another.
+        fs::create_dir_all(target.join("debug/node_modules")).unwrap();
+        fs::write(target.join("debug/package.json"), "{}").unwrap();
+        fs::write(target.join("debug/app"), vec![0u8; 5000]).unwrap();
+        fs::hard_link(target.join("debug/app"), target.join("debug/app-1x")).unwrap();
+        let flake = tmp.path().join("flake");
+        fs::create_dir_all(&flake).unwrap();
+        fs::write(flake.join("flake.nix"), "").unwrap();
+        std::os::unix::fs::symlink(&target, flake.join("result")).unwrap();
+
+        for allocated in [false, true] {
+            let options = SizeOptions {
+                allocated,
+                ..Default::default()
+            };
+            let mut expected = scan(tmp.path(), &all_rules());
+            compute_sizes_with(&mut expected, &options);
+            expected.sort_by(|a, b| a.path.cmp(&b.path));
+            let mut single = scan_and_size(
+                tmp.path(),
+                &all_rules(),
+                &options,
+                &Observer::default(),
+                &CancelToken::new(),
+            );
+            single.sort_by(|a, b| a.path.cmp(&b.path));
+
+            assert_eq!(single.len(), 2);
+            for (a, b) in single.iter().zip(&expected) {
+                assert_eq!(a.path, b.path);
+                assert_eq!(a.stats, b.stats, "{}", a.path.display());
+            }
+        }
+    }
 }
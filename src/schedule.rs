@@ -0,0 +1,582 @@
+use std::fs;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+
+use clap::ValueEnum;
+use log::warn;
+
+/// Name of the systemd units and the launchd log file.
+const NAME: &str = "clean-builds";
+/// Label of the launchd agent.
+const LABEL: &str = "com.github.jakebromberg.clean-builds";
+
+/// Error installing or removing a schedule.
+#[derive(thiserror::Error, Debug)]
+pub enum ScheduleError {
+    #[error("cannot find the home directory (set HOME)")]
+    NoHome,
+    #[error("scheduling needs systemd (Linux) or launchd (macOS)")]
+    Unsupported,
+    #[error("cannot write {}: {source}", path.display())]
+    Write {
+        path: PathBuf,
+        source: std::io::Error,
+    },
+    #[error("cannot remove {}: {source}", path.display())]
+    Remove {
+        path: PathBuf,
+        source: std::io::Error,
+    },
+    #[error("I/O error: {0}")]
+    Io(#[from] std::io::Error),
+}
+
+/// How often a scheduled cleanup runs.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Frequency {
+    Daily,
+    Weekly,
+    Monthly,
+}
+
+impl Frequency {
+    /// Lowercase name, which is also the systemd `OnCalendar` shorthand.
+    fn as_str(self) -> &'static str {
+        match self {
+            Frequency::Daily => "daily",
+            Frequency::Weekly => "weekly",
+            Frequency::Monthly => "monthly",
+        }
+    }
+
+    /// The `StartCalendarInterval` entries of a launchd agent: 3 a.m., on
+    /// Sundays for weekly runs and on the 1st for monthly ones.
+    fn launchd_interval(self) -> &'static [(&'static str, u32)] {
+        match self {
+            Frequency::Daily => &[("Hour", 3), ("Minute", 0)],
+            Frequency::Weekly => &[("Weekday", 0), ("Hour", 3), ("Minute", 0)],
+            Frequency::Monthly => &[("Day", 1), ("Hour", 3), ("Minute", 0)],
+        }
+    }
+}
+
+/// What a scheduled cleanup removes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum Profile {
+    /// Caches and build output of projects idle for 30 days, skipping
+    /// repositories with unpushed work and projects in use
+    Safe,
+    /// Every artifact of projects idle for 7 days, skipping projects in use
+    Aggressive,
+}
+
+impl Profile {
+    fn as_str(self) -> &'static str {
+        match self {
+            Profile::Safe => "safe",
+            Profile::Aggressive => "aggressive",
+        }
+    }
+
+    /// The filter options the profile adds to a run.
+    fn args(self) -> &'static [&'static str] {
+        match self {
+            Profile::Safe => &[
+                "--risk",
+                "cache,output",
+                "--idle-for",
+                "30d",
+                "--skip-unpushed",
+                "--skip-in-use",
+            ],
+            Profile::Aggressive => &["--idle-for", "7d", "--skip-in-use"],
+        }
+    }
+}
+
+/// A cleanup of `root` that runs `exe` on a schedule.
+#[derive(Debug, Clone)]
+pub struct Schedule {
+    pub exe: PathBuf,
+    pub root: PathBuf,
+    pub frequency: Frequency,
+    pub profile: Profile,
+}
+
+impl Schedule {
+    /// The command line each run executes: a deletion at low priority that
+    /// never prompts, with the profile's filters.
+    pub fn command(&self) -> Vec<String> {
+        let mut command = vec![
+            self.exe.display().to_string(),
+            self.root.display().to_string(),
+        ];
+        command.extend(
+            ["--delete", "--yes", "--no-input", "--nice"]
+                .iter()
+                .chain(self.profile.args())
+                .map(|arg| arg.to_string()),
+        );
+        command
+    }
+
+    fn description(&self) -> String {
+        format!(
+            "Clean build artifacts under {} {} ({} profile)",
+            self.root.display(),
+            self.frequency.as_str(),
+            self.profile.as_str()
+        )
+    }
+}
+
+/// The service manager that runs scheduled cleanups, and where its files go.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Scheduler {
+    /// A systemd user timer and service in `dir`.
+    Systemd { dir: PathBuf },
+    /// A launchd agent in `dir`, logging to `log`.
+    Launchd { dir: PathBuf, log: PathBuf },
+}
+
+impl Scheduler {
+    /// The scheduler of this platform, with its files in the current user's
+    /// configuration: `systemd/user` under `$XDG_CONFIG_HOME` or `~/.config`
+    /// on Linux, `~/Library/LaunchAgents` on macOS.
+    pub fn for_platform() -> Result<Self, ScheduleError> {
+        let home = std::env::var_os("HOME")
+            .filter(|h| !h.is_empty())
+            .map(PathBuf::from);
+        if cfg!(target_os = "macos") {
+            let home = home.ok_or(ScheduleError::NoHome)?;
+            return Ok(Scheduler::Launchd {
+                dir: home.join("Library/LaunchAgents"),
+                log: home.join("Library/Logs").join(format!("{NAME}.log")),
+            });
+        }
+        if !cfg!(target_os = "linux") {
+            return Err(ScheduleError::Unsupported);
+        }
+        let config = std::env::var_os("XDG_CONFIG_HOME")
+            .filter(|d| !d.is_empty())
+            .map(PathBuf::from)
+            .or_else(|| home.map(|h| h.join(".config")))
+            .ok_or(ScheduleError::NoHome)?;
+        Ok(Scheduler::Systemd {
+            dir: config.join("systemd/user"),
+        })
+    }
+
+    /// The files that install `schedule`, with their contents.
+    pub fn files(&self, schedule: &Schedule) -> Vec<(PathBuf, String)> {
+        let contents = match self {
+            Scheduler::Systemd { .. } => vec![systemd_service(schedule), systemd_timer(schedule)],
+            Scheduler::Launchd { log, .. } => vec![launchd_plist(schedule, log)],
+        };
+        self.paths().into_iter().zip(contents).collect()
+    }
+
+    /// Where the files of a schedule go, whether or not one is installed.
+    pub fn paths(&self) -> Vec<PathBuf> {
+        match self {
+            Scheduler::Systemd { dir } => vec![
+                dir.join(format!("{NAME}.service")),
+                dir.join(format!("{NAME}.timer")),
+            ],
+            Scheduler::Launchd { dir, .. } => vec![dir.join(format!("{LABEL}.plist"))],
+        }
+    }
+
+    /// Write the files for `schedule`, replacing any installed before,
+    /// without telling the service manager about them.
+    pub fn write(&self, schedule: &Schedule) -> Result<Vec<PathBuf>, ScheduleError> {
+        self.files(schedule)
+            .into_iter()
+            .map(|(path, contents)| {
+                let error = |source| ScheduleError::Write {
+                    path: path.clone(),
+                    source,
+                };
+                if let Some(parent) = path.parent() {
+                    fs::create_dir_all(parent).map_err(error)?;
+                }
+                fs::write(&path, contents).map_err(error)?;
+                Ok(path)
+            })
+            .collect()
+    }
+
+    /// Delete the files of an installed schedule, returning those there were.
+    pub fn delete(&self) -> Result<Vec<PathBuf>, ScheduleError> {
+        let mut removed = Vec::new();
+        for path in self.paths() {
+            match fs::remove_file(&path) {
+                Ok(()) => removed.push(path),
+                Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+                Err(source) => return Err(ScheduleError::Remove { path, source }),
+            }
+        }
+        Ok(removed)
+    }
+
+    /// Write the files for `schedule` and start it. If the service manager
+    /// cannot be reached, the files stay and how to start them is printed.
+    pub fn install(&self, out: &mut dyn Write, schedule: &Schedule) -> Result<(), ScheduleError> {
+        if schedule.exe.starts_with(&schedule.root) {
+            warn!(
+                "{} is under {}, so a cleanup may delete it; install clean-builds elsewhere, e.g. with `cargo install`",
+                schedule.exe.display(),
+                schedule.root.display()
+            );
+        }
+        for path in self.write(schedule)? {
+            writeln!(out, "Wrote {}", path.display())?;
+        }
+        let start = self.start_commands();
+        match start.iter().try_for_each(|command| run(command)) {
+            Ok(()) => writeln!(out, "{}.", schedule.description())?,
+            Err(e) => {
+                warn!("{e}");
+                writeln!(
+                    out,
+                    "The schedule is installed but not started. Start it with:"
+                )?;
+                for command in &start {
+                    writeln!(out, "  {}", command.join(" "))?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Stop the installed schedule and delete its files.
+    pub fn remove(&self, out: &mut dyn Write) -> Result<(), ScheduleError> {
+        if !self.installed() {
+            writeln!(out, "No schedule is installed.")?;
+            return Ok(());
+        }
+        // Stopping fails harmlessly if the schedule was never started.
+        let plist = self.plist();
+        let stop: Vec<String> = match self {
+            Scheduler::Systemd { .. } => {
+                vec![
+                    "systemctl",
+                    "--user",
+                    "disable",
+                    "--now",
+                    "clean-builds.timer",
+                ]
+            }
+            Scheduler::Launchd { .. } => vec!["launchctl", "unload", "-w", &plist],
+        }
+        .into_iter()
+        .map(String::from)
+        .collect();
+        if let Err(e) = run(&stop) {
+            warn!("{e}");
+        }
+        for path in self.delete()? {
+            writeln!(out, "Removed {}", path.display())?;
+        }
+        if let Scheduler::Systemd { .. } = self {
+            let _ = run(&["systemctl", "--user", "daemon-reload"].map(String::from));
+        }
+        Ok(())
+    }
+
+    /// Print where the installed schedule is and what the service manager
+    /// reports about it.
+    pub fn status(&self, out: &mut dyn Write) -> Result<(), ScheduleError> {
+        if !self.installed() {
+            writeln!(out, "No schedule is installed.")?;
+            return Ok(());
+        }
+        for path in self.paths().iter().filter(|path| path.exists()) {
+            writeln!(out, "Installed: {}", path.display())?;
+        }
+        let query: Vec<String> = match self {
+            Scheduler::Systemd { .. } => vec![
+                "systemctl",
+                "--user",
+                "list-timers",
+                "--all",
+                "--no-pager",
+                "clean-builds.timer",
+            ],
+            Scheduler::Launchd { .. } => vec!["launchctl", "list", LABEL],
+        }
+        .into_iter()
+        .map(String::from)
+        .collect();
+        match output(&query) {
+            Ok(report) => {
+                writeln!(out)?;
+                write!(out, "{report}")?;
+            }
+            Err(e) => writeln!(out, "Cannot ask the service manager about it: {e}")?,
+        }
+        Ok(())
+    }
+
+    /// Whether a schedule is installed.
+    pub fn installed(&self) -> bool {
+        self.paths().iter().any(|path| path.exists())
+    }
+
+    /// The commands that start an installed schedule.
+    fn start_commands(&self) -> Vec<Vec<String>> {
+        let plist = self.plist();
+        let commands: Vec<Vec<&str>> = match self {
+            Scheduler::Systemd { .. } => vec![
+                vec!["systemctl", "--user", "daemon-reload"],
+                vec![
+                    "systemctl",
+                    "--user",
+                    "enable",
+                    "--now",
+                    "clean-builds.timer",
+                ],
+            ],
+            // Loading an agent that is already loaded fails, so reload it.
+            Scheduler::Launchd { .. } => vec![
+                vec!["launchctl", "unload", &plist],
+                vec!["launchctl", "load", "-w", &plist],
+            ],
+        };
+        commands
+            .into_iter()
+            .map(|command| command.into_iter().map(String::from).collect())
+            .collect()
+    }
+
+    fn plist(&self) -> String {
+        self.paths()[0].display().to_string()
+    }
+}
+
+/// Run `command`, failing with its error output if it fails.
+fn run(command: &[String]) -> Result<(), String> {
+    output(command).map(drop)
+}
+
+/// Run `command` and return its output, or an error naming it.
+fn output(command: &[String]) -> Result<String, String> {
+    let display = command.join(" ");
+    let (program, args) = command.split_first().expect("command is not empty");
+    let output = Command::new(program)
+        .args(args)
+        .stdin(Stdio::null())
+        .output()
+        .map_err(|e| format!("cannot run {display}: {e}"))?;
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        return Err(format!("{display} failed: {}", stderr.trim()));
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+/// The systemd service that runs the cleanup once.
+fn systemd_service(schedule: &Schedule) -> String {
+    let command: Vec<String> = schedule
+        .command()
+        .iter()
+        .map(|arg| systemd_quote(arg))
+        .collect();
+    format!(
+        "# Installed by `clean-builds schedule install`.\n\
+         [Unit]\n\
+         Description={}\n\
+         \n\
+         [Service]\n\
+         Type=oneshot\n\
+         ExecStart={}\n",
+        schedule.description(),
+        command.join(" ")
+    )
+}
+
+/// The systemd timer that starts the service. `Persistent` catches up on a
+/// run missed while the machine was off.
+fn systemd_timer(schedule: &Schedule) -> String {
+    format!(
+        "# Installed by `clean-builds schedule install`.\n\
+         [Unit]\n\
+         Description={}\n\
+         \n\
+         [Timer]\n\
+         OnCalendar={}\n\
+         Persistent=true\n\
+         RandomizedDelaySec=1h\n\
+         \n\
+         [Install]\n\
+         WantedBy=timers.target\n",
+        schedule.description(),
+        schedule.frequency.as_str()
+    )
+}
+
+/// `arg` as one word of a systemd command line: quoted, with `%` and `$`
+/// doubled so they are not taken as specifiers or variables.
+fn systemd_quote(arg: &str) -> String {
+    let escaped = arg
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('%', "%%")
+        .replace('$', "$$");
+    format!("\"{escaped}\"")
+}
+
+/// The launchd agent that runs the cleanup, appending its output to `log`.
+fn launchd_plist(schedule: &Schedule, log: &Path) -> String {
+    let arguments: String = schedule
+        .command()
+        .iter()
+        .map(|arg| format!("        <string>{}</string>\n", xml_escape(arg)))
+        .collect();
+    let interval: String = schedule
+        .frequency
+        .launchd_interval()
+        .iter()
+        .map(|(key, value)| {
+            format!("        <key>{key}</key>\n        <integer>{value}</integer>\n")
+        })
+        .collect();
+    let log = xml_escape(&log.display().to_string());
+    format!(
+        r#"<?xml version="1.0" encoding="UTF-8"?>
+<!DOCTYPE plist PUBLIC "-//Apple//DTD PLIST 1.0//EN" "http://www.apple.com/DTDs/PropertyList-1.0.dtd">
+<!-- Installed by `clean-builds schedule install`: {} -->
+<plist version="1.0">
+<dict>
+    <key>Label</key>
+    <string>{LABEL}</string>
+    <key>ProgramArguments</key>
+    <array>
+{arguments}    </array>
+    <key>StartCalendarInterval</key>
+    <dict>
+{interval}    </dict>
+    <key>ProcessType</key>
+    <string>Background</string>
+    <key>StandardOutPath</key>
+    <string>{log}</string>
+    <key>StandardErrorPath</key>
+    <string>{log}</string>
+</dict>
+</plist>
+"#,
+        xml_escape(&schedule.description())
+    )
+}
+
+fn xml_escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&apos;")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    fn schedule(profile: Profile) -> Schedule {
+        Schedule {
+            exe: PathBuf::from("/usr/local/bin/clean-builds"),
+            root: PathBuf::from("/home/me/My Projects"),
+            frequency: Frequency::Weekly,
+            profile,
+        }
+    }
+
+    #[test]
+    fn command_deletes_without_prompting_with_the_profile_filters() {
+        let command = schedule(Profile::Aggressive).command();
+        assert_eq!(
+            command,
+            [
+                "/usr/local/bin/clean-builds",
+                "/home/me/My Projects",
+                "--delete",
+                "--yes",
+                "--no-input",
+                "--nice",
+                "--idle-for",
+                "7d",
+                "--skip-in-use",
+            ]
+        );
+        assert!(
+            schedule(Profile::Safe)
+                .command()
+                .contains(&"--skip-unpushed".to_string())
+        );
+    }
+
+    #[test]
+    fn systemd_units_quote_the_command() {
+        let scheduler = Scheduler::Systemd {
+            dir: PathBuf::from("/home/me/.config/systemd/user"),
+        };
+        let files = scheduler.files(&schedule(Profile::Safe));
+        let paths: Vec<_> = files.iter().map(|(path, _)| path.clone()).collect();
+        assert_eq!(paths, scheduler.paths());
+
+        let (_, service) = &files[0];
+        assert!(service.contains(
+            "ExecStart=\"/usr/local/bin/clean-builds\" \"/home/me/My Projects\" \"--delete\""
+        ));
+        assert!(service.contains("Type=oneshot"));
+        let (_, timer) = &files[1];
+        assert!(timer.contains("OnCalendar=weekly\nPersistent=true"));
+        assert!(timer.contains("WantedBy=timers.target"));
+
+        assert_eq!(
+            systemd_quote(r#"50% "off" $HOME\x"#),
+            r#""50%% \"off\" $$HOME\\x""#
+        );
+    }
+
+    #[test]
+    fn launchd_plist_lists_arguments_and_calendar() {
+        let scheduler = Scheduler::Launchd {
+            dir: PathBuf::from("/Users/me/Library/LaunchAgents"),
+            log: PathBuf::from("/Users/me/Library/Logs/clean-builds.log"),
+        };
+        let mut schedule = schedule(Profile::Safe);
+        schedule.root = PathBuf::from("/Users/me/R&D");
+        schedule.frequency = Frequency::Monthly;
+        let files = scheduler.files(&schedule);
+        let (path, plist) = &files[0];
+        assert_eq!(
+            path,
+            Path::new("/Users/me/Library/LaunchAgents/com.github.jakebromberg.clean-builds.plist")
+        );
+        assert!(plist.contains("<string>/Users/me/R&amp;D</string>\n"));
+        assert!(plist.contains("<key>Day</key>\n        <integer>1</integer>\n"));
+        assert!(plist.contains("<string>/Users/me/Library/Logs/clean-builds.log</string>"));
+    }
+
+    #[test]
+    fn write_replaces_and_delete_removes_the_files() {
+        let tmp = TempDir::new().unwrap();
+        let scheduler = Scheduler::Systemd {
+            dir: tmp.path().join("systemd/user"),
+        };
+        assert!(!scheduler.installed());
+
+        scheduler.write(&schedule(Profile::Safe)).unwrap();
+        let written = scheduler.write(&schedule(Profile::Aggressive)).unwrap();
+        assert_eq!(written, scheduler.paths());
+        assert!(scheduler.installed());
+        let service = fs::read_to_string(&written[0]).unwrap();
+        assert!(service.contains("(aggressive profile)"));
+
+        assert_eq!(scheduler.delete().unwrap(), written);
+        assert!(!scheduler.installed());
+        assert!(scheduler.delete().unwrap().is_empty());
+    }
+}
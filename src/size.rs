@@ -1,64 +1,1186 @@
-use std::path::Path;
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, OnceLock};
+use std::time::SystemTime;
 
 use jwalk::{Parallelism, WalkDir};
-use log::debug;
+use log::{debug, warn};
 use rayon::prelude::*;
+use rayon::{ThreadPool, ThreadPoolBuilder};
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
 
+use crate::filter::{Filter, Verdict};
+use crate::interrupt::CancelToken;
+use crate::progress::Progress;
 use crate::scanner::Artifact;
 
+/// How `compute_sizes_with` measures artifacts.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct SizeOptions {
+    /// Count the disk space allocated to files and directories, as `du`
+    /// does, instead of the apparent length of files (`--du`).
+    pub allocated: bool,
+    /// Count a file hard-linked into several artifacts only once, under the
+    /// artifact whose path sorts first (`--dedup-links`). Links within one
+    /// artifact are always counted once.
+    pub dedup_links: bool,
+    /// Estimate sizes by sampling the subdirectories of wide directories
+    /// instead of walking every file (`--estimate`).
+    pub estimate: bool,
+    /// Also measure what symlinks inside each artifact point to outside it
+    /// (`--link-targets`), into [`SizeStats::outside_bytes`].
+    pub link_targets: bool,
+    /// Stop walking an artifact as soon as its size exceeds this, leaving
+    /// a partial size that exceeds it too. For `--max-size`, which drops
+    /// such artifacts whatever their full size. Estimates, the native walk
+    /// and `dedup_links`, which can take bytes back out, walk in full.
+    pub stop_above: Option<u64>,
+    /// Also measure what deleting each artifact would actually free
+    /// (`--reclaimable`), into [`SizeStats::reclaimable`]. Only walks of
+    /// every file, without the native walk, can tell.
+    pub reclaimable: bool,
+}
+
+/// What one walk of an artifact counts.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct SizeStats {
+    /// The same figure as `Artifact::size_bytes`.
+    pub bytes: u64,
+    /// Files, counting each name of a hard-linked file.
+    pub files: u64,
+    /// Directories below the artifact.
+    pub dirs: u64,
+    /// The latest modification time of any file, or `None` if it holds none.
+    /// Serialized as RFC 3339 to the second.
+    #[cfg_attr(feature = "serde", serde(with = "crate::age::rfc3339", default))]
+    pub newest: Option<SystemTime>,
+    /// For an estimated size, how far `bytes` may be off: the half-width of
+    /// a 95% confidence interval. `None` when every file was counted.
+    pub margin: Option<u64>,
+    /// With [`SizeOptions::link_targets`], the apparent size of everything
+    /// symlinks inside the artifact point to outside it, each target counted
+    /// once. Not part of `bytes`, since deleting the artifact frees none of it.
+    pub outside_bytes: u64,
+    /// With [`SizeOptions::reclaimable`], the part of `bytes` deleting the
+    /// artifact alone would free: files also hard-linked from outside it,
+    /// such as pnpm's or Nix's stores, and blocks shared with copy-on-write
+    /// clones (btrfs, XFS and APFS) stay behind. Files hard-linked between
+    /// two artifacts count in neither.
+    pub reclaimable: Option<u64>,
+}
+
 /// Compute directory sizes for all artifacts in parallel.
 pub fn compute_sizes(artifacts: &mut [Artifact]) {
-    let sizes: Vec<u64> = artifacts
+    compute_sizes_with(artifacts, &SizeOptions::default());
+}
+
+/// How far sizing has got, passed to [`Progress::on_sized`] as each
+/// artifact is measured.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct SizingProgress {
+    /// Artifacts measured so far.
+    pub done: usize,
+    /// Artifacts being measured in all.
+    pub total: usize,
+    /// Bytes of the artifacts measured so far, before `--dedup-links` takes
+    /// out links shared between them.
+    pub bytes: u64,
+}
+
+/// Compute directory sizes for all artifacts in parallel, measured as
+/// `options` says.
+pub fn compute_sizes_with(artifacts: &mut [Artifact], options: &SizeOptions) {
+    compute_sizes_with_progress(artifacts, options, &(), &CancelToken::new());
+}
+
+/// [`compute_sizes_with`], telling `progress` as each artifact is measured.
+/// It is told from the sizing threads, in no particular order of artifacts,
+/// but `done` and `bytes` only grow. Artifacts not yet started when
+/// `cancel` is cancelled are left unsized, with no `stats`.
+pub fn compute_sizes_with_progress(
+    artifacts: &mut [Artifact],
+    options: &SizeOptions,
+    progress: &dyn Progress,
+    cancel: &CancelToken,
+) {
+    let total = artifacts.len();
+    let counts = Mutex::new(SizingProgress {
+        total,
+        ..Default::default()
+    });
+    let measured: Vec<Option<Measured>> = artifacts
         .par_iter()
         .map(|a| {
-            let size = dir_size(&a.path);
-            debug!("{}: {}", a.path.display(), format_size(size));
-            size
+            if cancel.is_cancelled() {
+                return None;
+            }
+            let m = if options.estimate {
+                estimate_size(&a.path, options)
+            } else {
+                dir_size(&a.path, options)
+            };
+            // Report under the lock, so updates arrive in order.
+            let mut counts = counts.lock().unwrap_or_else(|e| e.into_inner());
+            counts.done += 1;
+            counts.bytes += m.stats.bytes;
+            progress.on_sized(a, m.stats.bytes, *counts);
+            Some(m)
         })
         .collect();
+    assign_sizes(artifacts, measured, options);
+}
 
-    for (artifact, size) in artifacts.iter_mut().zip(sizes) {
-        artifact.size_bytes = size;
+/// Record each artifact's measurements, taking links shared between
+/// artifacts out first with `options.dedup_links`. Artifacts without
+/// measurements are left as they are.
+fn assign_sizes(
+    artifacts: &mut [Artifact],
+    mut measured: Vec<Option<Measured>>,
+    options: &SizeOptions,
+) {
+    if options.reclaimable {
+        for m in measured.iter_mut().flatten() {
+            m.stats.reclaimable = Some(m.reclaimable());
+        }
+    }
+    if options.dedup_links {
+        let mut order: Vec<usize> = (0..artifacts.len()).collect();
+        order.sort_by(|&a, &b| artifacts[a].path.cmp(&artifacts[b].path));
+        let mut seen = HashSet::new();
+        for i in order {
+            let Some(m) = &mut measured[i] else {
+                continue;
+            };
+            for &(id, bytes, _) in &m.linked {
+                if !seen.insert(id) {
+                    m.stats.bytes -= bytes;
+                }
+            }
+        }
+    }
+
+    for (artifact, m) in artifacts.iter_mut().zip(measured) {
+        let Some(m) = m else {
+            continue;
+        };
+        debug!(
+            "{}: {}",
+            artifact.path.display(),
+            format_size(m.stats.bytes)
+        );
+        artifact.size_bytes = m.stats.bytes;
+        artifact.stats = Some(m.stats);
     }
 }
 
-/// Calculate the total size of a directory tree.
-///
-/// Uses serial walking to avoid contention with the outer rayon `par_iter`
-/// that drives `compute_sizes`. Both share rayon's global thread pool, and
-/// nested parallel walks deadlock when the pool is saturated.
-fn dir_size(path: &Path) -> u64 {
-    WalkDir::new(path)
-        .parallelism(Parallelism::Serial)
+/// The size of one artifact, from `dir_size`.
+#[derive(Debug, Default)]
+struct Measured {
+    stats: SizeStats,
+    /// Each hard-linked file counted, by device and inode, with the bytes
+    /// counted for it and how many of those are shared with clones.
+    linked: Vec<((u64, u64), u64, u64)>,
+    /// The hard-linked files counted so far, with how many of their names
+    /// were found and how many they have (0 if unknown).
+    seen: HashMap<(u64, u64), (u64, u64)>,
+    /// Bytes of files without hard links shared with clones.
+    shared: u64,
+}
+
+/// An artifact sized by a walk other than [`dir_size`]'s, which feeds it
+/// the entries below the artifact one at a time: the single-pass scan's.
+/// Counts what `dir_size` would.
+#[derive(Debug)]
+pub(crate) struct Tally {
+    measured: Measured,
+    unit: u64,
+}
+
+impl Tally {
+    /// Start sizing the artifact at `path`, counting the artifact itself.
+    pub(crate) fn new(path: &Path, meta: &std::fs::Metadata, options: &SizeOptions) -> Self {
+        let unit = if options.allocated {
+            allocation_unit(path)
+        } else {
+            1
+        };
+        let mut tally = Self {
+            measured: Measured::default(),
+            unit,
+        };
+        if options.allocated || !meta.is_dir() {
+            tally
+                .measured
+                .count(&Counted::from_metadata(meta, options, unit, || {
+                    path.to_path_buf()
+                }));
+        }
+        tally
+    }
+
+    /// Whether [`count`](Self::count) needs the metadata of an entry of
+    /// `file_type`.
+    pub(crate) fn needs_metadata(file_type: std::fs::FileType, options: &SizeOptions) -> bool {
+        options.allocated || file_type.is_file() || file_type.is_symlink()
+    }
+
+    /// Count one entry below the artifact. `meta` is its metadata, if
+    /// [`needs_metadata`](Self::needs_metadata) and it could be read; `path`
+    /// gives its path.
+    pub(crate) fn count(
+        &mut self,
+        is_dir: bool,
+        meta: Option<&std::fs::Metadata>,
+        options: &SizeOptions,
+        path: impl Fn() -> PathBuf,
+    ) {
+        if is_dir {
+            self.measured.stats.dirs += 1;
+        }
+        if let Some(meta) = meta {
+            self.measured
+                .count(&Counted::from_metadata(meta, options, self.unit, path));
+        }
+    }
+}
+
+/// Record the sizes `tallies` counted for `artifacts`, in the same order,
+/// as [`compute_sizes_with`] would have.
+pub(crate) fn assign_tallies(
+    artifacts: &mut [Artifact],
+    tallies: Vec<Tally>,
+    options: &SizeOptions,
+) {
+    let measured = tallies.into_iter().map(|t| Some(t.measured)).collect();
+    assign_sizes(artifacts, measured, options);
+}
+
+/// The thread pool that walks artifacts while sizing them, apart from
+/// rayon's global pool. `compute_sizes` drives the walks from a `par_iter`
+/// on the global pool; walking on that pool too starves once it is
+/// saturated, and jwalk gives up with nothing walked. As many threads as
+/// the global pool, so `--jobs` limits both. `None`, walking serially, if
+/// the pool cannot be started.
+fn walk_pool() -> Option<Arc<ThreadPool>> {
+    static POOL: OnceLock<Option<Arc<ThreadPool>>> = OnceLock::new();
+    POOL.get_or_init(|| {
+        ThreadPoolBuilder::new()
+            .num_threads(rayon::current_num_threads())
+            .thread_name(|i| format!("size-walk-{i}"))
+            .build()
+            .map(Arc::new)
+            .inspect_err(|e| warn!("cannot start threads for sizing, walking serially: {e}"))
+            .ok()
+    })
+    .clone()
+}
+
+/// Calculate the total size of a directory tree (or of a single file
+/// artifact), counting each hard-linked file once, and its other
+/// [`SizeStats`] in the same walk. Symlinks are never followed: a symlink
+/// counts as its own length, the length of the path it holds. Directories are read in parallel on
+/// [`walk_pool`], or with the `native-walk` feature, by the platform's
+/// batched directory APIs where it has them.
+fn dir_size(path: &Path, options: &SizeOptions) -> Measured {
+    let unit = if options.allocated {
+        allocation_unit(path)
+    } else {
+        1
+    };
+    let mut measured = Measured::default();
+    match std::fs::symlink_metadata(path) {
+        Ok(meta) if !meta.is_dir() => {
+            measured.count(&Counted::from_metadata(&meta, options, unit, || {
+                path.to_path_buf()
+            }));
+            return measured;
+        }
+        _ => {}
+    }
+
+    // The native walk does not keep paths, which link targets need, nor
+    // count hard links, which reclaimable sizes need.
+    #[cfg(feature = "native-walk")]
+    if !options.link_targets && !options.reclaimable {
+        match native_dir_size(path, options, unit) {
+            Ok(measured) => return measured,
+            Err(e) => debug!("{}: native walk failed, using jwalk: {e}", path.display()),
+        }
+    }
+
+    let parallelism = match walk_pool() {
+        // The pool runs nothing but walks, which never wait on each other,
+        // so a free thread always turns up.
+        Some(pool) => Parallelism::RayonExistingPool {
+            pool,
+            busy_timeout: None,
+        },
+        None => Parallelism::Serial,
+    };
+    let stop_above = options.stop_above.filter(|_| !options.dedup_links);
+    let mut links = Vec::new();
+    // Dropping the walk early stops its threads.
+    for e in WalkDir::new(path)
+        .parallelism(parallelism)
         .follow_links(false)
         .skip_hidden(false)
         .into_iter()
         .filter_map(|e| e.ok())
-        .filter(|e| e.file_type().is_file())
-        .map(|e| e.metadata().map(|m| m.len()).unwrap_or(0))
-        .sum()
+    {
+        let file_type = e.file_type();
+        if e.depth() > 0 && file_type.is_dir() {
+            measured.stats.dirs += 1;
+        }
+        if file_type.is_symlink() && options.link_targets {
+            links.push(e.path());
+        }
+        if options.allocated || file_type.is_file() || file_type.is_symlink() {
+            if let Ok(meta) = e.metadata() {
+                measured.count(&Counted::from_metadata(&meta, options, unit, || e.path()));
+            }
+        }
+        if stop_above.is_some_and(|limit| measured.stats.bytes > limit) {
+            debug!(
+                "{}: stopped above {}",
+                path.display(),
+                format_size(measured.stats.bytes)
+            );
+            break;
+        }
+    }
+    if options.link_targets {
+        measured.stats.outside_bytes = outside_bytes(path, &links);
+    }
+    measured
 }
 
-/// Format a byte count as a human-readable string.
-pub fn format_size(bytes: u64) -> String {
-    const KB: u64 = 1024;
-    const MB: u64 = 1024 * KB;
-    const GB: u64 = 1024 * MB;
-
-    if bytes >= GB {
-        format!("{:.1} GB", bytes as f64 / GB as f64)
-    } else if bytes >= MB {
-        format!("{:.1} MB", bytes as f64 / MB as f64)
-    } else if bytes >= KB {
-        format!("{:.1} KB", bytes as f64 / KB as f64)
+/// The apparent size of what `links`, symlinks inside the artifact at
+/// `root`, point to outside it. Each target is counted once, as is anything
+/// inside a directory another link points to; broken links count nothing.
+fn outside_bytes(root: &Path, links: &[PathBuf]) -> u64 {
+    let Ok(root) = root.canonicalize() else {
+        return 0;
+    };
+    let mut targets: Vec<PathBuf> = links
+        .iter()
+        .filter_map(|link| link.canonicalize().ok())
+        .filter(|target| !target.starts_with(&root))
+        .collect();
+    // Sorted by component, everything inside a directory follows it.
+    targets.sort();
+    targets.dedup();
+    let mut total = 0;
+    let mut last_dir: Option<&Path> = None;
+    for target in &targets {
+        if last_dir.is_some_and(|dir| target.starts_with(dir)) {
+            continue;
+        }
+        match std::fs::metadata(target) {
+            Ok(meta) if meta.is_dir() => {
+                last_dir = Some(target);
+                total += dir_size(target, &SizeOptions::default()).stats.bytes;
+            }
+            Ok(meta) => total += meta.len(),
+            Err(_) => {}
+        }
+    }
+    total
+}
+
+/// [`dir_size`] of a directory by [`native_walk::walk`](crate::native_walk::walk),
+/// serially. Fails if the platform has no native walk or `path` cannot be
+/// read.
+#[cfg(feature = "native-walk")]
+fn native_dir_size(path: &Path, options: &SizeOptions, unit: u64) -> std::io::Result<Measured> {
+    let mut measured = Measured::default();
+    // The native walk lists only what is below the root.
+    if options.allocated {
+        if let Ok(meta) = std::fs::symlink_metadata(path) {
+            measured.count(&Counted::from_metadata(&meta, options, unit, || {
+                path.to_path_buf()
+            }));
+        }
+    }
+    crate::native_walk::walk(path, options.allocated, &mut |entry| {
+        if entry.is_dir {
+            measured.stats.dirs += 1;
+        }
+        if options.allocated || entry.is_file || entry.is_symlink {
+            measured.count(&Counted::from_native(entry, options, unit));
+        }
+    })?;
+    Ok(measured)
+}
+
+/// What [`Measured::count`] needs to know about one entry, however it was
+/// read.
+struct Counted {
+    is_file: bool,
+    /// Its size as [`entry_size`] measures it.
+    bytes: u64,
+    link: Option<(u64, u64)>,
+    /// How many names a hard-linked file has, or 0 if unknown.
+    links: u64,
+    /// How many of `bytes` are shared with clones, with
+    /// [`SizeOptions::reclaimable`].
+    shared: u64,
+    modified: Option<SystemTime>,
+}
+
+impl Counted {
+    /// `path` gives the entry's path, if measuring it needs one.
+    fn from_metadata(
+        meta: &std::fs::Metadata,
+        options: &SizeOptions,
+        unit: u64,
+        path: impl Fn() -> PathBuf,
+    ) -> Self {
+        let bytes = entry_size(meta, options, unit, &path);
+        let shared = if options.reclaimable && meta.is_file() {
+            shared_bytes(&path(), meta).min(bytes)
+        } else {
+            0
+        };
+        let link = link_id(meta);
+        Self {
+            is_file: meta.is_file(),
+            bytes,
+            link: link.map(|(id, _)| id),
+            links: link.map_or(0, |(_, links)| links),
+            shared,
+            modified: meta.modified().ok(),
+        }
+    }
+
+    /// Allocated sizes are rounded up to whole allocation units as
+    /// [`allocated_size`] does without Unix; where the listing has none,
+    /// the length of files is.
+    #[cfg(feature = "native-walk")]
+    fn from_native(
+        entry: &crate::native_walk::NativeEntry,
+        options: &SizeOptions,
+        unit: u64,
+    ) -> Self {
+        let bytes = if options.allocated {
+            let length = if entry.is_file { entry.len } else { 0 };
+            entry.allocated.unwrap_or(length).div_ceil(unit) * unit
+        } else if entry.is_file || entry.is_symlink {
+            entry.len
+        } else {
+            0
+        };
+        Self {
+            is_file: entry.is_file,
+            bytes,
+            link: entry.link,
+            links: 0,
+            shared: 0,
+            modified: entry.modified,
+        }
+    }
+}
+
+impl Measured {
+    /// Add one entry, counting a hard-linked file only the first time.
+    fn count(&mut self, entry: &Counted) {
+        if entry.is_file {
+            self.stats.files += 1;
+            self.stats.newest = self.stats.newest.max(entry.modified);
+        }
+        if let Some(id) = entry.link {
+            let names = &mut self.seen.entry(id).or_insert((0, entry.links)).0;
+            *names += 1;
+            if *names > 1 {
+                return;
+            }
+            self.linked.push((id, entry.bytes, entry.shared));
+        } else {
+            self.shared += entry.shared;
+        }
+        self.stats.bytes += entry.bytes;
+    }
+
+    /// The part of `stats.bytes` deleting the artifact would free, for
+    /// [`SizeStats::reclaimable`].
+    fn reclaimable(&self) -> u64 {
+        let mut kept = self.shared;
+        for &(id, bytes, shared) in &self.linked {
+            let (names, links) = self.seen[&id];
+            kept += if names < links { bytes } else { shared };
+        }
+        self.stats.bytes.saturating_sub(kept)
+    }
+}
+
+/// Subdirectories `--estimate` walks in a directory with more than twice as
+/// many; the sizes of the rest are extrapolated from them.
+const SAMPLED_DIRS: usize = 32;
+
+/// A size estimate of one directory, with the variance of its byte count.
+#[derive(Debug, Default)]
+struct Estimate {
+    bytes: f64,
+    files: f64,
+    dirs: f64,
+    newest: Option<SystemTime>,
+    variance: f64,
+}
+
+/// Estimate the size of the artifact at `path` by sampling: files are
+/// counted in every directory visited, but of a directory's subdirectories
+/// only an evenly spaced [`SAMPLED_DIRS`] are walked, when there are more
+/// than twice that, and the rest are assumed alike. Hard links are counted
+/// per name. Subdirectories are walked in parallel.
+fn estimate_size(path: &Path, options: &SizeOptions) -> Measured {
+    match std::fs::symlink_metadata(path) {
+        Ok(meta) if meta.is_dir() => {}
+        _ => return dir_size(path, options),
+    }
+    let unit = if options.allocated {
+        allocation_unit(path)
     } else {
+        1
+    };
+    let estimate = estimate_dir(path, options, unit);
+    Measured {
+        stats: SizeStats {
+            bytes: estimate.bytes.round() as u64,
+            files: estimate.files.round() as u64,
+            dirs: estimate.dirs.round() as u64,
+            newest: estimate.newest,
+            // 1.96 standard errors either side cover 95%.
+            margin: Some((1.96 * estimate.variance.sqrt()).round() as u64),
+            ..Default::default()
+        },
+        ..Default::default()
+    }
+}
+
+fn estimate_dir(dir: &Path, options: &SizeOptions, unit: u64) -> Estimate {
+    let mut estimate = Estimate::default();
+    let mut subdirs = Vec::new();
+    for entry in std::fs::read_dir(dir).into_iter().flatten().flatten() {
+        let Ok(file_type) = entry.file_type() else {
+            continue;
+        };
+        if file_type.is_dir() {
+            subdirs.push(entry.path());
+            if !options.allocated {
+                continue;
+            }
+        } else if !options.allocated && !file_type.is_file() && !file_type.is_symlink() {
+            continue;
+        }
+        let Ok(meta) = entry.metadata() else {
+            continue;
+        };
+        if meta.is_file() {
+            estimate.files += 1.0;
+            estimate.newest = estimate.newest.max(meta.modified().ok());
+        }
+        estimate.bytes += entry_size(&meta, options, unit, || entry.path()) as f64;
+    }
+    if options.allocated {
+        if let Ok(meta) = std::fs::symlink_metadata(dir) {
+            estimate.bytes += entry_size(&meta, options, unit, || dir.to_path_buf()) as f64;
+        }
+    }
+
+    subdirs.sort();
+    let total = subdirs.len();
+    let sampled: Vec<&PathBuf> = if total > 2 * SAMPLED_DIRS {
+        (0..SAMPLED_DIRS)
+            .map(|i| &subdirs[i * total / SAMPLED_DIRS])
+            .collect()
+    } else {
+        subdirs.iter().collect()
+    };
+    let children: Vec<Estimate> = sampled
+        .par_iter()
+        .map(|d| estimate_dir(d, options, unit))
+        .collect();
+
+    // Expanding a sample of n out of N subdirectories adds the variance of
+    // the sampled total, N^2 (1 - n/N) s^2 / n, to the scaled variances of
+    // the subdirectories' own estimates.
+    let n = children.len() as f64;
+    let scale = if children.is_empty() {
+        0.0
+    } else {
+        total as f64 / n
+    };
+    let mean = children.iter().map(|c| c.bytes).sum::<f64>() / n.max(1.0);
+    if children.len() < total && children.len() > 1 {
+        let spread = children
+            .iter()
+            .map(|c| (c.bytes - mean).powi(2))
+            .sum::<f64>()
+            / (n - 1.0);
+        let total = total as f64;
+        estimate.variance += total * total * (1.0 - n / total) * spread / n;
+    }
+    estimate.dirs += total as f64;
+    for child in children {
+        estimate.bytes += scale * child.bytes;
+        estimate.files += scale * child.files;
+        estimate.dirs += scale * child.dirs;
+        estimate.newest = estimate.newest.max(child.newest);
+        estimate.variance += scale * scale * child.variance;
+    }
+    estimate
+}
+
+/// Device and inode of a file with more than one hard link, and how many
+/// it has.
+#[cfg(unix)]
+fn link_id(meta: &std::fs::Metadata) -> Option<((u64, u64), u64)> {
+    use std::os::unix::fs::MetadataExt;
+    (meta.nlink() > 1 && !meta.is_dir()).then(|| ((meta.dev(), meta.ino()), meta.nlink()))
+}
+
+#[cfg(not(unix))]
+fn link_id(_meta: &std::fs::Metadata) -> Option<((u64, u64), u64)> {
+    None
+}
+
+/// Bytes of the file at `path` in extents it shares with other files, such
+/// as reflink copies on btrfs or XFS, from `FS_IOC_FIEMAP`; 0 if the
+/// filesystem cannot tell.
+#[cfg(target_os = "linux")]
+fn shared_bytes(path: &Path, _meta: &std::fs::Metadata) -> u64 {
+    use std::os::fd::AsRawFd;
+
+    const FS_IOC_FIEMAP: u32 = 0xC020_660B;
+    const FIEMAP_EXTENT_LAST: u32 = 0x1;
+    const FIEMAP_EXTENT_SHARED: u32 = 0x2000;
+    const EXTENTS: usize = 64;
+
+    #[repr(C)]
+    struct Extent {
+        logical: u64,
+        physical: u64,
+        length: u64,
+        reserved64: [u64; 2],
+        flags: u32,
+        reserved: [u32; 3],
+    }
+
+    #[repr(C)]
+    struct Fiemap {
+        start: u64,
+        length: u64,
+        flags: u32,
+        mapped_extents: u32,
+        extent_count: u32,
+        reserved: u32,
+        extents: [Extent; EXTENTS],
+    }
+
+    let Ok(file) = std::fs::File::open(path) else {
+        return 0;
+    };
+    let mut shared = 0;
+    let mut start = 0;
+    loop {
+        // SAFETY: all zeroes is a valid `Fiemap`, of plain integers.
+        let mut map: Fiemap = unsafe { std::mem::zeroed() };
+        map.start = start;
+        map.length = u64::MAX;
+        map.extent_count = EXTENTS as u32;
+        // SAFETY: `map` is a fiemap header followed by room for the
+        // `extent_count` extents the kernel may fill in.
+        if unsafe { libc::ioctl(file.as_raw_fd(), FS_IOC_FIEMAP as _, &mut map) } != 0 {
+            return shared;
+        }
+        let mapped = (map.mapped_extents as usize).min(EXTENTS);
+        let extents = &map.extents[..mapped];
+        shared += extents
+            .iter()
+            .filter(|e| e.flags & FIEMAP_EXTENT_SHARED != 0)
+            .map(|e| e.length)
+            .sum::<u64>();
+        match extents.last() {
+            Some(e) if e.flags & FIEMAP_EXTENT_LAST == 0 => start = e.logical + e.length,
+            _ => return shared,
+        }
+    }
+}
+
+/// Bytes allocated to the file at `path` that it shares with APFS clones:
+/// all it has allocated but its `ATTR_CMNEXT_PRIVATESIZE`.
+#[cfg(target_os = "macos")]
+fn shared_bytes(path: &Path, meta: &std::fs::Metadata) -> u64 {
+    use std::ffi::CString;
+    use std::os::unix::ffi::OsStrExt;
+    use std::os::unix::fs::MetadataExt;
+
+    const ATTR_CMNEXT_PRIVATESIZE: u32 = 0x0000_0008;
+    const FSOPT_ATTR_CMN_EXTENDED: u32 = 0x0000_0020;
+
+    /// The attribute buffer: its length, then the private size.
+    #[repr(C, packed(4))]
+    struct Reply {
+        length: u32,
+        private: i64,
+    }
+
+    let Ok(c_path) = CString::new(path.as_os_str().as_bytes()) else {
+        return 0;
+    };
+    // SAFETY: all zeroes is a valid `attrlist`, of plain integers.
+    let mut attrs: libc::attrlist = unsafe { std::mem::zeroed() };
+    attrs.bitmapcount = libc::ATTR_BIT_MAP_COUNT;
+    // With FSOPT_ATTR_CMN_EXTENDED, the fork group holds extended common
+    // attributes.
+    attrs.forkattr = ATTR_CMNEXT_PRIVATESIZE;
+    let mut reply = Reply {
+        length: 0,
+        private: 0,
+    };
+    // SAFETY: `c_path` is NUL-terminated and `reply` is as large as the
+    // size passed.
+    let status = unsafe {
+        libc::getattrlist(
+            c_path.as_ptr(),
+            (&raw mut attrs).cast(),
+            (&raw mut reply).cast(),
+            std::mem::size_of::<Reply>(),
+            libc::FSOPT_NOFOLLOW | FSOPT_ATTR_CMN_EXTENDED,
+        )
+    };
+    if status != 0 {
+        return 0;
+    }
+    let private = u64::try_from(reply.private).unwrap_or(0);
+    (meta.blocks() * 512).saturating_sub(private)
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "macos")))]
+fn shared_bytes(_path: &Path, _meta: &std::fs::Metadata) -> u64 {
+    0
+}
+
+/// The size of one entry: its apparent length if it is a file or symlink,
+/// or with `options.allocated`, the space allocated to it. `path` gives the
+/// entry's path, which only Windows needs, and only for some files.
+fn entry_size(
+    meta: &std::fs::Metadata,
+    options: &SizeOptions,
+    unit: u64,
+    path: impl FnOnce() -> PathBuf,
+) -> u64 {
+    if options.allocated {
+        allocated_size(meta, unit, path)
+    } else if meta.is_file() || meta.is_symlink() {
+        meta.len()
+    } else {
+        0
+    }
+}
+
+/// Space allocated to an entry: `st_blocks` counts 512-byte blocks.
+#[cfg(unix)]
+fn allocated_size(meta: &std::fs::Metadata, _unit: u64, _path: impl FnOnce() -> PathBuf) -> u64 {
+    use std::os::unix::fs::MetadataExt;
+    meta.blocks() * 512
+}
+
+/// Space allocated to an entry: files take whole clusters of `unit` bytes.
+/// NTFS-compressed and sparse files take only the clusters
+/// [`compressed_size`] reports, often far fewer than their length needs.
+#[cfg(windows)]
+fn allocated_size(meta: &std::fs::Metadata, unit: u64, path: impl FnOnce() -> PathBuf) -> u64 {
+    use std::os::windows::fs::MetadataExt;
+    use windows_sys::Win32::Storage::FileSystem::{
+        FILE_ATTRIBUTE_COMPRESSED, FILE_ATTRIBUTE_SPARSE_FILE,
+    };
+
+    if !meta.is_file() {
+        return 0;
+    }
+    let packed = meta.file_attributes() & (FILE_ATTRIBUTE_COMPRESSED | FILE_ATTRIBUTE_SPARSE_FILE);
+    let bytes = if packed != 0 {
+        compressed_size(&path()).unwrap_or(meta.len())
+    } else {
+        meta.len()
+    };
+    bytes.div_ceil(unit) * unit
+}
+
+/// Space allocated to an entry: files take whole clusters of `unit` bytes.
+#[cfg(not(any(unix, windows)))]
+fn allocated_size(meta: &std::fs::Metadata, unit: u64, _path: impl FnOnce() -> PathBuf) -> u64 {
+    if meta.is_file() {
+        meta.len().div_ceil(unit) * unit
+    } else {
+        0
+    }
+}
+
+/// The bytes a compressed or sparse file occupies on disk, from
+/// `GetCompressedFileSizeW`; for other files, their length.
+#[cfg(windows)]
+pub(crate) fn compressed_size(path: &Path) -> Option<u64> {
+    use std::os::windows::ffi::OsStrExt;
+    use windows_sys::Win32::Foundation::{GetLastError, NO_ERROR};
+    use windows_sys::Win32::Storage::FileSystem::{GetCompressedFileSizeW, INVALID_FILE_SIZE};
+
+    let wide: Vec<u16> = path.as_os_str().encode_wide().chain([0]).collect();
+    let mut high = 0u32;
+    // SAFETY: `wide` is NUL-terminated and `high` is a plain out-parameter.
+    let low = unsafe { GetCompressedFileSizeW(wide.as_ptr(), &mut high) };
+    // INVALID_FILE_SIZE is also a valid low half; only the error tells.
+    // SAFETY: reads the calling thread's last error.
+    if low == INVALID_FILE_SIZE && unsafe { GetLastError() } != NO_ERROR {
+        return None;
+    }
+    Some((u64::from(high) << 32) | u64::from(low))
+}
+
+/// The allocation unit of the filesystem holding `path`; Unix reports
+/// allocated blocks directly, so it is only needed elsewhere.
+#[cfg(unix)]
+fn allocation_unit(_path: &Path) -> u64 {
+    1
+}
+
+/// The cluster size of the volume holding `path`, or 4096, the NTFS
+/// default, if it cannot be read.
+#[cfg(windows)]
+fn allocation_unit(path: &Path) -> u64 {
+    use std::os::windows::ffi::OsStrExt;
+    use windows_sys::Win32::Storage::FileSystem::{GetDiskFreeSpaceW, GetVolumePathNameW};
+
+    let wide: Vec<u16> = path.as_os_str().encode_wide().chain([0]).collect();
+    let mut volume = [0u16; 1024];
+    let (mut sectors, mut bytes, mut free, mut total) = (0u32, 0u32, 0u32, 0u32);
+    // SAFETY: `wide` is NUL-terminated, `volume` holds the length passed,
+    // and the counts are plain out-parameters.
+    let ok = unsafe {
+        GetVolumePathNameW(wide.as_ptr(), volume.as_mut_ptr(), volume.len() as u32) != 0
+            && GetDiskFreeSpaceW(
+                volume.as_ptr(),
+                &mut sectors,
+                &mut bytes,
+                &mut free,
+                &mut total,
+            ) != 0
+    };
+    let unit = u64::from(sectors) * u64::from(bytes);
+    if ok && unit > 0 { unit } else { 4096 }
+}
+
+#[cfg(not(any(unix, windows)))]
+fn allocation_unit(_path: &Path) -> u64 {
+    4096
+}
+
+/// Error type for unparseable `--min-size` / `--max-size` values.
+#[derive(thiserror::Error, Debug)]
+#[error(
+    "invalid size '{0}': expected a number with an optional unit B, KB, MB, GB, or TB (e.g., 500MB)"
+)]
+pub struct SizeError(String);
+
+/// Parse a human-readable size such as `500MB`, `1.5G`, or `4096`.
+///
+/// Units are binary (1 KB = 1024 bytes) to match `format_size`, are
+/// case-insensitive, and may be written `K`, `KB`, or `KiB`.
+pub fn parse_size(s: &str) -> Result<u64, SizeError> {
+    let err = || SizeError(s.to_string());
+    let trimmed = s.trim();
+    let split = trimmed
+        .find(|c: char| !(c.is_ascii_digit() || c == '.'))
+        .unwrap_or(trimmed.len());
+    let (number, unit) = trimmed.split_at(split);
+    let value: f64 = number.parse().map_err(|_| err())?;
+    let exponent = match unit.trim_start().to_ascii_lowercase().as_str() {
+        "" | "b" => 0,
+        "k" | "kb" | "kib" => 1,
+        "m" | "mb" | "mib" => 2,
+        "g" | "gb" | "gib" => 3,
+        "t" | "tb" | "tib" => 4,
+        _ => return Err(err()),
+    };
+    let bytes = value * 1024f64.powi(exponent);
+    if !bytes.is_finite() || bytes >= u64::MAX as f64 {
+        return Err(err());
+    }
+    Ok(bytes.round() as u64)
+}
+
+/// Filters artifacts by their computed size. Must run after `compute_sizes`.
+#[derive(Debug, Default)]
+pub struct SizeFilter {
+    /// Skip artifacts smaller than this many bytes.
+    pub min: Option<u64>,
+    /// Skip artifacts larger than this many bytes.
+    pub max: Option<u64>,
+}
+
+impl SizeFilter {
+    /// Test whether an artifact of `size` bytes passes the filter.
+    pub fn keeps(&self, size: u64) -> bool {
+        self.verdict(size).is_keep()
+    }
+
+    /// Decide whether to keep an artifact of `size` bytes, and why.
+    pub fn verdict(&self, size: u64) -> Verdict {
+        if let Some(min) = self.min.filter(|&min| size < min) {
+            return Verdict::Drop(format!(
+                "{} is below --min-size {}",
+                format_size(size),
+                format_size(min)
+            ));
+        }
+        if let Some(max) = self.max.filter(|&max| size > max) {
+            return Verdict::Drop(format!(
+                "{} is above --max-size {}",
+                format_size(size),
+                format_size(max)
+            ));
+        }
+        if self.min.is_none() && self.max.is_none() {
+            return Verdict::Keep(None);
+        }
+        Verdict::Keep(Some(format!("size {}", format_size(size))))
+    }
+
+    /// Filter a list of sized artifacts.
+    pub fn apply(&self, artifacts: Vec<Artifact>) -> Vec<Artifact> {
+        artifacts
+            .into_iter()
+            .filter(|a| match self.verdict(a.size_bytes) {
+                Verdict::Keep(_) => true,
+                Verdict::Drop(reason) => {
+                    debug!("Filtered out: {} ({reason})", a.path.display());
+                    false
+                }
+            })
+            .collect()
+    }
+}
+
+impl Filter for SizeFilter {
+    fn keep(&self, artifact: &Artifact) -> bool {
+        self.keeps(artifact.size_bytes)
+    }
+
+    /// Decide by `artifact.size_bytes`, so sizes must be computed first.
+    fn verdict(&self, artifact: &Artifact) -> Verdict {
+        SizeFilter::verdict(self, artifact.size_bytes)
+    }
+}
+
+/// Picks the oldest artifacts whose sizes add up to a target (`--free`).
+///
+/// Artifacts are taken by modification time, oldest first (unreadable times
+/// sort as oldest), until the target is reached; the rest are dropped. Must
+/// run after `compute_sizes` and every other filter, since it chooses from
+/// whatever is left.
+#[derive(Debug)]
+pub struct FreeTarget {
+    target: u64,
+    chosen: HashSet<PathBuf>,
+    selected_bytes: u64,
+}
+
+impl FreeTarget {
+    /// Choose which of `artifacts` to delete to free `target` bytes.
+    pub fn select(artifacts: &[Artifact], target: u64) -> Self {
+        let by_age = artifacts
+            .iter()
+            .map(|a| {
+                let mtime = std::fs::symlink_metadata(&a.path)
+                    .and_then(|m| m.modified())
+                    .unwrap_or(SystemTime::UNIX_EPOCH);
+                (mtime, a.path.as_path(), a.size_bytes)
+            })
+            .collect();
+        let (chosen, selected_bytes) = oldest_until(by_age, target);
+        Self {
+            target,
+            chosen,
+            selected_bytes,
+        }
+    }
+
+    /// Total size of the chosen artifacts; below the target when everything
+    /// left after filtering is not enough.
+    pub fn selected_bytes(&self) -> u64 {
+        self.selected_bytes
+    }
+}
+
+impl Filter for FreeTarget {
+    fn keep(&self, artifact: &Artifact) -> bool {
+        self.chosen.contains(&artifact.path)
+    }
+
+    fn verdict(&self, artifact: &Artifact) -> Verdict {
+        if self.keep(artifact) {
+            Verdict::Keep(Some(format!(
+                "among the oldest needed to free {} (--free)",
+                format_size(self.target)
+            )))
+        } else {
+            Verdict::Drop(format!(
+                "older artifacts already free {} (--free)",
+                format_size(self.target)
+            ))
+        }
+    }
+}
+
+/// Take `(mtime, path, size)` entries oldest first until their sizes reach
+/// `target`, returning the chosen paths and their total size.
+fn oldest_until(
+    mut entries: Vec<(SystemTime, &Path, u64)>,
+    target: u64,
+) -> (HashSet<PathBuf>, u64) {
+    // Oldest first; ties broken by path so the choice is deterministic.
+    entries.sort_by(|a, b| a.0.cmp(&b.0).then_with(|| a.1.cmp(b.1)));
+    let mut chosen = HashSet::new();
+    let mut total = 0u64;
+    for (_, path, size) in entries {
+        if total >= target {
+            break;
+        }
+        chosen.insert(path.to_path_buf());
+        total = total.saturating_add(size);
+    }
+    (chosen, total)
+}
+
+/// Whether [`SizeFormat`] counts in powers of 1024 or of 1000.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum SizeUnits {
+    /// 1 KB is 1024 bytes, as [`parse_size`] reads it.
+    #[default]
+    Binary,
+    /// 1 kB is 1000 bytes, as drive makers and Finder count.
+    Si,
+}
+
+/// How [`format_size_with`] writes a byte count.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SizeFormat {
+    pub units: SizeUnits,
+    /// Digits after the decimal point, for sizes of a kilobyte and up.
+    pub precision: usize,
+    /// Right-align to [`width`](Self::width), so sizes line up in columns.
+    pub fixed_width: bool,
+}
+
+impl Default for SizeFormat {
+    /// The format of [`format_size`]: binary units, one decimal.
+    fn default() -> Self {
+        Self {
+            units: SizeUnits::Binary,
+            precision: 1,
+            fixed_width: false,
+        }
+    }
+}
+
+impl SizeFormat {
+    /// The widest size this format writes below a thousand terabytes, for
+    /// sizing columns: up to four digits before the point, since values
+    /// just under the next unit round up to `1024.0` or `1000.0`.
+    pub fn width(&self) -> usize {
+        let decimals = if self.precision > 0 {
+            self.precision + 1
+        } else {
+            0
+        };
+        4 + decimals + " TB".len()
+    }
+}
+
+/// Format a byte count as a human-readable string, such as `1.5 GB`.
+pub fn format_size(bytes: u64) -> String {
+    format_size_with(bytes, &SizeFormat::default())
+}
+
+/// Format a byte count as `format` says.
+pub fn format_size_with(bytes: u64, format: &SizeFormat) -> String {
+    let (base, units) = match format.units {
+        SizeUnits::Binary => (1024.0, ["KB", "MB", "GB", "TB"]),
+        SizeUnits::Si => (1000.0, ["kB", "MB", "GB", "TB"]),
+    };
+    let mut value = bytes as f64;
+    let text = if value < base {
         format!("{bytes} B")
+    } else {
+        let mut unit = 0;
+        value /= base;
+        while value >= base && unit + 1 < units.len() {
+            value /= base;
+            unit += 1;
+        }
+        format!("{value:.*} {}", format.precision, units[unit])
+    };
+    if format.fixed_width {
+        format!("{text:>width$}", width = format.width())
+    } else {
+        text
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::rules::Risk;
+
+    #[test]
+    fn parse_size_units() {
+        assert_eq!(parse_size("4096").unwrap(), 4096);
+        assert_eq!(parse_size("10B").unwrap(), 10);
+        assert_eq!(parse_size("2k").unwrap(), 2048);
+        assert_eq!(parse_size("500MB").unwrap(), 500 * 1024 * 1024);
+        assert_eq!(parse_size("1.5GiB").unwrap(), 3 * 512 * 1024 * 1024);
+        assert_eq!(parse_size("1 TB").unwrap(), 1024u64.pow(4));
+    }
+
+    #[test]
+    fn parse_size_rejects_malformed() {
+        for bad in ["", "MB", "1.2.3MB", "-5MB", "10PB", "lots"] {
+            let err = parse_size(bad).unwrap_err();
+            assert!(err.to_string().contains("invalid size"), "{bad}");
+        }
+    }
+
+    #[test]
+    fn size_filter_bounds_are_inclusive() {
+        let f = SizeFilter {
+            min: Some(100),
+            max: Some(200),
+        };
+        assert!(!f.keeps(99));
+        assert!(f.keeps(100));
+        assert!(f.keeps(200));
+        assert!(!f.keeps(201));
+        assert!(SizeFilter::default().keeps(0));
+    }
+
+    #[test]
+    fn size_filter_verdict_names_the_bound() {
+        let f = SizeFilter {
+            min: Some(1024 * 1024),
+            max: None,
+        };
+        assert_eq!(
+            f.verdict(512 * 1024),
+            Verdict::Drop("512.0 KB is below --min-size 1.0 MB".to_string())
+        );
+        assert_eq!(
+            f.verdict(2 * 1024 * 1024),
+            Verdict::Keep(Some("size 2.0 MB".to_string()))
+        );
+        assert_eq!(SizeFilter::default().verdict(0), Verdict::Keep(None));
+    }
 
     #[test]
     fn format_size_bytes() {
@@ -88,6 +1210,71 @@ mod tests {
         );
     }
 
+    #[test]
+    fn format_size_tb() {
+        assert_eq!(format_size(3 * 1024u64.pow(4) / 2), "1.5 TB");
+        assert_eq!(format_size(2048 * 1024u64.pow(4)), "2048.0 TB");
+    }
+
+    #[test]
+    fn format_size_with_units_precision_and_width() {
+        let si = SizeFormat {
+            units: SizeUnits::Si,
+            precision: 2,
+            ..Default::default()
+        };
+        assert_eq!(format_size_with(999, &si), "999 B");
+        assert_eq!(format_size_with(1_500_000, &si), "1.50 MB");
+        let whole = SizeFormat {
+            precision: 0,
+            ..Default::default()
+        };
+        assert_eq!(format_size_with(1536, &whole), "2 KB");
+
+        let fixed = SizeFormat {
+            fixed_width: true,
+            ..Default::default()
+        };
+        assert_eq!(format_size_with(1024, &fixed), "   1.0 KB");
+        // The widest value rounds up to the next unit's threshold.
+        let widest = format_size_with(1024 * 1024 - 1, &SizeFormat::default());
+        assert_eq!(widest, "1024.0 KB");
+        assert_eq!(
+            format_size_with(1024 * 1024 - 1, &fixed).len(),
+            fixed.width()
+        );
+        for format in [si, whole] {
+            let widest = format_size_with(999_999_999_999_999, &format);
+            assert!(widest.len() <= format.width(), "{widest}");
+        }
+    }
+
+    #[test]
+    fn oldest_until_stops_once_target_is_reached() {
+        use std::time::Duration;
+
+        let at = |secs| SystemTime::UNIX_EPOCH + Duration::from_secs(secs);
+        let entries = vec![
+            (at(30), Path::new("/c"), 100),
+            (at(10), Path::new("/a"), 40),
+            (at(20), Path::new("/b"), 40),
+        ];
+
+        let (chosen, total) = oldest_until(entries.clone(), 50);
+        assert_eq!(total, 80);
+        assert!(chosen.contains(Path::new("/a")));
+        assert!(chosen.contains(Path::new("/b")));
+        assert!(!chosen.contains(Path::new("/c")));
+
+        let (chosen, total) = oldest_until(entries.clone(), 0);
+        assert!(chosen.is_empty());
+        assert_eq!(total, 0);
+
+        let (chosen, total) = oldest_until(entries, 1000);
+        assert_eq!(chosen.len(), 3);
+        assert_eq!(total, 180);
+    }
+
     #[test]
     fn compute_sizes_populates_artifacts() {
         use std::fs;
@@ -98,20 +1285,364 @@ mod tests {
         fs::create_dir_all(&dir).unwrap();
         fs::write(dir.join("file.o"), "hello world").unwrap(); // 11 bytes
 
+        let mut artifacts = vec![Artifact::for_test(dir.clone(), "Rust/Cargo")];
+
+        compute_sizes(&mut artifacts);
+        assert_eq!(artifacts[0].size_bytes, 11);
+    }
+
+    #[test]
+    fn compute_sizes_counts_files_dirs_and_newest_in_one_walk() {
+        use std::fs;
+        use std::time::Duration;
+        use tempfile::TempDir;
+
+        let tmp = TempDir::new().unwrap();
+        let dir = tmp.path().join("target");
+        fs::create_dir_all(dir.join("debug/deps")).unwrap();
+        fs::write(dir.join("debug/app"), "12345").unwrap();
+        fs::write(dir.join("debug/deps/lib.rlib"), "123").unwrap();
+        let old = SystemTime::UNIX_EPOCH + Duration::from_secs(1_000_000);
+        fs::File::options()
+            .write(true)
+            .open(dir.join("debug/app"))
+            .unwrap()
+            .set_modified(old)
+            .unwrap();
+        let newest = fs::metadata(dir.join("debug/deps/lib.rlib"))
+            .unwrap()
+            .modified()
+            .unwrap();
+
+        let mut artifacts = vec![Artifact::for_test(dir.clone(), "Rust/Cargo")];
+
+        compute_sizes(&mut artifacts);
+        let stats = artifacts[0].stats.unwrap();
+        assert_eq!(stats.bytes, 8);
+        assert_eq!(stats.files, 2);
+        assert_eq!(stats.dirs, 2);
+        assert_eq!(stats.newest, Some(newest));
+    }
+
+    #[test]
+    fn estimate_matches_exact_size_of_narrow_trees() {
+        use std::fs;
+        use tempfile::TempDir;
+
+        let tmp = TempDir::new().unwrap();
+        let dir = tmp.path().join("node_modules");
+        for i in 0..10 {
+            fs::create_dir_all(dir.join(format!("pkg-{i}/lib"))).unwrap();
+            fs::write(dir.join(format!("pkg-{i}/lib/index.js")), vec![0u8; i * 10]).unwrap();
+        }
+        let options = SizeOptions {
+            estimate: true,
+            ..Default::default()
+        };
+
+        let estimate = estimate_size(&dir, &options).stats;
+        let exact = dir_size(&dir, &SizeOptions::default()).stats;
+        assert_eq!(estimate.bytes, exact.bytes);
+        assert_eq!(estimate.files, 10);
+        assert_eq!(estimate.dirs, 20);
+        assert_eq!(estimate.margin, Some(0));
+    }
+
+    #[test]
+    fn estimate_samples_wide_directories() {
+        use std::fs;
+        use tempfile::TempDir;
+
+        let tmp = TempDir::new().unwrap();
+        let dir = tmp.path().join("node_modules");
+        for i in 0..200 {
+            let pkg = dir.join(format!("pkg-{i:03}"));
+            fs::create_dir_all(&pkg).unwrap();
+            fs::write(pkg.join("index.js"), vec![0u8; 100 + (i * 37) % 500]).unwrap();
+        }
+        let options = SizeOptions {
+            estimate: true,
+            ..Default::default()
+        };
+
+        let estimate = estimate_size(&dir, &options).stats;
+        let exact = dir_size(&dir, &SizeOptions::default()).stats;
+        let margin = estimate.margin.unwrap();
+        assert!(margin > 0);
+        assert!(
+            estimate.bytes.abs_diff(exact.bytes) <= margin,
+            "{estimate:?} vs {exact:?}"
+        );
+        assert_eq!(estimate.files, 200);
+        assert_eq!(estimate.dirs, 200);
+    }
+
+    #[test]
+    fn progress_is_reported_after_each_artifact() {
+        use std::fs;
+        use tempfile::TempDir;
+
+        let tmp = TempDir::new().unwrap();
+        let mut artifacts: Vec<Artifact> = (0..5)
+            .map(|i| {
+                let dir = tmp.path().join(format!("p{i}/target"));
+                fs::create_dir_all(&dir).unwrap();
+                fs::write(dir.join("app"), vec![0u8; 10]).unwrap();
+                Artifact::for_test(dir, "Rust/Cargo")
+            })
+            .collect();
+
+        #[derive(Default)]
+        struct Updates(Mutex<Vec<(u64, SizingProgress)>>);
+        impl Progress for Updates {
+            fn on_sized(&self, _: &Artifact, bytes: u64, progress: SizingProgress) {
+                self.0.lock().unwrap().push((bytes, progress));
+            }
+        }
+        let updates = Updates::default();
+        compute_sizes_with_progress(
+            &mut artifacts,
+            &SizeOptions::default(),
+            &updates,
+            &CancelToken::new(),
+        );
+        let updates = updates.0.into_inner().unwrap();
+        assert_eq!(updates.len(), 5);
+        for (i, (bytes, update)) in updates.iter().enumerate() {
+            assert_eq!(*bytes, 10);
+            assert_eq!(update.done, i + 1);
+            assert_eq!(update.total, 5);
+            assert_eq!(update.bytes, 10 * (i as u64 + 1));
+        }
+
+        // Once cancelled, artifacts are left unsized.
+        let cancel = CancelToken::new();
+        cancel.cancel();
+        for artifact in &mut artifacts {
+            artifact.size_bytes = 0;
+            artifact.stats = None;
+        }
+        compute_sizes_with_progress(&mut artifacts, &SizeOptions::default(), &(), &cancel);
+        assert!(artifacts.iter().all(|a| a.stats.is_none()));
+    }
+
+    #[test]
+    fn compute_sizes_file_artifact() {
+        use std::fs;
+        use tempfile::TempDir;
+
+        let tmp = TempDir::new().unwrap();
+        let file = tmp.path().join(".ghc.environment.x86_64-linux-9.4.8");
+        fs::write(&file, "package-db").unwrap(); // 10 bytes
+
         let mut artifacts = vec![Artifact {
-            path: dir.clone(),
-            build_system: "Rust/Cargo",
-            artifact_dir: "target",
-            size_bytes: 0,
+            artifact_dir: ".ghc.environment.*",
+            risk: Risk::Cache,
+            ..Artifact::for_test(file, "Haskell/Cabal")
         }];
 
         compute_sizes(&mut artifacts);
-        assert_eq!(artifacts[0].size_bytes, 11);
+        assert_eq!(artifacts[0].size_bytes, 10);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn compute_sizes_does_not_follow_symlink_artifact() {
+        use std::fs;
+        use tempfile::TempDir;
+
+        let tmp = TempDir::new().unwrap();
+        let store = tmp.path().join("store");
+        fs::create_dir_all(&store).unwrap();
+        fs::write(store.join("big"), vec![0u8; 4096]).unwrap();
+        let link = tmp.path().join("result");
+        std::os::unix::fs::symlink(&store, &link).unwrap();
+
+        let mut artifacts = vec![Artifact {
+            artifact_dir: "result*",
+            ..Artifact::for_test(link, "Nix")
+        }];
+
+        compute_sizes(&mut artifacts);
+        assert!(artifacts[0].size_bytes < 4096);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn symlinks_count_themselves_and_optionally_their_targets_outside() {
+        use std::fs;
+        use std::os::unix::fs::symlink;
+        use tempfile::TempDir;
+
+        let tmp = TempDir::new().unwrap();
+        let cache = tmp.path().join("cache");
+        fs::create_dir_all(cache.join("dir")).unwrap();
+        fs::write(cache.join("big"), vec![0u8; 1000]).unwrap();
+        fs::write(cache.join("dir/inner"), vec![0u8; 300]).unwrap();
+        let dir = tmp.path().join("bazel-out");
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(dir.join("own"), vec![0u8; 10]).unwrap();
+        let links = [
+            ("a", cache.join("big")),
+            ("b", cache.join("big")),
+            ("c", cache.join("dir")),
+            ("d", cache.join("dir/inner")),
+            ("e", dir.join("own")),
+            ("f", tmp.path().join("missing")),
+        ];
+        let mut link_bytes = 0;
+        for (name, target) in &links {
+            symlink(target, dir.join(name)).unwrap();
+            link_bytes += target.as_os_str().len() as u64;
+        }
+
+        let plain = dir_size(&dir, &SizeOptions::default()).stats;
+        assert_eq!(plain.bytes, 10 + link_bytes);
+        assert_eq!(plain.outside_bytes, 0);
+
+        let options = SizeOptions {
+            link_targets: true,
+            ..Default::default()
+        };
+        let stats = dir_size(&dir, &options).stats;
+        assert_eq!(stats.bytes, plain.bytes);
+        assert_eq!(stats.outside_bytes, 1300);
+    }
+
+    #[test]
+    fn walks_stop_once_above_the_limit() {
+        use std::fs;
+        use tempfile::TempDir;
+
+        let tmp = TempDir::new().unwrap();
+        let dir = tmp.path().join("target");
+        fs::create_dir_all(&dir).unwrap();
+        for i in 0..100 {
+            fs::write(dir.join(format!("f{i}")), vec![0u8; 100]).unwrap();
+        }
+        let options = SizeOptions {
+            stop_above: Some(250),
+            ..Default::default()
+        };
+
+        let stats = dir_size(&dir, &options).stats;
+        assert!(stats.bytes > 250);
+        // The native walk always walks in full.
+        #[cfg(not(feature = "native-walk"))]
+        assert_eq!(stats.files, 3);
+
+        let full = SizeOptions {
+            stop_above: Some(10_000),
+            ..Default::default()
+        };
+        assert_eq!(dir_size(&dir, &full).stats.bytes, 10_000);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn allocated_size_counts_blocks_not_length() {
+        use std::fs;
+        use tempfile::TempDir;
+
+        let tmp = TempDir::new().unwrap();
+        let dir = tmp.path().join("target");
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(dir.join("small"), "x").unwrap();
+        // A sparse file: long, but with nothing written.
+        fs::File::create(dir.join("sparse"))
+            .unwrap()
+            .set_len(64 * 1024 * 1024)
+            .unwrap();
+
+        let apparent = dir_size(&dir, &SizeOptions::default()).stats.bytes;
+        let allocated = dir_size(
+            &dir,
+            &SizeOptions {
+                allocated: true,
+                ..Default::default()
+            },
+        )
+        .stats
+        .bytes;
+        assert_eq!(apparent, 64 * 1024 * 1024 + 1);
+        assert!(allocated < apparent, "{allocated}");
+        // The one-byte file takes at least a whole block.
+        assert!(allocated >= 512, "{allocated}");
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn hard_links_count_once() {
+        use std::fs;
+        use tempfile::TempDir;
+
+        let tmp = TempDir::new().unwrap();
+        let artifact = |name: &str| {
+            let dir = tmp.path().join(name).join("node_modules");
+            fs::create_dir_all(&dir).unwrap();
+            Artifact {
+                risk: Risk::Deps,
+                ..Artifact::for_test(dir, "Node.js")
+            }
+        };
+        let mut artifacts = vec![artifact("b"), artifact("a")];
+        let original = artifacts[1].path.join("lib.js");
+        fs::write(&original, vec![0u8; 1000]).unwrap();
+        fs::hard_link(&original, artifacts[1].path.join("copy.js")).unwrap();
+        fs::hard_link(&original, artifacts[0].path.join("lib.js")).unwrap();
+
+        compute_sizes(&mut artifacts);
+        assert_eq!(artifacts[0].size_bytes, 1000);
+        assert_eq!(artifacts[1].size_bytes, 1000);
+
+        let options = SizeOptions {
+            dedup_links: true,
+            ..Default::default()
+        };
+        compute_sizes_with(&mut artifacts, &options);
+        assert_eq!(artifacts[0].size_bytes, 0);
+        assert_eq!(artifacts[1].size_bytes, 1000);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn reclaimable_leaves_out_files_linked_from_outside() {
+        use std::fs;
+        use tempfile::TempDir;
+
+        let tmp = TempDir::new().unwrap();
+        let store = tmp.path().join(".pnpm-store");
+        fs::create_dir_all(&store).unwrap();
+        let dir = tmp.path().join("web/node_modules");
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(store.join("react.js"), vec![0u8; 3000]).unwrap();
+        fs::hard_link(store.join("react.js"), dir.join("react.js")).unwrap();
+        // Both names of this one are inside, so deleting frees it.
+        fs::write(dir.join("own.js"), vec![0u8; 200]).unwrap();
+        fs::hard_link(dir.join("own.js"), dir.join("own-copy.js")).unwrap();
+        fs::write(dir.join("plain.js"), vec![0u8; 100]).unwrap();
+
+        let options = SizeOptions {
+            reclaimable: true,
+            ..Default::default()
+        };
+        let stats = dir_size(&dir, &options);
+        let mut artifacts = [Artifact {
+            risk: Risk::Deps,
+            ..Artifact::for_test(dir, "Node.js")
+        }];
+        assert_eq!(stats.reclaimable(), 300);
+        compute_sizes_with(&mut artifacts, &options);
+        assert_eq!(artifacts[0].size_bytes, 3300);
+        assert_eq!(artifacts[0].stats.unwrap().reclaimable, Some(300));
+        compute_sizes(&mut artifacts);
+        assert_eq!(artifacts[0].stats.unwrap().reclaimable, None);
     }
 
-    /// Reproduces thread-pool contention between rayon par_iter and jwalk.
-    /// With enough artifacts saturating the rayon global pool, jwalk's
-    /// internal parallel walkers can't make progress and return 0.
+    /// Guards against thread-pool contention between rayon par_iter and
+    /// jwalk. If walks ran on the rayon global pool that the artifacts are
+    /// iterated on, saturating it would leave jwalk's parallel walkers unable
+    /// to make progress and return 0.
     #[test]
     fn compute_sizes_many_artifacts_no_zeros() {
         use std::fs;
@@ -128,10 +1659,8 @@ mod tests {
                 fs::create_dir_all(&dir).unwrap();
                 fs::write(dir.join("file.js"), "content").unwrap();
                 Artifact {
-                    path: dir,
-                    build_system: "Node.js",
-                    artifact_dir: "node_modules",
-                    size_bytes: 0,
+                    risk: Risk::Deps,
+                    ..Artifact::for_test(dir, "Node.js")
                 }
             })
             .collect();
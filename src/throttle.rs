@@ -0,0 +1,118 @@
+use std::time::Duration;
+
+use log::{debug, warn};
+
+/// How `--nice` slows deletion down so a background cleanup leaves the
+/// machine usable: artifacts are removed one at a time, at low IO priority,
+/// pausing after every `batch` files.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Throttle {
+    /// Files removed between pauses.
+    pub batch: usize,
+    /// How long each pause lasts.
+    pub pause: Duration,
+}
+
+impl Default for Throttle {
+    fn default() -> Self {
+        Self {
+            batch: 256,
+            pause: Duration::from_millis(10),
+        }
+    }
+}
+
+impl Throttle {
+    /// A counter that pauses the calling thread every `batch` files.
+    pub fn pacer(&self) -> Pacer {
+        Pacer {
+            throttle: *self,
+            count: 0,
+        }
+    }
+}
+
+/// Counts removed files for one [`Throttle`], sleeping between batches.
+#[derive(Debug)]
+pub struct Pacer {
+    throttle: Throttle,
+    count: usize,
+}
+
+impl Pacer {
+    /// Record one removed file, pausing if a batch is complete.
+    pub fn tick(&mut self) {
+        self.count += 1;
+        if self.throttle.batch > 0 && self.count % self.throttle.batch == 0 {
+            std::thread::sleep(self.throttle.pause);
+        }
+    }
+}
+
+/// Put the calling thread in the idle IO scheduling class, so its disk
+/// access only gets time nobody else wants. Child processes spawned from it
+/// inherit the class.
+#[cfg(target_os = "linux")]
+pub fn lower_io_priority() {
+    const IOPRIO_WHO_PROCESS: libc::c_long = 1;
+    const IOPRIO_CLASS_IDLE: libc::c_long = 3;
+    const IOPRIO_CLASS_SHIFT: libc::c_long = 13;
+    // SAFETY: ioprio_set takes plain integers; `who == 0` is the calling thread.
+    let rc = unsafe {
+        libc::syscall(
+            libc::SYS_ioprio_set,
+            IOPRIO_WHO_PROCESS,
+            0,
+            IOPRIO_CLASS_IDLE << IOPRIO_CLASS_SHIFT,
+        )
+    };
+    if rc == 0 {
+        debug!("IO priority set to idle");
+    } else {
+        warn!(
+            "cannot lower IO priority: {}",
+            std::io::Error::last_os_error()
+        );
+    }
+}
+
+/// Run the calling thread at background QoS, which also throttles its disk IO.
+#[cfg(target_os = "macos")]
+pub fn lower_io_priority() {
+    // SAFETY: only changes the scheduling class of the calling thread.
+    let rc =
+        unsafe { libc::pthread_set_qos_class_self_np(libc::qos_class_t::QOS_CLASS_BACKGROUND, 0) };
+    if rc == 0 {
+        debug!("QoS class set to background");
+    } else {
+        warn!(
+            "cannot lower IO priority: {}",
+            std::io::Error::from_raw_os_error(rc)
+        );
+    }
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "macos")))]
+pub fn lower_io_priority() {
+    debug!("Lowering IO priority is not supported on this platform; only pacing deletion");
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Instant;
+
+    #[test]
+    fn pacer_pauses_after_each_batch() {
+        let throttle = Throttle {
+            batch: 2,
+            pause: Duration::from_millis(20),
+        };
+        let mut pacer = throttle.pacer();
+        let start = Instant::now();
+        for _ in 0..5 {
+            pacer.tick();
+        }
+        assert!(start.elapsed() >= Duration::from_millis(40));
+    }
+}
@@ -0,0 +1,543 @@
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+use console::truncate_str;
+use ratatui::DefaultTerminal;
+use ratatui::crossterm::event::{self, Event, KeyCode, KeyEvent, KeyEventKind, KeyModifiers};
+use ratatui::style::{Modifier, Style};
+use ratatui::text::Line;
+use ratatui::widgets::Paragraph;
+
+use crate::age::format_age;
+use crate::scanner::Artifact;
+use crate::size::{SizeFormat, format_size};
+
+/// What the list is ordered by; `s` cycles through them.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Sort {
+    /// Largest first.
+    Size,
+    /// Least recently modified first.
+    Age,
+    Path,
+}
+
+impl Sort {
+    fn next(self) -> Self {
+        match self {
+            Sort::Size => Sort::Age,
+            Sort::Age => Sort::Path,
+            Sort::Path => Sort::Size,
+        }
+    }
+
+    fn label(self) -> &'static str {
+        match self {
+            Sort::Size => "size",
+            Sort::Age => "age",
+            Sort::Path => "path",
+        }
+    }
+}
+
+/// A key press, as far as the review cares.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Action {
+    Up,
+    Down,
+    PageUp,
+    PageDown,
+    Home,
+    End,
+    /// Select or deselect the artifact under the cursor.
+    Toggle,
+    /// Select every artifact, or none if all are selected.
+    ToggleAll,
+    CycleSort,
+    /// Group the list by build system, or stop grouping it.
+    ToggleGroups,
+    /// Ask to delete the selected artifacts.
+    Delete,
+    /// Answer yes to the question asked.
+    Yes,
+    Quit,
+    Other,
+}
+
+impl Action {
+    fn of(key: &KeyEvent) -> Self {
+        if key.modifiers.contains(KeyModifiers::CONTROL) {
+            // Raw mode, so Ctrl+C arrives as a key and the screen is restored.
+            return match key.code {
+                KeyCode::Char('c') => Action::Quit,
+                _ => Action::Other,
+            };
+        }
+        match key.code {
+            KeyCode::Up | KeyCode::Char('k') => Action::Up,
+            KeyCode::Down | KeyCode::Char('j') => Action::Down,
+            KeyCode::PageUp => Action::PageUp,
+            KeyCode::PageDown => Action::PageDown,
+            KeyCode::Home => Action::Home,
+            KeyCode::End => Action::End,
+            KeyCode::Char(' ') => Action::Toggle,
+            KeyCode::Char('a') => Action::ToggleAll,
+            KeyCode::Char('s') => Action::CycleSort,
+            KeyCode::Char('g') => Action::ToggleGroups,
+            KeyCode::Char('d') | KeyCode::Enter => Action::Delete,
+            KeyCode::Char('y' | 'Y') => Action::Yes,
+            KeyCode::Char('q') | KeyCode::Esc => Action::Quit,
+            _ => Action::Other,
+        }
+    }
+}
+
+/// How a review ended.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Done {
+    /// The user confirmed deleting the selected artifacts.
+    Delete,
+    /// The user left without deleting anything.
+    Quit,
+}
+
+/// One artifact in the list.
+#[derive(Debug)]
+struct Row {
+    artifact: Artifact,
+    /// Path shown, relative to the scan root.
+    shown: PathBuf,
+    /// When anything in the artifact last changed.
+    modified: Option<SystemTime>,
+    selected: bool,
+}
+
+/// The state of the interactive review (`clean-builds tui`): the artifacts
+/// found, which are selected, and how they are listed. Kept apart from the
+/// terminal so it can be driven by [`Action`]s and rendered to plain lines.
+#[derive(Debug)]
+pub struct Review {
+    rows: Vec<Row>,
+    sort: Sort,
+    grouped: bool,
+    /// Position of the highlighted artifact in [`Review::order`].
+    cursor: usize,
+    /// First list line on screen.
+    offset: usize,
+    /// List lines that fit on screen when last rendered.
+    page: usize,
+    confirming: bool,
+    message: Option<&'static str>,
+    now: SystemTime,
+}
+
+impl Review {
+    /// Review `artifacts` found under `root`, none of them selected, largest
+    /// first. Ages are counted back from `now`.
+    pub fn new(root: &Path, artifacts: Vec<Artifact>, now: SystemTime) -> Self {
+        let rows = artifacts
+            .into_iter()
+            .map(|artifact| Row {
+                shown: artifact
+                    .path
+                    .strip_prefix(root)
+                    .unwrap_or(&artifact.path)
+                    .to_path_buf(),
+                modified: artifact.stats.and_then(|s| s.newest).or_else(|| {
+                    std::fs::symlink_metadata(&artifact.path)
+                        .and_then(|m| m.modified())
+                        .ok()
+                }),
+                artifact,
+                selected: false,
+            })
+            .collect();
+        Self {
+            rows,
+            sort: Sort::Size,
+            grouped: false,
+            cursor: 0,
+            offset: 0,
+            page: 1,
+            confirming: false,
+            message: None,
+            now,
+        }
+    }
+
+    /// The artifacts selected, in the order they were found.
+    pub fn selected(&self) -> Vec<Artifact> {
+        self.rows
+            .iter()
+            .filter(|r| r.selected)
+            .map(|r| r.artifact.clone())
+            .collect()
+    }
+
+    /// Indexes into `rows` in the order listed: by build system first when
+    /// grouped, then by the sort key.
+    fn order(&self) -> Vec<usize> {
+        let mut order: Vec<usize> = (0..self.rows.len()).collect();
+        order.sort_by(|&a, &b| {
+            let (a, b) = (&self.rows[a], &self.rows[b]);
+            let group = if self.grouped {
+                a.artifact.build_system.cmp(b.artifact.build_system)
+            } else {
+                std::cmp::Ordering::Equal
+            };
+            let key = match self.sort {
+                Sort::Size => b.artifact.size_bytes.cmp(&a.artifact.size_bytes),
+                // Unknown times sort last.
+                Sort::Age => match (a.modified, b.modified) {
+                    (Some(a), Some(b)) => a.cmp(&b),
+                    (a, b) => b.is_none().cmp(&a.is_none()),
+                },
+                Sort::Path => std::cmp::Ordering::Equal,
+            };
+            group
+                .then(key)
+                .then_with(|| a.artifact.path.cmp(&b.artifact.path))
+        });
+        order
+    }
+
+    /// Apply `action`, returning how the review ended if it did.
+    pub fn handle(&mut self, action: Action) -> Option<Done> {
+        self.message = None;
+        if self.confirming {
+            self.confirming = false;
+            return match action {
+                Action::Yes => Some(Done::Delete),
+                _ => None,
+            };
+        }
+        let last = self.rows.len().saturating_sub(1);
+        match action {
+            Action::Up => self.cursor = self.cursor.saturating_sub(1),
+            Action::Down => self.cursor = (self.cursor + 1).min(last),
+            Action::PageUp => self.cursor = self.cursor.saturating_sub(self.page),
+            Action::PageDown => self.cursor = (self.cursor + self.page).min(last),
+            Action::Home => self.cursor = 0,
+            Action::End => self.cursor = last,
+            Action::Toggle => {
+                if let Some(&i) = self.order().get(self.cursor) {
+                    self.rows[i].selected = !self.rows[i].selected;
+                    self.cursor = (self.cursor + 1).min(last);
+                }
+            }
+            Action::ToggleAll => {
+                let all = self.rows.iter().all(|r| r.selected);
+                for row in &mut self.rows {
+                    row.selected = !all;
+                }
+            }
+            Action::CycleSort => self.reorder(|review| review.sort = review.sort.next()),
+            Action::ToggleGroups => self.reorder(|review| review.grouped = !review.grouped),
+            Action::Delete if self.rows.iter().any(|r| r.selected) => self.confirming = true,
+            Action::Delete => self.message = Some("Nothing selected: press space to select"),
+            Action::Quit => return Some(Done::Quit),
+            Action::Yes | Action::Other => {}
+        }
+        None
+    }
+
+    /// Change the order with `change`, keeping the cursor on its artifact.
+    fn reorder(&mut self, change: impl FnOnce(&mut Self)) {
+        let current = self.order().get(self.cursor).copied();
+        change(self);
+        if let Some(current) = current {
+            self.cursor = self.order().iter().position(|&i| i == current).unwrap_or(0);
+        }
+    }
+
+    /// The screen as `height` lines at most `width` wide: a title, the part
+    /// of the list around the cursor, and a line of help or the question
+    /// asked. Also returns which line is highlighted.
+    pub fn render(&mut self, width: usize, height: usize) -> (Vec<String>, Option<usize>) {
+        let order = self.order();
+        let size_width = SizeFormat::default().width();
+        let system_width = self
+            .rows
+            .iter()
+            .map(|r| r.artifact.build_system.len())
+            .max()
+            .unwrap_or(0);
+
+        // Every list line, with the position in `order` of the artifact on it.
+        let mut list: Vec<(Option<usize>, String)> = Vec::new();
+        let mut group = None;
+        for (pos, &i) in order.iter().enumerate() {
+            let row = &self.rows[i];
+            let system = row.artifact.build_system;
+            if self.grouped && group != Some(system) {
+                group = Some(system);
+                let members = order.iter().map(|&j| &self.rows[j]);
+                let members: Vec<&Row> = members
+                    .filter(|r| r.artifact.build_system == system)
+                    .collect();
+                let bytes = members.iter().map(|r| r.artifact.size_bytes).sum();
+                let selected = members.iter().filter(|r| r.selected).count();
+                list.push((
+                    None,
+                    format!(
+                        "{system}: {} artifacts, {}, {selected} selected",
+                        members.len(),
+                        format_size(bytes)
+                    ),
+                ));
+            }
+            let age = row
+                .modified
+                .and_then(|m| self.now.duration_since(m).ok())
+                .map_or_else(|| "?".to_string(), format_age);
+            let system = if self.grouped {
+                String::new()
+            } else {
+                format!("{system:<system_width$}  ")
+            };
+            list.push((
+                Some(pos),
+                format!(
+                    "{} [{}] {:>size_width$}  {age:>4}  {system}{}",
+                    if pos == self.cursor { '>' } else { ' ' },
+                    if row.selected { 'x' } else { ' ' },
+                    format_size(row.artifact.size_bytes),
+                    row.shown.display()
+                ),
+            ));
+        }
+
+        self.page = height.saturating_sub(2).max(1);
+        let cursor_line = list
+            .iter()
+            .position(|(pos, _)| *pos == Some(self.cursor))
+            .unwrap_or(0);
+        if cursor_line < self.offset {
+            // Show a group's heading along with its first artifact.
+            self.offset = cursor_line.saturating_sub(usize::from(self.grouped));
+        } else if cursor_line >= self.offset + self.page {
+            self.offset = cursor_line + 1 - self.page;
+        }
+
+        let selected: Vec<&Row> = self.rows.iter().filter(|r| r.selected).collect();
+        let selected_bytes = selected.iter().map(|r| r.artifact.size_bytes).sum();
+        let total_bytes = self.rows.iter().map(|r| r.artifact.size_bytes).sum();
+        let mut lines = vec![format!(
+            "{} artifacts, {}; {} selected, {}; sorted by {}{}",
+            self.rows.len(),
+            format_size(total_bytes),
+            selected.len(),
+            format_size(selected_bytes),
+            self.sort.label(),
+            if self.grouped {
+                ", by build system"
+            } else {
+                ""
+            }
+        )];
+        let shown = list.iter().skip(self.offset).take(self.page);
+        let highlighted = shown
+            .clone()
+            .position(|(pos, _)| *pos == Some(self.cursor))
+            .map(|line| line + 1);
+        lines.extend(shown.map(|(_, line)| line.clone()));
+        lines.resize(self.page + 1, String::new());
+        lines.push(if self.confirming {
+            format!(
+                "Delete {} artifacts ({})? [y/N]",
+                selected.len(),
+                format_size(selected_bytes)
+            )
+        } else if let Some(message) = self.message {
+            message.to_string()
+        } else {
+            "up/down move  space select  a all  s sort  g group  d delete  q quit".to_string()
+        });
+        let lines = lines
+            .into_iter()
+            .map(|line| truncate_str(&line, width, "…").into_owned())
+            .collect();
+        (lines, highlighted)
+    }
+}
+
+/// Run `review` with ratatui until the user quits or confirms deleting the
+/// selection, drawing it on the alternate screen so the scrollback is left
+/// as it was.
+pub fn run(review: &mut Review) -> io::Result<Done> {
+    let mut terminal = ratatui::init();
+    let done = run_loop(&mut terminal, review);
+    ratatui::restore();
+    done
+}
+
+fn run_loop(terminal: &mut DefaultTerminal, review: &mut Review) -> io::Result<Done> {
+    loop {
+        terminal.draw(|frame| {
+            let area = frame.area();
+            let (lines, highlighted) =
+                review.render(usize::from(area.width), usize::from(area.height));
+            let lines: Vec<Line> = lines
+                .into_iter()
+                .enumerate()
+                .map(|(i, line)| {
+                    let line = Line::raw(line);
+                    if Some(i) == highlighted {
+                        line.style(Style::new().add_modifier(Modifier::REVERSED))
+                    } else {
+                        line
+                    }
+                })
+                .collect();
+            frame.render_widget(Paragraph::new(lines), area);
+        })?;
+
+        // Only presses: Windows also reports releases.
+        if let Event::Key(key) = event::read()? {
+            if key.kind == KeyEventKind::Press {
+                if let Some(done) = review.handle(Action::of(&key)) {
+                    return Ok(done);
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    const DAY: Duration = Duration::from_secs(24 * 60 * 60);
+
+    fn artifact(path: &str, system: &'static str, size_bytes: u64) -> Artifact {
+        let path = PathBuf::from("/root").join(path);
+        Artifact {
+            size_bytes,
+            ..Artifact::for_test(path, system)
+        }
+    }
+
+    fn review() -> Review {
+        let now = SystemTime::UNIX_EPOCH + DAY * 1000;
+        let mut review = Review::new(
+            Path::new("/root"),
+            vec![
+                artifact("a/target", "Rust/Cargo", 100),
+                artifact("b/node_modules", "Node.js", 300),
+                artifact("c/target", "Rust/Cargo", 200),
+            ],
+            now,
+        );
+        for (row, days) in review.rows.iter_mut().zip([3, 1, 10]) {
+            row.modified = Some(now - DAY * days);
+        }
+        review
+    }
+
+    fn paths(review: &Review) -> Vec<String> {
+        review
+            .order()
+            .iter()
+            .map(|&i| review.rows[i].shown.display().to_string())
+            .collect()
+    }
+
+    #[test]
+    fn ctrl_c_quits_and_other_chords_are_ignored() {
+        let key = |code, modifiers| Action::of(&KeyEvent::new(code, modifiers));
+        assert_eq!(key(KeyCode::Char('c'), KeyModifiers::CONTROL), Action::Quit);
+        assert_eq!(
+            key(KeyCode::Char('d'), KeyModifiers::CONTROL),
+            Action::Other
+        );
+        assert_eq!(key(KeyCode::Char('d'), KeyModifiers::NONE), Action::Delete);
+        assert_eq!(key(KeyCode::Esc, KeyModifiers::NONE), Action::Quit);
+    }
+
+    #[test]
+    fn sorts_by_size_then_age_then_path() {
+        let mut review = review();
+        assert_eq!(paths(&review), ["b/node_modules", "c/target", "a/target"]);
+        review.handle(Action::CycleSort);
+        assert_eq!(paths(&review), ["c/target", "a/target", "b/node_modules"]);
+        review.handle(Action::CycleSort);
+        assert_eq!(paths(&review), ["a/target", "b/node_modules", "c/target"]);
+    }
+
+    #[test]
+    fn reordering_keeps_the_cursor_on_its_artifact() {
+        let mut review = review();
+        review.handle(Action::Down);
+        review.handle(Action::ToggleGroups);
+        assert_eq!(paths(&review), ["b/node_modules", "c/target", "a/target"]);
+        review.handle(Action::CycleSort);
+        assert_eq!(paths(&review)[review.cursor], "c/target");
+    }
+
+    #[test]
+    fn space_toggles_and_moves_down() {
+        let mut review = review();
+        review.handle(Action::Toggle);
+        review.handle(Action::Toggle);
+        review.handle(Action::Toggle);
+        review.handle(Action::Up);
+        review.handle(Action::Toggle);
+        let selected: Vec<_> = review.selected().iter().map(|a| a.size_bytes).collect();
+        assert_eq!(selected, [100, 300]);
+
+        review.handle(Action::ToggleAll);
+        assert_eq!(review.selected().len(), 3);
+        review.handle(Action::ToggleAll);
+        assert!(review.selected().is_empty());
+    }
+
+    #[test]
+    fn delete_asks_first_and_needs_a_selection() {
+        let mut review = review();
+        assert_eq!(review.handle(Action::Delete), None);
+        let (lines, _) = review.render(80, 10);
+        assert_eq!(lines[9], "Nothing selected: press space to select");
+
+        review.handle(Action::Toggle);
+        assert_eq!(review.handle(Action::Delete), None);
+        let (lines, _) = review.render(80, 10);
+        assert_eq!(lines[9], "Delete 1 artifacts (300 B)? [y/N]");
+        // Anything but yes goes back to the list.
+        assert_eq!(review.handle(Action::Other), None);
+        assert_eq!(review.handle(Action::Yes), None);
+
+        review.handle(Action::Delete);
+        assert_eq!(review.handle(Action::Yes), Some(Done::Delete));
+        assert_eq!(review.handle(Action::Quit), Some(Done::Quit));
+    }
+
+    #[test]
+    fn renders_groups_and_scrolls_to_the_cursor() {
+        let mut review = review();
+        review.handle(Action::ToggleGroups);
+        review.handle(Action::Toggle);
+        let (lines, highlighted) = review.render(80, 7);
+        assert_eq!(
+            lines,
+            [
+                "3 artifacts, 600 B; 1 selected, 300 B; sorted by size, by build system",
+                "Node.js: 1 artifacts, 300 B, 1 selected",
+                "  [x]     300 B    1d  b/node_modules",
+                "Rust/Cargo: 2 artifacts, 300 B, 0 selected",
+                "> [ ]     200 B   10d  c/target",
+                "  [ ]     100 B    3d  a/target",
+                "up/down move  space select  a all  s sort  g group  d delete  q quit",
+            ]
+        );
+        assert_eq!(highlighted, Some(4));
+
+        // Two list lines fit; the cursor's line stays on screen.
+        review.handle(Action::End);
+        let (lines, highlighted) = review.render(30, 4);
+        assert_eq!(lines[1], "  [ ]     200 B   10d  c/targ…");
+        assert_eq!(lines[2], "> [ ]     100 B    3d  a/targ…");
+        assert_eq!(highlighted, Some(2));
+    }
+}
@@ -0,0 +1,134 @@
+use std::io::Write;
+
+use clap::CommandFactory;
+use serde::Serialize;
+
+use crate::cli::Cli;
+use crate::rules::{all_rules, rules_revision, system_ids};
+
+/// What this build of clean-builds can do, for scripts and wrappers to check
+/// before passing newer options.
+#[derive(Debug, Serialize)]
+pub struct Capabilities {
+    pub version: &'static str,
+    /// Cargo features compiled in.
+    pub features: Vec<&'static str>,
+    /// Output formats a scan can be reported in.
+    pub formats: Vec<&'static str>,
+    pub rules: RuleSet,
+    pub subcommands: Vec<String>,
+    /// Long options the top-level command accepts, without the `--`.
+    pub options: Vec<String>,
+}
+
+/// The built-in rule set.
+#[derive(Debug, Serialize)]
+pub struct RuleSet {
+    /// Changes whenever a built-in rule does.
+    pub revision: String,
+    pub rules: usize,
+    pub systems: usize,
+}
+
+/// The capabilities of the running build.
+pub fn capabilities() -> Capabilities {
+    let command = Cli::command();
+    Capabilities {
+        version: env!("CARGO_PKG_VERSION"),
+        features: [
+            ("native-walk", cfg!(feature = "native-walk")),
+            ("serde", cfg!(feature = "serde")),
+        ]
+        .into_iter()
+        .filter_map(|(name, enabled)| enabled.then_some(name))
+        .collect(),
+        formats: vec!["text", "json"],
+        rules: RuleSet {
+            revision: rules_revision(),
+            rules: all_rules().len(),
+            systems: system_ids().len(),
+        },
+        subcommands: command
+            .get_subcommands()
+            .filter(|sub| !sub.is_hide_set())
+            .map(|sub| sub.get_name().to_string())
+            .collect(),
+        options: command
+            .get_arguments()
+            .filter(|arg| !arg.is_hide_set())
+            .filter_map(|arg| arg.get_long().map(String::from))
+            .collect(),
+    }
+}
+
+/// Print `capabilities` as text, or as JSON if `json`.
+pub fn print_version(
+    out: &mut dyn Write,
+    capabilities: &Capabilities,
+    json: bool,
+) -> std::io::Result<()> {
+    if json {
+        serde_json::to_writer_pretty(&mut *out, capabilities)?;
+        return writeln!(out);
+    }
+    let list = |items: &[&str]| {
+        if items.is_empty() {
+            "none".to_string()
+        } else {
+            items.join(", ")
+        }
+    };
+    writeln!(out, "clean-builds {}", capabilities.version)?;
+    writeln!(out, "features: {}", list(&capabilities.features))?;
+    writeln!(out, "formats:  {}", list(&capabilities.formats))?;
+    writeln!(
+        out,
+        "rules:    revision {} ({} rules for {} build systems)",
+        capabilities.rules.revision, capabilities.rules.rules, capabilities.rules.systems
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn capabilities_describe_this_build() {
+        let capabilities = capabilities();
+        assert_eq!(capabilities.version, env!("CARGO_PKG_VERSION"));
+        assert_eq!(
+            capabilities.features.contains(&"native-walk"),
+            cfg!(feature = "native-walk")
+        );
+        assert_eq!(
+            capabilities.features.contains(&"serde"),
+            cfg!(feature = "serde")
+        );
+        assert_eq!(capabilities.rules.revision.len(), 16);
+        assert!(capabilities.subcommands.contains(&"version".to_string()));
+        assert!(
+            !capabilities
+                .subcommands
+                .contains(&"background-worker".to_string())
+        );
+        assert!(capabilities.options.contains(&"json".to_string()));
+        assert!(!capabilities.options.contains(&"help".to_string()));
+    }
+
+    #[test]
+    fn prints_text_or_json() {
+        let capabilities = capabilities();
+        let mut text = Vec::new();
+        print_version(&mut text, &capabilities, false).unwrap();
+        let text = String::from_utf8(text).unwrap();
+        assert!(text.starts_with(&format!("clean-builds {}\n", capabilities.version)));
+        assert!(text.contains(&format!("revision {}", capabilities.rules.revision)));
+
+        let mut json = Vec::new();
+        print_version(&mut json, &capabilities, true).unwrap();
+        let json: serde_json::Value = serde_json::from_slice(&json).unwrap();
+        assert_eq!(json["version"], capabilities.version);
+        assert_eq!(json["formats"], serde_json::json!(["text", "json"]));
+        assert_eq!(json["rules"]["revision"], capabilities.rules.revision);
+    }
+}
@@ -0,0 +1,182 @@
+use std::ffi::OsString;
+use std::io::Write;
+use std::path::Path;
+use std::process::{Command, Stdio};
+use std::time::{Duration, Instant, SystemTime};
+
+use crate::age::UtcTime;
+use crate::export::{ExportedArtifact, parse_json};
+use crate::size::format_size;
+
+/// How `watch` rescans.
+#[derive(Debug, Clone)]
+pub struct WatchOptions {
+    /// Time from the start of one scan to the start of the next.
+    pub interval: Duration,
+    /// Delete what each scan finds without asking, instead of reporting
+    /// how much it grew.
+    pub delete: bool,
+    /// Stop after this many scans instead of running until stopped.
+    pub scans: Option<usize>,
+}
+
+/// What one scan found.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Totals {
+    pub artifacts: usize,
+    pub bytes: u64,
+}
+
+impl Totals {
+    pub fn of(artifacts: &[ExportedArtifact]) -> Self {
+        Self {
+            artifacts: artifacts.len(),
+            bytes: artifacts.iter().filter_map(|a| a.size_bytes).sum(),
+        }
+    }
+}
+
+/// The line reporting a scan at `at` that found `totals`, with how much the
+/// artifacts grew or shrank since the `previous` scan.
+pub fn report_line(at: SystemTime, totals: Totals, previous: Option<Totals>) -> String {
+    let change = match previous {
+        None => String::new(),
+        Some(p) if totals.bytes >= p.bytes => {
+            format!(
+                " (+{} since the last scan)",
+                format_size(totals.bytes - p.bytes)
+            )
+        }
+        Some(p) => format!(
+            " (-{} since the last scan)",
+            format_size(p.bytes - totals.bytes)
+        ),
+    };
+    format!(
+        "{}  {} artifacts, {}{change}",
+        UtcTime::of(at),
+        totals.artifacts,
+        format_size(totals.bytes)
+    )
+}
+
+/// Scan `root` every `options.interval` by running `command` (this
+/// executable) on it, printing a line per scan to `out`, until stopped or
+/// `options.scans` are done. Each scan is an ordinary run, with the
+/// defaults set in the environment: `root --json` when reporting, or
+/// `root --delete --yes` to clean up. A scan that fails is reported and
+/// the next one goes ahead as planned.
+pub fn watch(
+    out: &mut dyn Write,
+    command: &[OsString],
+    root: &Path,
+    options: &WatchOptions,
+) -> std::io::Result<()> {
+    let Some((program, args)) = command.split_first() else {
+        return Ok(());
+    };
+    let mut previous = None;
+    let mut scan = 0;
+    loop {
+        scan += 1;
+        let started = Instant::now();
+        let mut run = Command::new(program);
+        run.args(args).arg(root);
+        if options.delete {
+            // `--json` conflicts with `--delete`.
+            run.args(["--delete", "--yes"])
+                .env_remove("CLEAN_BUILDS_JSON")
+                .stdin(Stdio::null());
+            let line = match run.status() {
+                Ok(status) if status.success() => "cleaned up".to_string(),
+                Ok(status) => format!("cleanup failed ({status})"),
+                Err(e) => format!("cannot run cleanup: {e}"),
+            };
+            writeln!(out, "{}  {line}", UtcTime::of(SystemTime::now()))?;
+        } else {
+            // `--verbose` conflicts with `--json`.
+            run.arg("--json")
+                .env_remove("CLEAN_BUILDS_VERBOSE")
+                .stdin(Stdio::null())
+                .stderr(Stdio::inherit());
+            let now = SystemTime::now();
+            match run.output() {
+                Ok(output) if output.status.success() => {
+                    match parse_json(&String::from_utf8_lossy(&output.stdout)) {
+                        Ok(artifacts) => {
+                            let totals = Totals::of(&artifacts);
+                            writeln!(out, "{}", report_line(now, totals, previous))?;
+                            previous = Some(totals);
+                        }
+                        Err(e) => writeln!(out, "{}  scan failed: {e}", UtcTime::of(now))?,
+                    }
+                }
+                Ok(output) => {
+                    writeln!(out, "{}  scan failed ({})", UtcTime::of(now), output.status)?
+                }
+                Err(e) => writeln!(out, "{}  cannot run scan: {e}", UtcTime::of(now))?,
+            }
+        }
+        out.flush()?;
+        if options.scans.is_some_and(|n| scan >= n) {
+            return Ok(());
+        }
+        std::thread::sleep(options.interval.saturating_sub(started.elapsed()));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const GB: u64 = 1024 * 1024 * 1024;
+
+    #[test]
+    fn report_line_shows_growth_since_the_last_scan() {
+        let at = SystemTime::UNIX_EPOCH + Duration::from_secs(86_400);
+        let totals = Totals {
+            artifacts: 42,
+            bytes: 30 * GB,
+        };
+        assert_eq!(
+            report_line(at, totals, None),
+            "1970-01-02T00:00:00Z  42 artifacts, 30.0 GB"
+        );
+        let smaller = Totals {
+            artifacts: 40,
+            bytes: 28 * GB,
+        };
+        assert_eq!(
+            report_line(at, totals, Some(smaller)),
+            "1970-01-02T00:00:00Z  42 artifacts, 30.0 GB (+2.0 GB since the last scan)"
+        );
+        assert!(report_line(at, smaller, Some(totals)).ends_with("(-2.0 GB since the last scan)"));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn watch_runs_the_command_for_each_scan() {
+        // Stands in for clean-builds, printing the same scan every time.
+        let command: Vec<OsString> = [
+            "sh",
+            "-c",
+            r#"echo '[{"path": "/a/target", "size_bytes": 2048}]'"#,
+            "sh",
+        ]
+        .map(OsString::from)
+        .to_vec();
+        let options = WatchOptions {
+            interval: Duration::ZERO,
+            delete: false,
+            scans: Some(2),
+        };
+        let mut out = Vec::new();
+        watch(&mut out, &command, Path::new("/a"), &options).unwrap();
+
+        let output = String::from_utf8(out).unwrap();
+        let lines: Vec<&str> = output.lines().collect();
+        assert_eq!(lines.len(), 2);
+        assert!(lines[0].ends_with("  1 artifacts, 2.0 KB"), "{output}");
+        assert!(lines[1].ends_with("  1 artifacts, 2.0 KB (+0 B since the last scan)"));
+    }
+}
@@ -5,7 +5,21 @@ use predicates::prelude::*;
 use tempfile::TempDir;
 
 fn cmd() -> Command {
-    Command::cargo_bin("clean-builds").unwrap()
+    let mut cmd = assert_cmd::cargo::cargo_bin_cmd!("clean-builds");
+    // Keep the size cache out of the real home directory.
+    cmd.env("XDG_CACHE_HOME", env!("CARGO_TARGET_TMPDIR"));
+    // And the user's config file.
+    cmd.env(
+        "XDG_CONFIG_HOME",
+        std::path::Path::new(env!("CARGO_TARGET_TMPDIR")).join("config"),
+    );
+    // And defaults set in the environment out of the tests.
+    for (key, _) in std::env::vars_os() {
+        if key.to_string_lossy().starts_with("CLEAN_BUILDS_") {
+            cmd.env_remove(key);
+        }
+    }
+    cmd
 }
 
 fn set_up_rust_project(tmp: &TempDir) {
@@ -48,6 +62,21 @@ fn dry_run_shows_summary() {
         .stdout(predicate::str::contains("Run with --delete"));
 }
 
+#[test]
+fn breakdown_shows_only_the_largest_artifacts() {
+    let tmp = TempDir::new().unwrap();
+    set_up_rust_project(&tmp);
+    set_up_python_project(&tmp);
+
+    cmd()
+        .arg(tmp.path())
+        .arg("--breakdown=1")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("100%  (no extension)"))
+        .stdout(predicate::str::contains(".pyc").not());
+}
+
 #[test]
 fn dry_run_does_not_delete() {
     let tmp = TempDir::new().unwrap();
@@ -55,389 +84,1811 @@ fn dry_run_does_not_delete() {
 
     cmd().arg(tmp.path()).assert().success();
 
-    // target/ should still exist
-    assert!(tmp.path().join("my-rust-app").join("target").exists());
+    // target/ should still exist
+    assert!(tmp.path().join("my-rust-app").join("target").exists());
+}
+
+#[test]
+fn explain_reports_rules_and_filters_for_one_path() {
+    let tmp = TempDir::new().unwrap();
+    set_up_rust_project(&tmp);
+    let target = tmp.path().join("my-rust-app/target");
+
+    cmd()
+        .arg("explain")
+        .arg(&target)
+        .arg(tmp.path())
+        .assert()
+        .success()
+        .stdout(predicate::str::contains(
+            "cargo (Rust/Cargo): matches; Cargo.toml in",
+        ))
+        .stdout(predicate::str::contains(
+            "Filters: keep (no filter applies)",
+        ))
+        .stdout(predicate::str::contains("Result:  selected"));
+    cmd()
+        .arg("explain")
+        .arg(&target)
+        .arg(tmp.path())
+        .args(["--exclude", "my-rust-app/**", "--delete", "--yes"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains(
+            "Filters: drop (excluded by --exclude 'my-rust-app/**')",
+        ))
+        .stdout(predicate::str::contains("Result:  not selected"));
+    cmd()
+        .arg("explain")
+        .arg(target.join("debug"))
+        .arg(tmp.path())
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("Scan:    never reaches it"));
+
+    fs::remove_file(tmp.path().join("my-rust-app/Cargo.toml")).unwrap();
+    cmd()
+        .arg("explain")
+        .arg(&target)
+        .arg(tmp.path())
+        .assert()
+        .success()
+        .stdout(predicate::str::contains(
+            "cargo (Rust/Cargo): no; no Cargo.toml in",
+        ));
+
+    assert!(target.exists());
+}
+
+#[test]
+fn git_root_scans_the_enclosing_repository() {
+    let tmp = TempDir::new().unwrap();
+    set_up_rust_project(&tmp);
+    set_up_python_project(&tmp);
+    let inside = tmp.path().join("my-rust-app/src/deep");
+    fs::create_dir_all(&inside).unwrap();
+
+    cmd()
+        .arg(&inside)
+        .arg("--git-root")
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("is not inside a Git repository"));
+
+    fs::create_dir(tmp.path().join(".git")).unwrap();
+    cmd()
+        .current_dir(&inside)
+        .arg("--git-root")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("Rust/Cargo"))
+        .stdout(predicate::str::contains("Python"));
+}
+
+#[test]
+fn dry_run_flag_refuses_to_delete() {
+    let tmp = TempDir::new().unwrap();
+    set_up_rust_project(&tmp);
+
+    cmd()
+        .arg(tmp.path())
+        .arg("-n")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("Run with --delete"));
+    cmd()
+        .arg(tmp.path())
+        .args(["--delete", "--yes", "--dry-run"])
+        .assert()
+        .code(2);
+    cmd()
+        .arg(tmp.path())
+        .args(["--delete", "--yes"])
+        .env("CLEAN_BUILDS_DRY_RUN", "true")
+        .assert()
+        .code(2)
+        .stderr(predicate::str::contains("cannot be used with '--dry-run'"));
+
+    assert!(tmp.path().join("my-rust-app/target").exists());
+}
+
+#[test]
+fn delete_with_yes_removes_artifacts() {
+    let tmp = TempDir::new().unwrap();
+    set_up_rust_project(&tmp);
+
+    cmd()
+        .arg(tmp.path())
+        .arg("--delete")
+        .arg("--yes")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("Deleted 1 of 1"));
+
+    assert!(!tmp.path().join("my-rust-app").join("target").exists());
+    // Cargo.toml should still exist
+    assert!(tmp.path().join("my-rust-app").join("Cargo.toml").exists());
+}
+
+#[test]
+fn confirm_per_system_deletes_only_confirmed_systems() {
+    let tmp = TempDir::new().unwrap();
+    set_up_node_project(&tmp);
+    set_up_rust_project(&tmp);
+
+    cmd()
+        .arg(tmp.path())
+        .args(["--delete", "--confirm-per-system"])
+        .write_stdin("n\ny\n")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("Delete 1 Node.js artifacts"))
+        .stdout(predicate::str::contains("Delete 1 Rust/Cargo artifacts"))
+        .stdout(predicate::str::contains("Deleted 1 of 1"));
+
+    assert!(tmp.path().join("my-node-app/node_modules").exists());
+    assert!(!tmp.path().join("my-rust-app/target").exists());
+}
+
+#[test]
+fn yes_for_deletes_low_risk_artifacts_without_asking() {
+    let tmp = TempDir::new().unwrap();
+    set_up_node_project(&tmp);
+    set_up_rust_project(&tmp);
+
+    cmd()
+        .arg(tmp.path())
+        .args(["--delete", "--yes-for", "cache,output"])
+        .write_stdin("n\n")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("1 output artifacts"))
+        .stdout(predicate::str::contains("Delete 1 deps artifacts"))
+        .stdout(predicate::str::contains("Deleted 1 of 1"));
+
+    assert!(tmp.path().join("my-node-app/node_modules").exists());
+    assert!(!tmp.path().join("my-rust-app/target").exists());
+}
+
+#[test]
+fn no_input_exits_instead_of_prompting() {
+    let tmp = TempDir::new().unwrap();
+    set_up_rust_project(&tmp);
+
+    cmd()
+        .arg(tmp.path())
+        .args(["--delete", "--no-input"])
+        .write_stdin("y\n")
+        .assert()
+        .code(6)
+        .stderr(predicate::str::contains("--no-input forbids asking"));
+    assert!(tmp.path().join("my-rust-app/target").exists());
+
+    cmd()
+        .arg(tmp.path())
+        .args(["--delete", "--yes"])
+        .env("CLEAN_BUILDS_NO_INPUT", "true")
+        .assert()
+        .success();
+    assert!(!tmp.path().join("my-rust-app/target").exists());
+}
+
+#[test]
+fn background_deletes_after_returning() {
+    let tmp = TempDir::new().unwrap();
+    set_up_rust_project(&tmp);
+    let project = tmp.path().join("my-rust-app");
+
+    cmd()
+        .arg(tmp.path())
+        .args(["--delete", "--yes", "--background"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("Moved aside 1 of 1"));
+
+    assert!(!project.join("target").exists());
+    for _ in 0..100 {
+        let left: Vec<_> = fs::read_dir(&project).unwrap().collect();
+        if left.len() == 1 {
+            return; // Only Cargo.toml
+        }
+        std::thread::sleep(std::time::Duration::from_millis(50));
+    }
+    panic!("background worker did not finish");
+}
+
+#[test]
+fn declining_the_prompt_exits_with_nothing_deleted() {
+    let tmp = TempDir::new().unwrap();
+    set_up_rust_project(&tmp);
+
+    cmd()
+        .arg(tmp.path())
+        .arg("--delete")
+        .write_stdin("n\n")
+        .assert()
+        .code(4)
+        .stdout(predicate::str::contains("Aborted."));
+
+    assert!(tmp.path().join("my-rust-app/target").exists());
+}
+
+#[test]
+fn delete_from_json_removes_exported_artifacts_that_still_match() {
+    let tmp = TempDir::new().unwrap();
+    set_up_rust_project(&tmp);
+    set_up_node_project(&tmp);
+
+    let output = cmd().arg(tmp.path()).arg("--json").output().unwrap();
+    assert!(output.status.success());
+    let scan = String::from_utf8(output.stdout).unwrap();
+    assert!(scan.contains("\"artifacts\""), "{scan}");
+    // The Node project stops being one after the export.
+    fs::remove_file(tmp.path().join("my-node-app/package.json")).unwrap();
+
+    cmd()
+        .args(["delete", "--from-json", "-", "--yes"])
+        .write_stdin(scan)
+        .assert()
+        .code(3)
+        .stderr(predicate::str::contains("no longer matches a known rule"));
+
+    assert!(!tmp.path().join("my-rust-app/target").exists());
+    assert!(tmp.path().join("my-node-app/node_modules").exists());
+}
+
+#[test]
+fn single_pass_reports_the_same_sizes() {
+    let tmp = TempDir::new().unwrap();
+    set_up_rust_project(&tmp);
+    set_up_python_project(&tmp);
+
+    let two_pass = cmd().arg(tmp.path()).output().unwrap();
+    assert!(two_pass.status.success());
+    let single_pass = cmd().arg(tmp.path()).arg("--single-pass").output().unwrap();
+    assert!(single_pass.status.success());
+
+    assert_eq!(single_pass.stdout, two_pass.stdout);
+    assert!(String::from_utf8_lossy(&single_pass.stderr).contains("Scanning and sizing"));
+}
+
+#[test]
+fn delete_from_json_on_stdin_requires_yes() {
+    cmd()
+        .args(["delete", "--from-json", "-"])
+        .write_stdin("[]")
+        .assert()
+        .code(1)
+        .stderr(predicate::str::contains("--yes"));
+}
+
+#[test]
+fn large_deletion_is_not_confirmed_by_yes() {
+    let tmp = TempDir::new().unwrap();
+    set_up_rust_project(&tmp);
+
+    cmd()
+        .arg(tmp.path())
+        .args(["--delete", "--strict-confirm-size", "0"])
+        .write_stdin("y\n")
+        .assert()
+        .code(4)
+        .stdout(predicate::str::contains("Type 1 to confirm"));
+    assert!(tmp.path().join("my-rust-app/target").exists());
+
+    cmd()
+        .arg(tmp.path())
+        .args(["--delete", "--strict-confirm-size", "0"])
+        .write_stdin("1\n")
+        .assert()
+        .success();
+    assert!(!tmp.path().join("my-rust-app/target").exists());
+}
+
+#[cfg(unix)]
+#[test]
+fn delete_skips_projects_in_use() {
+    let tmp = TempDir::new().unwrap();
+    set_up_rust_project(&tmp);
+    let project = tmp.path().join("my-rust-app");
+    let mut child = std::process::Command::new("sleep")
+        .arg("10")
+        .current_dir(&project)
+        .spawn()
+        .unwrap();
+
+    let assert = cmd().arg(tmp.path()).args(["--delete", "--yes"]).assert();
+    let target_survived = project.join("target").exists();
+    cmd()
+        .arg(tmp.path())
+        .args(["--delete", "--yes", "--force-in-use"])
+        .assert()
+        .success();
+    child.kill().unwrap();
+    child.wait().unwrap();
+
+    assert
+        .code(4)
+        .stderr(predicate::str::contains("in use by sleep"))
+        .stdout(predicate::str::contains(
+            "Skipped 1 artifact directories in use",
+        ));
+    assert!(target_survived);
+    assert!(!project.join("target").exists());
+}
+
+#[test]
+fn check_reports_deletable_artifacts() {
+    let tmp = TempDir::new().unwrap();
+    set_up_rust_project(&tmp);
+
+    cmd()
+        .arg(tmp.path())
+        .arg("--check")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("Every artifact looks deletable."));
+}
+
+#[test]
+fn exit_code_reports_artifacts_found_in_dry_run() {
+    let tmp = TempDir::new().unwrap();
+    cmd().arg(tmp.path()).arg("--exit-code").assert().success();
+
+    set_up_rust_project(&tmp);
+    cmd().arg(tmp.path()).arg("--exit-code").assert().code(5);
+}
+
+// The freedesktop trash honours XDG_DATA_HOME, which keeps these tests out
+// of the real trash.
+#[cfg(target_os = "linux")]
+#[test]
+fn trash_moves_artifacts_to_trash() {
+    let tmp = TempDir::new().unwrap();
+    set_up_rust_project(&tmp);
+    let data = tmp.path().join("data");
+
+    cmd()
+        .arg(tmp.path())
+        .args(["--delete", "--yes", "--trash"])
+        .env("XDG_DATA_HOME", &data)
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("Trashed 1 of 1"));
+
+    assert!(!tmp.path().join("my-rust-app/target").exists());
+    assert!(data.join("Trash/files/target/debug/app").exists());
+}
+
+#[cfg(target_os = "linux")]
+#[test]
+fn trash_failure_offers_permanent_delete() {
+    let tmp = TempDir::new().unwrap();
+    set_up_rust_project(&tmp);
+    // A trash location under a regular file cannot be created.
+    let blocker = tmp.path().join("not-a-dir");
+    fs::write(&blocker, "").unwrap();
+
+    cmd()
+        .arg(tmp.path())
+        .args(["--delete", "--trash"])
+        .env("XDG_DATA_HOME", blocker.join("data"))
+        .write_stdin("y\ny\n")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("failed to move"))
+        .stdout(predicate::str::contains("Permanently delete"))
+        .stdout(predicate::str::contains("Trashed 0 and deleted 1 of 1"));
+
+    assert!(!tmp.path().join("my-rust-app/target").exists());
+}
+
+#[test]
+fn delete_refuses_while_another_run_holds_the_lock() {
+    let tmp = TempDir::new().unwrap();
+    set_up_rust_project(&tmp);
+    let cache = tmp.path().join("cache");
+    let root = tmp.path().canonicalize().unwrap();
+    let _held =
+        clean_builds::lock::RunLock::acquire_in(&cache.join("clean-builds/locks"), &root).unwrap();
+
+    cmd()
+        .arg(tmp.path())
+        .args(["--delete", "--yes"])
+        .env("XDG_CACHE_HOME", &cache)
+        .assert()
+        .code(1)
+        .stderr(predicate::str::contains("already deleting under"));
+    assert!(tmp.path().join("my-rust-app/target").exists());
+
+    // Dry runs do not lock.
+    cmd()
+        .arg(tmp.path())
+        .env("XDG_CACHE_HOME", &cache)
+        .assert()
+        .success();
+
+    cmd()
+        .arg(tmp.path())
+        .args(["--delete", "--yes", "--no-lock"])
+        .env("XDG_CACHE_HOME", &cache)
+        .assert()
+        .success();
+    assert!(!tmp.path().join("my-rust-app/target").exists());
+}
+
+#[test]
+fn quarantine_then_restore() {
+    let tmp = TempDir::new().unwrap();
+    set_up_rust_project(&tmp);
+    let data = tmp.path().join("data");
+    let target = tmp.path().join("my-rust-app/target");
+
+    cmd()
+        .arg(tmp.path())
+        .args(["--delete", "--yes", "--quarantine"])
+        .env("XDG_DATA_HOME", &data)
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("Quarantined 1 of 1"))
+        .stdout(predicate::str::contains("clean-builds restore"));
+    assert!(!target.exists());
+    assert!(data.join("clean-builds/quarantine").is_dir());
+
+    cmd()
+        .arg("restore")
+        .env("XDG_DATA_HOME", &data)
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("Restored 1 of 1"));
+    assert!(target.join("debug/app").exists());
+
+    cmd()
+        .arg("restore")
+        .env("XDG_DATA_HOME", &data)
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("Quarantine is empty."));
+}
+
+#[test]
+fn watch_rescans_and_cleans_up() {
+    let tmp = TempDir::new().unwrap();
+    set_up_rust_project(&tmp);
+
+    cmd()
+        .args(["watch".as_ref(), tmp.path().as_os_str()])
+        .args(["--interval", "0s", "--scans", "2"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("1 artifacts"))
+        .stdout(predicate::str::contains("since the last scan"));
+    assert!(tmp.path().join("my-rust-app/target").exists());
+
+    cmd()
+        .args(["watch".as_ref(), tmp.path().as_os_str()])
+        .args(["--interval", "0s", "--scans", "1", "--delete"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("cleaned up"));
+    assert!(!tmp.path().join("my-rust-app/target").exists());
+}
+
+#[test]
+fn tui_needs_a_terminal() {
+    let tmp = TempDir::new().unwrap();
+    set_up_rust_project(&tmp);
+
+    cmd()
+        .args(["tui".as_ref(), tmp.path().as_os_str()])
+        .assert()
+        .code(1)
+        .stderr(predicate::str::contains("tui needs a terminal"));
+    assert!(tmp.path().join("my-rust-app/target").exists());
+}
+
+#[test]
+fn doctor_reports_settings_and_failed_checks() {
+    let tmp = TempDir::new().unwrap();
+    let data = tmp.path().join("data");
+
+    cmd()
+        .args(["doctor".as_ref(), tmp.path().as_os_str()])
+        .env("XDG_DATA_HOME", &data)
+        .env("CLEAN_BUILDS_EXCLUDE", "vendor")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains(
+            "--exclude vendor (from CLEAN_BUILDS_EXCLUDE)",
+        ))
+        .stdout(predicate::str::contains("ok    built-in rules"))
+        .stdout(predicate::str::contains(format!(
+            "ok    quarantine: {}",
+            data.join("clean-builds/quarantine").display()
+        )));
+    // Checking writes nothing.
+    assert!(!data.exists());
+
+    cmd()
+        .args(["doctor".as_ref(), tmp.path().as_os_str()])
+        .env("CLEAN_BUILDS_EXCLUDE_FROM", tmp.path().join("missing.txt"))
+        .assert()
+        .code(1)
+        .stdout(predicate::str::contains("FAIL"))
+        .stdout(predicate::str::contains("cannot read pattern file"));
+}
+
+#[cfg(target_os = "linux")]
+#[test]
+fn schedule_prints_systemd_units_without_installing() {
+    let tmp = TempDir::new().unwrap();
+    let config = tmp.path().join("config");
+    let root = tmp.path().canonicalize().unwrap();
+
+    cmd()
+        .args([
+            "schedule".as_ref(),
+            "install".as_ref(),
+            tmp.path().as_os_str(),
+        ])
+        .args(["--daily", "--profile", "aggressive", "--print"])
+        .env("XDG_CONFIG_HOME", &config)
+        .assert()
+        .success()
+        .stdout(predicate::str::contains(format!(
+            "# {}",
+            config.join("systemd/user/clean-builds.timer").display()
+        )))
+        .stdout(predicate::str::contains("OnCalendar=daily"))
+        .stdout(predicate::str::contains(format!(
+            "\"{}\" \"--delete\" \"--yes\" \"--no-input\"",
+            root.display()
+        )));
+    assert!(!config.exists());
+
+    cmd()
+        .args(["schedule", "status"])
+        .env("XDG_CONFIG_HOME", &config)
+        .assert()
+        .success()
+        .stdout("No schedule is installed.\n");
+}
+
+#[test]
+fn init_writes_a_config_file_runs_default_to() {
+    let tmp = TempDir::new().unwrap();
+    set_up_rust_project(&tmp);
+    set_up_python_project(&tmp);
+    let elsewhere = TempDir::new().unwrap();
+    let config = elsewhere.path().join("config");
+
+    cmd()
+        .arg("init")
+        .arg(tmp.path())
+        .args(["--exclude", "**/__pycache__"])
+        .env("XDG_CONFIG_HOME", &config)
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("config.toml"));
+    let file = config.join("clean-builds/config.toml");
+    let text = fs::read_to_string(&file).unwrap();
+    assert!(text.contains("exclude = [\"**/__pycache__\"]"), "{text}");
+
+    // Without a path, a run scans the configured root and leaves out what
+    // the file excludes.
+    cmd()
+        .current_dir(elsewhere.path())
+        .env("XDG_CONFIG_HOME", &config)
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("Rust/Cargo"))
+        .stdout(predicate::str::contains("Python").not());
+    cmd()
+        .args(["doctor", "."])
+        .env("XDG_CONFIG_HOME", &config)
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("(from the config file)"));
+
+    cmd()
+        .arg("init")
+        .env("XDG_CONFIG_HOME", &config)
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("pass --force"));
+
+    fs::write(&file, "delete = true\n").unwrap();
+    cmd()
+        .env("XDG_CONFIG_HOME", &config)
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("`delete` is not an option"));
+}
+
+#[test]
+fn rules_lint_reports_problems_and_sample_matches() {
+    let tmp = TempDir::new().unwrap();
+    set_up_rust_project(&tmp);
+    std::fs::create_dir_all(tmp.path().join("my-rust-app/frob-out")).unwrap();
+    std::fs::write(tmp.path().join("my-rust-app/Frobfile"), "").unwrap();
+    let rules = tmp.path().join("rules.toml");
+    std::fs::write(
+        &rules,
+        r#"[[rule]]
+id = "frob"
+system = "Frob"
+dir = "frob-out"
+marker = { files = ["Frobfile"] }
+risk = "output"
+
+[[rule]]
+id = "my-cargo"
+system = "My Cargo"
+dir = "target"
+marker = { files = ["Cargo.toml"] }
+risk = "output"
+"#,
+    )
+    .unwrap();
+
+    cmd()
+        .args(["rules".as_ref(), "lint".as_ref(), rules.as_os_str()])
+        .arg("--sample")
+        .arg(tmp.path())
+        .assert()
+        .success()
+        .stdout(predicate::str::contains(format!(
+            "{}:8: warning: rule 2 (my-cargo): never matches, as built-in rule `cargo`",
+            rules.display()
+        )))
+        .stdout(predicate::str::contains("my-rust-app/frob-out  (Frob)"));
+
+    cmd()
+        .args(["rules", "lint", "-"])
+        .write_stdin("[[rule]]\nid = \"x\"\nsystem = \"X\"\ndir = \"out\"\nrisk = \"output\"\n")
+        .assert()
+        .code(1)
+        .stdout(predicate::str::contains("<stdin>:1: error: "))
+        .stdout(predicate::str::contains("marker"));
+}
+
+#[test]
+fn version_json_lists_capabilities() {
+    let output = cmd().args(["version", "--json"]).output().unwrap();
+    assert!(output.status.success());
+    let json: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    assert_eq!(json["version"], env!("CARGO_PKG_VERSION"));
+    assert!(json["rules"]["revision"].is_string());
+    assert!(
+        json["options"]
+            .as_array()
+            .unwrap()
+            .contains(&"delete".into())
+    );
+
+    cmd()
+        .arg("version")
+        .assert()
+        .success()
+        .stdout(predicate::str::starts_with(concat!(
+            "clean-builds ",
+            env!("CARGO_PKG_VERSION")
+        )));
+}
+
+#[test]
+fn purge_empties_quarantine() {
+    let tmp = TempDir::new().unwrap();
+    set_up_rust_project(&tmp);
+    let data = tmp.path().join("data");
+
+    cmd()
+        .arg(tmp.path())
+        .args(["--delete", "--yes", "--quarantine"])
+        .env("XDG_DATA_HOME", &data)
+        .assert()
+        .success();
+
+    cmd()
+        .args(["purge", "no-such-run"])
+        .env("XDG_DATA_HOME", &data)
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains(
+            "no quarantined run named 'no-such-run'",
+        ));
+
+    cmd()
+        .arg("purge")
+        .env("XDG_DATA_HOME", &data)
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("Purged 1 quarantined run"));
+    assert!(!tmp.path().join("my-rust-app/target").exists());
+}
+
+// Whether `cargo clean` succeeds on this stub manifest or cargo is missing
+// altogether, the target must end up removed.
+#[test]
+fn use_tool_removes_artifacts() {
+    let tmp = TempDir::new().unwrap();
+    set_up_rust_project(&tmp);
+    set_up_node_project(&tmp);
+
+    cmd()
+        .arg(tmp.path())
+        .args(["--delete", "--yes", "--use-tool"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("Deleted 2 of 2"));
+
+    assert!(!tmp.path().join("my-rust-app/target").exists());
+    assert!(!tmp.path().join("my-node-app/node_modules").exists());
+}
+
+#[test]
+fn log_file_records_deletions() {
+    let tmp = TempDir::new().unwrap();
+    set_up_rust_project(&tmp);
+    let data = tmp.path().join("data");
+
+    cmd()
+        .arg("--log-file")
+        .arg(tmp.path())
+        .args(["--delete", "--yes"])
+        .env("XDG_DATA_HOME", &data)
+        .assert()
+        .success();
+
+    let log = fs::read_to_string(data.join("clean-builds/deletions.toml")).unwrap();
+    assert!(log.contains("[[deletion]]"));
+    assert!(log.contains("my-rust-app/target"));
+    assert!(log.contains("build_system = \"Rust/Cargo\""));
+    assert!(log.contains("outcome = \"deleted\""));
+}
+
+#[cfg(unix)]
+#[test]
+fn hooks_run_around_deletion() {
+    let tmp = TempDir::new().unwrap();
+    set_up_rust_project(&tmp);
+    let log = tmp.path().join("hooks.log");
+
+    cmd()
+        .arg(tmp.path())
+        .args(["--delete", "--yes"])
+        .arg("--after-delete")
+        .arg(format!(
+            "echo \"$CLEAN_BUILDS_HOOK_SYSTEM $CLEAN_BUILDS_HOOK_OUTCOME\" >> {}",
+            log.display()
+        ))
+        .arg("--after-run")
+        .arg(format!(
+            "echo \"removed $CLEAN_BUILDS_HOOK_REMOVED of $CLEAN_BUILDS_HOOK_COUNT\" >> {}",
+            log.display()
+        ))
+        .assert()
+        .success();
+
+    assert_eq!(
+        fs::read_to_string(&log).unwrap(),
+        "Rust/Cargo deleted\nremoved 1 of 1\n"
+    );
+}
+
+#[test]
+fn log_file_is_not_written_for_dry_runs() {
+    let tmp = TempDir::new().unwrap();
+    set_up_rust_project(&tmp);
+    let log = tmp.path().join("deletions.toml");
+
+    cmd()
+        .arg(tmp.path())
+        .arg(format!("--log-file={}", log.display()))
+        .assert()
+        .failure();
+    cmd()
+        .arg(tmp.path())
+        .arg("--delete")
+        .arg(format!("--log-file={}", log.display()))
+        .write_stdin("n\n")
+        .assert()
+        .code(4);
+    assert!(!log.exists());
+}
+
+#[test]
+fn verbose_shows_paths() {
+    let tmp = TempDir::new().unwrap();
+    set_up_rust_project(&tmp);
+
+    cmd()
+        .arg(tmp.path())
+        .arg("--verbose")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("target"));
+}
+
+#[test]
+fn multiple_build_systems() {
+    let tmp = TempDir::new().unwrap();
+    set_up_rust_project(&tmp);
+    set_up_node_project(&tmp);
+
+    cmd()
+        .arg(tmp.path())
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("Rust/Cargo"))
+        .stdout(predicate::str::contains("Node.js"))
+        .stdout(predicate::str::contains("Total"));
+}
+
+#[test]
+fn exclude_by_glob_pattern() {
+    let tmp = TempDir::new().unwrap();
+    set_up_rust_project(&tmp);
+
+    cmd()
+        .arg(tmp.path())
+        .arg("--exclude")
+        .arg("my-rust*")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("No build artifacts found."));
+}
+
+#[test]
+fn environment_variables_act_as_defaults() {
+    let tmp = TempDir::new().unwrap();
+    set_up_rust_project(&tmp);
+    set_up_node_project(&tmp);
+
+    cmd()
+        .arg(tmp.path())
+        .env("CLEAN_BUILDS_EXCLUDE", "my-rust*")
+        .env("CLEAN_BUILDS_JSON", "true")
+        .env("CLEAN_BUILDS_JOBS", "2")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("\"build_system\": \"Node.js\""))
+        .stdout(predicate::str::contains("Rust/Cargo").not());
+
+    // The command line wins over the environment.
+    cmd()
+        .arg(tmp.path())
+        .arg("--exclude")
+        .arg("my-node*")
+        .env("CLEAN_BUILDS_EXCLUDE", "my-rust*")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("Rust/Cargo"))
+        .stdout(predicate::str::contains("Node.js").not());
+}
+
+#[test]
+fn include_only_matching_artifacts() {
+    let tmp = TempDir::new().unwrap();
+    set_up_rust_project(&tmp);
+    set_up_node_project(&tmp);
+
+    cmd()
+        .arg(tmp.path())
+        .arg("--include")
+        .arg("node_modules")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("Node.js"))
+        .stdout(predicate::str::contains("Rust/Cargo").not());
+}
+
+#[test]
+fn include_and_exclude_combined() {
+    let tmp = TempDir::new().unwrap();
+    set_up_rust_project(&tmp);
+    set_up_node_project(&tmp);
+
+    // Include both, but exclude the node one by project dir prefix
+    cmd()
+        .arg(tmp.path())
+        .arg("--include")
+        .arg("target")
+        .arg("--include")
+        .arg("node_modules")
+        .arg("--exclude")
+        .arg("my-node*")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("Rust/Cargo"))
+        .stdout(predicate::str::contains("Node.js").not());
+}
+
+#[test]
+fn negated_include_pattern_carves_out_a_project() {
+    let tmp = TempDir::new().unwrap();
+    set_up_rust_project(&tmp);
+    let critical = tmp.path().join("critical-service");
+    fs::create_dir_all(critical.join("target")).unwrap();
+    fs::write(critical.join("Cargo.toml"), "").unwrap();
+
+    cmd()
+        .arg(tmp.path())
+        .args(["--include", "target", "--include", "!critical-service/**"])
+        .arg("--verbose")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("my-rust-app"))
+        .stdout(predicate::str::contains("critical-service").not());
+}
+
+#[test]
+fn multiple_include_patterns() {
+    let tmp = TempDir::new().unwrap();
+    set_up_rust_project(&tmp);
+    set_up_node_project(&tmp);
+
+    cmd()
+        .arg(tmp.path())
+        .arg("--include")
+        .arg("node_modules")
+        .arg("--include")
+        .arg("target")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("Node.js"))
+        .stdout(predicate::str::contains("Rust/Cargo"));
+}
+
+#[test]
+fn exclude_by_directory_name_prefix() {
+    let tmp = TempDir::new().unwrap();
+    set_up_rust_project(&tmp);
+    set_up_node_project(&tmp);
+
+    // Exclude projects starting with "my-"
+    cmd()
+        .arg(tmp.path())
+        .arg("--exclude")
+        .arg("my-*")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("No build artifacts found."));
+}
+
+#[test]
+fn invalid_pattern_exits_with_error() {
+    let tmp = TempDir::new().unwrap();
+
+    cmd()
+        .arg(tmp.path())
+        .arg("--exclude")
+        .arg("[invalid")
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("Error"));
+}
+
+#[test]
+fn no_artifacts_found() {
+    let tmp = TempDir::new().unwrap();
+    fs::create_dir_all(tmp.path().join("empty-project")).unwrap();
+
+    cmd()
+        .arg(tmp.path())
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("No build artifacts found."));
+}
+
+#[test]
+fn nonexistent_path() {
+    cmd()
+        .arg("/nonexistent/path/that/does/not/exist")
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("Error"));
+}
+
+#[test]
+fn delete_multiple_systems_with_yes() {
+    let tmp = TempDir::new().unwrap();
+    set_up_rust_project(&tmp);
+    set_up_node_project(&tmp);
+
+    cmd()
+        .arg(tmp.path())
+        .arg("--delete")
+        .arg("--yes")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("Deleted 2 of 2"));
+
+    assert!(!tmp.path().join("my-rust-app").join("target").exists());
+    assert!(!tmp.path().join("my-node-app").join("node_modules").exists());
+}
+
+#[test]
+fn help_flag() {
+    cmd()
+        .arg("--help")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("Recursively scan"))
+        .stdout(predicate::str::contains("--delete"));
+}
+
+#[test]
+fn pycache_detected_without_marker() {
+    let tmp = TempDir::new().unwrap();
+    let pycache = tmp.path().join("src").join("__pycache__");
+    fs::create_dir_all(&pycache).unwrap();
+    fs::write(pycache.join("module.cpython-312.pyc"), "bytecode").unwrap();
+
+    cmd()
+        .arg(tmp.path())
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("Python"));
+}
+
+#[test]
+fn verbose_shows_debug_log_on_stderr() {
+    let tmp = TempDir::new().unwrap();
+    set_up_rust_project(&tmp);
+
+    cmd()
+        .arg(tmp.path())
+        .arg("--verbose")
+        .assert()
+        .success()
+        .stderr(predicate::str::contains("Found artifact"));
+}
+
+#[test]
+fn default_shows_info_log_on_stderr() {
+    let tmp = TempDir::new().unwrap();
+    set_up_rust_project(&tmp);
+
+    cmd()
+        .arg(tmp.path())
+        .assert()
+        .success()
+        .stderr(predicate::str::contains("Scanning"));
+}
+
+#[test]
+fn default_does_not_show_debug_log() {
+    let tmp = TempDir::new().unwrap();
+    set_up_rust_project(&tmp);
+
+    cmd()
+        .arg(tmp.path())
+        .assert()
+        .success()
+        .stderr(predicate::str::contains("Found artifact").not());
+}
+
+// -- System filter integration tests --
+
+#[test]
+fn system_filters_to_one_system() {
+    let tmp = TempDir::new().unwrap();
+    set_up_rust_project(&tmp);
+    set_up_node_project(&tmp);
+
+    cmd()
+        .arg(tmp.path())
+        .arg("--system")
+        .arg("cargo")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("Rust/Cargo"))
+        .stdout(predicate::str::contains("Node.js").not());
+}
+
+#[test]
+fn system_multiple() {
+    let tmp = TempDir::new().unwrap();
+    set_up_rust_project(&tmp);
+    set_up_node_project(&tmp);
+    set_up_python_project(&tmp);
+
+    cmd()
+        .arg(tmp.path())
+        .arg("--system")
+        .arg("cargo")
+        .arg("--system")
+        .arg("node")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("Rust/Cargo"))
+        .stdout(predicate::str::contains("Node.js"))
+        .stdout(predicate::str::contains("Python").not());
+}
+
+#[test]
+fn exclude_system() {
+    let tmp = TempDir::new().unwrap();
+    set_up_rust_project(&tmp);
+    set_up_node_project(&tmp);
+
+    cmd()
+        .arg(tmp.path())
+        .arg("--exclude-system")
+        .arg("node")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("Rust/Cargo"))
+        .stdout(predicate::str::contains("Node.js").not());
+}
+
+#[test]
+fn invalid_system_errors() {
+    let tmp = TempDir::new().unwrap();
+
+    cmd()
+        .arg(tmp.path())
+        .arg("--system")
+        .arg("nonexistent")
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("unknown build system"));
+}
+
+#[test]
+fn system_and_exclude_system_conflict() {
+    cmd()
+        .arg("--system")
+        .arg("cargo")
+        .arg("--exclude-system")
+        .arg("node")
+        .assert()
+        .failure();
+}
+
+#[test]
+fn list_systems_shows_table() {
+    cmd()
+        .arg("--list-systems")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("cargo"))
+        .stdout(predicate::str::contains("node"))
+        .stdout(predicate::str::contains("python"))
+        .stdout(predicate::str::contains("Rust/Cargo"))
+        .stdout(predicate::str::contains("ID"));
+}
+
+#[test]
+fn list_systems_works_without_path() {
+    // --list-systems should work even without a valid path argument
+    cmd()
+        .arg("--list-systems")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("cargo"));
+}
+
+#[test]
+fn system_combined_with_glob() {
+    let tmp = TempDir::new().unwrap();
+    set_up_node_project(&tmp);
+
+    cmd()
+        .arg(tmp.path())
+        .arg("--system")
+        .arg("node")
+        .arg("--exclude")
+        .arg("my-node*")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("No build artifacts found."));
+}
+
+#[test]
+fn delete_with_system_filter() {
+    let tmp = TempDir::new().unwrap();
+    set_up_rust_project(&tmp);
+    set_up_node_project(&tmp);
+
+    cmd()
+        .arg(tmp.path())
+        .arg("--system")
+        .arg("node")
+        .arg("--delete")
+        .arg("--yes")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("Deleted 1 of 1"));
+
+    // node_modules should be deleted
+    assert!(!tmp.path().join("my-node-app").join("node_modules").exists());
+    // Rust target should still exist
+    assert!(tmp.path().join("my-rust-app").join("target").exists());
+}
+
+// -- Risk filter integration tests --
+
+#[test]
+fn risk_cache_skips_deps_and_output() {
+    let tmp = TempDir::new().unwrap();
+    set_up_rust_project(&tmp);
+    set_up_node_project(&tmp);
+    set_up_python_project(&tmp);
+
+    cmd()
+        .arg(tmp.path())
+        .arg("--risk")
+        .arg("cache")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("Python"))
+        .stdout(predicate::str::contains("Rust/Cargo").not())
+        .stdout(predicate::str::contains("Node.js").not());
+}
+
+#[test]
+fn risk_multiple_categories() {
+    let tmp = TempDir::new().unwrap();
+    set_up_rust_project(&tmp);
+    set_up_node_project(&tmp);
+
+    cmd()
+        .arg(tmp.path())
+        .arg("--risk")
+        .arg("cache,output")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("Rust/Cargo"))
+        .stdout(predicate::str::contains("Node.js").not());
+}
+
+#[test]
+fn verbose_shows_risk() {
+    let tmp = TempDir::new().unwrap();
+    set_up_node_project(&tmp);
+
+    cmd()
+        .arg(tmp.path())
+        .arg("--verbose")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains(", deps)"));
+}
+
+// -- Age filter integration tests --
+
+/// Backdate the modification time of `path` by `days`.
+fn backdate(path: &std::path::Path, days: u64) {
+    let mtime = std::time::SystemTime::now() - std::time::Duration::from_secs(days * 86_400);
+    fs::File::open(path).unwrap().set_modified(mtime).unwrap();
+}
+
+#[test]
+fn newer_than_protects_recent_artifacts() {
+    let tmp = TempDir::new().unwrap();
+    set_up_rust_project(&tmp);
+    set_up_node_project(&tmp);
+    backdate(&tmp.path().join("my-rust-app/target"), 60);
+
+    cmd()
+        .arg(tmp.path())
+        .arg("--newer-than")
+        .arg("7d")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("Rust/Cargo"))
+        .stdout(predicate::str::contains("Node.js").not());
 }
 
 #[test]
-fn delete_with_yes_removes_artifacts() {
+fn older_than_and_keep_recent_compose() {
     let tmp = TempDir::new().unwrap();
     set_up_rust_project(&tmp);
+    set_up_node_project(&tmp);
+    set_up_python_project(&tmp);
+    backdate(&tmp.path().join("my-rust-app/target"), 60);
+    backdate(&tmp.path().join("my-node-app/node_modules"), 20);
 
     cmd()
         .arg(tmp.path())
-        .arg("--delete")
-        .arg("--yes")
+        .args(["--older-than", "30d", "--keep-recent", "7d"])
         .assert()
         .success()
-        .stdout(predicate::str::contains("Deleted 1 of 1"));
-
-    assert!(!tmp.path().join("my-rust-app").join("target").exists());
-    // Cargo.toml should still exist
-    assert!(tmp.path().join("my-rust-app").join("Cargo.toml").exists());
+        .stdout(predicate::str::contains("Rust/Cargo"))
+        .stdout(predicate::str::contains("Node.js").not())
+        .stdout(predicate::str::contains("Python").not());
 }
 
 #[test]
-fn verbose_shows_paths() {
+fn free_deletes_only_the_oldest_artifacts_needed() {
     let tmp = TempDir::new().unwrap();
     set_up_rust_project(&tmp);
+    set_up_node_project(&tmp);
+    backdate(&tmp.path().join("my-rust-app/target"), 60);
+    backdate(&tmp.path().join("my-node-app/node_modules"), 20);
 
     cmd()
         .arg(tmp.path())
-        .arg("--verbose")
+        .args(["--free", "1B", "--delete", "--yes"])
         .assert()
         .success()
-        .stdout(predicate::str::contains("target"));
+        .stdout(predicate::str::contains("Deleted 1 of 1"));
+
+    assert!(!tmp.path().join("my-rust-app/target").exists());
+    assert!(tmp.path().join("my-node-app/node_modules").exists());
 }
 
 #[test]
-fn multiple_build_systems() {
+fn not_accessed_in_skips_recently_read_artifacts() {
     let tmp = TempDir::new().unwrap();
     set_up_rust_project(&tmp);
     set_up_node_project(&tmp);
+    let atime = std::time::SystemTime::now() - std::time::Duration::from_secs(120 * 86_400);
+    fs::File::options()
+        .write(true)
+        .open(tmp.path().join("my-rust-app/target/debug/app"))
+        .unwrap()
+        .set_times(fs::FileTimes::new().set_accessed(atime))
+        .unwrap();
 
     cmd()
         .arg(tmp.path())
+        .args(["--not-accessed-in", "90d"])
         .assert()
         .success()
         .stdout(predicate::str::contains("Rust/Cargo"))
-        .stdout(predicate::str::contains("Node.js"))
-        .stdout(predicate::str::contains("Total"));
+        .stdout(predicate::str::contains("Node.js").not());
 }
 
 #[test]
-fn exclude_by_glob_pattern() {
+fn invalid_duration_errors() {
     let tmp = TempDir::new().unwrap();
-    set_up_rust_project(&tmp);
 
     cmd()
         .arg(tmp.path())
-        .arg("--exclude")
-        .arg("my-rust*")
+        .args(["--older-than", "30 days"])
         .assert()
-        .success()
-        .stdout(predicate::str::contains("No build artifacts found."));
+        .failure()
+        .stderr(predicate::str::contains("invalid duration"));
 }
 
 #[test]
-fn include_only_matching_artifacts() {
+fn idle_for_ignores_touched_artifacts() {
     let tmp = TempDir::new().unwrap();
-    set_up_rust_project(&tmp);
     set_up_node_project(&tmp);
+    set_up_rust_project(&tmp);
+    // The Node project's sources are old; only node_modules was touched.
+    let node = tmp.path().join("my-node-app");
+    fs::File::options()
+        .write(true)
+        .open(node.join("package.json"))
+        .unwrap()
+        .set_modified(std::time::SystemTime::now() - std::time::Duration::from_secs(90 * 86_400))
+        .unwrap();
 
     cmd()
         .arg(tmp.path())
-        .arg("--include")
-        .arg("node_modules")
+        .args(["--idle-for", "60d"])
         .assert()
         .success()
         .stdout(predicate::str::contains("Node.js"))
         .stdout(predicate::str::contains("Rust/Cargo").not());
 }
 
+// -- Size filter integration tests --
+
 #[test]
-fn include_and_exclude_combined() {
+fn min_size_skips_small_artifacts() {
     let tmp = TempDir::new().unwrap();
     set_up_rust_project(&tmp);
-    set_up_node_project(&tmp);
+    set_up_python_project(&tmp);
+    let big = tmp.path().join("my-rust-app/target/debug/big.rlib");
+    fs::write(big, vec![0u8; 64 * 1024]).unwrap();
 
-    // Include both, but exclude the node one by project dir prefix
     cmd()
         .arg(tmp.path())
-        .arg("--include")
-        .arg("target")
-        .arg("--include")
-        .arg("node_modules")
-        .arg("--exclude")
-        .arg("my-node*")
+        .args(["--min-size", "32KB"])
         .assert()
         .success()
         .stdout(predicate::str::contains("Rust/Cargo"))
-        .stdout(predicate::str::contains("Node.js").not());
+        .stdout(predicate::str::contains("Python").not());
 }
 
 #[test]
-fn multiple_include_patterns() {
+fn max_size_skips_large_artifacts() {
     let tmp = TempDir::new().unwrap();
     set_up_rust_project(&tmp);
-    set_up_node_project(&tmp);
+    set_up_python_project(&tmp);
+    let big = tmp.path().join("my-rust-app/target/debug/big.rlib");
+    fs::write(big, vec![0u8; 64 * 1024]).unwrap();
 
     cmd()
         .arg(tmp.path())
-        .arg("--include")
-        .arg("node_modules")
-        .arg("--include")
-        .arg("target")
+        .args(["--max-size", "1KB"])
         .assert()
         .success()
-        .stdout(predicate::str::contains("Node.js"))
-        .stdout(predicate::str::contains("Rust/Cargo"));
+        .stdout(predicate::str::contains("Python"))
+        .stdout(predicate::str::contains("Rust/Cargo").not());
 }
 
 #[test]
-fn exclude_by_directory_name_prefix() {
+fn size_filter_removing_everything_reports_nothing_found() {
+    let tmp = TempDir::new().unwrap();
+    set_up_python_project(&tmp);
+
+    cmd()
+        .arg(tmp.path())
+        .args(["--min-size", "1GB"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("No build artifacts found."));
+}
+
+// -- Git integration tests --
+
+fn git(dir: &std::path::Path, args: &[&str]) {
+    let out = std::process::Command::new("git")
+        .arg("-C")
+        .arg(dir)
+        .args(["-c", "user.name=Test", "-c", "user.email=test@example.com"])
+        .args(args)
+        .output()
+        .unwrap();
+    assert!(out.status.success(), "git {args:?}: {out:?}");
+}
+
+#[test]
+fn skip_unpushed_leaves_local_only_repos() {
     let tmp = TempDir::new().unwrap();
     set_up_rust_project(&tmp);
     set_up_node_project(&tmp);
+    // The Rust project has a commit that was never pushed anywhere.
+    let rust = tmp.path().join("my-rust-app");
+    git(&rust, &["init", "-q"]);
+    git(&rust, &["add", "Cargo.toml"]);
+    git(&rust, &["commit", "-q", "-m", "init"]);
 
-    // Exclude projects starting with "my-"
     cmd()
         .arg(tmp.path())
-        .arg("--exclude")
-        .arg("my-*")
+        .arg("--skip-unpushed")
         .assert()
         .success()
-        .stdout(predicate::str::contains("No build artifacts found."));
+        .stdout(predicate::str::contains("Node.js"))
+        .stdout(predicate::str::contains("Rust/Cargo").not());
 }
 
+// -- Pattern file integration tests --
+
 #[test]
-fn invalid_pattern_exits_with_error() {
+fn exclude_from_file() {
     let tmp = TempDir::new().unwrap();
+    set_up_rust_project(&tmp);
+    set_up_node_project(&tmp);
+    let patterns = tmp.path().join("excludes.txt");
+    fs::write(&patterns, "# shared exclusions\n\nmy-rust-*\n").unwrap();
 
     cmd()
         .arg(tmp.path())
-        .arg("--exclude")
-        .arg("[invalid")
+        .arg("--exclude-from")
+        .arg(&patterns)
         .assert()
-        .failure()
-        .stderr(predicate::str::contains("Error"));
+        .success()
+        .stdout(predicate::str::contains("Node.js"))
+        .stdout(predicate::str::contains("Rust/Cargo").not());
 }
 
 #[test]
-fn no_artifacts_found() {
+fn include_from_merges_with_include() {
     let tmp = TempDir::new().unwrap();
-    fs::create_dir_all(tmp.path().join("empty-project")).unwrap();
+    set_up_rust_project(&tmp);
+    set_up_node_project(&tmp);
+    set_up_python_project(&tmp);
+    let patterns = tmp.path().join("includes.txt");
+    fs::write(&patterns, "target\n").unwrap();
 
     cmd()
         .arg(tmp.path())
+        .arg("--include-from")
+        .arg(&patterns)
+        .args(["--include", "node_modules"])
         .assert()
         .success()
-        .stdout(predicate::str::contains("No build artifacts found."));
+        .stdout(predicate::str::contains("Rust/Cargo"))
+        .stdout(predicate::str::contains("Node.js"))
+        .stdout(predicate::str::contains("Python").not());
 }
 
 #[test]
-fn nonexistent_path() {
+fn missing_pattern_file_errors() {
+    let tmp = TempDir::new().unwrap();
+
     cmd()
-        .arg("/nonexistent/path/that/does/not/exist")
+        .arg(tmp.path())
+        .arg("--exclude-from")
+        .arg(tmp.path().join("nope.txt"))
         .assert()
         .failure()
-        .stderr(predicate::str::contains("Error"));
+        .stderr(predicate::str::contains("cannot read pattern file"));
 }
 
+// -- Regex filter integration tests --
+
 #[test]
-fn delete_multiple_systems_with_yes() {
+fn exclude_regex_skips_matching_paths() {
     let tmp = TempDir::new().unwrap();
     set_up_rust_project(&tmp);
     set_up_node_project(&tmp);
 
     cmd()
         .arg(tmp.path())
-        .arg("--delete")
-        .arg("--yes")
+        .args(["--exclude-regex", r"^my-(rust|go)-app/"])
         .assert()
         .success()
-        .stdout(predicate::str::contains("Deleted 2 of 2"));
-
-    assert!(!tmp.path().join("my-rust-app").join("target").exists());
-    assert!(!tmp.path().join("my-node-app").join("node_modules").exists());
+        .stdout(predicate::str::contains("Node.js"))
+        .stdout(predicate::str::contains("Rust/Cargo").not());
 }
 
 #[test]
-fn help_flag() {
+fn include_regex_selects_matching_paths() {
+    let tmp = TempDir::new().unwrap();
+    set_up_rust_project(&tmp);
+    set_up_node_project(&tmp);
+
     cmd()
-        .arg("--help")
+        .arg(tmp.path())
+        .args(["--include-regex", r"/node_modules$"])
         .assert()
         .success()
-        .stdout(predicate::str::contains("Recursively scan"))
-        .stdout(predicate::str::contains("--delete"));
+        .stdout(predicate::str::contains("Node.js"))
+        .stdout(predicate::str::contains("Rust/Cargo").not());
 }
 
 #[test]
-fn pycache_detected_without_marker() {
+fn invalid_regex_exits_with_error() {
     let tmp = TempDir::new().unwrap();
-    let pycache = tmp.path().join("src").join("__pycache__");
-    fs::create_dir_all(&pycache).unwrap();
-    fs::write(pycache.join("module.cpython-312.pyc"), "bytecode").unwrap();
 
     cmd()
         .arg(tmp.path())
+        .args(["--include-regex", "(unclosed"])
         .assert()
-        .success()
-        .stdout(predicate::str::contains("Python"));
+        .failure()
+        .stderr(predicate::str::contains("invalid filter regex"));
 }
 
 #[test]
-fn verbose_shows_debug_log_on_stderr() {
+fn keep_latest_spares_most_recent_project() {
     let tmp = TempDir::new().unwrap();
-    set_up_rust_project(&tmp);
+    for (name, days) in [("old-app", 40), ("new-app", 1)] {
+        let project = tmp.path().join(name);
+        fs::create_dir_all(project.join("target")).unwrap();
+        fs::write(project.join("Cargo.toml"), "").unwrap();
+        backdate(&project.join("target"), days);
+    }
 
     cmd()
         .arg(tmp.path())
-        .arg("--verbose")
+        .args(["--keep-latest", "1", "--verbose"])
         .assert()
         .success()
-        .stderr(predicate::str::contains("Found artifact"));
+        .stdout(predicate::str::contains("old-app"))
+        .stdout(predicate::str::contains("new-app").not());
 }
 
+// -- Project filter integration tests --
+
 #[test]
-fn default_shows_info_log_on_stderr() {
+fn exclude_project_matches_at_any_depth() {
     let tmp = TempDir::new().unwrap();
-    set_up_rust_project(&tmp);
+    for dir in ["shared", "nested/deeper/shared", "other"] {
+        let project = tmp.path().join(dir);
+        fs::create_dir_all(project.join("target")).unwrap();
+        fs::write(project.join("Cargo.toml"), "").unwrap();
+    }
 
     cmd()
         .arg(tmp.path())
+        .args(["--exclude-project", "shared"])
         .assert()
         .success()
-        .stderr(predicate::str::contains("Scanning"));
+        .stdout(predicate::str::is_match(r"Rust/Cargo\s+1\s").unwrap());
 }
 
 #[test]
-fn default_does_not_show_debug_log() {
+fn project_includes_only_matching_projects() {
     let tmp = TempDir::new().unwrap();
     set_up_rust_project(&tmp);
+    set_up_node_project(&tmp);
 
     cmd()
         .arg(tmp.path())
+        .args(["--project", "my-node-*"])
         .assert()
         .success()
-        .stderr(predicate::str::contains("Found artifact").not());
+        .stdout(predicate::str::contains("Node.js"))
+        .stdout(predicate::str::contains("Rust/Cargo").not());
 }
 
-// -- System filter integration tests --
+// -- Explain integration tests --
 
 #[test]
-fn system_filters_to_one_system() {
+fn explain_names_the_deciding_filter() {
     let tmp = TempDir::new().unwrap();
     set_up_rust_project(&tmp);
     set_up_node_project(&tmp);
+    set_up_python_project(&tmp);
+    backdate(&tmp.path().join("my-python-app/__pycache__"), 60);
 
     cmd()
         .arg(tmp.path())
-        .arg("--system")
-        .arg("cargo")
+        .args(["--explain", "--exclude", "my-rust-*", "--newer-than", "7d"])
         .assert()
         .success()
-        .stdout(predicate::str::contains("Rust/Cargo"))
-        .stdout(predicate::str::contains("Node.js").not());
+        .stdout(predicate::str::contains(
+            "drop  my-rust-app/target  (excluded by --exclude 'my-rust-*')",
+        ))
+        .stdout(
+            predicate::str::is_match(
+                r"drop  my-node-app/node_modules  \(modified \d+s ago, within --newer-than 7d\)",
+            )
+            .unwrap(),
+        )
+        .stdout(predicate::str::contains(
+            "keep  my-python-app/__pycache__  (modified 60d ago)",
+        ))
+        .stdout(predicate::str::contains("Python"));
 }
 
 #[test]
-fn system_multiple() {
+fn explain_still_reports_when_everything_is_dropped() {
     let tmp = TempDir::new().unwrap();
-    set_up_rust_project(&tmp);
-    set_up_node_project(&tmp);
     set_up_python_project(&tmp);
 
     cmd()
         .arg(tmp.path())
-        .arg("--system")
-        .arg("cargo")
-        .arg("--system")
-        .arg("node")
+        .args(["--explain", "--min-size", "1GB"])
         .assert()
         .success()
-        .stdout(predicate::str::contains("Rust/Cargo"))
-        .stdout(predicate::str::contains("Node.js"))
-        .stdout(predicate::str::contains("Python").not());
+        .stdout(predicate::str::contains(
+            "drop  my-python-app/__pycache__  (8 B is below --min-size 1.0 GB)",
+        ))
+        .stdout(predicate::str::contains("No build artifacts found."));
 }
 
+// -- Depth filter integration tests --
+
 #[test]
-fn exclude_system() {
+fn max_artifact_depth_skips_nested_projects() {
     let tmp = TempDir::new().unwrap();
     set_up_rust_project(&tmp);
-    set_up_node_project(&tmp);
+    let nested = tmp.path().join("my-rust-app/vendor/dep");
+    fs::create_dir_all(nested.join("target")).unwrap();
+    fs::write(nested.join("Cargo.toml"), "").unwrap();
 
     cmd()
         .arg(tmp.path())
-        .arg("--exclude-system")
-        .arg("node")
+        .args(["--max-artifact-depth", "2"])
         .assert()
         .success()
-        .stdout(predicate::str::contains("Rust/Cargo"))
-        .stdout(predicate::str::contains("Node.js").not());
+        .stdout(predicate::str::is_match(r"Rust/Cargo\s+1\s").unwrap());
 }
 
 #[test]
-fn invalid_system_errors() {
+fn min_depth_skips_top_level_artifacts() {
     let tmp = TempDir::new().unwrap();
+    set_up_node_project(&tmp);
+    fs::write(tmp.path().join("package.json"), "{}").unwrap();
+    fs::create_dir_all(tmp.path().join("node_modules")).unwrap();
 
     cmd()
         .arg(tmp.path())
-        .arg("--system")
-        .arg("nonexistent")
+        .args(["--min-depth", "2", "--explain"])
         .assert()
-        .failure()
-        .stderr(predicate::str::contains("unknown build system"));
+        .success()
+        .stdout(predicate::str::contains(
+            "drop  node_modules  (at depth 1, shallower than --min-depth 2)",
+        ))
+        .stdout(predicate::str::is_match(r"Node.js\s+1\s").unwrap());
 }
 
+// -- Owner filter integration tests --
+
+#[cfg(unix)]
 #[test]
-fn system_and_exclude_system_conflict() {
+fn only_mine_keeps_own_artifacts() {
+    let tmp = TempDir::new().unwrap();
+    set_up_rust_project(&tmp);
+
     cmd()
-        .arg("--system")
-        .arg("cargo")
-        .arg("--exclude-system")
-        .arg("node")
+        .arg(tmp.path())
+        .arg("--only-mine")
         .assert()
-        .failure();
+        .success()
+        .stdout(predicate::str::contains("Rust/Cargo"));
 }
 
+#[cfg(unix)]
 #[test]
-fn list_systems_shows_table() {
+fn owner_filter_skips_other_users() {
+    let tmp = TempDir::new().unwrap();
+    set_up_rust_project(&tmp);
+
+    // No test artifact is owned by this UID.
     cmd()
-        .arg("--list-systems")
+        .arg(tmp.path())
+        .args(["--owner", "4294967294"])
         .assert()
         .success()
-        .stdout(predicate::str::contains("cargo"))
-        .stdout(predicate::str::contains("node"))
-        .stdout(predicate::str::contains("python"))
-        .stdout(predicate::str::contains("Rust/Cargo"))
-        .stdout(predicate::str::contains("ID"));
+        .stdout(predicate::str::contains("No build artifacts found."));
 }
 
 #[test]
-fn list_systems_works_without_path() {
-    // --list-systems should work even without a valid path argument
+fn unknown_owner_errors() {
+    let tmp = TempDir::new().unwrap();
+
     cmd()
-        .arg("--list-systems")
+        .arg(tmp.path())
+        .args(["--owner", "no-such-user-here"])
         .assert()
-        .success()
-        .stdout(predicate::str::contains("cargo"));
+        .failure()
+        .stderr(predicate::str::contains("Error:"));
 }
 
+// -- In-use integration tests --
+
+#[cfg(unix)]
 #[test]
-fn system_combined_with_glob() {
+fn skip_in_use_leaves_projects_with_running_processes() {
     let tmp = TempDir::new().unwrap();
     set_up_node_project(&tmp);
+    set_up_rust_project(&tmp);
+    let mut server = std::process::Command::new("sleep")
+        .arg("10")
+        .current_dir(tmp.path().join("my-node-app"))
+        .spawn()
+        .unwrap();
 
-    cmd()
-        .arg(tmp.path())
-        .arg("--system")
-        .arg("node")
-        .arg("--exclude")
-        .arg("my-node*")
-        .assert()
+    let assert = cmd().arg(tmp.path()).arg("--skip-in-use").assert();
+    server.kill().unwrap();
+    server.wait().unwrap();
+    assert
         .success()
-        .stdout(predicate::str::contains("No build artifacts found."));
+        .stdout(predicate::str::contains("Rust/Cargo"))
+        .stdout(predicate::str::contains("Node.js").not());
 }
 
+// -- Filter expression integration tests --
+
 #[test]
-fn delete_with_system_filter() {
+fn filter_expression_combines_predicates() {
     let tmp = TempDir::new().unwrap();
     set_up_rust_project(&tmp);
+    set_up_python_project(&tmp);
     set_up_node_project(&tmp);
+    let big = tmp.path().join("my-rust-app/target/debug/big.rlib");
+    fs::write(big, vec![0u8; 64 * 1024]).unwrap();
 
     cmd()
         .arg(tmp.path())
-        .arg("--system")
-        .arg("node")
-        .arg("--delete")
-        .arg("--yes")
+        .args(["--filter", r#"size > 32KB || system == "python""#])
         .assert()
         .success()
-        .stdout(predicate::str::contains("Deleted 1 of 1"));
+        .stdout(predicate::str::contains("Rust/Cargo"))
+        .stdout(predicate::str::contains("Python"))
+        .stdout(predicate::str::contains("Node.js").not());
+}
 
-    // node_modules should be deleted
-    assert!(!tmp.path().join("my-node-app").join("node_modules").exists());
-    // Rust target should still exist
-    assert!(tmp.path().join("my-rust-app").join("target").exists());
+#[test]
+fn invalid_filter_expression_errors() {
+    let tmp = TempDir::new().unwrap();
+
+    cmd()
+        .arg(tmp.path())
+        .args(["--filter", "size >> 1GB"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains(
+            "invalid filter expression at column",
+        ));
 }